@@ -0,0 +1,65 @@
+package io.dymium.provider
+
+import android.app.NotificationChannel
+import android.app.NotificationManager
+import android.app.PendingIntent
+import android.app.Service
+import android.content.Context
+import android.content.Intent
+import android.content.pm.ServiceInfo
+import android.os.Build
+import android.os.IBinder
+import androidx.core.app.NotificationCompat
+import androidx.core.app.ServiceCompat
+import androidx.core.content.ContextCompat
+
+/**
+ * Foreground service that keeps the process alive while the token refresh
+ * loop has work to do. The loop itself runs in Rust, which starts and stops
+ * this service (`MobileService::keep_alive`); Android just doesn't stop an
+ * app with an ongoing notification.
+ */
+class RefreshService : Service() {
+    override fun onBind(intent: Intent?): IBinder? = null
+
+    override fun onStartCommand(intent: Intent?, flags: Int, startId: Int): Int {
+        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.O) {
+            getSystemService(NotificationManager::class.java).createNotificationChannel(
+                NotificationChannel(CHANNEL_ID, "Token refresh", NotificationManager.IMPORTANCE_LOW)
+            )
+        }
+        val open = packageManager.getLaunchIntentForPackage(packageName)?.let {
+            PendingIntent.getActivity(this, 0, it, PendingIntent.FLAG_IMMUTABLE)
+        }
+        val notification = NotificationCompat.Builder(this, CHANNEL_ID)
+            .setContentTitle("Dymium Provider")
+            .setContentText("Keeping your GhostLLM token fresh")
+            .setSmallIcon(applicationInfo.icon)
+            .setContentIntent(open)
+            .setOngoing(true)
+            .build()
+        val type = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.UPSIDE_DOWN_CAKE) {
+            ServiceInfo.FOREGROUND_SERVICE_TYPE_SPECIAL_USE
+        } else {
+            0
+        }
+        ServiceCompat.startForeground(this, NOTIFICATION_ID, notification, type)
+        // Without the app's process there's no refresh loop to keep alive
+        return START_NOT_STICKY
+    }
+
+    companion object {
+        private const val CHANNEL_ID = "token-refresh"
+        private const val NOTIFICATION_ID = 1
+
+        @JvmStatic
+        fun start(context: Context) {
+            ContextCompat.startForegroundService(context, Intent(context, RefreshService::class.java))
+        }
+
+        @JvmStatic
+        fun stop(context: Context) {
+            context.stopService(Intent(context, RefreshService::class.java))
+        }
+    }
+}
@@ -0,0 +1,468 @@
+//! Command-line interface
+//!
+//! `dymium-provider <command>` works on the same files as the app
+//! (`~/.dymium/config.json`, the keystore and `~/.dymium/token`) without
+//! starting the GUI, so scripts don't need it. Without a command the tray
+//! app starts as usual. With `--json`, the result or error is printed as a
+//! single JSON object on stdout.
+
+use crate::headless;
+use crate::services::audit::{AuditLog, AuditReport};
+use crate::services::config::{AppConfig, AuthMode, Secrets, TokenStatePublic};
+use crate::services::error_code::ErrorCode;
+use crate::services::logs::LogBuffer;
+use crate::services::opencode::OpenCodeService;
+use crate::services::systemd::{self, SystemdError, SystemdService};
+use crate::services::token::{SessionLogin, TokenError, TokenService};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: dymium-provider [command] [--json]
+
+Commands:
+  status      Show the configured endpoint and whether the token is valid
+  login       Authenticate and sync the tools; DYMIUM_* variables or
+              --stdin-secret supply credentials without stored ones
+  refresh     Get a new access token
+  token       Print the current access token
+  logout      Forget the stored credentials and token
+  audit       Show the credential audit log and verify its hash chain
+  --headless  Keep the token refreshed without the tray icon or window
+  install-service
+              Write a systemd user unit that runs --headless (Linux)
+
+Options:
+  --json      Print the result or error as JSON
+
+Without a command, the tray app starts.";
+
+/// A failed command, with a stable code for scripts
+#[derive(Debug)]
+pub(crate) struct CliError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<TokenError> for CliError {
+    fn from(e: TokenError) -> Self {
+        Self::new(ErrorCode::from(&e), e.to_string())
+    }
+}
+
+impl From<SystemdError> for CliError {
+    fn from(e: SystemdError) -> Self {
+        let code = match &e {
+            SystemdError::Unsupported => ErrorCode::Unsupported,
+            SystemdError::IoError(_) => ErrorCode::Io,
+            SystemdError::NoDirError => ErrorCode::ConfigInvalid,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+/// Result of `token`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenOutput {
+    token: String,
+    /// From the JWT `exp` claim; unset for static keys
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// The token file, as reported by `status`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenFileStatus {
+    present: bool,
+    expires_at: Option<DateTime<Utc>>,
+    expired: bool,
+}
+
+/// Result of `status`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    auth_mode: AuthMode,
+    llm_endpoint: String,
+    active_profile: Option<String>,
+    /// "configured", "missing" or "locked"
+    credentials: &'static str,
+    token: TokenFileStatus,
+}
+
+/// What a command prints on success
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Output {
+    Status(Status),
+    State(TokenStatePublic),
+    Token(TokenOutput),
+    Audit(AuditReport),
+    /// A file that was written
+    Written {
+        path: PathBuf,
+    },
+    Usage {
+        usage: &'static str,
+    },
+}
+
+impl Output {
+    /// Human-readable form, for when `--json` isn't given
+    fn text(&self) -> String {
+        match self {
+            Self::Status(status) => {
+                let mode = match status.auth_mode {
+                    AuthMode::OAuth => "OAuth (Keycloak)",
+                    AuthMode::StaticKey => "Static API key",
+                };
+                let token = match status.token.expires_at {
+                    _ if !status.token.present => "none".to_string(),
+                    Some(at) if status.token.expired => format!("expired at {}", at.to_rfc3339()),
+                    Some(at) => format!("valid until {}", at.to_rfc3339()),
+                    None => "present".to_string(),
+                };
+                let mut lines = vec![
+                    format!("Mode:        {}", mode),
+                    format!("Endpoint:    {}", status.llm_endpoint),
+                ];
+                if let Some(profile) = &status.active_profile {
+                    lines.push(format!("Profile:     {}", profile));
+                }
+                lines.push(format!("Credentials: {}", status.credentials));
+                lines.push(format!("Token:       {}", token));
+                lines.join("\n")
+            }
+            Self::State(TokenStatePublic::Authenticated { expires_at }) => format!(
+                "Authenticated, token valid until {}",
+                expires_at.to_rfc3339()
+            ),
+            Self::State(TokenStatePublic::Idle) => "Logged out".to_string(),
+            Self::State(state) => format!("{:?}", state),
+            Self::Token(token) => token.token.clone(),
+            Self::Audit(report) => {
+                let mut lines: Vec<String> = report
+                    .entries
+                    .iter()
+                    .rev()
+                    .map(|entry| {
+                        let mode = match entry.mode {
+                            Some(AuthMode::OAuth) => "oauth",
+                            Some(AuthMode::StaticKey) => "static",
+                            None => "-",
+                        };
+                        format!(
+                            "{}  {:<18} {:<7} {:<8} {}",
+                            entry.timestamp.to_rfc3339(),
+                            entry.action,
+                            mode,
+                            entry.outcome,
+                            entry.detail
+                        )
+                        .trim_end()
+                        .to_string()
+                    })
+                    .collect();
+                lines.push(format!("{} entries, hash chain intact", report.total));
+                lines.join("\n")
+            }
+            Self::Written { path } => format!(
+                "Wrote {}\nStart it with: systemctl --user daemon-reload && systemctl --user enable --now {}",
+                path.display(),
+                systemd::UNIT_NAME
+            ),
+            Self::Usage { usage } => usage.to_string(),
+        }
+    }
+}
+
+/// Run the command named in `args`, if any, returning the exit code.
+/// `None` means no command was given and the GUI should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.is_empty() {
+        return None;
+    }
+    let json = args.iter().any(|a| a == "--json");
+    let command = args
+        .iter()
+        .map(String::as_str)
+        .find(|a| !matches!(*a, "--json" | "--stdin-secret"))
+        .unwrap_or("help");
+    attach_console();
+
+    if command == "--headless" {
+        return Some(match headless::run() {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("dymium-provider: {}", e.message);
+                e.code.exit_code()
+            }
+        });
+    }
+
+    // Service warnings go to stderr, filtered by RUST_LOG, and to the log file
+    LogBuffer::init(&AppConfig::load().log_file);
+    let result = match command {
+        "status" => status(),
+        "login" => block_on(login(args.iter().any(|a| a == "--stdin-secret"))),
+        "refresh" => block_on(refresh()),
+        "token" => token(),
+        "logout" => logout(),
+        "audit" => audit(),
+        "install-service" => SystemdService::install()
+            .map(|path| Output::Written { path })
+            .map_err(CliError::from),
+        "help" | "--help" | "-h" => Ok(Output::Usage { usage: USAGE }),
+        other => Err(CliError::new(
+            ErrorCode::Usage,
+            format!("Unknown command {}\n\n{}", other, USAGE),
+        )),
+    };
+
+    Some(match result {
+        Ok(output) => {
+            if json {
+                println!("{}", serde_json::to_string(&output).unwrap_or_default());
+            } else {
+                println!("{}", output.text());
+            }
+            0
+        }
+        Err(e) => {
+            if json {
+                let error = serde_json::json!({
+                    "code": e.code.name(),
+                    "exitCode": e.code.exit_code(),
+                    "message": e.message,
+                });
+                println!("{}", serde_json::json!({ "error": error }));
+            } else {
+                eprintln!("dymium-provider: {}", e.message);
+            }
+            e.code.exit_code()
+        }
+    })
+}
+
+/// Release builds on Windows are GUI-subsystem programs without a console;
+/// borrow the terminal the command was run from so output shows up
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+fn block_on<F: std::future::Future<Output = Result<Output, CliError>>>(
+    future: F,
+) -> Result<Output, CliError> {
+    tokio::runtime::Runtime::new()
+        .map_err(|e| CliError::new(ErrorCode::General, e.to_string()))?
+        .block_on(future)
+}
+
+/// A token service that can authenticate, with any credentials from the
+/// environment (and stdin, with `stdin_secret`) in place of the stored ones
+pub(crate) fn token_service(stdin_secret: bool) -> Result<TokenService, CliError> {
+    let mut service = TokenService::new();
+    if let Some(login) = session_login(service.config().auth_mode, stdin_secret)? {
+        service.use_session_login(login)?;
+    }
+    if service.is_locked() {
+        return Err(CliError::new(
+            ErrorCode::VaultLocked,
+            "The credential vault is enabled; unlock it in the app",
+        ));
+    }
+    if !service.has_credentials() {
+        return Err(CliError::new(
+            ErrorCode::NoCredentials,
+            "No credentials configured; set them up in the app first",
+        ));
+    }
+    Ok(service)
+}
+
+/// Settings and credentials from `DYMIUM_*` variables and, with
+/// `stdin_secret`, the secret for `auth_mode` from the first line of stdin
+fn session_login(
+    auth_mode: AuthMode,
+    stdin_secret: bool,
+) -> Result<Option<SessionLogin>, CliError> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+    let auth_mode_override = match var("DYMIUM_AUTH_MODE").as_deref() {
+        None => None,
+        Some("oauth") => Some(AuthMode::OAuth),
+        Some("static") => Some(AuthMode::StaticKey),
+        Some(other) => {
+            return Err(CliError::new(
+                ErrorCode::ConfigInvalid,
+                format!("DYMIUM_AUTH_MODE must be oauth or static, not {}", other),
+            ))
+        }
+    };
+    let mut login = SessionLogin {
+        auth_mode: auth_mode_override,
+        llm_endpoint: var("DYMIUM_LLM_ENDPOINT"),
+        ghostllm_app: var("DYMIUM_APP"),
+        keycloak_url: var("DYMIUM_KEYCLOAK_URL"),
+        realm: var("DYMIUM_REALM"),
+        client_id: var("DYMIUM_CLIENT_ID"),
+        username: var("DYMIUM_USERNAME"),
+        secrets: Secrets {
+            client_secret: var("DYMIUM_CLIENT_SECRET"),
+            password: var("DYMIUM_PASSWORD"),
+            refresh_token: None,
+            static_api_key: var("DYMIUM_STATIC_API_KEY"),
+        },
+    };
+    if login.auth_mode.is_none() && login.secrets.static_api_key.is_some() {
+        login.auth_mode = Some(AuthMode::StaticKey);
+    }
+
+    if stdin_secret {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .map_err(|e| CliError::new(ErrorCode::Io, e.to_string()))?;
+        let secret = line.trim_end_matches(['\r', '\n']).to_string();
+        if secret.is_empty() {
+            return Err(CliError::new(
+                ErrorCode::NoCredentials,
+                "--stdin-secret was given but stdin is empty",
+            ));
+        }
+        match login.auth_mode.unwrap_or(auth_mode) {
+            AuthMode::StaticKey => login.secrets.static_api_key = Some(secret),
+            AuthMode::OAuth => login.secrets.password = Some(secret),
+        }
+    }
+
+    let supplied = login.auth_mode.is_some()
+        || login.llm_endpoint.is_some()
+        || login.ghostllm_app.is_some()
+        || login.keycloak_url.is_some()
+        || login.realm.is_some()
+        || login.client_id.is_some()
+        || login.username.is_some()
+        || !login.secrets.is_empty();
+    Ok(supplied.then_some(login))
+}
+
+fn status() -> Result<Output, CliError> {
+    let service = TokenService::new();
+    let config = service.config();
+    let credentials = if service.is_locked() {
+        "locked"
+    } else if service.has_credentials() {
+        "configured"
+    } else {
+        "missing"
+    };
+    let token = read_token()?;
+    let expires_at = token.as_deref().and_then(token_expiry);
+    Ok(Output::Status(Status {
+        auth_mode: config.auth_mode,
+        llm_endpoint: config.llm_endpoint.clone(),
+        active_profile: config.active_profile.clone(),
+        credentials,
+        token: TokenFileStatus {
+            present: token.is_some(),
+            expires_at,
+            expired: expires_at.is_some_and(|at| at <= Utc::now()),
+        },
+    }))
+}
+
+async fn login(stdin_secret: bool) -> Result<Output, CliError> {
+    let mut service = token_service(stdin_secret)?;
+    let config = service.config().clone();
+    if let Err(e) = OpenCodeService::ensure_dymium_provider(&config) {
+        log::warn!("Failed to sync OpenCode config: {}", e);
+    }
+    service.start_refresh_loop().await?;
+    Ok(Output::State(service.state().into()))
+}
+
+async fn refresh() -> Result<Output, CliError> {
+    let mut service = token_service(false)?;
+    if service.config().is_static_key_mode() {
+        // Static keys don't expire; check the key still works instead
+        service.manual_refresh().await?;
+    } else {
+        service.refresh_tick().await?;
+    }
+    Ok(Output::State(service.state().into()))
+}
+
+fn token() -> Result<Output, CliError> {
+    let token = read_token()?.ok_or_else(|| {
+        CliError::new(
+            ErrorCode::NotLoggedIn,
+            "Not logged in; run `dymium-provider login`",
+        )
+    })?;
+    let expires_at = token_expiry(&token);
+    if expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(CliError::new(
+            ErrorCode::TokenExpired,
+            "The token has expired; run `dymium-provider refresh`",
+        ));
+    }
+    Ok(Output::Token(TokenOutput { token, expires_at }))
+}
+
+fn logout() -> Result<Output, CliError> {
+    let mut service = TokenService::new();
+    service.log_out()?;
+    Ok(Output::State(service.state().into()))
+}
+
+fn audit() -> Result<Output, CliError> {
+    let report =
+        AuditLog::read(usize::MAX, 0).map_err(|e| CliError::new(ErrorCode::Io, e.to_string()))?;
+    if let Some(line) = report.broken_at {
+        return Err(CliError::new(
+            ErrorCode::AuditTampered,
+            format!(
+                "The audit log was modified: the hash chain breaks at line {}",
+                line
+            ),
+        ));
+    }
+    Ok(Output::Audit(report))
+}
+
+/// Contents of `~/.dymium/token`, if any
+fn read_token() -> Result<Option<String>, CliError> {
+    let path = AppConfig::token_path()
+        .map_err(|e| CliError::new(ErrorCode::ConfigInvalid, e.to_string()))?;
+    match fs::read_to_string(path) {
+        Ok(token) => Ok(Some(token.trim().to_string()).filter(|t| !t.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(CliError::new(ErrorCode::Io, e.to_string())),
+    }
+}
+
+/// The `exp` claim of a JWT; `None` for static keys and opaque tokens
+fn token_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    DateTime::from_timestamp(claims.get("exp")?.as_i64()?, 0)
+}
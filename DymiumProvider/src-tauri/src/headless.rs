@@ -0,0 +1,313 @@
+//! Headless daemon mode
+//!
+//! `dymium-provider --headless` keeps the token refreshed and the managed
+//! files in sync without the tray icon or the setup window, for servers and
+//! containers. Settings come from `~/.dymium/config.json` as usual; the local
+//! proxy and token socket run when enabled there. Runs until SIGINT/SIGTERM.
+//! Under systemd (`Type=notify`) it reports readiness, status and watchdog
+//! pings; see `dymium-provider install-service`.
+
+use crate::cli::{self, CliError};
+use crate::services::config::{AppConfig, TokenState};
+use crate::services::crash::CrashReporter;
+use crate::services::error_code::ErrorCode;
+use crate::services::failover::Failover;
+use crate::services::health_check::HealthCheckService;
+use crate::services::logs::LogBuffer;
+use crate::services::opencode::OpenCodeService;
+use crate::services::proxy::ProxyService;
+use crate::services::proxy_log::ProxyLog;
+use crate::services::systemd::Notifier;
+use crate::services::telemetry::Telemetry;
+use crate::services::token::SharedTokenService;
+use crate::services::token_socket::TokenSocketService;
+use crate::services::usage::UsageMeter;
+use crate::services::watcher::FileWatcher;
+use chrono::Utc;
+use log::LevelFilter;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Run without a GUI until a shutdown signal arrives
+pub fn run() -> Result<(), CliError> {
+    let config = AppConfig::load();
+    let logs = LogBuffer::init(&config.log_file);
+    CrashReporter::install(logs.clone());
+    let expired = config
+        .log_level_until
+        .is_some_and(|until| until <= Utc::now());
+    match config.log_level.as_deref().map(LevelFilter::from_str) {
+        Some(Ok(level)) if !expired => logs.set_level(level),
+        Some(Err(_)) => log::warn!("Ignoring unknown logLevel in config"),
+        _ => {}
+    }
+
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| CliError::new(ErrorCode::General, e.to_string()))?;
+    runtime.block_on(async {
+        let token_service = cli::token_service(false)?;
+        let ts = Arc::new(SharedTokenService::new(token_service));
+        let notifier = Notifier::from_env().map(Arc::new);
+        for path in AppConfig::exposed_paths() {
+            log::warn!(
+                "{} is readable by other users; run chmod go-rwx on it",
+                path.display()
+            );
+        }
+
+        {
+            let mut service = ts.lock().await;
+            let config = service.config().clone();
+            if let Err(e) = OpenCodeService::ensure_dymium_provider(&config) {
+                log::warn!("Failed to sync OpenCode config on startup: {}", e);
+            }
+            log::info!("Starting initial authentication...");
+            if let Err(e) = service.start_refresh_loop().await {
+                log::error!("Failed initial authentication: {}", e);
+            }
+            if let Some(notifier) = &notifier {
+                notifier.status(&status_line(service.state()));
+            }
+        }
+
+        spawn_repair(ts.clone());
+        spawn_proxy(ts.clone()).await;
+        spawn_token_socket(ts.clone()).await;
+        spawn_health_check(ts.clone()).await;
+        tokio::spawn(revocation_loop(ts.clone(), notifier.clone()));
+        tokio::spawn(managed_config_loop(ts.clone()));
+        tokio::spawn(refresh_loop(ts.clone(), notifier.clone()));
+        tokio::spawn(Telemetry::send_loop());
+        tokio::spawn(async {
+            if let Err(e) = CrashReporter::upload_pending(&AppConfig::load().crash_reports).await {
+                log::warn!("Crash reports not uploaded: {}", e);
+            }
+        });
+        if let Some(notifier) = &notifier {
+            notifier.ready();
+        }
+
+        shutdown_signal().await;
+        log::info!("Shutting down");
+        if let Some(notifier) = &notifier {
+            notifier.stopping();
+        }
+        // Let a refresh in progress finish writing the token and files
+        let _service = ts.lock().await;
+        Ok(())
+    })
+}
+
+/// Periodic OAuth refresh; static keys don't expire. Pings the systemd
+/// watchdog while it runs.
+async fn refresh_loop(ts: Arc<SharedTokenService>, notifier: Option<Arc<Notifier>>) {
+    loop {
+        let interval_secs = {
+            let service = ts.lock().await;
+            if service.needs_refresh_loop() {
+                Some(service.refresh_interval_secs())
+            } else {
+                None
+            }
+        };
+        let Some(interval_secs) = interval_secs else {
+            sleep(Duration::from_secs(5), notifier.as_deref()).await;
+            continue;
+        };
+        sleep(Duration::from_secs(interval_secs), notifier.as_deref()).await;
+
+        let mut service = ts.lock().await;
+        if !service.needs_refresh_loop() {
+            continue;
+        }
+        if let Err(e) = service.refresh_tick().await {
+            log::error!("Periodic token refresh failed: {}", e);
+        }
+        if let Some(notifier) = &notifier {
+            notifier.status(&status_line(service.state()));
+            notifier.watchdog();
+        }
+    }
+}
+
+/// Poll the kill switch, if `revocation.url` is set
+async fn revocation_loop(ts: Arc<SharedTokenService>, notifier: Option<Arc<Notifier>>) {
+    loop {
+        let mut service = ts.lock().await;
+        let interval_minutes = service.config().revocation.interval_minutes.max(1);
+        if service.check_revocation().await {
+            if service.blocked().is_none() {
+                log::info!("Re-authenticating after re-enable...");
+                if let Err(e) = service.start_refresh_loop().await {
+                    log::error!("Failed to re-authenticate: {}", e);
+                }
+            }
+            if let Some(notifier) = &notifier {
+                notifier.status(&status_line(service.state()));
+            }
+        }
+        drop(service);
+        tokio::time::sleep(Duration::from_secs(interval_minutes * 60)).await;
+    }
+}
+
+/// Fetch the signed managed config, if `managedConfig.url` is set
+async fn managed_config_loop(ts: Arc<SharedTokenService>) {
+    loop {
+        let mut service = ts.lock().await;
+        if let Err(e) = service.sync_managed_config().await {
+            log::warn!("Managed config not updated: {}", e);
+        }
+        let interval_minutes = service.config().managed_config.interval_minutes.max(1);
+        drop(service);
+        tokio::time::sleep(Duration::from_secs(interval_minutes * 60)).await;
+    }
+}
+
+/// Sleep, pinging the watchdog often enough to keep it satisfied
+async fn sleep(duration: Duration, notifier: Option<&Notifier>) {
+    let Some((notifier, period)) = notifier.and_then(|n| Some((n, n.watchdog_period()?))) else {
+        tokio::time::sleep(duration).await;
+        return;
+    };
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        notifier.watchdog();
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return;
+        }
+        tokio::time::sleep(period.min(deadline - now)).await;
+    }
+}
+
+/// systemd status line for the token state
+fn status_line(state: &TokenState) -> String {
+    match state {
+        TokenState::Authenticated { expires_at, .. } => {
+            format!(
+                "Authenticated, token valid until {}",
+                expires_at.to_rfc3339()
+            )
+        }
+        TokenState::Failed { error, .. } => format!("Authentication failed: {}", error),
+        TokenState::Locked => "Credential vault locked".to_string(),
+        _ => "Not authenticated".to_string(),
+    }
+}
+
+/// Re-apply the dymium sections if OpenCode or the user drops them
+fn spawn_repair(ts: Arc<SharedTokenService>) {
+    let watched = OpenCodeService::managed_paths()
+        .map_err(|e| e.to_string())
+        .and_then(|paths| FileWatcher::new(&paths).map_err(|e| e.to_string()));
+    let (watcher, mut changes) = match watched {
+        Ok(watched) => watched,
+        Err(e) => {
+            log::warn!("Not watching OpenCode config for changes: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while changes.recv().await.is_some() {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            while changes.try_recv().is_ok() {}
+
+            let service = ts.lock().await;
+            match service.repair_opencode_config() {
+                Ok(repaired) if repaired.is_empty() => {}
+                Ok(repaired) => log::info!(
+                    "Re-applied Dymium settings removed from {}",
+                    repaired.join(", ")
+                ),
+                Err(e) => log::error!("Failed to re-apply OpenCode config: {}", e),
+            }
+        }
+    });
+}
+
+/// Local authenticating proxy, if enabled
+async fn spawn_proxy(ts: Arc<SharedTokenService>) {
+    let (config, upstream) = {
+        let service = ts.lock().await;
+        (service.config().clone(), service.upstream())
+    };
+    if !config.proxy.enabled {
+        return;
+    }
+    let (failover, mut switches) = Failover::new();
+    tokio::spawn(async move {
+        while switches.changed().await.is_ok() {
+            let active = switches.borrow_and_update().clone();
+            if let Some(active) = active {
+                if active.is_primary() {
+                    log::info!("Primary endpoint recovered: {}", active.base_url);
+                } else {
+                    log::warn!("Switched to fallback endpoint {}", active.base_url);
+                }
+            }
+        }
+    });
+    let port = config.proxy.port;
+    tokio::spawn(async move {
+        let result = ProxyService::serve(
+            config,
+            upstream,
+            ProxyLog::default(),
+            UsageMeter::load(),
+            failover,
+        )
+        .await;
+        if let Err(e) = result {
+            log::error!("Proxy on port {} stopped: {}", port, e);
+        }
+    });
+}
+
+/// Token socket for local tools, if enabled
+async fn spawn_token_socket(ts: Arc<SharedTokenService>) {
+    let (enabled, upstream) = {
+        let service = ts.lock().await;
+        (service.config().token_socket, service.upstream())
+    };
+    if enabled {
+        tokio::spawn(async move {
+            if let Err(e) = TokenSocketService::serve(upstream, ts, None).await {
+                log::error!("Token socket stopped: {}", e);
+            }
+        });
+    }
+}
+
+/// `/healthz` and `/readyz`, if enabled
+async fn spawn_health_check(ts: Arc<SharedTokenService>) {
+    let health_check = ts.lock().await.config().health_check.clone();
+    if health_check.enabled {
+        tokio::spawn(async move {
+            if let Err(e) = HealthCheckService::serve(health_check.port, ts).await {
+                log::error!("Health check on port {} stopped: {}", health_check.port, e);
+            }
+        });
+    }
+}
+
+/// Wait for Ctrl-C, or SIGTERM on Unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => log::warn!("Cannot listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
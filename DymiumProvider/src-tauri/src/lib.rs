@@ -3,36 +3,124 @@
 //! This application runs as a system tray app and manages authentication tokens
 //! for GhostLLM. It supports both OAuth (Keycloak) and static API key authentication.
 
+mod cli;
+mod headless;
 mod services;
 
-use services::config::{AppConfig, TokenState};
-use services::opencode::OpenCodeService;
-use services::token::TokenService;
+use chrono::{Local, Utc};
+use log::LevelFilter;
+use services::activity::ActivityLog;
+use services::audit::{AuditLog, AuditReport};
+use services::autostart::AutostartService;
+use services::config::{
+    AppConfig, AppConfigPublic, EgressProxyConfig, ModelEntry, SecretKind, TokenState,
+    TokenStatePublic, UpdateChannel,
+};
+use services::crash::CrashReporter;
+use services::egress_proxy::EgressRoute;
+use services::failover::{ActiveEndpoint, Failover};
+use services::files::BackupInfo;
+use services::health::{EndpointKind, HealthLog, HealthReport};
+use services::health_check::HealthCheckService;
+use services::integrations::{self, ManagedFile};
+use services::keystore::CredentialKey;
+#[cfg(desktop)]
+use services::last_state::LastStatus;
+use services::logs::{self, LogBuffer, LogPage};
+#[cfg(mobile)]
+use services::mobile::MobileService;
+use services::notifications::{NotificationGate, Urgency};
+use services::opencode::{OpenCodeFile, OpenCodeService};
+use services::org_config::OrgConfig;
+use services::os_auth::OsAuthService;
+use services::proxy::ProxyService;
+use services::proxy_log::{ProxyLog, ProxyLogEntry};
+use services::proxy_tls::ProxyTls;
+use services::revocation::RevocationService;
+use services::screen_lock::ScreenLock;
+use services::secret_scan::SecretFinding;
+use services::shell::ShellService;
+use services::telemetry::{Telemetry, TelemetryReport};
+use services::token::{
+    AccessToken, EndpointTest, ModelList, OAuthSettings, SharedTokenService, TokenError,
+    TokenService, ValidationStep,
+};
+use services::token_socket::{StateListener, TokenSocketService};
+#[cfg(desktop)]
+use services::tray_badge::Badge;
+use services::tray_support::TraySupport;
+use services::updater::{StagedUpdate, UpdatePackage, UpdaterService};
+use services::updates::{UpdateInfo, UpdateService};
+use services::usage::{UsageMeter, UsageRecord};
+use services::watcher::FileWatcher;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+#[cfg(desktop)]
 use tauri::{
-    menu::{Menu, MenuItem, PredefinedMenuItem},
+    image::Image,
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager, State, WindowEvent,
 };
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, WindowEvent};
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_opener::OpenerExt;
+
+/// Menu item ID prefix for the tray's profile entries
+#[cfg(desktop)]
+const PROFILE_MENU_PREFIX: &str = "profile:";
+
+/// How often the release feed is checked
+const UPDATE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
 
 /// Shared application state
 pub struct AppState {
-    pub token_service: Arc<Mutex<TokenService>>,
+    pub token_service: Arc<SharedTokenService>,
+    pub proxy_log: ProxyLog,
+    pub activity: ActivityLog,
+    /// Last Keycloak and endpoint check results
+    pub health: HealthLog,
+    pub usage: UsageMeter,
+    pub logs: LogBuffer,
+    /// Result of the last update check
+    pub update: std::sync::Mutex<Option<UpdateInfo>>,
+    /// Newer release the updater can install
+    pub update_package: std::sync::Mutex<Option<UpdatePackage>>,
+    /// Downloaded update waiting for "Restart to Update"
+    pub staged_update: std::sync::Mutex<Option<StagedUpdate>>,
+    pub update_downloading: AtomicBool,
+    /// Throttles auth failure and recovery notifications
+    pub notifications: NotificationGate,
+    /// Periodic token refresh is paused by the user
+    pub refresh_paused: AtomicBool,
+    /// Fallback endpoint in use, for the tray tooltip
+    pub fallback_endpoint: std::sync::Mutex<Option<String>>,
+    /// Files found holding plaintext credentials
+    pub advisories: std::sync::Mutex<Vec<SecretFinding>>,
+    /// No tray: the window stays available and closing it minimizes
+    pub window_mode: bool,
 }
 
-/// Get current token state
+/// Current token state without the access token
 #[tauri::command]
-async fn get_state(state: State<'_, AppState>) -> Result<TokenState, String> {
-    let service = state.token_service.lock().await;
-    Ok(service.state().clone())
+async fn get_state_public(state: State<'_, AppState>) -> Result<TokenStatePublic, String> {
+    Ok((&state.token_service.snapshot().state).into())
 }
 
-/// Get current configuration
+/// Current configuration with secrets reduced to `has*` flags
 #[tauri::command]
-async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
-    let service = state.token_service.lock().await;
-    Ok(service.config().clone())
+async fn get_config_public(state: State<'_, AppState>) -> Result<AppConfigPublic, String> {
+    record_activity(&state);
+    Ok((&state.token_service.snapshot().config).into())
+}
+
+/// Reset the vault's idle timer, unless the service is busy. Whatever holds
+/// it was started by the user and reset the timer itself.
+fn record_activity(state: &AppState) {
+    if let Some(mut service) = state.token_service.try_lock() {
+        service.record_activity();
+    }
 }
 
 /// Save OAuth configuration
@@ -50,6 +138,7 @@ async fn save_oauth_config(
     password: String,
 ) -> Result<(), String> {
     let mut service = state.token_service.lock().await;
+    service.record_activity();
     let result = service.save_oauth_setup(
         keycloak_url,
         realm,
@@ -61,7 +150,10 @@ async fn save_oauth_config(
         password,
     );
     update_tray_status(&app, service.state());
-    let _ = app.emit("token-state-changed", service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
     result.map_err(|e| e.to_string())
 }
 
@@ -75,9 +167,13 @@ async fn save_static_key_config(
     ghostllm_app: Option<String>,
 ) -> Result<(), String> {
     let mut service = state.token_service.lock().await;
+    service.record_activity();
     let result = service.save_static_key_setup(llm_endpoint, static_api_key, ghostllm_app);
     update_tray_status(&app, service.state());
-    let _ = app.emit("token-state-changed", service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
     result.map_err(|e| e.to_string())
 }
 
@@ -85,9 +181,13 @@ async fn save_static_key_config(
 #[tauri::command]
 async fn manual_refresh(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     let mut service = state.token_service.lock().await;
+    service.record_activity();
     let result = service.manual_refresh().await;
     update_tray_status(&app, service.state());
-    let _ = app.emit("token-state-changed", service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
     result.map_err(|e| e.to_string())
 }
 
@@ -97,28 +197,873 @@ async fn log_out(app: AppHandle, state: State<'_, AppState>) -> Result<(), Strin
     let mut service = state.token_service.lock().await;
     let result = service.log_out();
     update_tray_status(&app, service.state());
-    let _ = app.emit("token-state-changed", service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
+    result.map_err(|e| e.to_string())
+}
+
+/// Forget one stored secret without logging out
+#[tauri::command]
+async fn delete_credential(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    credential: CredentialKey,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let result = service.delete_credential(credential);
+    update_tray_status(&app, service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
+    result.map_err(|e| e.to_string())
+}
+
+/// Check if credentials are configured
+#[tauri::command]
+async fn has_credentials(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.token_service.snapshot().has_credentials)
+}
+
+/// Start the token refresh loop
+#[tauri::command]
+async fn start_refresh_loop(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    let result = service.start_refresh_loop().await;
+    update_tray_status(&app, service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
     result.map_err(|e| e.to_string())
 }
 
-/// Check if credentials are configured
+/// Return a raw secret after the user confirms with Touch ID / Windows Hello / polkit
+#[tauri::command]
+async fn reveal_secret(state: State<'_, AppState>, secret: SecretKind) -> Result<String, String> {
+    let mode = {
+        // Fail before prompting when there is nothing to reveal
+        let service = state.token_service.lock().await;
+        if service.is_locked() {
+            return Err("Unlock the credential vault first".to_string());
+        }
+        service.config().auth_mode
+    };
+
+    let reason = format!("reveal the Dymium {}", secret.label());
+    tauri::async_runtime::spawn_blocking(move || OsAuthService::confirm(&reason))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            AuditLog::record("reveal_secret", Some(mode), "denied", secret.label());
+            e.to_string()
+        })?;
+
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .reveal_secret(secret)
+        .ok_or_else(|| format!("No {} available", secret.label()))
+}
+
+/// Current access token and expiry for programmatic use (needs `tokenCommand`),
+/// after the same OS confirmation as `reveal_secret`
+#[tauri::command]
+async fn get_token(state: State<'_, AppState>) -> Result<AccessToken, String> {
+    let mode = {
+        // Fail before prompting when the command is off or there's no token
+        let service = state.token_service.lock().await;
+        service.access_token().map_err(|e| e.to_string())?;
+        service.config().auth_mode
+    };
+
+    let secret = SecretKind::AccessToken;
+    let reason = format!("reveal the Dymium {}", secret.label());
+    tauri::async_runtime::spawn_blocking(move || OsAuthService::confirm(&reason))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            AuditLog::record("get_token", Some(mode), "denied", secret.label());
+            e.to_string()
+        })?;
+
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let result = service.access_token().map_err(|e| e.to_string());
+    if result.is_ok() {
+        AuditLog::record("get_token", Some(mode), "success", secret.label());
+    }
+    result
+}
+
+/// Check an endpoint and key from the setup form without saving anything
+#[tauri::command]
+async fn test_llm_endpoint(
+    state: State<'_, AppState>,
+    endpoint: String,
+    key: String,
+    app: Option<String>,
+) -> Result<EndpointTest, String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .test_endpoint(&endpoint, &key, app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Try OAuth settings from the setup form (password grant, then endpoint
+/// check) without saving them or touching any files
+#[tauri::command]
+async fn validate_oauth_credentials(
+    state: State<'_, AppState>,
+    settings: OAuthSettings,
+) -> Result<Vec<ValidationStep>, String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    Ok(service.validate_oauth(settings).await)
+}
+
+/// Shell `export` lines for .bashrc/.envrc. With `inline`, the current token is
+/// embedded (after the same OS confirmation as `reveal_secret`); otherwise the
+/// snippet reads the token file.
+#[tauri::command]
+async fn get_shell_snippet(state: State<'_, AppState>, inline: bool) -> Result<String, String> {
+    if inline {
+        let reason = format!("reveal the Dymium {}", SecretKind::AccessToken.label());
+        tauri::async_runtime::spawn_blocking(move || OsAuthService::confirm(&reason))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let token = if inline {
+        Some(
+            service
+                .reveal_secret(SecretKind::AccessToken)
+                .ok_or_else(|| format!("No {} available", SecretKind::AccessToken.label()))?,
+        )
+    } else {
+        None
+    };
+    ShellService::snippet(service.config(), token.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Whether credentials are protected by a master password, and whether it is unlocked
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultStatus {
+    enabled: bool,
+    locked: bool,
+    auto_lock_minutes: u64,
+    /// The idle lock is engaged (see `unlock_idle`)
+    idle_locked: bool,
+}
+
+/// Get master-password vault status
+#[tauri::command]
+async fn get_vault_status(state: State<'_, AppState>) -> Result<VaultStatus, String> {
+    let service = state.token_service.lock().await;
+    Ok(VaultStatus {
+        enabled: service.config().vault_enabled,
+        locked: service.is_locked(),
+        auto_lock_minutes: service.config().vault_auto_lock_minutes,
+        idle_locked: service.is_idle_locked(),
+    })
+}
+
+/// This install's ID for the kill switch, for admins to disable it by
+#[tauri::command]
+async fn get_client_id() -> Result<String, String> {
+    RevocationService::client_id().map_err(|e| e.to_string())
+}
+
+/// Protect stored credentials with a master password
+#[tauri::command]
+async fn enable_vault(state: State<'_, AppState>, master_password: String) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .enable_vault(&master_password)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove the master password (credentials go back to config.json)
+#[tauri::command]
+async fn disable_vault(state: State<'_, AppState>, master_password: String) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .disable_vault(&master_password)
+        .map_err(|e| e.to_string())
+}
+
+/// Unlock stored credentials and authenticate
+#[tauri::command]
+async fn unlock_vault(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    master_password: String,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service
+        .unlock_vault(&master_password)
+        .map_err(|e| e.to_string())?;
+    update_tray_status(&app, service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
+
+    if service.has_credentials() {
+        let result = service.start_refresh_loop().await;
+        update_tray_status(&app, service.state());
+        let _ = app.emit(
+            "token-state-changed",
+            TokenStatePublic::from(service.state()),
+        );
+        result.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Release the idle lock and re-authenticate. Needs the master password
+/// with the vault enabled, OS authentication otherwise.
+#[tauri::command]
+async fn unlock_idle(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    master_password: Option<String>,
+) -> Result<(), String> {
+    let vault_enabled = state.token_service.lock().await.config().vault_enabled;
+    if !vault_enabled {
+        tauri::async_runtime::spawn_blocking(|| OsAuthService::confirm("unlock Dymium Provider"))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut service = state.token_service.lock().await;
+    service
+        .unlock_idle(master_password.as_deref())
+        .map_err(|e| e.to_string())?;
+    if service.has_credentials() {
+        let result = service.start_refresh_loop().await;
+        update_tray_status(&app, service.state());
+        let _ = app.emit(
+            "token-state-changed",
+            TokenStatePublic::from(service.state()),
+        );
+        result.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Lock stored credentials immediately
+#[tauri::command]
+async fn lock_vault(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.lock_vault();
+    update_tray_status(&app, service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
+    Ok(())
+}
+
+/// List the curated OpenCode model catalog
+#[tauri::command]
+async fn list_model_catalog(state: State<'_, AppState>) -> Result<Vec<ModelEntry>, String> {
+    let service = state.token_service.lock().await;
+    Ok(service.model_catalog().to_vec())
+}
+
+/// Models the LLM endpoint lists, from the cache unless it's stale or
+/// `force_refresh` is set
+#[tauri::command]
+async fn list_models(
+    state: State<'_, AppState>,
+    force_refresh: Option<bool>,
+) -> Result<ModelList, String> {
+    let mut service = state.token_service.lock().await;
+    service
+        .list_models(force_refresh.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Add a model to the catalog
+#[tauri::command]
+async fn add_catalog_model(state: State<'_, AppState>, model: ModelEntry) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .upsert_catalog_model(model, false)
+        .map_err(|e| e.to_string())
+}
+
+/// Edit a model already in the catalog
+#[tauri::command]
+async fn update_catalog_model(state: State<'_, AppState>, model: ModelEntry) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .upsert_catalog_model(model, true)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a model from the catalog
+#[tauri::command]
+async fn remove_catalog_model(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service.remove_catalog_model(&id).map_err(|e| e.to_string())
+}
+
+/// Opt out of (or back into) registering the Dymium plugin in opencode.json
+#[tauri::command]
+async fn set_skip_opencode_plugin(state: State<'_, AppState>, skip: bool) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_skip_opencode_plugin(skip)
+        .map_err(|e| e.to_string())
+}
+
+/// Whether the window stands in for a missing tray, so the UI minimizes
+/// instead of hiding and offers Quit
+#[tauri::command]
+async fn get_window_mode(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.window_mode)
+}
+
+/// Exit the app (the tray's Quit, for window mode)
+#[tauri::command]
+fn quit_app(app: AppHandle) {
+    app.exit(0);
+}
+
+/// Start the app at login
+#[tauri::command]
+async fn set_launch_at_login(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_launch_at_login(enabled)
+        .map_err(|e| e.to_string())
+}
+
+/// Keep the token out of opencode.json, leaving it to the auth plugin
+#[tauri::command]
+async fn set_skip_opencode_api_key(state: State<'_, AppState>, skip: bool) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_skip_opencode_api_key(skip)
+        .map_err(|e| e.to_string())
+}
+
+/// List every file the app writes to, per integration
+#[tauri::command]
+async fn get_managed_files(state: State<'_, AppState>) -> Result<Vec<ManagedFile>, String> {
+    let service = state.token_service.lock().await;
+    Ok(integrations::managed_files(service.config()))
+}
+
+/// Enable or disable a managed integration (e.g. "continueDev", "zed")
+#[tauri::command]
+async fn set_integration_enabled(
+    state: State<'_, AppState>,
+    id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_integration_enabled(&id, enabled)
+        .map_err(|e| e.to_string())
+}
+
+/// List backups of opencode.json or auth.json, newest first
+#[tauri::command]
+async fn list_opencode_backups(file: OpenCodeFile) -> Result<Vec<BackupInfo>, String> {
+    OpenCodeService::list_backups(file).map_err(|e| e.to_string())
+}
+
+/// Restore opencode.json or auth.json from a backup
+#[tauri::command]
+async fn restore_opencode_backup(file: OpenCodeFile, backup: String) -> Result<(), String> {
+    OpenCodeService::restore_backup(file, &backup).map_err(|e| e.to_string())
+}
+
+/// Unified diff of the changes the app would make to opencode.json / auth.json
+#[tauri::command]
+async fn preview_opencode_changes(state: State<'_, AppState>) -> Result<String, String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    OpenCodeService::preview_changes(service.config()).map_err(|e| e.to_string())
+}
+
+/// Recently proxied requests, oldest first (needs `proxy.logRequests`)
+#[tauri::command]
+async fn get_proxy_log(state: State<'_, AppState>) -> Result<Vec<ProxyLogEntry>, String> {
+    Ok(state.proxy_log.entries())
+}
+
+/// Forget recorded proxy requests
+#[tauri::command]
+async fn clear_proxy_log(state: State<'_, AppState>) -> Result<(), String> {
+    state.proxy_log.clear();
+    Ok(())
+}
+
+/// Add the proxy's generated HTTPS certificate to the user's trust store
+#[tauri::command]
+async fn trust_proxy_certificate() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(ProxyTls::trust_certificate)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Daily request and token totals per app and model for the last `days` days
+/// (all history if omitted)
+#[tauri::command]
+async fn get_usage_stats(
+    state: State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<Vec<UsageRecord>, String> {
+    Ok(state.usage.stats(days))
+}
+
+/// Save the current connection settings as a named profile
+#[tauri::command]
+async fn save_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let result = service.save_profile(&name);
+    update_tray_status(&app, service.state());
+    result.map_err(|e| e.to_string())
+}
+
+/// Delete a saved profile
+#[tauri::command]
+async fn delete_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let result = service.delete_profile(&name);
+    update_tray_status(&app, service.state());
+    result.map_err(|e| e.to_string())
+}
+
+/// Set (or with no profile, remove) the profile a project directory uses
+#[tauri::command]
+async fn set_workspace_profile(
+    state: State<'_, AppState>,
+    dir: String,
+    profile: Option<String>,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_workspace_profile(&dir, profile.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Switch to a saved profile and re-authenticate
+#[tauri::command]
+async fn switch_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    let result = activate_profile(&mut service, &name).await;
+    update_tray_status(&app, service.state());
+    let _ = app.emit(
+        "token-state-changed",
+        TokenStatePublic::from(service.state()),
+    );
+    result.map_err(|e| e.to_string())
+}
+
+/// Load a profile and authenticate with it, resyncing the managed files
+async fn activate_profile(service: &mut TokenService, name: &str) -> Result<(), TokenError> {
+    service.switch_profile(name)?;
+    if service.has_credentials() {
+        service.start_refresh_loop().await?;
+    }
+    Ok(())
+}
+
+/// Open the app's log directory (~/.dymium/logs) and the auth plugin's, if
+/// present, in the file manager
+#[tauri::command]
+async fn open_logs(app: AppHandle) -> Result<(), String> {
+    open_log_dirs(&app)
+}
+
+fn open_log_dirs(app: &AppHandle) -> Result<(), String> {
+    let app_dir = AppConfig::logs_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    let mut dirs = vec![app_dir];
+    match OpenCodeService::plugin_log_dir() {
+        Ok(dir) if dir.exists() => dirs.push(dir),
+        Ok(_) => {}
+        Err(e) => log::warn!("Plugin log directory unknown: {}", e),
+    }
+    for dir in dirs {
+        app.opener()
+            .open_path(dir.to_string_lossy(), None::<&str>)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Pause periodic token refresh, e.g. to silence outbound traffic
+#[tauri::command]
+async fn pause_refresh(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    set_refresh_paused(&app, &state.token_service.snapshot().state, true);
+    Ok(())
+}
+
+/// Resume periodic token refresh
+#[tauri::command]
+async fn resume_refresh(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    set_refresh_paused(&app, &state.token_service.snapshot().state, false);
+    Ok(())
+}
+
+/// Whether periodic token refresh is paused
+#[tauri::command]
+async fn is_refresh_paused(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.refresh_paused.load(Ordering::SeqCst))
+}
+
+fn set_refresh_paused(app: &AppHandle, token_state: &TokenState, paused: bool) {
+    let app_state = app.state::<AppState>();
+    if app_state.refresh_paused.swap(paused, Ordering::SeqCst) != paused {
+        log::info!(
+            "Token refresh {}",
+            if paused { "paused" } else { "resumed" }
+        );
+    }
+    update_tray_status(app, token_state);
+    let _ = app.emit("refresh-paused-changed", paused);
+}
+
+/// Outcome, HTTP status and latency of the last Keycloak token request and
+/// LLM endpoint verification
+#[tauri::command]
+async fn get_endpoint_health(state: State<'_, AppState>) -> Result<HealthReport, String> {
+    Ok(state.health.report())
+}
+
+/// Recent log lines at `level` ("error" … "trace") or more severe, newest
+/// first, `limit` (default 200) at a time from `offset`
+#[tauri::command]
+async fn get_logs(
+    state: State<'_, AppState>,
+    level: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<LogPage, String> {
+    state
+        .logs
+        .page(level.as_deref(), limit.unwrap_or(200), offset.unwrap_or(0))
+}
+
+/// Credential audit log, newest first, `limit` (default 200) at a time from
+/// `offset`, and whether its hash chain verifies
+#[tauri::command]
+async fn get_audit_log(limit: Option<usize>, offset: Option<usize>) -> Result<AuditReport, String> {
+    AuditLog::read(limit.unwrap_or(200), offset.unwrap_or(0)).map_err(|e| e.to_string())
+}
+
+/// Opt in to (or out of) anonymous telemetry, sent to `endpoint`
+#[tauri::command]
+async fn set_telemetry(
+    state: State<'_, AppState>,
+    enabled: bool,
+    endpoint: Option<String>,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_telemetry(enabled, endpoint)
+        .map_err(|e| e.to_string())
+}
+
+/// Change the outgoing proxy; `password` (if given) goes to the keystore,
+/// an empty one removes it
+#[tauri::command]
+async fn set_egress_proxy(
+    state: State<'_, AppState>,
+    egress_proxy: EgressProxyConfig,
+    password: Option<String>,
+) -> Result<(), String> {
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    service
+        .set_egress_proxy(egress_proxy, password)
+        .map_err(|e| e.to_string())
+}
+
+/// The proxy outgoing requests use, as resolved from the settings
+#[tauri::command]
+async fn get_egress_proxy(state: State<'_, AppState>) -> Result<EgressRoute, String> {
+    let service = state.token_service.lock().await;
+    Ok(service.egress_route())
+}
+
+/// The counts the next telemetry report would send
+#[tauri::command]
+async fn get_telemetry_report() -> Result<TelemetryReport, String> {
+    Telemetry::report().map_err(|e| e.to_string())
+}
+
+/// Change the log level ("error" … "trace") without a restart. Levels more
+/// verbose than info revert after `logLevelRevertMinutes`.
+#[tauri::command]
+async fn set_log_level(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    level: String,
+) -> Result<(), String> {
+    let level =
+        LevelFilter::from_str(&level).map_err(|_| format!("Unknown log level {}", level))?;
+    let mut service = state.token_service.lock().await;
+    service.record_activity();
+    apply_log_level(&app, &mut service, level)
+}
+
+/// Set, persist and show the log level
+fn apply_log_level(
+    app: &AppHandle,
+    service: &mut TokenService,
+    level: LevelFilter,
+) -> Result<(), String> {
+    app.state::<AppState>().logs.set_level(level);
+    let minutes = service.config().log_level_revert_minutes;
+    let until = (level > logs::DEFAULT_LEVEL && minutes > 0)
+        .then(|| Utc::now() + chrono::Duration::minutes(minutes as i64));
+    let persisted = (level != logs::DEFAULT_LEVEL).then(|| level.as_str().to_lowercase());
+    log::info!("Log level set to {}", level);
+    let result = service.set_log_level(persisted, until);
+    update_tray_status(app, service.state());
+    result.map_err(|e| e.to_string())
+}
+
+/// Ask the release feed whether a newer version is out
+#[tauri::command]
+async fn check_for_updates(app: AppHandle) -> Result<UpdateInfo, String> {
+    run_update_check(&app).await
+}
+
+/// Check for updates, remember the result and show it in the tray
+async fn run_update_check(app: &AppHandle) -> Result<UpdateInfo, String> {
+    let ts = app.state::<AppState>().token_service.clone();
+    let (feed_url, updater, channel, clients) = {
+        let service = ts.lock().await;
+        let config = service.config();
+        (
+            config.update_feed_url.clone(),
+            config.updater.clone(),
+            config.update_channel,
+            service.clients(),
+        )
+    };
+    let info = if UpdaterService::enabled(&updater) {
+        let (info, package) = UpdaterService::check(&clients, &updater, channel)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Ok(mut pending) = app.state::<AppState>().update_package.lock() {
+            *pending = package;
+        }
+        info
+    } else {
+        let feed_url = feed_url
+            .as_deref()
+            .unwrap_or(UpdateService::default_feed_url(channel));
+        UpdateService::check(feed_url, channel)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    if let Ok(mut update) = app.state::<AppState>().update.lock() {
+        *update = Some(info.clone());
+    }
+    update_tray_status(app, &ts.snapshot().state);
+    Ok(info)
+}
+
+/// Take updates from the stable or beta channel, and check it
+#[tauri::command]
+async fn set_update_channel(app: AppHandle, channel: UpdateChannel) -> Result<UpdateInfo, String> {
+    apply_update_channel(&app, channel).await
+}
+
+/// Save the channel, forget updates found on the other one and check again
+async fn apply_update_channel(
+    app: &AppHandle,
+    channel: UpdateChannel,
+) -> Result<UpdateInfo, String> {
+    let state = app.state::<AppState>();
+    {
+        let mut service = state.token_service.lock().await;
+        service.record_activity();
+        service
+            .set_update_channel(channel)
+            .map_err(|e| e.to_string())?;
+    }
+    if let Ok(mut staged) = state.staged_update.lock() {
+        *staged = None;
+    }
+    if let Ok(mut package) = state.update_package.lock() {
+        *package = None;
+    }
+    run_update_check(app).await
+}
+
+/// Download and verify the newer release found by the last check
+#[tauri::command]
+async fn download_update(app: AppHandle) -> Result<String, String> {
+    run_update_download(&app).await
+}
+
+/// Install the downloaded update and restart into it
+#[tauri::command]
+async fn install_update(app: AppHandle) -> Result<(), String> {
+    run_update_install(&app).await
+}
+
+/// Download the pending update, returning its version
+async fn run_update_download(app: &AppHandle) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let package = state
+        .update_package
+        .lock()
+        .ok()
+        .and_then(|package| package.clone())
+        .ok_or("No update to download")?;
+    if state.update_downloading.swap(true, Ordering::SeqCst) {
+        return Err("The update is already downloading".to_string());
+    }
+    let clients = state.token_service.lock().await.clients();
+    let result = UpdaterService::download(&clients, &package).await;
+    state.update_downloading.store(false, Ordering::SeqCst);
+    let staged = result.map_err(|e| e.to_string())?;
+    let version = staged.version.clone();
+    if let Ok(mut current) = state.staged_update.lock() {
+        *current = Some(staged);
+    }
+    show_notification(
+        app,
+        Urgency::Normal,
+        &format!("Dymium Provider {} is ready", version),
+        "Choose Restart to Update in the tray menu",
+    );
+    update_tray_status(app, &state.token_service.snapshot().state);
+    Ok(version)
+}
+
+/// Install the staged update and restart (or exit for the installer)
+async fn run_update_install(app: &AppHandle) -> Result<(), String> {
+    let staged = app
+        .state::<AppState>()
+        .staged_update
+        .lock()
+        .ok()
+        .and_then(|staged| staged.clone())
+        .ok_or("No update has been downloaded")?;
+    tauri::async_runtime::spawn_blocking(move || UpdaterService::install(&staged))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    if cfg!(windows) {
+        // The installer starts the new version
+        app.exit(0);
+        Ok(())
+    } else {
+        app.restart()
+    }
+}
+
+/// The tray's update item: restart into a downloaded update, download an
+/// installable one, or open the release page
+async fn run_update_action(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    if state.staged_update.lock().is_ok_and(|s| s.is_some()) {
+        return run_update_install(app).await;
+    }
+    if state.update_package.lock().is_ok_and(|p| p.is_some()) {
+        return run_update_download(app).await.map(|_| ());
+    }
+    let url = state
+        .update
+        .lock()
+        .ok()
+        .and_then(|u| u.as_ref().and_then(|info| info.url.clone()));
+    if let Some(url) = url {
+        app.opener()
+            .open_url(url, None::<&str>)
+            .map_err(|e| format!("Failed to open release page: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Files holding plaintext credentials
 #[tauri::command]
-async fn has_credentials(state: State<'_, AppState>) -> Result<bool, String> {
-    let service = state.token_service.lock().await;
-    Ok(service.has_credentials())
+async fn get_security_advisories(app: AppHandle) -> Result<Vec<SecretFinding>, String> {
+    let ts = app.state::<AppState>().token_service.clone();
+    let service = ts.lock().await;
+    let findings = service.secret_advisories();
+    set_advisories(&app, service.state(), findings.clone());
+    Ok(findings)
 }
 
-/// Start the token refresh loop
+/// Move plaintext credentials out of the files the app owns; returns what is
+/// left in the clear
 #[tauri::command]
-async fn start_refresh_loop(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    let mut service = state.token_service.lock().await;
-    let result = service.start_refresh_loop().await;
-    update_tray_status(&app, service.state());
-    let _ = app.emit("token-state-changed", service.state());
-    result.map_err(|e| e.to_string())
+async fn harden_secrets(app: AppHandle) -> Result<Vec<SecretFinding>, String> {
+    run_harden_secrets(&app).await.map_err(|e| e.to_string())
+}
+
+async fn run_harden_secrets(app: &AppHandle) -> Result<Vec<SecretFinding>, TokenError> {
+    let ts = app.state::<AppState>().token_service.clone();
+    let mut service = ts.lock().await;
+    service.record_activity();
+    let result = service.harden_secrets();
+    let findings = service.secret_advisories();
+    set_advisories(app, service.state(), findings.clone());
+    result.map(|_| findings)
+}
+
+/// Remember the advisory, update the tray and tell the UI
+fn set_advisories(app: &AppHandle, state: &TokenState, findings: Vec<SecretFinding>) {
+    if let Ok(mut advisories) = app.state::<AppState>().advisories.lock() {
+        *advisories = findings.clone();
+    }
+    update_tray_status(app, state);
+    let _ = app.emit("security-advisory", findings);
 }
 
 /// Build the tray menu
+#[cfg(desktop)]
 fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
     let status = MenuItem::with_id(
         app,
@@ -128,44 +1073,188 @@ fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
         None::<&str>,
     )?;
     let refresh = MenuItem::with_id(app, "refresh", "Refresh Now", true, None::<&str>)?;
+    let paused = app
+        .try_state::<AppState>()
+        .is_some_and(|state| state.refresh_paused.load(Ordering::SeqCst));
+    let pause = CheckMenuItem::with_id(
+        app,
+        "pause_refresh",
+        "Pause Token Refresh",
+        true,
+        paused,
+        None::<&str>,
+    )?;
     let separator1 = PredefinedMenuItem::separator(app)?;
     let setup = MenuItem::with_id(app, "setup", "Setup...", true, None::<&str>)?;
+    let open_logs = MenuItem::with_id(app, "open_logs", "Open Logs", true, None::<&str>)?;
+    let available = app
+        .try_state::<AppState>()
+        .and_then(|state| state.update.lock().ok().and_then(|u| u.clone()))
+        .filter(|info| info.update_available);
+    let latest = available
+        .as_ref()
+        .map(|info| info.latest_version.as_str())
+        .unwrap_or_default();
+    let (staged, installable) = app
+        .try_state::<AppState>()
+        .map(|state| {
+            (
+                state.staged_update.lock().is_ok_and(|s| s.is_some()),
+                state.update_package.lock().is_ok_and(|p| p.is_some()),
+            )
+        })
+        .unwrap_or_default();
+    let update_label = if staged {
+        format!("Restart to Update ({})", latest)
+    } else if installable {
+        format!("Download Update ({})...", latest)
+    } else {
+        format!("Update Available ({})...", latest)
+    };
+    let update = MenuItem::with_id(app, "update", update_label, true, None::<&str>)?;
+    let fixable = app
+        .try_state::<AppState>()
+        .and_then(|state| {
+            state
+                .advisories
+                .lock()
+                .ok()
+                .map(|a| a.iter().any(|f| f.fixable))
+        })
+        .unwrap_or(false);
+    let harden = MenuItem::with_id(
+        app,
+        "harden",
+        "Secure Stored Credentials",
+        true,
+        None::<&str>,
+    )?;
+    let debug_logging = CheckMenuItem::with_id(
+        app,
+        "debug_logging",
+        "Debug Logging",
+        true,
+        app.try_state::<AppState>()
+            .is_some_and(|state| state.logs.level() >= LevelFilter::Debug),
+        None::<&str>,
+    )?;
     let separator2 = PredefinedMenuItem::separator(app)?;
     let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
 
-    Menu::with_items(
+    // Read from disk: callers may hold the token service lock
+    let config = AppConfig::load();
+    // Greyed out when the org config locks the channel
+    let channel_locked = OrgConfig::load()
+        .locked()
+        .iter()
+        .any(|key| key == "updateChannel");
+    let beta_updates = CheckMenuItem::with_id(
         app,
-        &[&status, &refresh, &separator1, &setup, &separator2, &quit],
-    )
+        "beta_updates",
+        "Beta Updates",
+        !channel_locked,
+        config.update_channel == UpdateChannel::Beta,
+        None::<&str>,
+    )?;
+    let profiles = config
+        .profiles
+        .keys()
+        .map(|name| {
+            let active = config.active_profile.as_ref() == Some(name);
+            CheckMenuItem::with_id(
+                app,
+                format!("{}{}", PROFILE_MENU_PREFIX, name),
+                name,
+                true,
+                active,
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let profile_items: Vec<&dyn IsMenuItem<tauri::Wry>> = profiles
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+        .collect();
+    let profile_menu = Submenu::with_items(app, "Profile", true, &profile_items)?;
+
+    let activity = app
+        .try_state::<AppState>()
+        .map(|state| state.activity.recent())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|event| {
+            let label = format!(
+                "{}  {}",
+                event.timestamp.with_timezone(&Local).format("%H:%M"),
+                event.message
+            );
+            MenuItem::new(app, label, false, None::<&str>)
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let activity_items: Vec<&dyn IsMenuItem<tauri::Wry>> = activity
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+        .collect();
+    let activity_menu = Submenu::with_items(app, "Recent activity", true, &activity_items)?;
+
+    let mut items: Vec<&dyn IsMenuItem<tauri::Wry>> = vec![&status];
+    if available.is_some() {
+        items.push(&update);
+    }
+    if fixable {
+        items.push(&harden);
+    }
+    items.extend([&refresh as &dyn IsMenuItem<tauri::Wry>, &pause, &separator1]);
+    if !profiles.is_empty() {
+        items.push(&profile_menu);
+    }
+    if !activity.is_empty() {
+        items.push(&activity_menu);
+    }
+    items.extend([
+        &setup as &dyn IsMenuItem<tauri::Wry>,
+        &open_logs,
+        &debug_logging,
+        &beta_updates,
+        &separator2,
+        &quit,
+    ]);
+    Menu::with_items(app, &items)
 }
 
 /// Update tray menu status text
 fn update_tray_status(app: &AppHandle, state: &TokenState) {
+    notify_state_change(app, state);
+    #[cfg(desktop)]
+    update_tray_menu(app, state);
+    #[cfg(desktop)]
+    update_tray_tooltip(app);
+}
+
+/// Rebuild the tray menu and badge for `state`
+#[cfg(desktop)]
+fn update_tray_menu(app: &AppHandle, state: &TokenState) {
     let status_text = match state {
         TokenState::Idle => "Status: Not configured".to_string(),
-        TokenState::Authenticating => "Status: Connecting...".to_string(),
-        TokenState::Verifying => "Status: Verifying endpoint...".to_string(),
+        TokenState::Authenticating => {
+            last_known_status(app).unwrap_or_else(|| "Status: Connecting...".to_string())
+        }
+        TokenState::Verifying => {
+            last_known_status(app).unwrap_or_else(|| "Status: Verifying endpoint...".to_string())
+        }
         TokenState::Authenticated { expires_at, .. } => {
             format!("Status: Connected (expires {})", expires_at.format("%H:%M"))
         }
-        TokenState::Failed { error } => {
-            let normalized = error.to_lowercase();
-            if normalized.contains("401")
-                || normalized.contains("unauthorized")
-                || normalized.contains("invalid api key")
-                || normalized.contains("invalid oidc token")
-            {
-                "Status: Unauthorized".to_string()
-            } else if normalized.contains("timed out") {
-                "Status: Endpoint timeout".to_string()
-            } else if normalized.contains("cannot reach llm endpoint") {
-                "Status: Endpoint unreachable".to_string()
-            } else if normalized.contains("failed to update opencode config") {
-                "Status: OpenCode config error".to_string()
-            } else {
-                "Status: Error".to_string()
-            }
-        }
+        TokenState::Locked => "Status: Locked".to_string(),
+        TokenState::Failed { kind, .. } => format!("Status: {}", kind.label()),
+    };
+    let paused = app
+        .try_state::<AppState>()
+        .is_some_and(|state| state.refresh_paused.load(Ordering::SeqCst));
+    let status_text = if paused {
+        format!("{} (refresh paused)", status_text)
+    } else {
+        status_text
     };
 
     // Update the menu item text
@@ -179,34 +1268,197 @@ fn update_tray_status(app: &AppHandle, state: &TokenState) {
             }
             let _ = tray.set_menu(Some(menu));
         }
+
+        let badge = Badge::for_state(state);
+        let _ = tray.set_icon(Some(badge.render(&tray_icon())));
+        let _ = tray.set_icon_as_template(!badge.is_coloured());
+    }
+}
+
+/// Tray tooltip naming the fallback endpoint in use, if any, and the average
+/// round-trip time to the LLM endpoint
+#[cfg(desktop)]
+fn update_tray_tooltip(app: &AppHandle) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let mut details = Vec::new();
+    if let Some(fallback) = state
+        .fallback_endpoint
+        .lock()
+        .ok()
+        .and_then(|fallback| fallback.clone())
+    {
+        details.push(format!("fallback: {}", fallback));
+    }
+    if let Some(latency) = state.health.average_latency(EndpointKind::LlmEndpoint) {
+        details.push(format!("GhostLLM {} ms", latency));
+    }
+    let tooltip = if details.is_empty() {
+        "Dymium Provider".to_string()
+    } else {
+        format!("Dymium Provider ({})", details.join(", "))
+    };
+    if let Some(tray) = app.tray_by_id("main") {
+        let _ = tray.set_tooltip(Some(&tooltip));
+    }
+}
+
+/// Status from before the (re)authentication in progress, while it is
+/// worth showing: an unexpired token or the last failure
+#[cfg(desktop)]
+fn last_known_status(app: &AppHandle) -> Option<String> {
+    let state = app.try_state::<AppState>()?;
+    let last_known = state.token_service.snapshot().last_known.clone()?;
+    match last_known.status {
+        LastStatus::Authenticated => last_known
+            .expires_at
+            .filter(|expires_at| *expires_at > Utc::now())
+            .map(|expires_at| {
+                format!(
+                    "Status: Connected until {} (refreshing...)",
+                    expires_at.format("%H:%M")
+                )
+            }),
+        LastStatus::Failed => Some(format!(
+            "Status: {} (retrying...)",
+            last_known.kind.unwrap_or_default().label()
+        )),
+        LastStatus::Idle | LastStatus::Locked => None,
+    }
+}
+
+/// Tray icon from embedded PNG bytes (44x44 for retina displays)
+#[cfg(desktop)]
+fn tray_icon() -> Image<'static> {
+    Image::from_bytes(include_bytes!("../icons/tray-icon.png")).expect("Failed to load tray icon")
+}
+
+/// Notify when authentication breaks and when it recovers, throttled by
+/// `NotificationGate`
+fn notify_state_change(app: &AppHandle, state: &TokenState) {
+    let Some(app_state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let gate = &app_state.notifications;
+    match state {
+        TokenState::Failed { error, kind, .. } => {
+            if gate.failure(&AppConfig::load().notifications) {
+                show_notification(
+                    app,
+                    Urgency::Critical,
+                    &format!("GhostLLM authentication failed: {}", kind.label()),
+                    error,
+                );
+            }
+        }
+        TokenState::Authenticated { .. } => {
+            if gate.recovery() {
+                show_notification(
+                    app,
+                    Urgency::Normal,
+                    "GhostLLM connection restored",
+                    "Authentication succeeded and the endpoint is responding again",
+                );
+            }
+        }
+        TokenState::Idle | TokenState::Locked => gate.reset(),
+        TokenState::Authenticating | TokenState::Verifying => {}
+    }
+}
+
+/// Show and focus the setup window
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Show a desktop notification, unless quiet hours hold it back
+fn show_notification(app: &AppHandle, urgency: Urgency, title: &str, body: &str) {
+    if !NotificationGate::allows(&AppConfig::load().notifications, urgency) {
+        log::info!("Quiet hours: not showing notification '{}'", title);
+        return;
+    }
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Tell the user which endpoint the proxy or token service switched to,
+/// emitting `event` for the UI
+fn show_endpoint_switch(app: &AppHandle, active: &ActiveEndpoint, event: &str) {
+    let title = if active.is_primary() {
+        "Primary endpoint recovered"
+    } else {
+        "Switched to fallback endpoint"
+    };
+    if let Some(state) = app.try_state::<AppState>() {
+        if let Ok(mut fallback) = state.fallback_endpoint.lock() {
+            *fallback = (!active.is_primary()).then(|| active.base_url.clone());
+        }
     }
+    #[cfg(desktop)]
+    update_tray_tooltip(app);
+    show_notification(app, Urgency::Normal, title, &active.base_url);
+    let _ = app.emit(event, active);
+}
+
+/// Run the command-line command in `args`, if any, returning the exit code
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    cli::run(args)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::init();
+    // Before anything looks for ~/.dymium or the keystore
+    #[cfg(mobile)]
+    MobileService::init();
+
+    let logs = LogBuffer::init(&AppConfig::load().log_file);
+    CrashReporter::install(logs.clone());
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .plugin(tauri_plugin_notification::init())
+        .setup(move |app| {
             // Create the token service
-            let token_service = Arc::new(Mutex::new(TokenService::new()));
+            let token_service = TokenService::new();
+            let activity = token_service.activity();
+            let health = token_service.health();
+            let token_service = Arc::new(SharedTokenService::new(token_service));
+            let window_mode = TraySupport::window_mode(AppConfig::load().window_mode);
 
             // Store in app state
             app.manage(AppState {
                 token_service: token_service.clone(),
+                proxy_log: ProxyLog::default(),
+                activity,
+                health,
+                usage: UsageMeter::load(),
+                logs,
+                update: std::sync::Mutex::new(None),
+                update_package: std::sync::Mutex::new(None),
+                staged_update: std::sync::Mutex::new(None),
+                update_downloading: AtomicBool::new(false),
+                notifications: NotificationGate::default(),
+                refresh_paused: AtomicBool::new(false),
+                fallback_endpoint: std::sync::Mutex::new(None),
+                advisories: std::sync::Mutex::new(Vec::new()),
+                window_mode,
             });
 
-            // Build the tray menu
+            // Build the tray menu (there is no tray on mobile)
+            #[cfg(desktop)]
             let menu = build_tray_menu(app.handle())?;
 
-            // Load tray icon from embedded PNG bytes (44x44 for retina displays)
-            let icon_bytes = include_bytes!("../icons/tray-icon.png");
-            let icon =
-                tauri::image::Image::from_bytes(icon_bytes).expect("Failed to load tray icon");
+            #[cfg(desktop)]
+            let icon = tray_icon();
 
             // Create the tray icon
-            let _tray = TrayIconBuilder::with_id("main")
+            #[cfg(desktop)]
+            let tray = TrayIconBuilder::with_id("main")
                 .icon(icon)
                 .icon_as_template(true) // Use as template for macOS menu bar (respects dark/light mode)
                 .menu(&menu)
@@ -218,25 +1470,124 @@ pub fn run() {
                             let ts = token_service.clone();
                             tauri::async_runtime::spawn(async move {
                                 let mut service = ts.lock().await;
+                                service.record_activity();
                                 if let Err(e) = service.manual_refresh().await {
                                     log::error!("Manual refresh failed: {}", e);
                                 }
                                 // Update tray status
                                 update_tray_status(&app, service.state());
                                 // Emit event to frontend
-                                let _ = app.emit("token-state-changed", service.state());
+                                let _ = app.emit(
+                                    "token-state-changed",
+                                    TokenStatePublic::from(service.state()),
+                                );
+                            });
+                        }
+                        "setup" => show_main_window(app),
+                        "pause_refresh" => {
+                            let app = app.clone();
+                            let ts = token_service.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let service = ts.lock().await;
+                                let paused = app
+                                    .state::<AppState>()
+                                    .refresh_paused
+                                    .load(Ordering::SeqCst);
+                                set_refresh_paused(&app, service.state(), !paused);
+                            });
+                        }
+                        "debug_logging" => {
+                            let app = app.clone();
+                            let ts = token_service.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let debug =
+                                    app.state::<AppState>().logs.level() >= LevelFilter::Debug;
+                                let level = if debug {
+                                    logs::DEFAULT_LEVEL
+                                } else {
+                                    LevelFilter::Debug
+                                };
+                                let mut service = ts.lock().await;
+                                service.record_activity();
+                                if let Err(e) = apply_log_level(&app, &mut service, level) {
+                                    log::error!("Failed to save log level: {}", e);
+                                }
+                            });
+                        }
+                        "beta_updates" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let channel = match AppConfig::load().update_channel {
+                                    UpdateChannel::Stable => UpdateChannel::Beta,
+                                    UpdateChannel::Beta => UpdateChannel::Stable,
+                                };
+                                if let Err(e) = apply_update_channel(&app, channel).await {
+                                    log::warn!("Update check failed: {}", e);
+                                }
+                            });
+                        }
+                        "update" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = run_update_action(&app).await {
+                                    log::error!("Update failed: {}", e);
+                                    show_notification(&app, Urgency::Critical, "Update failed", &e);
+                                }
                             });
                         }
-                        "setup" => {
-                            // Show the setup window
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
+                        "harden" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                match run_harden_secrets(&app).await {
+                                    Ok(left) => show_notification(
+                                        &app,
+                                        Urgency::Normal,
+                                        "Stored credentials secured",
+                                        &match left.len() {
+                                            0 => "No plaintext credentials left".to_string(),
+                                            n => format!(
+                                                "{} file(s) still hold credentials OpenCode needs",
+                                                n
+                                            ),
+                                        },
+                                    ),
+                                    Err(e) => {
+                                        log::error!("Failed to secure stored credentials: {}", e);
+                                        show_notification(
+                                            &app,
+                                            Urgency::Critical,
+                                            "Could not secure credentials",
+                                            &e.to_string(),
+                                        );
+                                    }
+                                }
+                            });
+                        }
+                        "open_logs" => {
+                            if let Err(e) = open_log_dirs(app) {
+                                log::error!("Failed to open log directory: {}", e);
                             }
                         }
                         "quit" => {
                             app.exit(0);
                         }
+                        id if id.starts_with(PROFILE_MENU_PREFIX) => {
+                            let name = id[PROFILE_MENU_PREFIX.len()..].to_string();
+                            let app = app.clone();
+                            let ts = token_service.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let mut service = ts.lock().await;
+                                service.record_activity();
+                                if let Err(e) = activate_profile(&mut service, &name).await {
+                                    log::error!("Switching to profile {} failed: {}", name, e);
+                                }
+                                update_tray_status(&app, service.state());
+                                let _ = app.emit(
+                                    "token-state-changed",
+                                    TokenStatePublic::from(service.state()),
+                                );
+                            });
+                        }
                         _ => {}
                     }
                 })
@@ -250,15 +1601,418 @@ pub fn run() {
                         // Left click shows menu (default behavior)
                     }
                 })
-                .build(app)?;
+                .build(app);
+            #[cfg(desktop)]
+            match tray {
+                Ok(_) => {}
+                Err(e) if window_mode => log::warn!("Tray icon not created: {}", e),
+                Err(e) => return Err(e.into()),
+            }
+            if window_mode {
+                log::warn!("No system tray found; keeping the window available instead");
+                show_main_window(app.handle());
+            }
+
+            // Global shortcut to the setup window; the tray icon is easy to lose
+            #[cfg(desktop)]
+            {
+                use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+                app.handle().plugin(
+                    tauri_plugin_global_shortcut::Builder::new()
+                        .with_handler(|app, _shortcut, event| {
+                            if event.state == ShortcutState::Pressed {
+                                show_main_window(app);
+                            }
+                        })
+                        .build(),
+                )?;
+                if let Some(shortcut) = AppConfig::load().global_shortcut {
+                    if let Err(e) = app.global_shortcut().register(shortcut.as_str()) {
+                        log::warn!("Failed to register global shortcut {}: {}", shortcut, e);
+                    }
+                }
+            }
+
+            // Show the last known status right away; with saved credentials
+            // that is "Connecting..." until the initial authentication below
+            // finishes
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                if let Some(mut service) = ts.try_lock() {
+                    service.begin_authentication();
+                }
+                update_tray_status(app.handle(), &ts.snapshot().state);
+            }
+
+            // Persisted log level, and reverting it once its time is up
+            {
+                let config = AppConfig::load();
+                let expired = config
+                    .log_level_until
+                    .is_some_and(|until| until <= Utc::now());
+                match config.log_level.as_deref().map(LevelFilter::from_str) {
+                    Some(Ok(level)) if !expired => app.state::<AppState>().logs.set_level(level),
+                    Some(Err(_)) => log::warn!("Ignoring unknown logLevel in config"),
+                    _ => {}
+                }
+
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        {
+                            let mut service = ts.lock().await;
+                            let due = service
+                                .config()
+                                .log_level_until
+                                .is_some_and(|until| until <= Utc::now());
+                            if due {
+                                log::info!("Debug logging period over");
+                                if let Err(e) =
+                                    apply_log_level(&app_handle, &mut service, logs::DEFAULT_LEVEL)
+                                {
+                                    log::error!("Failed to save log level: {}", e);
+                                }
+                            }
+                        }
+                        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                    }
+                });
+            }
 
-            // Initialize tray status immediately from current in-memory state.
+            // Plaintext credential check
             {
                 let ts = app.state::<AppState>().token_service.clone();
                 let app_handle = app.handle().clone();
                 tauri::async_runtime::spawn(async move {
                     let service = ts.lock().await;
-                    update_tray_status(&app_handle, service.state());
+                    let findings = service.secret_advisories();
+                    if findings.iter().any(|f| f.fixable) {
+                        let files: Vec<&str> = findings
+                            .iter()
+                            .filter(|f| f.fixable)
+                            .filter_map(|f| Path::new(&f.path).file_name()?.to_str())
+                            .collect();
+                        log::warn!("Plaintext credentials found in {}", files.join(", "));
+                        show_notification(
+                            &app_handle,
+                            Urgency::Normal,
+                            "Plaintext credentials found",
+                            &format!(
+                                "{} hold credentials in the clear; choose Secure Stored Credentials in the tray menu",
+                                files.join(", ")
+                            ),
+                        );
+                    }
+                    set_advisories(&app_handle, service.state(), findings);
+
+                    let exposed = AppConfig::exposed_paths();
+                    if !exposed.is_empty() {
+                        let paths: Vec<String> =
+                            exposed.iter().map(|p| p.display().to_string()).collect();
+                        log::warn!("Readable by other users: {}", paths.join(", "));
+                        show_notification(
+                            &app_handle,
+                            Urgency::Normal,
+                            "Settings readable by others",
+                            &format!(
+                                "Other users on this computer can read {}; run chmod go-rwx on it",
+                                paths.join(", ")
+                            ),
+                        );
+                    }
+                });
+            }
+
+            // Keep the autostart entry in line with launchAtLogin
+            AutostartService::sync(AppConfig::load().launch_at_login);
+
+            // Daily update check
+            if AppConfig::load().update_check {
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let mut announced: Option<String> = None;
+                    loop {
+                        match run_update_check(&app_handle).await {
+                            Ok(info) if info.update_available => {
+                                if announced.as_ref() != Some(&info.latest_version) {
+                                    show_notification(
+                                        &app_handle,
+                                        Urgency::Normal,
+                                        &format!(
+                                            "Dymium Provider {} is available",
+                                            info.latest_version
+                                        ),
+                                        &format!("You are running {}", info.current_version),
+                                    );
+                                    announced = Some(info.latest_version);
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Update check failed: {}", e),
+                        }
+                        tokio::time::sleep(UPDATE_CHECK_INTERVAL).await;
+                    }
+                });
+            }
+
+            // Opt-in telemetry reports
+            tauri::async_runtime::spawn(Telemetry::send_loop());
+
+            // Upload crash reports left by earlier runs, if opted in
+            tauri::async_runtime::spawn(async {
+                if let Err(e) =
+                    CrashReporter::upload_pending(&AppConfig::load().crash_reports).await
+                {
+                    log::warn!("Crash reports not uploaded: {}", e);
+                }
+            });
+
+            // Auto-lock the credential vault and the idle lock
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                        let on_screen_lock = {
+                            let service = ts.lock().await;
+                            let policy = &service.config().idle_lock;
+                            policy.enabled && policy.on_screen_lock
+                        };
+                        let screen_locked = on_screen_lock
+                            && tauri::async_runtime::spawn_blocking(ScreenLock::is_locked)
+                                .await
+                                .unwrap_or(false);
+                        let mut service = ts.lock().await;
+                        if service.idle_lock_due(screen_locked) {
+                            service.idle_lock();
+                            update_tray_status(&app_handle, service.state());
+                            let _ = app_handle.emit(
+                                "token-state-changed",
+                                TokenStatePublic::from(service.state()),
+                            );
+                            show_notification(
+                                &app_handle,
+                                Urgency::Normal,
+                                "Dymium Provider locked",
+                                "The access token was removed. Open Setup to unlock.",
+                            );
+                        } else if service.auto_lock_due() {
+                            log::info!("Locking credential vault after inactivity");
+                            service.lock_vault();
+                            update_tray_status(&app_handle, service.state());
+                            let _ = app_handle.emit(
+                                "token-state-changed",
+                                TokenStatePublic::from(service.state()),
+                            );
+                        }
+                    }
+                });
+            }
+
+            // Remote kill switch
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        let mut service = ts.lock().await;
+                        let interval_minutes =
+                            service.config().revocation.interval_minutes.max(1);
+                        if service.check_revocation().await {
+                            match service.blocked() {
+                                Some(message) => show_notification(
+                                    &app_handle,
+                                    Urgency::Critical,
+                                    "Dymium Provider disabled",
+                                    message,
+                                ),
+                                None => {
+                                    if let Err(e) = service.start_refresh_loop().await {
+                                        log::error!("Failed to re-authenticate: {}", e);
+                                    }
+                                    show_notification(
+                                        &app_handle,
+                                        Urgency::Normal,
+                                        "Dymium Provider re-enabled",
+                                        "Your administrator lifted the block.",
+                                    );
+                                }
+                            }
+                            update_tray_status(&app_handle, service.state());
+                            let _ = app_handle.emit(
+                                "token-state-changed",
+                                TokenStatePublic::from(service.state()),
+                            );
+                        }
+                        drop(service);
+                        tokio::time::sleep(std::time::Duration::from_secs(interval_minutes * 60))
+                            .await;
+                    }
+                });
+            }
+
+            // Signed managed config
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        let mut service = ts.lock().await;
+                        match service.sync_managed_config().await {
+                            Ok(true) => {
+                                update_tray_status(&app_handle, service.state());
+                                let _ = app_handle.emit(
+                                    "config-changed",
+                                    AppConfigPublic::from(service.config()),
+                                );
+                            }
+                            Ok(false) => {}
+                            Err(e) => log::warn!("Managed config not updated: {}", e),
+                        }
+                        let interval_minutes =
+                            service.config().managed_config.interval_minutes.max(1);
+                        drop(service);
+                        tokio::time::sleep(std::time::Duration::from_secs(interval_minutes * 60))
+                            .await;
+                    }
+                });
+            }
+
+            // Re-apply the dymium sections if OpenCode or the user drops them
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                let watched = OpenCodeService::managed_paths()
+                    .map_err(|e| e.to_string())
+                    .and_then(|paths| FileWatcher::new(&paths).map_err(|e| e.to_string()));
+                match watched {
+                    Ok((watcher, mut changes)) => {
+                        tauri::async_runtime::spawn(async move {
+                            let _watcher = watcher;
+                            while changes.recv().await.is_some() {
+                                // Let the writer finish, then coalesce the burst
+                                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                                while changes.try_recv().is_ok() {}
+
+                                let service = ts.lock().await;
+                                match service.repair_opencode_config() {
+                                    Ok(repaired) if repaired.is_empty() => {}
+                                    Ok(repaired) => show_notification(
+                                        &app_handle,
+                                        Urgency::Normal,
+                                        "OpenCode config restored",
+                                        &format!(
+                                            "Re-applied Dymium settings removed from {}",
+                                            repaired.join(", ")
+                                        ),
+                                    ),
+                                    Err(e) => {
+                                        log::error!("Failed to re-apply OpenCode config: {}", e);
+                                        show_notification(
+                                            &app_handle,
+                                            Urgency::Critical,
+                                            "OpenCode config error",
+                                            &format!("Could not restore Dymium settings: {}", e),
+                                        );
+                                    }
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => log::warn!("Not watching OpenCode config for changes: {}", e),
+                }
+            }
+
+            // LLM endpoint failover
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let mut switches = ts.lock().await.endpoint_switches();
+                    while switches.changed().await.is_ok() {
+                        let active = switches.borrow_and_update().clone();
+                        if let Some(active) = active {
+                            show_endpoint_switch(&app_handle, &active, "endpoint-changed");
+                        }
+                    }
+                });
+            }
+
+            // Local authenticating proxy
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let proxy_log = app.state::<AppState>().proxy_log.clone();
+                let usage = app.state::<AppState>().usage.clone();
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let (config, upstream) = {
+                        let service = ts.lock().await;
+                        (service.config().clone(), service.upstream())
+                    };
+                    if config.proxy.enabled {
+                        let port = config.proxy.port;
+                        let (failover, mut switches) = Failover::new();
+                        tauri::async_runtime::spawn(async move {
+                            while switches.changed().await.is_ok() {
+                                let active = switches.borrow_and_update().clone();
+                                if let Some(active) = active {
+                                    show_endpoint_switch(
+                                        &app_handle,
+                                        &active,
+                                        "proxy-endpoint-changed",
+                                    );
+                                }
+                            }
+                        });
+                        if let Err(e) =
+                            ProxyService::serve(config, upstream, proxy_log, usage, failover).await
+                        {
+                            log::error!("Proxy on port {} stopped: {}", port, e);
+                        }
+                    }
+                });
+            }
+
+            // Token socket for local tools
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                let app_handle = app.handle().clone();
+                let on_change: StateListener = Arc::new(move |state: &TokenState| {
+                    update_tray_status(&app_handle, state);
+                    let _ = app_handle.emit("token-state-changed", TokenStatePublic::from(state));
+                });
+                tauri::async_runtime::spawn(async move {
+                    let (enabled, upstream) = {
+                        let service = ts.lock().await;
+                        (service.config().token_socket, service.upstream())
+                    };
+                    if enabled {
+                        if let Err(e) =
+                            TokenSocketService::serve(upstream, ts, Some(on_change)).await
+                        {
+                            log::error!("Token socket stopped: {}", e);
+                        }
+                    }
+                });
+            }
+
+            // Health-check endpoint for wrapper scripts
+            {
+                let ts = app.state::<AppState>().token_service.clone();
+                tauri::async_runtime::spawn(async move {
+                    let health_check = ts.lock().await.config().health_check.clone();
+                    if health_check.enabled {
+                        if let Err(e) = HealthCheckService::serve(health_check.port, ts).await {
+                            log::error!(
+                                "Health check on port {} stopped: {}",
+                                health_check.port,
+                                e
+                            );
+                        }
+                    }
                 });
             }
 
@@ -266,24 +2020,35 @@ pub fn run() {
             let app_handle = app.handle().clone();
             let ts = app.state::<AppState>().token_service.clone();
             tauri::async_runtime::spawn(async move {
+                // Always sync opencode.json and auth.json on startup, without
+                // holding up the service
+                let config = ts.snapshot().config.clone();
+                let synced = tauri::async_runtime::spawn_blocking(move || {
+                    OpenCodeService::ensure_dymium_provider(&config)
+                })
+                .await;
+                if let Ok(Err(e)) = synced {
+                    log::warn!("Failed to sync OpenCode config on startup: {}", e);
+                }
+
                 // --- Initial authentication ---
                 {
                     let mut service = ts.lock().await;
-
-                    // Always sync opencode.json and auth.json on startup
-                    let config = service.config().clone();
-                    if let Err(e) = OpenCodeService::ensure_dymium_provider(&config) {
-                        log::warn!("Failed to sync OpenCode config on startup: {}", e);
-                    }
-
                     if service.has_credentials() {
                         log::info!("Starting initial authentication...");
                         if let Err(e) = service.start_refresh_loop().await {
                             log::error!("Failed initial authentication: {}", e);
                         }
+                    } else if service.is_locked() {
+                        // Prompt for the master password
+                        log::info!("Credential vault is locked; waiting for unlock");
+                        show_main_window(&app_handle);
                     }
                     update_tray_status(&app_handle, service.state());
-                    let _ = app_handle.emit("token-state-changed", service.state());
+                    let _ = app_handle.emit(
+                        "token-state-changed",
+                        TokenStatePublic::from(service.state()),
+                    );
                 }
                 // Lock released here — periodic loop can proceed independently
 
@@ -292,8 +2057,16 @@ pub fn run() {
                 loop {
                     let interval_secs = {
                         let service = ts.lock().await;
-                        if !service.needs_refresh_loop() {
-                            // Not OAuth or not authenticated — park until
+                        let paused = app_handle
+                            .state::<AppState>()
+                            .refresh_paused
+                            .load(Ordering::SeqCst);
+                        let active = !paused && service.needs_refresh_loop();
+                        // Android stops background apps that show no notification
+                        #[cfg(target_os = "android")]
+                        MobileService::keep_alive(active);
+                        if !active {
+                            // Paused, not OAuth or not authenticated — park until
                             // something changes (manual refresh / re-save)
                             drop(service);
                             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
@@ -305,16 +2078,25 @@ pub fn run() {
                     tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
 
                     let mut service = ts.lock().await;
-                    if !service.needs_refresh_loop() {
+                    let paused = app_handle
+                        .state::<AppState>()
+                        .refresh_paused
+                        .load(Ordering::SeqCst);
+                    if paused || !service.needs_refresh_loop() {
                         continue;
                     }
                     match service.refresh_tick().await {
                         Ok(()) => {
                             update_tray_status(&app_handle, service.state());
-                            let _ = app_handle.emit("token-state-changed", service.state());
+                            let _ = app_handle.emit(
+                                "token-state-changed",
+                                TokenStatePublic::from(service.state()),
+                            );
                         }
                         Err(e) => {
                             log::error!("Periodic token refresh failed: {}", e);
+                            // Show the failure under Recent activity
+                            update_tray_status(&app_handle, service.state());
                             // Don't set Failed state — the existing token might
                             // still be valid until it actually expires. Just log.
                         }
@@ -327,19 +2109,81 @@ pub fn run() {
         .on_window_event(|window, event| {
             // Hide window instead of closing when user clicks X
             if let WindowEvent::CloseRequested { api, .. } = event {
-                window.hide().unwrap();
+                // Without a tray, a hidden window couldn't be brought back
+                let window_mode = window
+                    .try_state::<AppState>()
+                    .is_some_and(|state| state.window_mode);
+                if window_mode {
+                    let _ = window.minimize();
+                } else {
+                    window.hide().unwrap();
+                }
                 api.prevent_close();
             }
         })
         .invoke_handler(tauri::generate_handler![
-            get_state,
-            get_config,
+            get_state_public,
+            get_config_public,
             save_oauth_config,
             save_static_key_config,
             manual_refresh,
             log_out,
+            delete_credential,
             has_credentials,
             start_refresh_loop,
+            reveal_secret,
+            get_token,
+            test_llm_endpoint,
+            validate_oauth_credentials,
+            get_shell_snippet,
+            get_vault_status,
+            get_client_id,
+            enable_vault,
+            disable_vault,
+            unlock_vault,
+            lock_vault,
+            unlock_idle,
+            list_models,
+            list_model_catalog,
+            add_catalog_model,
+            update_catalog_model,
+            remove_catalog_model,
+            set_skip_opencode_plugin,
+            set_launch_at_login,
+            get_window_mode,
+            quit_app,
+            set_update_channel,
+            download_update,
+            install_update,
+            set_skip_opencode_api_key,
+            get_managed_files,
+            set_integration_enabled,
+            list_opencode_backups,
+            restore_opencode_backup,
+            preview_opencode_changes,
+            get_proxy_log,
+            clear_proxy_log,
+            get_usage_stats,
+            trust_proxy_certificate,
+            save_profile,
+            delete_profile,
+            set_workspace_profile,
+            switch_profile,
+            open_logs,
+            pause_refresh,
+            resume_refresh,
+            is_refresh_paused,
+            get_endpoint_health,
+            get_logs,
+            get_audit_log,
+            set_telemetry,
+            set_egress_proxy,
+            get_egress_proxy,
+            get_telemetry_report,
+            set_log_level,
+            check_for_updates,
+            get_security_advisories,
+            harden_secrets,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
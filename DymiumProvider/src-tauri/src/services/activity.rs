@@ -0,0 +1,55 @@
+//! Recent activity
+//!
+//! A short in-memory history of significant token events (refreshes,
+//! failures, verified endpoints, saved settings) for the tray's "Recent
+//! activity" submenu.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Events kept in memory
+const CAPACITY: usize = 5;
+/// Longest message kept, so menu items stay readable
+const MAX_MESSAGE_CHARS: usize = 80;
+
+/// One recorded event
+#[derive(Debug, Clone)]
+pub struct ActivityEvent {
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Shared ring buffer of recent events
+#[derive(Clone, Default)]
+pub struct ActivityLog {
+    events: Arc<Mutex<VecDeque<ActivityEvent>>>,
+}
+
+impl ActivityLog {
+    /// Record an event, dropping the oldest past capacity
+    pub fn record(&self, message: impl Into<String>) {
+        let mut message: String = message.into();
+        if let Some((cut, _)) = message.char_indices().nth(MAX_MESSAGE_CHARS) {
+            message.truncate(cut);
+            message.push('…');
+        }
+        if let Ok(mut events) = self.events.lock() {
+            if events.len() >= CAPACITY {
+                events.pop_front();
+            }
+            events.push_back(ActivityEvent {
+                timestamp: Utc::now(),
+                message,
+            });
+        }
+    }
+
+    /// Recent events, newest first
+    pub fn recent(&self) -> Vec<ActivityEvent> {
+        self.events
+            .lock()
+            .map(|e| e.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,167 @@
+//! Credential audit log
+//!
+//! Append-only record of credential events in ~/.dymium/audit.log, one JSON
+//! object per line. Entries never contain secret material.
+//!
+//! Each entry carries the SHA-256 of the previous one (`prevHash`) and its own
+//! (`hash`, over the entry with `hash` empty), so a line edited, removed or
+//! moved within the file breaks the chain and shows up in `AuditLog::read`.
+//! The hashes aren't keyed and nothing outside the file records the last
+//! one, so lines cut off the end, or a file rewritten with fresh hashes,
+//! still verify: the chain catches corruption and careless edits, not
+//! someone who can write the file. Lines written before the chain existed
+//! are reported but can't be verified.
+
+use crate::services::config::{AppConfig, AuthMode, ConfigError};
+use crate::services::files;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A single audit record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub action: String,
+    /// Auth mode at the time, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<AuthMode>,
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+    /// `hash` of the previous entry ("" for the first)
+    #[serde(default)]
+    pub prev_hash: String,
+    /// SHA-256 of this entry, hex
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Hash of the entry with `hash` left empty
+    fn digest(&self) -> Result<String, serde_json::Error> {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let digest = Sha256::digest(serde_json::to_vec(&unhashed)?);
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// The audit log with the result of checking its hash chain
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditReport {
+    /// Newest first
+    pub entries: Vec<AuditEntry>,
+    /// Entries in the file, across all pages
+    pub total: usize,
+    /// The hash chain verifies: no line was altered, removed or reordered
+    /// without the hashes after it being recomputed. Lines cut off the end
+    /// aren't detected.
+    pub intact: bool,
+    /// 1-based line where the chain first breaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<usize>,
+}
+
+/// Service for appending and reading credential audit records
+pub struct AuditLog;
+
+impl AuditLog {
+    /// Get the audit log path (~/.dymium/audit.log)
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        Ok(AppConfig::config_dir()?.join("audit.log"))
+    }
+
+    /// Append an entry. Failures are logged, never propagated — auditing must
+    /// not break the credential operation being audited.
+    pub fn record(action: &str, mode: Option<AuthMode>, outcome: &str, detail: &str) {
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            action: action.to_string(),
+            mode,
+            outcome: outcome.to_string(),
+            detail: detail.to_string(),
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        if let Err(e) = Self::append(entry) {
+            log::error!("Failed to write audit log entry '{}': {}", action, e);
+        }
+    }
+
+    fn append(mut entry: AuditEntry) -> Result<(), ConfigError> {
+        let path = Self::path()?;
+        // The app and the CLI may append at the same time
+        let _lock = files::lock(&path)?;
+
+        entry.prev_hash = files::read_optional(&path)?
+            .as_deref()
+            .and_then(|content| content.lines().rev().find(|l| !l.trim().is_empty()))
+            .and_then(|line| serde_json::from_str::<AuditEntry>(line).ok())
+            .map(|last| last.hash)
+            .unwrap_or_default();
+        entry.hash = entry.digest()?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        // Set restrictive permissions (Unix only)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
+    /// Up to `limit` entries after skipping `offset`, newest first, and
+    /// whether the whole chain verifies (see the module docs for what that
+    /// can't show)
+    pub fn read(limit: usize, offset: usize) -> Result<AuditReport, ConfigError> {
+        let content = files::read_optional(&Self::path()?)?.unwrap_or_default();
+        let mut entries = Vec::new();
+        let mut broken_at = None;
+        let mut prev_hash = String::new();
+        let mut chained = false;
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+                broken_at.get_or_insert(index + 1);
+                continue;
+            };
+            if entry.hash.is_empty() {
+                // Written before the chain existed; fine only before it starts
+                if chained {
+                    broken_at.get_or_insert(index + 1);
+                }
+            } else {
+                let valid = entry.prev_hash == prev_hash && entry.digest()? == entry.hash;
+                if !valid {
+                    broken_at.get_or_insert(index + 1);
+                }
+                chained = true;
+            }
+            prev_hash = entry.hash.clone();
+            entries.push(entry);
+        }
+
+        let total = entries.len();
+        let entries = entries.into_iter().rev().skip(offset).take(limit).collect();
+        Ok(AuditReport {
+            entries,
+            total,
+            intact: broken_at.is_none(),
+            broken_at,
+        })
+    }
+}
@@ -0,0 +1,364 @@
+//! Launch at login
+//!
+//! `launchAtLogin` starts the app when the user logs in:
+//! - Linux: an XDG autostart entry (~/.config/autostart/dymium-provider.desktop).
+//!   It runs what the user actually launched: the AppImage file (`$APPIMAGE`)
+//!   rather than its temporary mount point, or `flatpak run <id>` inside a
+//!   Flatpak, whose entry goes to the host's ~/.config/autostart (the
+//!   manifest needs `--filesystem=xdg-config/autostart:create`).
+//! - macOS: a login item registered with `SMAppService` (macOS 13+), or a
+//!   launch agent (~/Library/LaunchAgents/io.dymium.provider.plist) on older
+//!   versions and outside an app bundle. A translocated app (run from
+//!   Downloads without being moved) is refused, since its path is temporary.
+//!
+//! The registration is renewed at every start, so it follows an app moved
+//! between /Applications and ~/Applications or a moved AppImage.
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AutostartError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config directory not found")]
+    NoDirError,
+    #[cfg(target_os = "macos")]
+    #[error("Dymium Provider is running from a temporary location; move it to Applications first")]
+    Translocated,
+    #[cfg(target_os = "macos")]
+    #[error("Login item not registered: {0}")]
+    Registration(String),
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    #[error("Launch at login is only supported on Linux and macOS")]
+    Unsupported,
+}
+
+/// Service for starting the app at login
+pub struct AutostartService;
+
+impl AutostartService {
+    /// Register (`enabled`) or remove the login item
+    pub fn set(enabled: bool) -> Result<(), AutostartError> {
+        platform::set(enabled)
+    }
+
+    /// Bring the login item in line with `launchAtLogin` at startup.
+    /// Failures are logged only.
+    pub fn sync(enabled: bool) {
+        if let Err(e) = platform::sync(enabled) {
+            log::warn!("Failed to update launch at login: {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::AutostartError;
+    use std::path::PathBuf;
+
+    /// File name of the autostart entry
+    const DESKTOP_FILE: &str = "dymium-provider.desktop";
+
+    /// ~/.config/autostart/dymium-provider.desktop (on the host for Flatpaks)
+    fn entry_path() -> Result<PathBuf, AutostartError> {
+        let config_dir = if std::env::var_os("FLATPAK_ID").is_some() {
+            // XDG_CONFIG_HOME points into the sandbox
+            dirs::home_dir().map(|home| home.join(".config"))
+        } else {
+            dirs::config_dir()
+        };
+        config_dir
+            .map(|dir| dir.join("autostart").join(DESKTOP_FILE))
+            .ok_or(AutostartError::NoDirError)
+    }
+
+    /// Command line that starts this app
+    fn exec() -> Result<String, AutostartError> {
+        if let Ok(id) = std::env::var("FLATPAK_ID") {
+            return Ok(format!("flatpak run {}", quote(&id)));
+        }
+        let exe = match std::env::var_os("APPIMAGE") {
+            Some(appimage) => PathBuf::from(appimage),
+            None => std::env::current_exe()?,
+        };
+        Ok(quote(&exe.to_string_lossy()))
+    }
+
+    /// Contents of the autostart entry
+    fn entry() -> Result<String, AutostartError> {
+        Ok(format!(
+            "[Desktop Entry]
+Type=Application
+Name=Dymium Provider
+Comment=GhostLLM token refresh for OpenCode
+Exec={}
+Icon=dymium-provider
+Terminal=false
+X-GNOME-Autostart-enabled=true
+",
+            exec()?
+        ))
+    }
+
+    pub fn set(enabled: bool) -> Result<(), AutostartError> {
+        let path = entry_path()?;
+        if !enabled {
+            return match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    log::info!("Removed {}", path.display());
+                    Ok(())
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e.into()),
+            };
+        }
+        let entry = entry()?;
+        if std::fs::read_to_string(&path).is_ok_and(|current| current == entry) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, entry)?;
+        log::info!("Wrote {}", path.display());
+        Ok(())
+    }
+
+    pub fn sync(enabled: bool) -> Result<(), AutostartError> {
+        set(enabled)
+    }
+
+    /// Quote an Exec argument per the Desktop Entry spec, if it needs it
+    fn quote(arg: &str) -> String {
+        const RESERVED: &[char] = &[
+            ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#',
+            '(', ')', '`',
+        ];
+        if !arg.contains(RESERVED) {
+            return arg.replace('%', "%%");
+        }
+        let mut quoted = String::from("\"");
+        for c in arg.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        // `%` starts a field code even inside quotes, and string escaping is
+        // applied to the value before the quoting above
+        quoted.replace('%', "%%").replace('\\', "\\\\")
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::AutostartError;
+    use crate::services::config::AppConfig;
+    use objc2::msg_send;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::NSError;
+    use std::path::{Path, PathBuf};
+
+    #[link(name = "ServiceManagement", kind = "framework")]
+    unsafe extern "C" {}
+
+    /// Bundle identifier, also the launch agent's label
+    const BUNDLE_ID: &str = "io.dymium.provider";
+
+    /// `SMAppServiceStatus` values
+    const STATUS_ENABLED: isize = 1;
+    const STATUS_REQUIRES_APPROVAL: isize = 2;
+
+    /// The .app bundle this binary runs from, if any
+    fn bundle_path() -> Result<Option<PathBuf>, AutostartError> {
+        let exe = std::env::current_exe()?;
+        Ok(exe
+            .ancestors()
+            .find(|p| p.extension().is_some_and(|ext| ext == "app"))
+            .map(Path::to_path_buf))
+    }
+
+    fn is_translocated(bundle: &Path) -> bool {
+        bundle.to_string_lossy().contains("/AppTranslocation/")
+    }
+
+    /// Where the bundle path of the last `SMAppService` registration is kept
+    fn marker_path() -> Result<PathBuf, AutostartError> {
+        AppConfig::config_dir()
+            .map(|dir| dir.join("login-item"))
+            .map_err(|_| AutostartError::NoDirError)
+    }
+
+    fn agent_path() -> Result<PathBuf, AutostartError> {
+        dirs::home_dir()
+            .map(|home| {
+                home.join("Library")
+                    .join("LaunchAgents")
+                    .join(format!("{}.plist", BUNDLE_ID))
+            })
+            .ok_or(AutostartError::NoDirError)
+    }
+
+    /// `SMAppService.mainAppService`, on macOS 13 and later
+    fn main_app_service() -> Option<Retained<AnyObject>> {
+        let class = AnyClass::get(c"SMAppService")?;
+        unsafe { msg_send![class, mainAppService] }
+    }
+
+    fn status(service: &AnyObject) -> isize {
+        unsafe { msg_send![service, status] }
+    }
+
+    fn registration_error(e: Retained<NSError>) -> AutostartError {
+        AutostartError::Registration(e.localizedDescription().to_string())
+    }
+
+    fn register(service: &AnyObject, bundle: &Path) -> Result<(), AutostartError> {
+        let marker = marker_path()?;
+        let registered = std::fs::read_to_string(&marker).ok();
+        let current = bundle.to_string_lossy();
+        let moved = registered.as_deref().is_some_and(|path| path != current);
+        if moved {
+            // The login item still points at the old location
+            unregister(service)?;
+        } else if status(service) == STATUS_ENABLED {
+            return Ok(());
+        }
+        let result: Result<(), Retained<NSError>> =
+            unsafe { msg_send![service, registerAndReturnError: _] };
+        result.map_err(registration_error)?;
+        if let Some(parent) = marker.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&marker, current.as_bytes())?;
+        if status(service) == STATUS_REQUIRES_APPROVAL {
+            log::warn!("Allow Dymium Provider in System Settings > General > Login Items");
+        }
+        log::info!("Registered login item for {}", current);
+        Ok(())
+    }
+
+    fn unregister(service: &AnyObject) -> Result<(), AutostartError> {
+        if matches!(status(service), STATUS_ENABLED | STATUS_REQUIRES_APPROVAL) {
+            let result: Result<(), Retained<NSError>> =
+                unsafe { msg_send![service, unregisterAndReturnError: _] };
+            result.map_err(registration_error)?;
+            log::info!("Unregistered login item");
+        }
+        remove(&marker_path()?)
+    }
+
+    /// Launch agent that opens the app by bundle ID (so it survives moves),
+    /// or runs this binary outside a bundle
+    fn agent(bundle: Option<&Path>) -> Result<String, AutostartError> {
+        let arguments = match bundle {
+            Some(_) => vec![
+                "/usr/bin/open".to_string(),
+                "-b".to_string(),
+                BUNDLE_ID.to_string(),
+            ],
+            None => vec![std::env::current_exe()?.to_string_lossy().into_owned()],
+        };
+        let arguments: String = arguments
+            .iter()
+            .map(|a| format!("        <string>{}</string>\n", escape(a)))
+            .collect();
+        Ok(format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+{}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            BUNDLE_ID, arguments
+        ))
+    }
+
+    fn write_agent(bundle: Option<&Path>) -> Result<(), AutostartError> {
+        let path = agent_path()?;
+        let agent = agent(bundle)?;
+        if std::fs::read_to_string(&path).is_ok_and(|current| current == agent) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, agent)?;
+        log::info!("Wrote {}", path.display());
+        Ok(())
+    }
+
+    fn remove(path: &Path) -> Result<(), AutostartError> {
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set(enabled: bool) -> Result<(), AutostartError> {
+        let bundle = bundle_path()?;
+        let service = main_app_service();
+        if !enabled {
+            if let Some(service) = &service {
+                unregister(service)?;
+            }
+            return remove(&agent_path()?);
+        }
+        if bundle.as_deref().is_some_and(is_translocated) {
+            return Err(AutostartError::Translocated);
+        }
+        match (&bundle, &service) {
+            (Some(bundle), Some(service)) => {
+                register(service, bundle)?;
+                // Replaced by the login item
+                remove(&agent_path()?)
+            }
+            _ => write_agent(bundle.as_deref()),
+        }
+    }
+
+    pub fn sync(enabled: bool) -> Result<(), AutostartError> {
+        if enabled && bundle_path()?.as_deref().is_some_and(is_translocated) {
+            log::warn!("Not renewing launch at login from a translocated app");
+            return Ok(());
+        }
+        if !enabled && !marker_path()?.exists() && !agent_path()?.exists() {
+            return Ok(());
+        }
+        set(enabled)
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use super::AutostartError;
+
+    pub fn set(enabled: bool) -> Result<(), AutostartError> {
+        if enabled {
+            Err(AutostartError::Unsupported)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn sync(_enabled: bool) -> Result<(), AutostartError> {
+        Ok(())
+    }
+}
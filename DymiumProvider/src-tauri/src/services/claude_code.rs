@@ -0,0 +1,101 @@
+//! Claude Code integration
+//!
+//! Sets `ANTHROPIC_BASE_URL` and `ANTHROPIC_AUTH_TOKEN` in the `env` block of
+//! ~/.claude/settings.json so Claude Code talks to GhostLLM's
+//! Anthropic-compatible route with the current token. Other settings are left
+//! untouched.
+
+use crate::services::config::AppConfig;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::jsonc;
+use crate::services::opencode::OpenCodeService;
+use serde_json::json;
+use std::path::PathBuf;
+use thiserror::Error;
+
+const BASE_URL_KEY: &[&str] = &["env", "ANTHROPIC_BASE_URL"];
+const AUTH_TOKEN_KEY: &[&str] = &["env", "ANTHROPIC_AUTH_TOKEN"];
+
+#[derive(Error, Debug)]
+pub enum ClaudeCodeError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Home directory not found")]
+    NoHomeDir,
+}
+
+/// Service for managing Claude Code settings
+pub struct ClaudeCodeService;
+
+impl ClaudeCodeService {
+    /// Get the Claude Code user settings path (~/.claude/settings.json)
+    pub fn settings_path() -> Result<PathBuf, ClaudeCodeError> {
+        dirs::home_dir()
+            .map(|p| p.join(".claude/settings.json"))
+            .ok_or(ClaudeCodeError::NoHomeDir)
+    }
+
+    /// Anthropic base URL: the Anthropic SDK appends `/v1/messages` itself,
+    /// so the OpenAI-style `/v1` suffix is dropped.
+    pub fn base_url(config: &AppConfig) -> String {
+        let url = OpenCodeService::compute_base_url(config);
+        url.strip_suffix("/v1").unwrap_or(&url).to_string()
+    }
+
+    /// Point Claude Code at GhostLLM with the current token
+    pub fn sync(config: &AppConfig, token: &str) -> Result<(), ClaudeCodeError> {
+        let path = Self::settings_path()?;
+        let edits = [
+            (BASE_URL_KEY, Some(json!(Self::base_url(config)))),
+            (AUTH_TOKEN_KEY, Some(json!(token))),
+        ];
+        if jsonc::update_file(&path, &edits)? {
+            log::info!("Updated {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Remove the Dymium env vars (on log out / mode switch)
+    pub fn clear() {
+        let result = Self::settings_path().and_then(|path| {
+            let edits = [(BASE_URL_KEY, None), (AUTH_TOKEN_KEY, None)];
+            Ok(jsonc::update_file(&path, &edits)?)
+        });
+        match result {
+            Ok(true) => log::info!("Cleared Dymium settings from Claude Code"),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to clear Claude Code settings: {}", e),
+        }
+    }
+}
+
+impl Integration for ClaudeCodeService {
+    fn id(&self) -> &'static str {
+        "claudeCode"
+    }
+
+    fn name(&self) -> &'static str {
+        "Claude Code"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::settings_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "env.ANTHROPIC_BASE_URL and env.ANTHROPIC_AUTH_TOKEN"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.token)?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+
+    /// The env vars may have been set by hand before the integration was enabled
+    fn owns_entries(&self) -> bool {
+        false
+    }
+}
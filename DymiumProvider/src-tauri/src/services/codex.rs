@@ -0,0 +1,142 @@
+//! Codex CLI integration
+//!
+//! Maintains `[model_providers.dymium]` (GhostLLM base URL, key read from the
+//! `DYMIUM_API_KEY` environment variable) and a `[profiles.dymium]` using it in
+//! ~/.codex/config.toml, so `codex --profile dymium` talks to GhostLLM. The
+//! rest of the file, comments included, is left as it was. Codex reads keys
+//! only from the environment; the shell snippet, direnv helper and env file
+//! all export `DYMIUM_API_KEY`.
+
+use crate::services::config::AppConfig;
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use std::path::PathBuf;
+use thiserror::Error;
+use toml_edit::{table, value, DocumentMut, Item};
+
+/// Provider and profile name in config.toml
+const PROVIDER_ID: &str = "dymium";
+/// Environment variable Codex reads the key from
+pub const API_KEY_ENV: &str = "DYMIUM_API_KEY";
+
+#[derive(Error, Debug)]
+pub enum CodexError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("TOML error: {0}")]
+    TomlError(#[from] toml_edit::TomlError),
+    #[error("Home directory not found")]
+    NoHomeDir,
+}
+
+/// Service for managing Codex CLI configuration
+pub struct CodexService;
+
+impl CodexService {
+    /// Get the Codex config path (~/.codex/config.toml)
+    pub fn config_path() -> Result<PathBuf, CodexError> {
+        dirs::home_dir()
+            .map(|p| p.join(".codex/config.toml"))
+            .ok_or(CodexError::NoHomeDir)
+    }
+
+    /// Write the Dymium provider and profile. The profile uses the first model.
+    pub fn sync(config: &AppConfig, model_ids: &[String]) -> Result<(), CodexError> {
+        let path = Self::config_path()?;
+        let original = files::read_optional(&path)?;
+        let mut doc: DocumentMut = original.as_deref().unwrap_or_default().parse()?;
+
+        let provider = Self::subtable(&mut doc, "model_providers");
+        provider["name"] = value("Dymium");
+        provider["base_url"] = value(OpenCodeService::compute_base_url(config));
+        provider["env_key"] = value(API_KEY_ENV);
+        provider["wire_api"] = value("chat");
+
+        if let Some(model) = model_ids.first() {
+            let profile = Self::subtable(&mut doc, "profiles");
+            profile["model_provider"] = value(PROVIDER_ID);
+            profile["model"] = value(model.as_str());
+        }
+
+        let content = doc.to_string();
+        if original.as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+        files::write_with_backup(&path, content.as_bytes())?;
+        log::info!("Updated {}", path.display());
+        Ok(())
+    }
+
+    /// Remove the Dymium provider and profile (on log out / mode switch)
+    pub fn clear() {
+        if let Err(e) = Self::do_clear() {
+            log::error!("Failed to clear Codex config: {}", e);
+        }
+    }
+
+    fn do_clear() -> Result<(), CodexError> {
+        let path = Self::config_path()?;
+        let Some(original) = files::read_optional(&path)? else {
+            return Ok(());
+        };
+        let mut doc: DocumentMut = original.parse()?;
+
+        let mut removed = false;
+        for parent in ["model_providers", "profiles"] {
+            if let Some(t) = doc.get_mut(parent).and_then(Item::as_table_mut) {
+                removed |= t.remove(PROVIDER_ID).is_some();
+                if t.is_empty() {
+                    doc.remove(parent);
+                }
+            }
+        }
+        if removed {
+            files::write_with_backup(&path, doc.to_string().as_bytes())?;
+            log::info!("Cleared Dymium provider from {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// `[<parent>.dymium]`, created if missing. The parent stays implicit so no
+    /// bare `[<parent>]` header is written.
+    fn subtable<'a>(doc: &'a mut DocumentMut, parent: &str) -> &'a mut Item {
+        let parent = doc.entry(parent).or_insert_with(table);
+        if !parent.is_table() {
+            *parent = table();
+        }
+        let parent = parent.as_table_mut().unwrap();
+        parent.set_implicit(true);
+        let entry = parent.entry(PROVIDER_ID).or_insert_with(table);
+        if !entry.is_table() {
+            *entry = table();
+        }
+        entry
+    }
+}
+
+impl Integration for CodexService {
+    fn id(&self) -> &'static str {
+        "codex"
+    }
+
+    fn name(&self) -> &'static str {
+        "Codex CLI"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::config_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "[model_providers.dymium] and [profiles.dymium]"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, &ctx.model_ids())?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
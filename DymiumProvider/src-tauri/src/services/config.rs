@@ -1,9 +1,16 @@
 //! Configuration management
 //!
-//! Handles loading and saving configuration from ~/.dymium/config.json
+//! Handles loading and saving configuration from ~/.dymium/config.json,
+//! layered over the organization's base file (see `org_config`)
 
-use chrono::{DateTime, Utc};
+use crate::services::config_template::ConfigTemplate;
+use crate::services::error_code::{ErrorCode, FailureKind};
+use crate::services::files;
+use crate::services::org_config::OrgConfig;
+use crate::services::token::TokenError;
+use chrono::{DateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -32,6 +39,8 @@ pub enum AuthMode {
 ///   Idle → Authenticating → Verifying → Authenticated
 ///                         ↘ Failed    ↗ Failed
 ///
+///   Locked → Idle (after the master-password vault is unlocked)
+///
 /// "Authenticated" means the token is valid AND the LLM endpoint responded.
 /// "Failed" includes the specific reason (auth error, connection error, etc.)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,7 +59,18 @@ pub enum TokenState {
         expires_at: DateTime<Utc>,
     },
     /// Something is wrong — descriptive error for the user
-    Failed { error: String },
+    Failed {
+        error: String,
+        /// Class of failure, for scripts and monitoring
+        #[serde(default, skip_deserializing)]
+        code: ErrorCode,
+        /// What went wrong, for the tray and the setup window
+        #[serde(default)]
+        kind: FailureKind,
+    },
+    /// Credentials are encrypted in the vault and need the master password,
+    /// or the idle lock is waiting for an unlock
+    Locked,
 }
 
 impl Default for TokenState {
@@ -60,6 +80,15 @@ impl Default for TokenState {
 }
 
 impl TokenState {
+    /// Failed with `e`
+    pub fn failed(e: &TokenError) -> Self {
+        Self::Failed {
+            error: e.to_string(),
+            code: ErrorCode::from(e),
+            kind: FailureKind::from(e),
+        }
+    }
+
     pub fn is_authenticated(&self) -> bool {
         matches!(self, Self::Authenticated { .. })
     }
@@ -75,6 +104,670 @@ impl TokenState {
     pub fn is_failed(&self) -> bool {
         matches!(self, Self::Failed { .. })
     }
+
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Self::Locked)
+    }
+}
+
+/// [`TokenState`] without the access token, for the webview
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TokenStatePublic {
+    Idle,
+    Authenticating,
+    Verifying,
+    #[serde(rename_all = "camelCase")]
+    Authenticated {
+        expires_at: DateTime<Utc>,
+    },
+    Failed {
+        error: String,
+        code: ErrorCode,
+        kind: FailureKind,
+    },
+    Locked,
+}
+
+impl From<&TokenState> for TokenStatePublic {
+    fn from(state: &TokenState) -> Self {
+        match state {
+            TokenState::Idle => Self::Idle,
+            TokenState::Authenticating => Self::Authenticating,
+            TokenState::Verifying => Self::Verifying,
+            TokenState::Authenticated { expires_at, .. } => Self::Authenticated {
+                expires_at: *expires_at,
+            },
+            TokenState::Failed { error, code, kind } => Self::Failed {
+                error: error.clone(),
+                code: *code,
+                kind: *kind,
+            },
+            TokenState::Locked => Self::Locked,
+        }
+    }
+}
+
+/// Secrets that can be revealed to the UI after OS authentication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SecretKind {
+    /// The current access token (OAuth) or static key in use
+    AccessToken,
+    /// The configured static API key
+    StaticApiKey,
+    /// The Keycloak client secret, for editing it in the setup form
+    ClientSecret,
+    /// The Keycloak password, for editing it in the setup form
+    Password,
+}
+
+impl SecretKind {
+    /// Human-readable name for prompts and errors
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::AccessToken => "access token",
+            Self::StaticApiKey => "static API key",
+            Self::ClientSecret => "client secret",
+            Self::Password => "Keycloak password",
+        }
+    }
+}
+
+/// The secret fields of [`AppConfig`], stored outside config.json when possible
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Secrets {
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub static_api_key: Option<String>,
+}
+
+impl Secrets {
+    /// Collect the secret fields from a config
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            client_secret: config.client_secret.clone(),
+            password: config.password.clone(),
+            refresh_token: config.refresh_token.clone(),
+            static_api_key: config.static_api_key.clone(),
+        }
+    }
+
+    /// Copy the secrets back into a config
+    pub fn apply(self, config: &mut AppConfig) {
+        config.client_secret = self.client_secret;
+        config.password = self.password;
+        config.refresh_token = self.refresh_token;
+        config.static_api_key = self.static_api_key;
+    }
+
+    /// Remove every secret field from a config
+    pub fn scrub(config: &mut AppConfig) {
+        Self::default().apply(config);
+    }
+
+    /// Whether any secret is set
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.client_secret,
+            &self.password,
+            &self.refresh_token,
+            &self.static_api_key,
+        ]
+        .iter()
+        .all(|v| v.as_deref().map(str::is_empty).unwrap_or(true))
+    }
+}
+
+/// A curated OpenCode model entry. Unset fields fall back to what the
+/// endpoint reports (or OpenCode defaults).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelEntry {
+    /// Model ID as served by GhostLLM
+    pub id: String,
+    /// Display name in OpenCode
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Context window in tokens
+    #[serde(default)]
+    pub context_limit: Option<u64>,
+    /// Maximum output tokens
+    #[serde(default)]
+    pub output_limit: Option<u64>,
+    /// Whether the model emits reasoning content
+    #[serde(default)]
+    pub reasoning: Option<bool>,
+}
+
+/// Per-integration enable flags, keyed by integration ID (see
+/// `integrations::REGISTRY`). Integrations are opt-in: missing means disabled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct IntegrationsConfig(BTreeMap<String, bool>);
+
+impl IntegrationsConfig {
+    /// Whether the integration with this ID is enabled
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.0.get(id).copied().unwrap_or(false)
+    }
+
+    /// Enable or disable the integration with this ID
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) {
+        self.0.insert(id.to_string(), enabled);
+    }
+}
+
+/// API an upstream speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpstreamApi {
+    /// OpenAI-compatible chat completions (GhostLLM's default)
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Anthropic Messages API
+    Anthropic,
+}
+
+/// Sends requests for matching models to another GhostLLM app or base URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyRoute {
+    /// Model name, or a prefix ending in `*` (e.g. "claude-*")
+    pub model: String,
+    /// GhostLLM app to use instead of `ghostllmApp`
+    #[serde(default)]
+    pub app: Option<String>,
+    /// Base URL to use instead of the endpoint (takes precedence over `app`)
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// API the upstream speaks; requests are translated to it
+    #[serde(default)]
+    pub api: UpstreamApi,
+}
+
+impl ProxyRoute {
+    /// Whether this route applies to `model`
+    pub fn matches(&self, model: &str) -> bool {
+        match self.model.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => model == self.model,
+        }
+    }
+}
+
+/// Local authenticating proxy (see `proxy`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxyConfig {
+    /// Start the proxy with the app
+    pub enabled: bool,
+    /// Port on 127.0.0.1
+    pub port: u16,
+    /// Serve HTTPS instead of HTTP
+    pub tls: bool,
+    /// PEM certificate to serve; a localhost certificate is generated if unset
+    pub tls_cert_path: Option<String>,
+    /// PEM private key for `tls_cert_path`
+    pub tls_key_path: Option<String>,
+    /// Record each request (model, status, latency, token usage)
+    pub log_requests: bool,
+    /// Also record request and response bodies (prompts and completions)
+    pub log_bodies: bool,
+    /// Requests allowed per minute before answering 429 (0 = unlimited)
+    pub requests_per_minute: u32,
+    /// Requests allowed in flight at once (0 = unlimited)
+    pub max_concurrent_requests: u32,
+    /// Model names rewritten before forwarding, e.g. "gpt-4o" → "claude-sonnet-4"
+    pub model_aliases: BTreeMap<String, String>,
+    /// Per-model upstream overrides, applied after aliasing; first match wins
+    pub routes: Vec<ProxyRoute>,
+    /// Further GhostLLM endpoints (like `llmEndpoint`) to fail over to, in order
+    pub fallback_endpoints: Vec<String>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 4141,
+            tls: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            log_requests: false,
+            log_bodies: false,
+            requests_per_minute: 0,
+            max_concurrent_requests: 0,
+            model_aliases: BTreeMap::new(),
+            routes: Vec::new(),
+            fallback_endpoints: Vec::new(),
+        }
+    }
+}
+
+impl ProxyConfig {
+    /// Base URL clients use to reach the proxy
+    pub fn local_base_url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://127.0.0.1:{}/v1", scheme, self.port)
+    }
+}
+
+/// Certificate trust for Keycloak and the LLM endpoint (see `tls_trust`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TlsTrustConfig {
+    /// PEM files with further CA certificates to trust
+    pub extra_ca_certs: Vec<String>,
+    /// Hosts whose certificates aren't verified, e.g. "keycloak.dev.local"
+    /// or "*.dev.local"; "*" for all
+    pub insecure_hosts: Vec<String>,
+    /// SHA-256 hashes of the public keys (SPKI) accepted per host, as
+    /// "sha256/<base64>"; the same host patterns as `insecure_hosts`
+    pub pins: BTreeMap<String, Vec<String>>,
+    /// Refuse `http://` URLs for Keycloak and the LLM endpoint
+    pub https_only: bool,
+    /// Hosts still reachable over plain HTTP with `https_only`, e.g. lab
+    /// servers; the same host patterns as `insecure_hosts`
+    pub plain_http_hosts: Vec<String>,
+}
+
+/// Where outgoing requests get their proxy from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum EgressProxyMode {
+    /// Environment variables, then the OS proxy settings (including PAC)
+    #[default]
+    System,
+    /// `egressProxy.url`
+    Manual,
+    /// The PAC script at `egressProxy.pacUrl`
+    Pac,
+    /// Always connect directly
+    Direct,
+}
+
+/// Proxy for requests to Keycloak and the LLM endpoint (see `egress_proxy`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EgressProxyConfig {
+    pub mode: EgressProxyMode,
+    /// Proxy URL for `manual`, e.g. "http://proxy.corp:3128"
+    pub url: Option<String>,
+    /// PAC script for `pac` (http(s):// or file://)
+    pub pac_url: Option<String>,
+    /// Username for proxy authentication; the password is in the keystore
+    pub username: Option<String>,
+    /// Further hosts to reach directly, e.g. "keycloak.corp" or ".internal"
+    pub no_proxy: Vec<String>,
+}
+
+/// HTTP version spoken to a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum HttpVersion {
+    /// HTTP/1.1 only, which port-forwards and some gateways need
+    #[default]
+    Http1,
+    /// HTTP/2 where TLS negotiates it, HTTP/1.1 otherwise
+    Auto,
+    /// HTTP/2 without negotiation (also over plain http://)
+    Http2,
+}
+
+/// Connection settings for one kind of server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpProfile {
+    /// Limit for a whole request, in seconds
+    pub timeout_secs: u64,
+    /// Limit for opening a connection, in seconds
+    pub connect_timeout_secs: u64,
+    /// Close pooled connections idle this long, in seconds (0 = don't reuse)
+    pub idle_timeout_secs: u64,
+    /// TCP keep-alive interval, in seconds (0 = off)
+    pub tcp_keepalive_secs: u64,
+    pub http_version: HttpVersion,
+    /// Proxy for this kind of server instead of `egressProxy`
+    pub egress_proxy: Option<EgressProxyConfig>,
+}
+
+impl Default for HttpProfile {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            idle_timeout_secs: 90,
+            tcp_keepalive_secs: 0,
+            http_version: HttpVersion::default(),
+            egress_proxy: None,
+        }
+    }
+}
+
+/// Connection settings for Keycloak and the LLM endpoint (see `tls_trust`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpConfig {
+    /// Keycloak, and the managed config, revocation and update servers
+    pub keycloak: HttpProfile,
+    /// The LLM endpoint and its fallbacks
+    pub llm: HttpProfile,
+}
+
+/// Slower refreshes on battery or a metered connection (see `PowerService`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PowerSavingConfig {
+    /// Stretch the refresh interval while on battery or metered
+    pub enabled: bool,
+    /// How many times longer the refresh interval gets
+    pub interval_multiplier: u64,
+    /// Skip the endpoint check on refresh and the fallback probes
+    pub skip_verification: bool,
+}
+
+impl Default for PowerSavingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_multiplier: 5,
+            skip_verification: true,
+        }
+    }
+}
+
+/// Local readiness endpoint (see `health_check`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HealthCheckConfig {
+    /// Serve `/healthz` and `/readyz` with the app
+    pub enabled: bool,
+    /// Port on 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 4142,
+        }
+    }
+}
+
+/// Idle lock for shared workstations (see `TokenService::idle_lock_due`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IdleLockConfig {
+    /// Purge the token and require an unlock after `idle_hours`
+    pub enabled: bool,
+    /// Hours without user activity in the app before locking
+    pub idle_hours: u64,
+    /// Also lock as soon as the screen is locked
+    pub on_screen_lock: bool,
+}
+
+impl Default for IdleLockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_hours: 8,
+            on_screen_lock: false,
+        }
+    }
+}
+
+/// Remote kill switch (see `RevocationService`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RevocationConfig {
+    /// URL answering whether this client is disabled; unset turns polling off
+    pub url: Option<String>,
+    /// Minutes between checks
+    pub interval_minutes: u64,
+}
+
+impl Default for RevocationConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            interval_minutes: 15,
+        }
+    }
+}
+
+/// Org config fetched from a URL (see `ManagedConfigService`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ManagedConfig {
+    /// URL of the config; its signature is at the same URL plus ".minisig"
+    pub url: Option<String>,
+    /// minisign public key the config must be signed with
+    pub public_key: Option<String>,
+    /// Minutes between fetches
+    pub interval_minutes: u64,
+}
+
+impl Default for ManagedConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            public_key: None,
+            interval_minutes: 60,
+        }
+    }
+}
+
+/// Which releases updates come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    /// Pre-releases as well
+    Beta,
+}
+
+/// Signed updates in the Tauri updater format (see `UpdaterService`)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UpdaterConfig {
+    /// Updater manifest (`latest.json`) of the releases
+    pub manifest_url: Option<String>,
+    /// Manifest of the beta channel, which otherwise uses `manifest_url`
+    pub beta_manifest_url: Option<String>,
+    /// Public key from `tauri signer generate`, for builds without one
+    /// built in. Only read from the org file.
+    pub public_key: Option<String>,
+}
+
+/// Whether the setup window stands in for the tray (see `tray_support`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowMode {
+    /// When the desktop has no tray
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Line format of the log files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    /// One JSON object per line
+    #[default]
+    Json,
+    /// `ts=... level=... target=... msg="..."`
+    KeyValue,
+}
+
+/// Log files under ~/.dymium/logs (see `log_file`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogFileConfig {
+    /// Write the app's log lines to ~/.dymium/logs/dymium.log
+    pub enabled: bool,
+    pub format: LogFormat,
+    /// Start a new file once the current one reaches this size
+    pub max_size_mb: u64,
+    /// Start a new file each day (UTC)
+    pub rotate_daily: bool,
+    /// Rotated files kept, newest first
+    pub max_files: usize,
+    /// Rotated files older than this are deleted (0 = no age limit)
+    pub max_age_days: u64,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            format: LogFormat::Json,
+            max_size_mb: 10,
+            rotate_daily: true,
+            max_files: 10,
+            max_age_days: 30,
+        }
+    }
+}
+
+/// Local time range, e.g. 22:00 to 07:00 (may span midnight)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// Start, "HH:MM"
+    pub start: String,
+    /// End, "HH:MM"
+    pub end: String,
+}
+
+/// Desktop notification preferences (see `notifications`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationConfig {
+    /// Hold back non-critical notifications during these hours
+    pub quiet_hours: Option<QuietHours>,
+    /// Minimum minutes between auth failure notifications (0 = one per
+    /// failure streak)
+    pub failure_cooldown_minutes: u64,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            quiet_hours: None,
+            failure_cooldown_minutes: 30,
+        }
+    }
+}
+
+/// Anonymous usage reports (see `telemetry`); off unless the user opts in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TelemetryConfig {
+    /// Count auth outcomes and send them daily
+    pub enabled: bool,
+    /// Where reports are POSTed; nothing is sent without one
+    pub endpoint: Option<String>,
+}
+
+/// Crash report uploads (see `crash`); off unless the user opts in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CrashReportConfig {
+    /// Upload saved crash reports on the next start
+    pub enabled: bool,
+    /// Where reports are POSTed; nothing is sent without one
+    pub endpoint: Option<String>,
+}
+
+impl NotificationConfig {
+    /// Whether `time` falls in the configured quiet hours
+    pub fn is_quiet_at(&self, time: NaiveTime) -> bool {
+        let Some(quiet) = &self.quiet_hours else {
+            return false;
+        };
+        let parse = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").ok();
+        let (Some(start), Some(end)) = (parse(&quiet.start), parse(&quiet.end)) else {
+            log::warn!(
+                "Ignoring quiet hours {}-{}: use HH:MM",
+                quiet.start,
+                quiet.end
+            );
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            // Overnight, e.g. 22:00-07:00
+            time >= start || time < end
+        }
+    }
+}
+
+/// Named connection settings to switch between. Credentials are shared by
+/// all profiles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    #[serde(default)]
+    pub llm_endpoint: String,
+    #[serde(default)]
+    pub keycloak_url: String,
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub realm: String,
+    #[serde(default)]
+    pub ghostllm_app: Option<String>,
+}
+
+impl Profile {
+    /// Capture the connection settings of a config
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            auth_mode: config.auth_mode,
+            llm_endpoint: config.llm_endpoint.clone(),
+            keycloak_url: config.keycloak_url.clone(),
+            client_id: config.client_id.clone(),
+            username: config.username.clone(),
+            realm: config.realm.clone(),
+            ghostllm_app: config.ghostllm_app.clone(),
+        }
+    }
+
+    /// Copy the connection settings into a config
+    pub fn apply(&self, config: &mut AppConfig) {
+        config.auth_mode = self.auth_mode;
+        config.llm_endpoint = self.llm_endpoint.clone();
+        config.keycloak_url = self.keycloak_url.clone();
+        config.client_id = self.client_id.clone();
+        config.username = self.username.clone();
+        config.realm = self.realm.clone();
+        config.ghostllm_app = self.ghostllm_app.clone();
+    }
+
+    /// Whether tokens issued for `other` are valid here (same Keycloak client
+    /// and user)
+    pub fn same_identity(&self, other: &Self) -> bool {
+        self.keycloak_url == other.keycloak_url
+            && self.realm == other.realm
+            && self.client_id == other.client_id
+            && self.username == other.username
+    }
 }
 
 /// Application configuration
@@ -89,10 +782,24 @@ pub struct AppConfig {
     #[serde(default)]
     pub llm_endpoint: String,
 
+    /// Backup GhostLLM endpoints, used in order while `llm_endpoint` is down
+    #[serde(default)]
+    pub llm_fallback_endpoints: Vec<String>,
+
+    /// Extra headers for the LLM endpoint, e.g. "X-Org-Id" or Cloudflare
+    /// Access service tokens; also written into the OpenCode provider
+    #[serde(default)]
+    pub llm_headers: BTreeMap<String, String>,
+
     // --- OAuth mode fields ---
     #[serde(default)]
     pub keycloak_url: String,
 
+    /// Further Keycloak base URLs serving the same realm (e.g. a DR
+    /// replica), tried in order when `keycloak_url` can't be reached
+    #[serde(default)]
+    pub keycloak_fallback_urls: Vec<String>,
+
     #[serde(default)]
     pub client_id: String,
 
@@ -109,40 +816,303 @@ pub struct AppConfig {
     #[serde(default)]
     pub ghostllm_app: Option<String>,
 
-    // OAuth credentials (stored in config for portability, will add keyring later)
+    /// Further GhostLLM apps, each written to OpenCode as its own
+    /// `dymium-<app>` provider sharing the token (OAuth mode)
     #[serde(default)]
+    pub ghostllm_extra_apps: Vec<String>,
+
+    // OAuth credentials (stored in config for portability, will add keyring later)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub client_secret: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<String>,
 
     // --- Static API Key mode fields ---
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub static_api_key: Option<String>,
+
+    // --- Master-password vault ---
+    /// Keep credentials encrypted in ~/.dymium/vault.json instead of this file
+    #[serde(default)]
+    pub vault_enabled: bool,
+
+    /// Lock the vault after this many minutes without user activity (0 = never)
+    #[serde(default = "default_vault_auto_lock")]
+    pub vault_auto_lock_minutes: u64,
+
+    /// Secrets live in the system keystore rather than this file
+    #[serde(default)]
+    pub secrets_in_keystore: bool,
+
+    /// Curated models written to opencode.json. When empty, the models the
+    /// endpoint lists in /v1/models are used instead.
+    #[serde(default)]
+    pub model_catalog: Vec<ModelEntry>,
+
+    /// How long `list_models` serves the last /v1/models answer before
+    /// asking the endpoint again, in minutes (0 = always ask)
+    #[serde(default = "default_models_cache_ttl")]
+    pub models_cache_ttl_minutes: u64,
+
+    /// Manage only the provider entry and auth.json; never touch the
+    /// `plugin` array in opencode.json
+    #[serde(default)]
+    pub skip_opencode_plugin: bool,
+
+    /// Leave `options.apiKey` out of opencode.json; the auth plugin reads
+    /// the token from auth.json on every request instead
+    #[serde(default)]
+    pub skip_opencode_api_key: bool,
+
+    /// Other tools kept pointed at GhostLLM with the current token
+    #[serde(default)]
+    pub integrations: IntegrationsConfig,
+
+    /// Where the `envFile` integration writes (default ~/.dymium/ghostllm.env)
+    #[serde(default)]
+    pub env_file_path: Option<String>,
+
+    /// Extra CAs and hosts allowed to use self-signed certificates
+    #[serde(default)]
+    pub tls_trust: TlsTrustConfig,
+
+    /// Proxy for outgoing requests, e.g. an authenticated corporate proxy
+    #[serde(default)]
+    pub egress_proxy: EgressProxyConfig,
+
+    /// Timeouts, connection reuse, HTTP version and proxy per kind of server
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Refresh cadence on battery or a metered connection
+    #[serde(default)]
+    pub power_saving: PowerSavingConfig,
+
+    /// IP addresses for host names missing from DNS, e.g.
+    /// "llm.lab.home" → "10.0.0.5"
+    #[serde(default)]
+    pub host_overrides: BTreeMap<String, String>,
+
+    /// Local proxy that injects the token into forwarded requests
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+
+    /// Serve the token over ~/.dymium/token.sock (a named pipe on Windows)
+    #[serde(default)]
+    pub token_socket: bool,
+
+    /// Never write the access token to disk: ~/.dymium/token is not kept,
+    /// OpenCode goes through the local proxy and other integrations are
+    /// not synced. Tools get the token from the socket or the proxy.
+    #[serde(default)]
+    pub socket_only: bool,
+
+    /// `GET /healthz` and `/readyz` on localhost
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+
+    /// Lock after inactivity or on screen lock, requiring an unlock
+    #[serde(default)]
+    pub idle_lock: IdleLockConfig,
+
+    /// Poll an admin endpoint for a "disable this client" signal
+    #[serde(default)]
+    pub revocation: RevocationConfig,
+
+    /// Signed org config to fetch from a URL
+    #[serde(default)]
+    pub managed_config: ManagedConfig,
+
+    /// Start the app when the user logs in (Linux and macOS)
+    #[serde(default)]
+    pub launch_at_login: bool,
+
+    /// Keep the window available instead of relying on the tray
+    #[serde(default)]
+    pub window_mode: WindowMode,
+
+    /// Allow the `get_token` command to return the access token
+    #[serde(default)]
+    pub token_command: bool,
+
+    /// Level of the in-memory log (see `logs`), e.g. "debug"; info if unset
+    #[serde(default)]
+    pub log_level: Option<String>,
+
+    /// When a more verbose `log_level` reverts to info
+    #[serde(default)]
+    pub log_level_until: Option<DateTime<Utc>>,
+
+    /// Minutes until debug/trace logging reverts to info (0 = never)
+    #[serde(default = "default_log_level_revert")]
+    pub log_level_revert_minutes: u64,
+
+    /// Rotating log files; read at startup
+    #[serde(default)]
+    pub log_file: LogFileConfig,
+
+    /// Check the release feed for a newer version daily
+    #[serde(default = "default_true")]
+    pub update_check: bool,
+
+    /// Release feed to check instead of the GitHub releases of the app
+    #[serde(default)]
+    pub update_feed_url: Option<String>,
+
+    /// Download and install updates from the tray
+    #[serde(default)]
+    pub updater: UpdaterConfig,
+
+    /// Stable releases only, or pre-releases too
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+
+    /// Quiet hours and throttling for desktop notifications
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+
+    /// Opt-in anonymous telemetry
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Opt-in crash report uploads
+    #[serde(default)]
+    pub crash_reports: CrashReportConfig,
+
+    /// Global shortcut that opens the setup window (null disables it)
+    #[serde(default = "default_global_shortcut")]
+    pub global_shortcut: Option<String>,
+
+    /// Saved connection settings by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Profile the current settings were loaded from
+    #[serde(default)]
+    pub active_profile: Option<String>,
+
+    /// Project directories and the profile each one's opencode.json uses
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, String>,
+}
+
+/// [`AppConfig`] with the secrets replaced by whether they are set, for the
+/// webview. The values stay behind `reveal_secret`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppConfigPublic {
+    #[serde(flatten)]
+    pub config: AppConfig,
+    pub has_client_secret: bool,
+    pub has_password: bool,
+    pub has_refresh_token: bool,
+    pub has_static_api_key: bool,
+    /// Keys set by the organization that the user can't change
+    pub locked_keys: Vec<String>,
+}
+
+impl From<&AppConfig> for AppConfigPublic {
+    fn from(config: &AppConfig) -> Self {
+        let is_set = |v: &Option<String>| v.as_deref().is_some_and(|s| !s.is_empty());
+        let mut public = config.clone();
+        Secrets::scrub(&mut public);
+        Self {
+            config: public,
+            has_client_secret: is_set(&config.client_secret),
+            has_password: is_set(&config.password),
+            has_refresh_token: is_set(&config.refresh_token),
+            has_static_api_key: is_set(&config.static_api_key),
+            locked_keys: OrgConfig::load().locked().to_vec(),
+        }
+    }
 }
 
 fn default_refresh_interval() -> u64 {
     60
 }
 
+fn default_vault_auto_lock() -> u64 {
+    15
+}
+
+fn default_models_cache_ttl() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_log_level_revert() -> u64 {
+    60
+}
+
+fn default_global_shortcut() -> Option<String> {
+    Some("CommandOrControl+Shift+D".to_string())
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             auth_mode: AuthMode::OAuth,
             llm_endpoint: "http://spoofcorp.llm.dymium.home:9090/v1".to_string(),
             keycloak_url: "https://192.168.50.100:9173".to_string(),
+            keycloak_fallback_urls: Vec::new(),
             client_id: "dymium".to_string(),
             username: "dev_mcp_admin@dymium.io".to_string(),
             realm: "dymium".to_string(),
             refresh_interval_seconds: 60,
             ghostllm_app: None,
+            ghostllm_extra_apps: Vec::new(),
+            llm_fallback_endpoints: Vec::new(),
+            llm_headers: BTreeMap::new(),
             client_secret: None,
             password: None,
             refresh_token: None,
             static_api_key: None,
+            vault_enabled: false,
+            vault_auto_lock_minutes: default_vault_auto_lock(),
+            secrets_in_keystore: false,
+            model_catalog: Vec::new(),
+            models_cache_ttl_minutes: default_models_cache_ttl(),
+            skip_opencode_plugin: false,
+            skip_opencode_api_key: false,
+            integrations: IntegrationsConfig::default(),
+            env_file_path: None,
+            tls_trust: TlsTrustConfig::default(),
+            egress_proxy: EgressProxyConfig::default(),
+            http: HttpConfig::default(),
+            power_saving: PowerSavingConfig::default(),
+            host_overrides: BTreeMap::new(),
+            proxy: ProxyConfig::default(),
+            token_socket: false,
+            socket_only: false,
+            health_check: HealthCheckConfig::default(),
+            idle_lock: IdleLockConfig::default(),
+            revocation: RevocationConfig::default(),
+            managed_config: ManagedConfig::default(),
+            launch_at_login: false,
+            window_mode: WindowMode::default(),
+            token_command: false,
+            log_level: None,
+            log_level_until: None,
+            log_level_revert_minutes: default_log_level_revert(),
+            log_file: LogFileConfig::default(),
+            update_check: true,
+            update_feed_url: None,
+            updater: UpdaterConfig::default(),
+            update_channel: UpdateChannel::default(),
+            notifications: NotificationConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            crash_reports: CrashReportConfig::default(),
+            global_shortcut: default_global_shortcut(),
+            profiles: BTreeMap::new(),
+            workspaces: BTreeMap::new(),
+            active_profile: None,
         }
     }
 }
@@ -160,6 +1130,11 @@ impl AppConfig {
         Ok(Self::config_dir()?.join("config.json"))
     }
 
+    /// Get the log directory path (~/.dymium/logs)
+    pub fn logs_dir() -> Result<PathBuf, ConfigError> {
+        Ok(Self::config_dir()?.join("logs"))
+    }
+
     /// Get the token file path (~/.dymium/token)
     pub fn token_path() -> Result<PathBuf, ConfigError> {
         Ok(Self::config_dir()?.join("token"))
@@ -170,31 +1145,81 @@ impl AppConfig {
         Self::try_load().unwrap_or_default()
     }
 
-    /// Try to load configuration from disk
+    /// Try to load configuration from disk, over the org file if any. A
+    /// missing config.json is first seeded from the machine template.
     pub fn try_load() -> Result<Self, ConfigError> {
         let path = Self::config_path()?;
-        let content = std::fs::read_to_string(path)?;
-        let config: Self = serde_json::from_str(&content)?;
+        let org = OrgConfig::load();
+        let user = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                match ConfigTemplate::seed(&path)? {
+                    Some(seeded) => seeded,
+                    // The org file alone is enough to start from
+                    None if !org.is_empty() => serde_json::Value::Object(Default::default()),
+                    None => return Err(e.into()),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let config: Self = serde_json::from_value(org.merge(user))?;
         Ok(config)
     }
 
-    /// Save configuration to disk
+    /// Save configuration to disk. Values from the org file are left out.
     pub fn save(&self) -> Result<(), ConfigError> {
         let dir = Self::config_dir()?;
         std::fs::create_dir_all(&dir)?;
 
         let path = Self::config_path()?;
-        let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
+        let value = OrgConfig::load().strip(serde_json::to_value(self)?);
+        let content = serde_json::to_string_pretty(&value)?;
+        std::fs::write(&path, content)?;
+        // Holds passwords and refresh tokens when no keystore is used
+        files::restrict_to_owner(&path)?;
+        for exposed in Self::exposed_paths() {
+            log::warn!(
+                "{} is readable by other users; run chmod go-rwx on it",
+                exposed.display()
+            );
+        }
         Ok(())
     }
 
-    /// Get the Keycloak token endpoint URL
-    pub fn token_endpoint_url(&self) -> String {
-        format!(
-            "{}/realms/{}/protocol/openid-connect/token",
-            self.keycloak_url, self.realm
-        )
+    /// ~/.dymium and config.json, if other users can read them
+    pub fn exposed_paths() -> Vec<PathBuf> {
+        [Self::config_dir(), Self::config_path()]
+            .into_iter()
+            .flatten()
+            .filter(|path| files::readable_by_others(path))
+            .collect()
+    }
+
+    /// `llm_endpoint` followed by the fallback endpoints
+    pub fn llm_endpoints(&self) -> Vec<String> {
+        std::iter::once(&self.llm_endpoint)
+            .chain(
+                self.llm_fallback_endpoints
+                    .iter()
+                    .filter(|e| !e.trim().is_empty()),
+            )
+            .cloned()
+            .collect()
+    }
+
+    /// Keycloak token endpoint URLs: the primary, then its fallbacks
+    pub fn token_endpoint_urls(&self) -> Vec<String> {
+        std::iter::once(&self.keycloak_url)
+            .chain(&self.keycloak_fallback_urls)
+            .map(|base| base.trim().trim_end_matches('/'))
+            .filter(|base| !base.is_empty())
+            .map(|base| {
+                format!(
+                    "{}/realms/{}/protocol/openid-connect/token",
+                    base, self.realm
+                )
+            })
+            .collect()
     }
 
     /// Whether using static API key authentication
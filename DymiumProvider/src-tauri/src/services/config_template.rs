@@ -0,0 +1,66 @@
+//! Machine-wide first-run template
+//!
+//! Lab and VDI images can ship a template.json next to the org file, in
+//! /etc/dymium (Linux), /Library/Application Support/Dymium (macOS) or
+//! %ProgramData%\Dymium (Windows), or name one with `DYMIUM_CONFIG_TEMPLATE`.
+//! When a user has no ~/.dymium/config.json yet, the template is copied in
+//! as their config; from then on the values are the user's own, and later
+//! changes to the template don't reach them. Settings that should keep
+//! applying, or be locked, belong in the org file (see `org_config`).
+
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the template
+pub const CONFIG_TEMPLATE_ENV: &str = "DYMIUM_CONFIG_TEMPLATE";
+
+/// Service for seeding a new user's config from the machine template
+pub struct ConfigTemplate;
+
+impl ConfigTemplate {
+    /// Where the template is looked for
+    fn candidate() -> Option<PathBuf> {
+        if let Ok(path) = std::env::var(CONFIG_TEMPLATE_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(|dir| PathBuf::from(dir).join("Dymium").join("template.json"))
+        } else if cfg!(target_os = "macos") {
+            Some(PathBuf::from(
+                "/Library/Application Support/Dymium/template.json",
+            ))
+        } else {
+            Some(PathBuf::from("/etc/dymium/template.json"))
+        }
+    }
+
+    /// Path of the template, if there is one
+    pub fn path() -> Option<PathBuf> {
+        Self::candidate().filter(|p| p.is_file())
+    }
+
+    /// Write the template to `config_path` as the user's config and return
+    /// it. Returns `None` without a template.
+    pub fn seed(config_path: &Path) -> Result<Option<Value>, ConfigError> {
+        let Some(template) = Self::path() else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(&template)?;
+        let value: Value = serde_json::from_str(&content)?;
+        // Reject a template the app couldn't load before writing it anywhere
+        serde_json::from_value::<AppConfig>(value.clone())?;
+
+        std::fs::create_dir_all(AppConfig::config_dir()?)?;
+        std::fs::write(config_path, serde_json::to_string_pretty(&value)?)?;
+        files::restrict_to_owner(config_path)?;
+        log::info!(
+            "Created {} from {}",
+            config_path.display(),
+            template.display()
+        );
+        Ok(Some(value))
+    }
+}
@@ -0,0 +1,538 @@
+//! Continue.dev integration
+//!
+//! Keeps one OpenAI-compatible model entry per GhostLLM model in
+//! ~/.continue/config.yaml, with the current token as `apiKey`. Entries are
+//! recognised by the " (Dymium)" suffix on their name; everything else in the
+//! file is left alone.
+//!
+//! The file is edited as text: only the lines of the managed entries in the
+//! top-level `models:` list are replaced, so comments, anchors and the
+//! formatting of everything else survive. A `models` value that isn't a block
+//! list (other than an empty `[]`) is reported rather than rewritten.
+
+use crate::services::config::AppConfig;
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use std::ops::Range;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Suffix marking model entries this app manages
+const NAME_SUFFIX: &str = " (Dymium)";
+/// Indent of the `-` markers in a `models` list this app creates
+const DEFAULT_INDENT: usize = 2;
+
+#[derive(Error, Debug)]
+pub enum ContinueError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("config.yaml is not a mapping")]
+    NotAMapping,
+    #[error("config.yaml 'models' is not a block list")]
+    NotABlockList,
+    #[error("Home directory not found")]
+    NoHomeDir,
+}
+
+/// The top-level `models:` list of config.yaml, by line index
+struct ModelsBlock {
+    /// Line holding the `models:` key
+    key: usize,
+    /// Whether the key holds an empty flow list (`models: []`)
+    empty_flow: bool,
+    /// Indent of the list's `-` markers
+    indent: usize,
+    /// Lines of each list item, without trailing blank or comment lines
+    items: Vec<Range<usize>>,
+}
+
+/// Service for managing Continue.dev configuration
+pub struct ContinueService;
+
+impl ContinueService {
+    /// Get the Continue config path (~/.continue/config.yaml)
+    pub fn config_path() -> Result<PathBuf, ContinueError> {
+        dirs::home_dir()
+            .map(|p| p.join(".continue/config.yaml"))
+            .ok_or(ContinueError::NoHomeDir)
+    }
+
+    /// Point the Dymium model entries at the current endpoint and token.
+    /// With an empty `model_ids`, existing entries are kept and only their
+    /// endpoint and key are updated.
+    pub fn sync(
+        config: &AppConfig,
+        token: &str,
+        model_ids: &[String],
+    ) -> Result<(), ContinueError> {
+        let path = Self::config_path()?;
+        let original = files::read_optional(&path)?;
+        let base = match original.as_deref() {
+            Some(content) if !content.trim().is_empty() => content.to_string(),
+            _ => Self::new_config(),
+        };
+
+        let ids: Vec<String> = if model_ids.is_empty() {
+            Self::managed_models(&base)?
+        } else {
+            model_ids.to_vec()
+        };
+        if ids.is_empty() {
+            log::debug!("No GhostLLM models known yet; leaving Continue config alone");
+            return Ok(());
+        }
+
+        let api_base = OpenCodeService::compute_base_url(config);
+        let content = Self::replace_managed(&base, &ids, &api_base, token)?;
+        if original.as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+
+        files::write_with_backup(&path, content.as_bytes())?;
+        log::info!("Updated {} ({} Dymium models)", path.display(), ids.len());
+        Ok(())
+    }
+
+    /// Remove the Dymium model entries (on log out / mode switch)
+    pub fn clear() {
+        if let Err(e) = Self::do_clear() {
+            log::error!("Failed to clear Dymium models from Continue config: {}", e);
+        }
+    }
+
+    fn do_clear() -> Result<(), ContinueError> {
+        let path = Self::config_path()?;
+        let Some(content) = files::read_optional(&path)? else {
+            return Ok(());
+        };
+        let cleared = Self::replace_managed(&content, &[], "", "")?;
+        if cleared != content {
+            files::write_with_backup(&path, cleared.as_bytes())?;
+            log::info!("Cleared Dymium models from {}", path.display());
+        }
+        Ok(())
+    }
+
+    fn new_config() -> String {
+        "name: Local Assistant\nversion: 1.0.0\nschema: v1\n".to_string()
+    }
+
+    /// Model ids of the managed entries in `content`
+    fn managed_models(content: &str) -> Result<Vec<String>, ContinueError> {
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let Some(block) = Self::find_models(&lines)? else {
+            return Ok(Vec::new());
+        };
+        Ok(block
+            .items
+            .iter()
+            .filter(|item| Self::is_managed(&lines, item, block.indent))
+            .filter_map(|item| Self::item_value(&lines, item, block.indent, "model"))
+            .collect())
+    }
+
+    /// Replace the managed entries in `content` with one per id, where the
+    /// first of them was (or at the end of the list). An empty `ids` removes
+    /// them.
+    fn replace_managed(
+        content: &str,
+        ids: &[String],
+        api_base: &str,
+        token: &str,
+    ) -> Result<String, ContinueError> {
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let mut out = String::with_capacity(content.len());
+
+        let Some(block) = Self::find_models(&lines)? else {
+            if ids.is_empty() {
+                return Ok(content.to_string());
+            }
+            Self::check_root_mapping(&lines)?;
+            out.push_str(content);
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push_str(newline);
+            }
+            out.push_str("models:");
+            out.push_str(newline);
+            out.push_str(&Self::entries(
+                ids,
+                DEFAULT_INDENT,
+                api_base,
+                token,
+                newline,
+            ));
+            return Ok(out);
+        };
+
+        let managed: Vec<&Range<usize>> = block
+            .items
+            .iter()
+            .filter(|item| Self::is_managed(&lines, item, block.indent))
+            .collect();
+        let at = managed
+            .first()
+            .map(|item| item.start)
+            .or_else(|| block.items.last().map(|item| item.end))
+            .unwrap_or(block.key + 1);
+        let entries = Self::entries(ids, block.indent, api_base, token, newline);
+        // `models: []` becomes a block list when entries are added, and a list
+        // left with no items becomes `[]` rather than null
+        let key_line = if block.empty_flow && !ids.is_empty() {
+            Some("models:")
+        } else if ids.is_empty() && !managed.is_empty() && managed.len() == block.items.len() {
+            Some("models: []")
+        } else {
+            None
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            if i == at {
+                out.push_str(&entries);
+            }
+            if let Some(key_line) = key_line.filter(|_| i == block.key) {
+                out.push_str(key_line);
+                if let Some(comment) = line.find(" #").map(|p| line[p..].trim_end()) {
+                    out.push_str(comment);
+                }
+                out.push_str(newline);
+                continue;
+            }
+            if managed.iter().any(|item| item.contains(&i)) {
+                continue;
+            }
+            out.push_str(line);
+        }
+        if at >= lines.len() && !entries.is_empty() {
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push_str(newline);
+            }
+            out.push_str(&entries);
+        }
+        Ok(out)
+    }
+
+    /// Locate the top-level `models:` key and the items of its block list
+    fn find_models(lines: &[&str]) -> Result<Option<ModelsBlock>, ContinueError> {
+        let mut found = None;
+        for (i, line) in lines.iter().enumerate() {
+            let Some(rest) = line.strip_prefix("models:") else {
+                continue;
+            };
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                continue;
+            }
+            match rest.split(" #").next().unwrap_or_default().trim() {
+                "" => found = Some((i, false)),
+                "[]" => found = Some((i, true)),
+                _ => return Err(ContinueError::NotABlockList),
+            }
+            break;
+        }
+        let Some((key, empty_flow)) = found else {
+            return Ok(None);
+        };
+
+        let mut indent = None;
+        let mut items: Vec<Range<usize>> = Vec::new();
+        for (i, line) in lines.iter().enumerate().skip(key + 1) {
+            if Self::is_blank_or_comment(line) {
+                continue;
+            }
+            let col = Self::indent_of(line);
+            let body = line[col..].trim_end();
+            if col == 0
+                && (!body.starts_with('-') || body.starts_with("---") || body.starts_with("..."))
+            {
+                break;
+            }
+            if empty_flow {
+                return Err(ContinueError::NotABlockList);
+            }
+            let seq = *indent.get_or_insert(col);
+            if col == seq && (body == "-" || body.starts_with("- ")) {
+                items.push(i..i + 1);
+            } else if col > seq && !items.is_empty() {
+                if let Some(item) = items.last_mut() {
+                    item.end = i + 1;
+                }
+            } else {
+                return Err(ContinueError::NotABlockList);
+            }
+        }
+
+        Ok(Some(ModelsBlock {
+            key,
+            empty_flow,
+            indent: indent.unwrap_or(DEFAULT_INDENT),
+            items,
+        }))
+    }
+
+    /// Refuse to append a `models:` key to a document that isn't a block
+    /// mapping (a top-level list or flow mapping)
+    fn check_root_mapping(lines: &[&str]) -> Result<(), ContinueError> {
+        let first = lines.iter().map(|l| l.trim_end()).find(|l| {
+            !l.trim().is_empty()
+                && !l.trim_start().starts_with('#')
+                && !l.starts_with('%')
+                && *l != "---"
+        });
+        match first {
+            Some(l) if l.starts_with('-') || l.starts_with('[') || l.starts_with('{') => {
+                Err(ContinueError::NotAMapping)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_managed(lines: &[&str], item: &Range<usize>, indent: usize) -> bool {
+        Self::item_value(lines, item, indent, "name").is_some_and(|n| n.ends_with(NAME_SUFFIX))
+    }
+
+    /// Scalar value of `key` in the mapping held by a list item
+    fn item_value(lines: &[&str], item: &Range<usize>, indent: usize, key: &str) -> Option<String> {
+        let first = lines[item.start][indent + 1..].trim_end();
+        let inline = first.trim_start();
+        let mut key_col = (!inline.is_empty()).then(|| indent + 1 + first.len() - inline.len());
+        let mut candidates = vec![(key_col, inline)];
+        for line in &lines[item.start + 1..item.end] {
+            if Self::is_blank_or_comment(line) {
+                continue;
+            }
+            let col = Self::indent_of(line);
+            key_col.get_or_insert(col);
+            candidates.push((Some(col), line[col..].trim_end()));
+        }
+        candidates
+            .into_iter()
+            .filter(|(col, _)| *col == key_col)
+            .find_map(|(_, text)| {
+                let rest = text.strip_prefix(key)?.strip_prefix(':')?;
+                (rest.is_empty() || rest.starts_with(' ')).then(|| Self::scalar(rest))
+            })
+    }
+
+    /// Value of a single-line YAML scalar, with any trailing comment removed
+    fn scalar(text: &str) -> String {
+        let text = text.trim();
+        if text.starts_with('"') {
+            if let Some(Ok(value)) = serde_json::Deserializer::from_str(text)
+                .into_iter::<String>()
+                .next()
+            {
+                return value;
+            }
+        } else if let Some(quoted) = text.strip_prefix('\'') {
+            let mut value = String::new();
+            let mut chars = quoted.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    '\'' => break,
+                    c => value.push(c),
+                }
+            }
+            return value;
+        }
+        text.split(" #")
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    }
+
+    fn is_blank_or_comment(line: &str) -> bool {
+        let trimmed = line.trim();
+        trimmed.is_empty() || trimmed.starts_with('#')
+    }
+
+    fn indent_of(line: &str) -> usize {
+        line.len() - line.trim_start_matches(' ').len()
+    }
+
+    /// List items for `ids`, at `indent`. Values are double-quoted, which YAML
+    /// reads the same as JSON strings.
+    fn entries(
+        ids: &[String],
+        indent: usize,
+        api_base: &str,
+        token: &str,
+        newline: &str,
+    ) -> String {
+        let pad = " ".repeat(indent);
+        let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+        ids.iter()
+            .map(|id| {
+                [
+                    format!("{pad}- name: {}", quote(&format!("{}{}", id, NAME_SUFFIX))),
+                    format!("{pad}  provider: openai"),
+                    format!("{pad}  model: {}", quote(id)),
+                    format!("{pad}  apiBase: {}", quote(api_base)),
+                    format!("{pad}  apiKey: {}", quote(token)),
+                ]
+                .map(|line| line + newline)
+                .concat()
+            })
+            .collect()
+    }
+}
+
+impl Integration for ContinueService {
+    fn id(&self) -> &'static str {
+        "continueDev"
+    }
+
+    fn name(&self) -> &'static str {
+        "Continue"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::config_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "an openai model entry per GhostLLM model, named \"<model> (Dymium)\""
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.token, &ctx.model_ids())?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Continue config, edited by hand
+name: My Assistant # keep this name
+version: 1.0.0
+schema: v1
+
+defaults: &defaults
+  provider: ollama
+  apiBase: http://localhost:11434
+
+models:
+  # local models
+  - name: Llama
+    <<: *defaults
+    model: llama3
+  - name: \"gpt-4o (Dymium)\"
+    provider: openai
+    model: \"gpt-4o\"
+    apiBase: \"http://old/v1\"
+    apiKey: \"old-token\"
+  - name: Codestral   # autocomplete
+    model: codestral
+    roles: [autocomplete]
+
+context:
+  - provider: code
+";
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn sync_keeps_comments_anchors_and_other_models() {
+        let updated = ContinueService::replace_managed(
+            CONFIG,
+            &ids(&["gpt-4o", "claude"]),
+            "http://new/v1",
+            "tok",
+        )
+        .unwrap();
+        let expected = CONFIG.replace(
+            "  - name: \"gpt-4o (Dymium)\"
+    provider: openai
+    model: \"gpt-4o\"
+    apiBase: \"http://old/v1\"
+    apiKey: \"old-token\"
+",
+            "  - name: \"gpt-4o (Dymium)\"
+    provider: openai
+    model: \"gpt-4o\"
+    apiBase: \"http://new/v1\"
+    apiKey: \"tok\"
+  - name: \"claude (Dymium)\"
+    provider: openai
+    model: \"claude\"
+    apiBase: \"http://new/v1\"
+    apiKey: \"tok\"
+",
+        );
+        assert_eq!(updated, expected);
+        assert_eq!(
+            ContinueService::managed_models(&updated).unwrap(),
+            ids(&["gpt-4o", "claude"])
+        );
+    }
+
+    #[test]
+    fn clear_removes_only_managed_entries() {
+        let synced =
+            ContinueService::replace_managed(CONFIG, &ids(&["gpt-4o"]), "http://new/v1", "tok")
+                .unwrap();
+        let cleared = ContinueService::replace_managed(&synced, &[], "", "").unwrap();
+        assert!(!cleared.contains("(Dymium)"));
+        assert!(cleared.contains("# local models\n  - name: Llama\n"));
+        assert!(cleared.contains("  - name: Codestral   # autocomplete\n"));
+        assert!(cleared.contains("defaults: &defaults\n"));
+        // Clearing again changes nothing
+        assert_eq!(
+            ContinueService::replace_managed(&cleared, &[], "", "").unwrap(),
+            cleared
+        );
+    }
+
+    #[test]
+    fn entries_are_added_to_empty_or_missing_lists() {
+        let empty = "name: a # n\nmodels: [] # none yet\n";
+        let added =
+            ContinueService::replace_managed(empty, &ids(&["m"]), "http://x/v1", "t").unwrap();
+        assert!(added.starts_with("name: a # n\nmodels: # none yet\n  - name: \"m (Dymium)\"\n"));
+        assert_eq!(
+            ContinueService::replace_managed(&added, &[], "", "").unwrap(),
+            "name: a # n\nmodels: [] # none yet\n"
+        );
+
+        let missing = "name: a\nschema: v1";
+        let added =
+            ContinueService::replace_managed(missing, &ids(&["m"]), "http://x/v1", "t").unwrap();
+        assert!(added.starts_with("name: a\nschema: v1\nmodels:\n  - name: \"m (Dymium)\"\n"));
+    }
+
+    #[test]
+    fn unindented_lists_and_other_layouts() {
+        let flat =
+            "models:\n- name: x\n  model: x\n-\n  name: 'y (Dymium)'\n  model: 'y'\nrules: []\n";
+        assert_eq!(ContinueService::managed_models(flat).unwrap(), ids(&["y"]));
+        let updated = ContinueService::replace_managed(flat, &ids(&["z"]), "b", "t").unwrap();
+        assert!(updated.starts_with("models:\n- name: x\n  model: x\n- name: \"z (Dymium)\"\n"));
+        assert!(updated.ends_with("  apiKey: \"t\"\nrules: []\n"));
+
+        assert!(matches!(
+            ContinueService::replace_managed("models: [a, b]\n", &ids(&["m"]), "b", "t"),
+            Err(ContinueError::NotABlockList)
+        ));
+        assert!(matches!(
+            ContinueService::replace_managed("- a\n- b\n", &ids(&["m"]), "b", "t"),
+            Err(ContinueError::NotAMapping)
+        ));
+    }
+}
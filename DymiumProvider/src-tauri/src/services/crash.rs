@@ -0,0 +1,194 @@
+//! Crash reports
+//!
+//! A panic hook saves a report (message, location, backtrace and the last
+//! log lines, scrubbed by `redact`) to ~/.dymium/crashes/ before the process
+//! goes down. With `crashReports.enabled` and an `endpoint`, saved reports
+//! are uploaded on the next start and deleted once accepted; otherwise they
+//! stay on disk for the user to attach to a bug report.
+
+use crate::services::config::{AppConfig, ConfigError, CrashReportConfig};
+use crate::services::logs::LogBuffer;
+use crate::services::redact::redact;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Log lines included in a report
+const LOG_LINES: usize = 100;
+/// Saved reports kept when they aren't uploaded
+const MAX_REPORTS: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum CrashError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Crash report endpoint returned {0}")]
+    Rejected(u16),
+}
+
+/// A panic, as saved and uploaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub timestamp: DateTime<Utc>,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Thread that panicked
+    pub thread: String,
+    pub message: String,
+    /// file:line:column
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// Lines logged before the crash, oldest first
+    pub log: Vec<String>,
+}
+
+/// Service for saving and uploading crash reports
+pub struct CrashReporter;
+
+impl CrashReporter {
+    /// Get the crash report directory (~/.dymium/crashes)
+    pub fn dir() -> Result<PathBuf, ConfigError> {
+        Ok(AppConfig::config_dir()?.join("crashes"))
+    }
+
+    /// Save a report for every panic, then run the default hook
+    pub fn install(logs: LogBuffer) {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic".to_string());
+            let log = logs
+                .page(None, LOG_LINES, 0)
+                .map(|page| {
+                    page.entries
+                        .iter()
+                        .rev()
+                        .map(|e| {
+                            let line = format!(
+                                "{} {} {} {}",
+                                e.timestamp.to_rfc3339(),
+                                e.level,
+                                e.target,
+                                e.message
+                            );
+                            redact(&line)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let report = CrashReport {
+                timestamp: Utc::now(),
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                os: std::env::consts::OS.to_string(),
+                arch: std::env::consts::ARCH.to_string(),
+                thread: std::thread::current()
+                    .name()
+                    .unwrap_or("unnamed")
+                    .to_string(),
+                message: redact(&message),
+                location: info
+                    .location()
+                    .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+                backtrace: redact(&std::backtrace::Backtrace::force_capture().to_string()),
+                log,
+            };
+            match Self::save(&report) {
+                Ok(path) => eprintln!("Crash report saved to {}", path.display()),
+                Err(e) => eprintln!("Failed to save crash report: {}", e),
+            }
+            default_hook(info);
+        }));
+    }
+
+    fn save(report: &CrashReport) -> Result<PathBuf, CrashError> {
+        let dir = Self::dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "crash-{}.json",
+            report.timestamp.format("%Y%m%d-%H%M%S%.3f")
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(report)?)?;
+        Self::prune()?;
+        Ok(path)
+    }
+
+    /// Saved reports, oldest first
+    fn saved() -> Result<Vec<PathBuf>, CrashError> {
+        let dir = Self::dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Drop the oldest reports beyond `MAX_REPORTS`
+    fn prune() -> Result<(), CrashError> {
+        let paths = Self::saved()?;
+        let excess = paths.len().saturating_sub(MAX_REPORTS);
+        for path in &paths[..excess] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Upload saved reports if the user opted in, deleting each one the
+    /// endpoint accepts. Returns how many were sent.
+    pub async fn upload_pending(config: &CrashReportConfig) -> Result<usize, CrashError> {
+        let Some(endpoint) = config.endpoint.as_deref().filter(|_| config.enabled) else {
+            return Ok(0);
+        };
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()?;
+        let mut sent = 0;
+        for path in Self::saved()? {
+            let report: CrashReport = match std::fs::read_to_string(&path)
+                .map_err(CrashError::from)
+                .and_then(|content| Ok(serde_json::from_str(&content)?))
+            {
+                Ok(report) => report,
+                Err(e) => {
+                    log::warn!("Skipping unreadable crash report {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let response = client
+                .post(endpoint)
+                .header(
+                    "User-Agent",
+                    concat!("dymium-provider/", env!("CARGO_PKG_VERSION")),
+                )
+                .json(&report)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(CrashError::Rejected(response.status().as_u16()));
+            }
+            std::fs::remove_file(&path)?;
+            sent += 1;
+        }
+        if sent > 0 {
+            log::info!("Uploaded {} crash report(s)", sent);
+        }
+        Ok(sent)
+    }
+}
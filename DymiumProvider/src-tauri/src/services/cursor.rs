@@ -0,0 +1,346 @@
+//! Cursor integration
+//!
+//! Cursor has no settings file for its "Override OpenAI Base URL" and OpenAI
+//! API key; both live in its VS Code-style state database
+//! (<config dir>/Cursor/User/globalStorage/state.vscdb). The base URL and the
+//! "use my key" toggle are fields of a JSON blob under the reactive storage
+//! key, the API key is a row of its own. Cursor reads them at startup, so a
+//! rotated token is picked up when Cursor restarts.
+//!
+//! The first sync saves the user's own override, toggle and key in a row of
+//! the same database; clearing puts them back.
+
+use crate::services::config::AppConfig;
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{json, Map, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Row holding Cursor's persisted settings as JSON
+const STORAGE_KEY: &str =
+    "src.vs.platform.reactivestorage.browser.reactiveStorageServiceImpl.persistentStorage.applicationUser";
+/// Row holding the OpenAI API key
+const API_KEY_KEY: &str = "cursorAuth/openAIKey";
+/// Row holding the user's own settings from before the first sync, as JSON,
+/// restored on clear
+const SAVED_KEY: &str = "dymium/previousOpenAISettings";
+/// Settings fields this integration sets
+const MANAGED_FIELDS: [&str; 2] = ["openAIBaseUrl", "useOpenAIKey"];
+/// Field of the saved settings holding the API key row
+const SAVED_API_KEY: &str = "openAIKey";
+/// Backup name for copies of the settings row
+const STORAGE_BACKUP_NAME: &str = "cursor-settings.json";
+
+#[derive(Error, Debug)]
+pub enum CursorError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Cursor's settings are not a JSON object")]
+    InvalidSettings,
+    #[error("Config directory not found")]
+    NoConfigDir,
+}
+
+/// What to do to Cursor's OpenAI settings
+enum Change<'a> {
+    /// Point them at GhostLLM, saving the user's own values the first time
+    Sync { base_url: &'a str, token: &'a str },
+    /// Put the user's own values back
+    Clear,
+}
+
+/// Outcome of applying a [`Change`]
+struct Applied {
+    changed: bool,
+    /// Settings row as it was, if it was rewritten
+    replaced_settings: Option<String>,
+}
+
+/// Service for managing Cursor's custom OpenAI endpoint
+pub struct CursorService;
+
+impl CursorService {
+    /// Get Cursor's state database path
+    pub fn state_db_path() -> Result<PathBuf, CursorError> {
+        dirs::config_dir()
+            .map(|p| p.join("Cursor/User/globalStorage/state.vscdb"))
+            .ok_or(CursorError::NoConfigDir)
+    }
+
+    /// Point Cursor's OpenAI override at GhostLLM with the current token
+    pub fn sync(config: &AppConfig, token: &str) -> Result<(), CursorError> {
+        let base_url = OpenCodeService::compute_base_url(config);
+        let changed = Self::update(Change::Sync {
+            base_url: &base_url,
+            token,
+        })?;
+        if changed {
+            log::info!("Updated Cursor OpenAI endpoint settings");
+        }
+        Ok(())
+    }
+
+    /// Restore the override, toggle and key the user had before the first
+    /// sync (on log out / mode switch)
+    pub fn clear() {
+        match Self::update(Change::Clear) {
+            Ok(true) => log::info!("Cleared Dymium settings from Cursor"),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to clear Cursor settings: {}", e),
+        }
+    }
+
+    /// Apply `change` to the database in one transaction, off the runtime's
+    /// workers. Does nothing if Cursor isn't installed.
+    /// Returns whether anything changed.
+    fn update(change: Change) -> Result<bool, CursorError> {
+        let path = Self::state_db_path()?;
+        if !path.exists() {
+            log::debug!("Cursor state database not found; skipping");
+            return Ok(false);
+        }
+
+        let applied = files::blocking(|| {
+            let mut conn = Connection::open(&path)?;
+            // Cursor holds the database open while running
+            conn.busy_timeout(Duration::from_secs(5))?;
+            Self::apply(&mut conn, change)
+        })?;
+        // Keep a copy of the row, not the whole (large, live) database
+        if let Some(raw) = applied.replaced_settings {
+            if let Err(e) = files::backup_content(STORAGE_BACKUP_NAME, raw.as_bytes()) {
+                log::warn!("Failed to back up Cursor settings: {}", e);
+            }
+        }
+        Ok(applied.changed)
+    }
+
+    fn apply(conn: &mut Connection, change: Change) -> Result<Applied, CursorError> {
+        let tx = conn.transaction()?;
+        let raw = Self::get(&tx, STORAGE_KEY)?;
+        // The blob holds all of Cursor's preferences: one that can't be read
+        // is left alone rather than replaced with only ours
+        let mut storage = match raw.as_deref() {
+            Some(raw) => match serde_json::from_str::<Value>(raw)? {
+                Value::Object(map) => map,
+                _ => return Err(CursorError::InvalidSettings),
+            },
+            None => Map::new(),
+        };
+        let current_key = Self::get(&tx, API_KEY_KEY)?;
+        let saved = Self::get(&tx, SAVED_KEY)?;
+
+        let before = storage.clone();
+        let api_key = match change {
+            Change::Sync { base_url, token } => {
+                // Settings that already point here were written by an older
+                // version of this integration, not by the user
+                let ours = storage.get("openAIBaseUrl").and_then(Value::as_str) == Some(base_url);
+                if saved.is_none() {
+                    let mut previous = Map::new();
+                    if !ours {
+                        for field in MANAGED_FIELDS {
+                            if let Some(value) = storage.get(field) {
+                                previous.insert(field.to_string(), value.clone());
+                            }
+                        }
+                        if let Some(key) = &current_key {
+                            previous.insert(SAVED_API_KEY.to_string(), json!(key));
+                        }
+                    }
+                    Self::put(&tx, SAVED_KEY, &serde_json::to_string(&previous)?)?;
+                }
+                storage.insert("openAIBaseUrl".to_string(), json!(base_url));
+                storage.insert("useOpenAIKey".to_string(), json!(true));
+                Some(token.to_string())
+            }
+            Change::Clear => match saved.as_deref() {
+                Some(saved) => {
+                    let previous: Map<String, Value> = serde_json::from_str(saved)?;
+                    for field in MANAGED_FIELDS {
+                        match previous.get(field) {
+                            Some(value) => storage.insert(field.to_string(), value.clone()),
+                            None => storage.remove(field),
+                        };
+                    }
+                    tx.execute("DELETE FROM ItemTable WHERE key = ?1", [SAVED_KEY])?;
+                    previous
+                        .get(SAVED_API_KEY)
+                        .and_then(Value::as_str)
+                        .map(str::to_owned)
+                }
+                None => {
+                    // Nothing saved: synced before the user's settings were
+                    // kept, so just turn the override off
+                    storage.remove("openAIBaseUrl");
+                    storage.insert("useOpenAIKey".to_string(), json!(false));
+                    None
+                }
+            },
+        };
+
+        let settings_changed = storage != before;
+        if settings_changed {
+            Self::put(&tx, STORAGE_KEY, &serde_json::to_string(&storage)?)?;
+        }
+        let key_changed = api_key != current_key;
+        match &api_key {
+            Some(key) if key_changed => Self::put(&tx, API_KEY_KEY, key)?,
+            None if key_changed => {
+                tx.execute("DELETE FROM ItemTable WHERE key = ?1", [API_KEY_KEY])?;
+            }
+            _ => {}
+        }
+
+        tx.commit()?;
+        Ok(Applied {
+            changed: settings_changed || key_changed,
+            replaced_settings: raw.filter(|_| settings_changed),
+        })
+    }
+
+    fn get(conn: &Connection, key: &str) -> Result<Option<String>, CursorError> {
+        Ok(conn
+            .query_row("SELECT value FROM ItemTable WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn put(conn: &Connection, key: &str, value: &str) -> Result<(), CursorError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?1, ?2)",
+            (key, value),
+        )?;
+        Ok(())
+    }
+}
+
+impl Integration for CursorService {
+    fn id(&self) -> &'static str {
+        "cursor"
+    }
+
+    fn name(&self) -> &'static str {
+        "Cursor"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::state_db_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "the OpenAI base URL override and OpenAI API key"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.token)?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+
+    /// The override may point at the user's own endpoint
+    fn owns_entries(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(settings: Value, api_key: Option<&str>) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE ItemTable (key TEXT UNIQUE ON CONFLICT REPLACE, value BLOB)",
+            (),
+        )
+        .unwrap();
+        CursorService::put(&conn, STORAGE_KEY, &settings.to_string()).unwrap();
+        if let Some(key) = api_key {
+            CursorService::put(&conn, API_KEY_KEY, key).unwrap();
+        }
+        conn
+    }
+
+    fn settings(conn: &Connection) -> Value {
+        serde_json::from_str(&CursorService::get(conn, STORAGE_KEY).unwrap().unwrap()).unwrap()
+    }
+
+    fn sync(conn: &mut Connection, token: &str) -> bool {
+        let change = Change::Sync {
+            base_url: "http://ghostllm/v1",
+            token,
+        };
+        CursorService::apply(conn, change).unwrap().changed
+    }
+
+    #[test]
+    fn clear_restores_the_users_own_settings() {
+        let own = json!({
+            "openAIBaseUrl": "https://my-proxy/v1",
+            "useOpenAIKey": true,
+            "theme": "dark"
+        });
+        let mut conn = database(own.clone(), Some("sk-mine"));
+
+        assert!(sync(&mut conn, "tok-1"));
+        assert!(sync(&mut conn, "tok-2"));
+        assert!(!sync(&mut conn, "tok-2"));
+        assert_eq!(settings(&conn)["openAIBaseUrl"], "http://ghostllm/v1");
+        assert_eq!(
+            CursorService::get(&conn, API_KEY_KEY).unwrap().as_deref(),
+            Some("tok-2")
+        );
+
+        assert!(
+            CursorService::apply(&mut conn, Change::Clear)
+                .unwrap()
+                .changed
+        );
+        assert_eq!(settings(&conn), own);
+        assert_eq!(
+            CursorService::get(&conn, API_KEY_KEY).unwrap().as_deref(),
+            Some("sk-mine")
+        );
+        assert_eq!(CursorService::get(&conn, SAVED_KEY).unwrap(), None);
+    }
+
+    #[test]
+    fn clear_removes_what_the_user_never_set() {
+        let mut conn = database(json!({ "theme": "dark" }), None);
+        assert!(sync(&mut conn, "tok"));
+        assert!(
+            CursorService::apply(&mut conn, Change::Clear)
+                .unwrap()
+                .changed
+        );
+        assert_eq!(settings(&conn), json!({ "theme": "dark" }));
+        assert_eq!(CursorService::get(&conn, API_KEY_KEY).unwrap(), None);
+    }
+
+    #[test]
+    fn clear_without_saved_settings_turns_the_override_off() {
+        let mut conn = database(
+            json!({ "openAIBaseUrl": "http://ghostllm/v1", "useOpenAIKey": true }),
+            Some("tok"),
+        );
+        assert!(
+            CursorService::apply(&mut conn, Change::Clear)
+                .unwrap()
+                .changed
+        );
+        assert_eq!(settings(&conn), json!({ "useOpenAIKey": false }));
+        assert_eq!(CursorService::get(&conn, API_KEY_KEY).unwrap(), None);
+    }
+}
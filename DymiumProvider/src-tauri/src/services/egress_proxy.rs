@@ -0,0 +1,417 @@
+//! Proxy for outgoing requests
+//!
+//! Requests to Keycloak and the LLM endpoint (from the token service and the
+//! local proxy) go through the proxy picked by `egressProxy.mode`:
+//!
+//! - `system`: `HTTPS_PROXY`/`ALL_PROXY`/`HTTP_PROXY` and `NO_PROXY`, else
+//!   the OS settings (macOS network settings, the Windows Internet Options,
+//!   GNOME's proxy settings), including a PAC script if one is configured
+//! - `manual`: `egressProxy.url`
+//! - `pac`: the PAC script at `egressProxy.pacUrl`
+//! - `direct`: no proxy
+//!
+//! PAC scripts aren't executed: the first `PROXY` or `HTTPS` entry they
+//! return is used for every host not in the bypass list. Proxies that need
+//! a login get Basic credentials from `egressProxy.username` and the
+//! password in the keystore (or `DYMIUM_PROXY_PASSWORD`).
+
+use crate::services::config::{EgressProxyConfig, EgressProxyMode};
+use crate::services::keystore::{KeystoreError, KeystoreService};
+use reqwest::{Client, ClientBuilder, NoProxy, Proxy, Url};
+use serde::Serialize;
+use std::process::Command;
+use std::time::Duration;
+
+/// Keystore entry of the proxy password
+const PASSWORD_ENTRY: &str = "egress_proxy_password";
+/// Overrides the keystore, e.g. on CI runners
+const PASSWORD_ENV: &str = "DYMIUM_PROXY_PASSWORD";
+/// How long a PAC download may take
+const PAC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Proxy settings as found, before the PAC script is read
+#[derive(Default)]
+struct Found {
+    url: Option<String>,
+    pac_url: Option<String>,
+    no_proxy: Vec<String>,
+    source: &'static str,
+}
+
+/// How outgoing requests are routed, as shown to the user
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EgressRoute {
+    /// Proxy URL without credentials; unset for direct connections
+    pub proxy_url: Option<String>,
+    /// "config", "environment", "system" or "direct"
+    pub source: String,
+    /// PAC script the proxy came from
+    pub pac_url: Option<String>,
+    /// Hosts reached directly
+    pub no_proxy: Vec<String>,
+    /// Whether a username and password are sent to the proxy
+    pub authenticated: bool,
+    #[serde(skip)]
+    proxy: Option<String>,
+    #[serde(skip)]
+    credentials: Option<(String, String)>,
+}
+
+impl EgressRoute {
+    /// Route `builder`'s requests accordingly
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        let Some(url) = &self.proxy else {
+            return builder.no_proxy();
+        };
+        match Proxy::all(url) {
+            Ok(proxy) => {
+                let mut proxy = proxy.no_proxy(NoProxy::from_string(&self.no_proxy.join(",")));
+                if let Some((username, password)) = &self.credentials {
+                    proxy = proxy.basic_auth(username, password);
+                }
+                builder.proxy(proxy)
+            }
+            Err(e) => {
+                log::error!(
+                    "Ignoring proxy {}: {}",
+                    self.proxy_url.as_deref().unwrap_or_default(),
+                    e
+                );
+                builder.no_proxy()
+            }
+        }
+    }
+}
+
+/// Service for finding the outgoing proxy
+pub struct EgressProxy;
+
+impl EgressProxy {
+    /// Work out the route for `config`. May run OS tools and download a PAC
+    /// script; failures are logged and fall back to direct connections.
+    pub fn resolve(config: &EgressProxyConfig) -> EgressRoute {
+        let found = match config.mode {
+            EgressProxyMode::Direct => Found {
+                source: "direct",
+                ..Found::default()
+            },
+            EgressProxyMode::Manual => Found {
+                url: config.url.clone(),
+                source: "config",
+                ..Found::default()
+            },
+            EgressProxyMode::Pac => Found {
+                pac_url: config.pac_url.clone(),
+                source: "config",
+                ..Found::default()
+            },
+            EgressProxyMode::System => from_env().or_else(from_os).unwrap_or(Found {
+                source: "direct",
+                ..Found::default()
+            }),
+        };
+
+        let mut url = found.url.filter(|u| !u.trim().is_empty());
+        let pac_url = found.pac_url.filter(|u| !u.trim().is_empty());
+        if url.is_none() {
+            if let Some(pac_url) = &pac_url {
+                match fetch_pac(pac_url) {
+                    Ok(script) => url = pac_proxy(&script),
+                    Err(e) => log::warn!(
+                        "Can't read PAC script {}, connecting directly: {}",
+                        pac_url,
+                        e
+                    ),
+                }
+            }
+        }
+        let proxy = url.map(|u| {
+            let u = u.trim();
+            if u.contains("://") {
+                u.to_string()
+            } else {
+                format!("http://{}", u)
+            }
+        });
+
+        let credentials = config
+            .username
+            .clone()
+            .filter(|u| !u.is_empty())
+            .and_then(|username| match Self::password() {
+                Some(password) => Some((username, password)),
+                None => {
+                    log::warn!("No proxy password stored for {}", username);
+                    None
+                }
+            });
+        let no_proxy = found
+            .no_proxy
+            .iter()
+            .chain(&config.no_proxy)
+            .filter_map(|host| {
+                let host = host.trim();
+                // "*.corp" and ".corp" mean the same; "<local>" is Windows-only
+                let host = host.strip_prefix('*').unwrap_or(host);
+                (!host.is_empty() && host != "<local>").then(|| host.to_string())
+            })
+            .collect();
+
+        let route = EgressRoute {
+            proxy_url: proxy.as_deref().map(without_credentials),
+            source: found.source.to_string(),
+            pac_url,
+            no_proxy,
+            authenticated: credentials.is_some()
+                || proxy
+                    .as_deref()
+                    .and_then(|u| Url::parse(u).ok())
+                    .is_some_and(|u| !u.username().is_empty()),
+            proxy,
+            credentials,
+        };
+        match &route.proxy_url {
+            Some(url) => log::info!("Outgoing requests use proxy {} ({})", url, route.source),
+            None => log::debug!("Outgoing requests connect directly"),
+        }
+        route
+    }
+
+    /// Store the proxy password in the keystore, or remove it with `None`
+    pub fn set_password(password: Option<&str>) -> Result<(), KeystoreError> {
+        match password.filter(|p| !p.is_empty()) {
+            Some(password) => KeystoreService::save_entry(PASSWORD_ENTRY, password),
+            None => KeystoreService::delete_entry(PASSWORD_ENTRY),
+        }
+    }
+
+    fn password() -> Option<String> {
+        if let Ok(password) = std::env::var(PASSWORD_ENV) {
+            return Some(password);
+        }
+        KeystoreService::load_entry(PASSWORD_ENTRY).unwrap_or_else(|e| {
+            log::warn!("Can't read the proxy password: {}", e);
+            None
+        })
+    }
+}
+
+/// The conventional proxy variables, HTTPS first since both servers use it
+fn from_env() -> Option<Found> {
+    let url = [
+        "HTTPS_PROXY",
+        "https_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+    ]
+    .iter()
+    .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))?;
+    let no_proxy = std::env::var("NO_PROXY")
+        .or_else(|_| std::env::var("no_proxy"))
+        .unwrap_or_default();
+    Some(Found {
+        url: Some(url),
+        no_proxy: no_proxy.split(',').map(str::to_string).collect(),
+        source: "environment",
+        ..Found::default()
+    })
+}
+
+/// Output of an OS tool, if it ran
+#[cfg(any(unix, windows))]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `scutil --proxy`: a dictionary of `Key : value` lines
+#[cfg(target_os = "macos")]
+fn from_os() -> Option<Found> {
+    let output = run("scutil", &["--proxy"])?;
+    let mut values = std::collections::HashMap::new();
+    let mut no_proxy = Vec::new();
+    let mut in_exceptions = false;
+    for line in output.lines().map(str::trim) {
+        if line.starts_with("ExceptionsList") {
+            in_exceptions = true;
+        } else if line == "}" {
+            in_exceptions = false;
+        } else if let Some((key, value)) = line.split_once(" : ") {
+            if in_exceptions {
+                no_proxy.push(value.to_string());
+            } else {
+                values.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    let enabled = |key: &str| values.get(key).is_some_and(|v| v == "1");
+    let url = if enabled("HTTPSEnable") {
+        values.get("HTTPSProxy").map(|host| {
+            let port = values.get("HTTPSPort").map_or("443", String::as_str);
+            format!("http://{}:{}", host, port)
+        })
+    } else {
+        None
+    };
+    let pac_url = enabled("ProxyAutoConfigEnable")
+        .then(|| values.get("ProxyAutoConfigURLString").cloned())
+        .flatten();
+    (url.is_some() || pac_url.is_some()).then_some(Found {
+        url,
+        pac_url,
+        no_proxy,
+        source: "system",
+    })
+}
+
+/// Internet Options in the registry (`reg query` lines: name, type, value)
+#[cfg(windows)]
+fn from_os() -> Option<Found> {
+    let output = run(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings",
+        ],
+    )?;
+    let value = |name: &str| {
+        output.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next() == Some(name))
+                .then(|| parts.nth(1).map(str::to_string))
+                .flatten()
+        })
+    };
+    // "host:port", or per protocol: "http=host:port;https=host:port"
+    let url = (value("ProxyEnable").as_deref() == Some("0x1"))
+        .then(|| value("ProxyServer"))
+        .flatten()
+        .and_then(|server| {
+            if !server.contains('=') {
+                return Some(server);
+            }
+            let entry = |scheme: &str| {
+                server
+                    .split(';')
+                    .find_map(|e| e.strip_prefix(&format!("{}=", scheme)))
+                    .map(str::to_string)
+            };
+            entry("https").or_else(|| entry("http"))
+        });
+    let pac_url = value("AutoConfigURL");
+    let no_proxy = value("ProxyOverride")
+        .map(|hosts| hosts.split(';').map(str::to_string).collect())
+        .unwrap_or_default();
+    (url.is_some() || pac_url.is_some()).then_some(Found {
+        url,
+        pac_url,
+        no_proxy,
+        source: "system",
+    })
+}
+
+/// GNOME's `org.gnome.system.proxy` settings
+#[cfg(all(unix, not(target_os = "macos")))]
+fn from_os() -> Option<Found> {
+    let get = |schema: &str, key: &str| {
+        run("gsettings", &["get", schema, key])
+            .map(|v| v.trim().trim_matches('\'').to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let no_proxy = get("org.gnome.system.proxy", "ignore-hosts")
+        .map(|hosts| {
+            hosts
+                .trim_matches(|c| c == '[' || c == ']')
+                .split(',')
+                .map(|h| h.trim().trim_matches('\'').to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let (url, pac_url) = match get("org.gnome.system.proxy", "mode")?.as_str() {
+        "manual" => {
+            let host = get("org.gnome.system.proxy.https", "host")?;
+            let port = get("org.gnome.system.proxy.https", "port").unwrap_or_default();
+            (Some(format!("http://{}:{}", host, port)), None)
+        }
+        "auto" => (None, Some(get("org.gnome.system.proxy", "autoconfig-url")?)),
+        _ => return None,
+    };
+    Some(Found {
+        url,
+        pac_url,
+        no_proxy,
+        source: "system",
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_os() -> Option<Found> {
+    None
+}
+
+/// Download (or read) a PAC script. Runs on its own thread and runtime so
+/// it can be called from sync code inside the app's runtime.
+fn fetch_pac(url: &str) -> Result<String, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return std::fs::read_to_string(path).map_err(|e| e.to_string());
+    }
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        runtime
+            .block_on(async {
+                let client = Client::builder().no_proxy().timeout(PAC_TIMEOUT).build()?;
+                client
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            })
+            .map_err(|e| e.to_string())
+    })
+    .join()
+    .map_err(|_| "download thread panicked".to_string())?
+}
+
+/// First `PROXY host:port` or `HTTPS host:port` in the script's string
+/// literals, as a URL; `None` if it only ever returns `DIRECT`
+fn pac_proxy(script: &str) -> Option<String> {
+    let mut rest = script;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = rest[start..].chars().next()?;
+        let literal_end = rest[start + 1..].find(quote)? + start + 1;
+        let literal = &rest[start + 1..literal_end];
+        rest = &rest[literal_end + 1..];
+        for entry in literal.split(';').map(str::trim) {
+            if let Some(address) = entry.strip_prefix("PROXY ") {
+                return Some(format!("http://{}", address.trim()));
+            }
+            if let Some(address) = entry.strip_prefix("HTTPS ") {
+                return Some(format!("https://{}", address.trim()));
+            }
+        }
+    }
+    None
+}
+
+fn without_credentials(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
@@ -0,0 +1,118 @@
+//! .env file integration
+//!
+//! Writes `OPENAI_BASE_URL` and `OPENAI_API_KEY` (plus `DYMIUM_API_KEY`, read
+//! by Codex and Zed) to an env file (default
+//! ~/.dymium/ghostllm.env, configurable via `envFilePath`) for scripts and
+//! docker-compose `env_file:` entries. The file is owned by the app: it is
+//! rewritten whole, atomically, on every refresh.
+
+use crate::services::codex::API_KEY_ENV;
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// First line of every file we write; a file without it is never deleted
+const HEADER: &str = "# Managed by Dymium Provider; rewritten on every token refresh";
+
+#[derive(Error, Debug)]
+pub enum EnvFileError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Home directory not found")]
+    NoHomeDir,
+}
+
+/// Service for maintaining the GhostLLM env file
+pub struct EnvFileService;
+
+impl EnvFileService {
+    /// Configured env file path (`~/` expanded), or ~/.dymium/ghostllm.env
+    pub fn path(config: &AppConfig) -> Result<PathBuf, EnvFileError> {
+        match config.env_file_path.as_deref().map(str::trim) {
+            Some(p) if !p.is_empty() => match p.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir()
+                    .map(|home| home.join(rest))
+                    .ok_or(EnvFileError::NoHomeDir),
+                None => Ok(PathBuf::from(p)),
+            },
+            _ => Ok(AppConfig::config_dir()?.join("ghostllm.env")),
+        }
+    }
+
+    /// Rewrite the env file with the current endpoint and token
+    pub fn sync(config: &AppConfig, token: &str) -> Result<(), EnvFileError> {
+        let path = Self::path(config)?;
+        let content = format!(
+            "{}\nOPENAI_BASE_URL={}\nOPENAI_API_KEY={}\n{}={}\n",
+            HEADER,
+            OpenCodeService::compute_base_url(config),
+            token,
+            API_KEY_ENV,
+            token
+        );
+        if files::read_optional(&path)?.as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+
+        let existed = path.exists();
+        files::write_atomic(&path, content.as_bytes())?;
+        if !existed {
+            // Holds the token; new files are private
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        log::info!("Updated {}", path.display());
+        Ok(())
+    }
+
+    /// Delete the env file if we wrote it (on log out / mode switch)
+    pub fn clear(config: &AppConfig) {
+        let result = Self::path(config).and_then(|path| {
+            let ours =
+                files::read_optional(&path)?.is_some_and(|content| content.starts_with(HEADER));
+            if ours {
+                fs::remove_file(&path)?;
+                log::info!("Removed {}", path.display());
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("Failed to remove env file: {}", e);
+        }
+    }
+}
+
+impl Integration for EnvFileService {
+    fn id(&self) -> &'static str {
+        "envFile"
+    }
+
+    fn name(&self) -> &'static str {
+        ".env file"
+    }
+
+    fn target_files(&self, config: &AppConfig) -> Vec<PathBuf> {
+        Self::path(config).into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "OPENAI_BASE_URL and OPENAI_API_KEY (whole file)"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.token)?)
+    }
+
+    fn clear(&self, config: &AppConfig) {
+        Self::clear(config)
+    }
+}
@@ -0,0 +1,211 @@
+//! Error taxonomy for automation
+//!
+//! Stable numeric codes for classes of failure. They are the CLI and
+//! headless exit statuses and travel with failed token states (the
+//! `token-state-changed` event, `get_state_public`, the RPC socket), so
+//! wrapper scripts and monitoring can branch on them instead of parsing
+//! messages. Never renumber a code; add new ones instead.
+//!
+//! `FailureKind` is the finer, user-facing split of the same failures that
+//! the tray, notifications and setup window label a failed state with.
+
+use crate::services::token::TokenError;
+use crate::services::vault::VaultError;
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Class of failure, with its exit status as the discriminant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorCode {
+    /// Anything not classified below
+    #[default]
+    General = 1,
+    /// Unknown command or bad arguments
+    Usage = 2,
+    /// Keycloak rejected the credentials or refresh token
+    AuthFailed = 10,
+    /// Keycloak or the LLM endpoint could not be reached or timed out
+    EndpointUnreachable = 11,
+    /// The LLM endpoint answered with an error, e.g. 401 for the key
+    EndpointRejected = 12,
+    /// A server's TLS certificate isn't trusted
+    TlsUntrusted = 13,
+    /// A server's certificate doesn't match its pinned key
+    TlsPinMismatch = 14,
+    /// Settings are missing or invalid
+    ConfigInvalid = 20,
+    /// No credentials are stored
+    NoCredentials = 21,
+    /// No token has been obtained yet
+    NotLoggedIn = 22,
+    /// The stored token has expired
+    TokenExpired = 23,
+    /// An administrator disabled this client through the kill switch
+    ClientDisabled = 24,
+    /// The system keystore or vault can't be used
+    KeystoreUnavailable = 30,
+    /// The master-password vault is locked
+    VaultLocked = 31,
+    /// A file could not be read or written
+    Io = 40,
+    /// The audit log's hash chain doesn't verify
+    AuditTampered = 41,
+    /// Not available on this platform
+    Unsupported = 50,
+}
+
+impl ErrorCode {
+    /// Exit status for the CLI and headless mode
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+
+    /// Short name, as used in the CLI's JSON output
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::General => "error",
+            Self::Usage => "unknown_command",
+            Self::AuthFailed => "auth_failed",
+            Self::EndpointUnreachable => "endpoint_unreachable",
+            Self::EndpointRejected => "endpoint_rejected",
+            Self::TlsUntrusted => "tls_untrusted",
+            Self::TlsPinMismatch => "tls_pin_mismatch",
+            Self::ConfigInvalid => "config_error",
+            Self::NoCredentials => "no_credentials",
+            Self::NotLoggedIn => "not_logged_in",
+            Self::TokenExpired => "token_expired",
+            Self::ClientDisabled => "client_disabled",
+            Self::KeystoreUnavailable => "keystore_unavailable",
+            Self::VaultLocked => "vault_locked",
+            Self::Io => "io_error",
+            Self::AuditTampered => "audit_tampered",
+            Self::Unsupported => "unsupported",
+        }
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl From<&TokenError> for ErrorCode {
+    fn from(e: &TokenError) -> Self {
+        match e {
+            TokenError::AuthFailed { .. } | TokenError::InvalidResponse => Self::AuthFailed,
+            TokenError::EndpointUnreachable(_) | TokenError::EndpointTimeout(_) => {
+                Self::EndpointUnreachable
+            }
+            TokenError::HttpError(e) if e.is_connect() || e.is_timeout() => {
+                Self::EndpointUnreachable
+            }
+            TokenError::HttpError(_) => Self::General,
+            TokenError::EndpointRejected { .. } => Self::EndpointRejected,
+            TokenError::CertificateRejected(_) => Self::TlsUntrusted,
+            TokenError::CertificatePinMismatch(_) => Self::TlsPinMismatch,
+            TokenError::InvalidUrl
+            | TokenError::ConfigError(_)
+            | TokenError::OpenCodeWrite(_)
+            | TokenError::PlainHttpRefused(_) => Self::ConfigInvalid,
+            TokenError::MissingClientSecret | TokenError::MissingPassword => Self::NoCredentials,
+            TokenError::KeystoreError(_) => Self::KeystoreUnavailable,
+            TokenError::VaultError(VaultError::Locked) | TokenError::IdleLocked => {
+                Self::VaultLocked
+            }
+            TokenError::VaultError(_) => Self::KeystoreUnavailable,
+            TokenError::IoError(_) => Self::Io,
+            TokenError::ClientDisabled(_) => Self::ClientDisabled,
+        }
+    }
+}
+
+/// What went wrong, as shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FailureKind {
+    /// Keycloak or the LLM endpoint refused the credentials or key
+    Unauthorized,
+    /// Keycloak or the LLM endpoint didn't answer in time
+    EndpointTimeout,
+    /// Keycloak or the LLM endpoint couldn't be connected to
+    EndpointUnreachable,
+    /// Keycloak or the LLM endpoint answered with another error
+    ServerError,
+    /// A server's certificate isn't trusted or doesn't match its pin
+    Certificate,
+    /// opencode.json or the plugin couldn't be written
+    OpenCodeWrite,
+    /// The token file or another file couldn't be written
+    FileWrite,
+    /// Settings are missing or invalid
+    Config,
+    /// No credentials are stored
+    MissingCredentials,
+    /// The system keystore or vault can't be used
+    Keystore,
+    /// The vault or idle lock is waiting for an unlock
+    Locked,
+    /// An administrator disabled this client
+    Disabled,
+    /// Anything not classified above
+    #[default]
+    Other,
+}
+
+impl FailureKind {
+    /// Short label for the tray and notifications
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unauthorized => "Unauthorized",
+            Self::EndpointTimeout => "Endpoint timeout",
+            Self::EndpointUnreachable => "Endpoint unreachable",
+            Self::ServerError => "Server error",
+            Self::Certificate => "Certificate error",
+            Self::OpenCodeWrite => "OpenCode config error",
+            Self::FileWrite => "File error",
+            Self::Config => "Config error",
+            Self::MissingCredentials => "No credentials",
+            Self::Keystore => "Keystore error",
+            Self::Locked => "Locked",
+            Self::Disabled => "Disabled",
+            Self::Other => "Error",
+        }
+    }
+}
+
+impl From<&TokenError> for FailureKind {
+    fn from(e: &TokenError) -> Self {
+        match e {
+            TokenError::AuthFailed { status, .. } if *status >= 500 => Self::ServerError,
+            TokenError::AuthFailed { .. } => Self::Unauthorized,
+            TokenError::InvalidResponse => Self::ServerError,
+            TokenError::EndpointRejected {
+                status: 401 | 403, ..
+            } => Self::Unauthorized,
+            TokenError::EndpointRejected { .. } => Self::ServerError,
+            TokenError::EndpointUnreachable(_) => Self::EndpointUnreachable,
+            TokenError::EndpointTimeout(_) => Self::EndpointTimeout,
+            TokenError::HttpError(e) if e.is_timeout() => Self::EndpointTimeout,
+            TokenError::HttpError(e) if e.is_connect() => Self::EndpointUnreachable,
+            TokenError::HttpError(e) if e.status().is_some_and(|s| s.as_u16() == 401) => {
+                Self::Unauthorized
+            }
+            TokenError::HttpError(_) => Self::Other,
+            TokenError::CertificateRejected(_) | TokenError::CertificatePinMismatch(_) => {
+                Self::Certificate
+            }
+            TokenError::OpenCodeWrite(_) => Self::OpenCodeWrite,
+            TokenError::IoError(_) => Self::FileWrite,
+            TokenError::InvalidUrl
+            | TokenError::ConfigError(_)
+            | TokenError::PlainHttpRefused(_) => Self::Config,
+            TokenError::MissingClientSecret | TokenError::MissingPassword => {
+                Self::MissingCredentials
+            }
+            TokenError::KeystoreError(_) => Self::Keystore,
+            TokenError::VaultError(VaultError::Locked) | TokenError::IdleLocked => Self::Locked,
+            TokenError::VaultError(_) => Self::Keystore,
+            TokenError::ClientDisabled(_) => Self::Disabled,
+        }
+    }
+}
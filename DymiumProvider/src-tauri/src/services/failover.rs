@@ -0,0 +1,112 @@
+//! Endpoint failover
+//!
+//! The token service verifies against `llmEndpoint` and then each of
+//! `llmFallbackEndpoints`, and keeps the first that answers active (see
+//! `TokenService::verify_endpoint`). With `proxy.fallbackEndpoints` set, the
+//! local proxy keeps its own active endpoint:
+//! the primary (`llmEndpoint`) to begin with. A connection error or 5xx from
+//! the active endpoint switches to the next one and the request is retried
+//! there once. While failed over, the primary is health-checked periodically
+//! and traffic fails back as soon as it answers again. Every switch is
+//! published so the tray can show which endpoint is in use.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The endpoint the proxy is using
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveEndpoint {
+    /// 0 for the primary, 1.. for `fallbackEndpoints`
+    pub index: usize,
+    pub base_url: String,
+}
+
+impl ActiveEndpoint {
+    pub fn is_primary(&self) -> bool {
+        self.index == 0
+    }
+}
+
+/// Shared failover state
+#[derive(Clone)]
+pub struct Failover {
+    active: Arc<AtomicUsize>,
+    events: Arc<watch::Sender<Option<ActiveEndpoint>>>,
+}
+
+impl Failover {
+    /// New failover state on the primary, and a receiver for switches
+    pub fn new() -> (Self, watch::Receiver<Option<ActiveEndpoint>>) {
+        let (tx, rx) = watch::channel(None);
+        (
+            Self {
+                active: Arc::new(AtomicUsize::new(0)),
+                events: Arc::new(tx),
+            },
+            rx,
+        )
+    }
+
+    /// Index of the active endpoint among `count` endpoints
+    pub fn active(&self, count: usize) -> usize {
+        self.active
+            .load(Ordering::SeqCst)
+            .min(count.saturating_sub(1))
+    }
+
+    /// The endpoint at `failed` didn't answer; move to the next one. Returns the
+    /// index to use now (unchanged if another request already moved on).
+    pub fn report_failure(&self, failed: usize, base_urls: &[String]) -> usize {
+        let next = (failed + 1) % base_urls.len().max(1);
+        match self
+            .active
+            .compare_exchange(failed, next, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => {
+                log::warn!(
+                    "Proxy: endpoint {} failed, switching to {}",
+                    base_urls[failed],
+                    base_urls[next]
+                );
+                self.publish(next, &base_urls[next]);
+                next
+            }
+            Err(current) => current,
+        }
+    }
+
+    /// Make `index` the active endpoint. Returns whether that was a switch.
+    pub fn switch_to(&self, index: usize, base_url: &str) -> bool {
+        if self.active.swap(index, Ordering::SeqCst) == index {
+            return false;
+        }
+        self.publish(index, base_url);
+        true
+    }
+
+    /// Receiver for switches
+    pub fn subscribe(&self) -> watch::Receiver<Option<ActiveEndpoint>> {
+        self.events.subscribe()
+    }
+
+    /// The primary answers again; send traffic back to it
+    pub fn fail_back(&self, primary: &str) {
+        if self.active.swap(0, Ordering::SeqCst) != 0 {
+            log::info!(
+                "Proxy: primary endpoint {} recovered, failing back",
+                primary
+            );
+            self.publish(0, primary);
+        }
+    }
+
+    fn publish(&self, index: usize, base_url: &str) {
+        self.events.send_replace(Some(ActiveEndpoint {
+            index,
+            base_url: base_url.to_string(),
+        }));
+    }
+}
@@ -0,0 +1,478 @@
+//! Safe file writes for managed files
+//!
+//! Files owned by other tools (opencode.json, auth.json) are written atomically
+//! — to a temp file in the same directory, then renamed over the original — so
+//! a crash mid-write can't leave them truncated. Before each write the previous
+//! version is copied to ~/.dymium/backups/<name>.<timestamp>.bak, keeping the
+//! most recent few per file.
+//!
+//! Writes that wouldn't change a file's bytes are skipped. A file that still
+//! holds what this process last wrote to it (compared by SHA-256, so tokens
+//! aren't kept in memory) is backed up at most once per `OWN_BACKUP_INTERVAL`,
+//! so a token refresh every minute doesn't churn the backups; edits by users
+//! and other tools are always backed up.
+//!
+//! New files, temp files and backups are created readable by their owner
+//! only, before any bytes are written to them.
+
+use crate::services::config::AppConfig;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::RuntimeFlavor;
+
+/// Backups kept per managed file
+const MAX_BACKUPS: usize = 10;
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+/// Minimum time between backups of a file's contents written by this process
+const OWN_BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often `lock_within` retries a lock held by another writer
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Digest of the last write to each file, and when its contents were last
+/// backed up
+struct OwnWrite {
+    digest: [u8; 32],
+    backed_up: Instant,
+}
+
+static OWN_WRITES: LazyLock<Mutex<HashMap<PathBuf, OwnWrite>>> = LazyLock::new(Default::default);
+
+/// Keeps temp file names unique between threads of this process
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A backup of a managed file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    /// Backup file name, used to select it for restore
+    pub name: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub size: u64,
+}
+
+/// Write `content` to `path` atomically, preserving the existing file's
+/// permissions. A new file is readable by its owner only.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp_path = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| {
+        let mut file = create_private(&tmp_path)?;
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Create `path`, which must not exist, readable and writable by its owner
+/// only before anything is written to it
+pub fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    #[cfg(windows)]
+    restrict_to_owner(path)?;
+    Ok(file)
+}
+
+/// Create the directory `path`, which must not exist, accessible by its
+/// owner only
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)?;
+    #[cfg(windows)]
+    restrict_to_owner(path)?;
+    Ok(())
+}
+
+/// Make `path` readable and writable by its owner only: mode 0600 on Unix,
+/// an ACL granting the current user alone on Windows
+pub fn restrict_to_owner(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+    }
+    #[cfg(windows)]
+    {
+        let user = std::env::var("USERNAME").map_err(io::Error::other)?;
+        let user = match std::env::var("USERDOMAIN") {
+            Ok(domain) => format!("{}\\{}", domain, user),
+            Err(_) => user,
+        };
+        let status = std::process::Command::new("icacls")
+            .arg(path)
+            .args(["/inheritance:r", "/grant:r", &format!("{}:F", user)])
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("icacls exited with {}", status)))
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Whether users other than the owner can read `path` (Unix only)
+pub fn readable_by_others(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o044 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Exclusive advisory lock on a managed file, released on drop
+pub struct FileLock {
+    _file: fs::File,
+}
+
+/// Take an exclusive lock on `path`, blocking until it is available. The lock
+/// is held on a `.<name>.lock` sibling, since `path` itself is replaced on
+/// every atomic write.
+pub fn lock(path: &Path) -> io::Result<FileLock> {
+    let file = open_lock_file(path)?;
+    file.lock()?;
+    Ok(FileLock { _file: file })
+}
+
+/// Like [`lock`], but gives up with `ErrorKind::TimedOut` if another writer
+/// still holds the lock after `timeout`
+pub fn lock_within(path: &Path, timeout: Duration) -> io::Result<FileLock> {
+    let file = open_lock_file(path)?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(FileLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(LOCK_POLL_INTERVAL);
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("{} is locked by another writer", path.display()),
+                ));
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(e),
+        }
+    }
+}
+
+fn open_lock_file(path: &Path) -> io::Result<fs::File> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(format!(".{}.lock", file_name_of(path))))
+}
+
+/// Run blocking file or database work from code that may be on a runtime
+/// worker, typically with the token service locked. On a multi-threaded
+/// runtime the worker's other tasks are handed to another thread meanwhile.
+pub fn blocking<T>(work: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(work)
+        }
+        _ => work(),
+    }
+}
+
+/// Read `path` as text, or `None` if it doesn't exist
+pub fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Back up the current contents of `path` (if any), then write atomically.
+/// Returns `false` without touching the file if it already holds `content`.
+pub fn write_with_backup(path: &Path, content: &[u8]) -> io::Result<bool> {
+    let current = match fs::read(path) {
+        Ok(current) => Some(current),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if current.as_deref() == Some(content) {
+        return Ok(false);
+    }
+
+    let mut own_writes = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    let previous = own_writes.get(path);
+    let own_content = previous.is_some_and(|write| {
+        current
+            .as_deref()
+            .is_some_and(|current| Sha256::digest(current)[..] == write.digest)
+    });
+    let mut backed_up = previous.map(|write| write.backed_up);
+    let backup_due =
+        !own_content || backed_up.is_none_or(|instant| instant.elapsed() >= OWN_BACKUP_INTERVAL);
+    if backup_due {
+        match backup(path) {
+            Ok(_) => backed_up = Some(Instant::now()),
+            Err(e) => log::warn!("Failed to back up {}: {}", path.display(), e),
+        }
+    }
+
+    write_atomic(path, content)?;
+    own_writes.insert(
+        path.to_path_buf(),
+        OwnWrite {
+            digest: Sha256::digest(content).into(),
+            backed_up: backed_up.unwrap_or_else(Instant::now),
+        },
+    );
+    Ok(true)
+}
+
+/// Directory holding backups (~/.dymium/backups)
+pub fn backup_dir() -> io::Result<PathBuf> {
+    AppConfig::config_dir()
+        .map(|p| p.join("backups"))
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))
+}
+
+/// Copy `path` to a timestamped backup. Returns `None` if there was nothing to back up.
+pub fn backup(path: &Path) -> io::Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let backup_path = backup_content(&file_name_of(path), &fs::read(path)?)?;
+    log::debug!("Backed up {} to {}", path.display(), backup_path.display());
+    Ok(Some(backup_path))
+}
+
+/// Save `content` as a timestamped backup named after `file_name`, for data
+/// that isn't a whole file of its own, like one row of a database
+pub fn backup_content(file_name: &str, content: &[u8]) -> io::Result<PathBuf> {
+    let dir = backup_dir()?;
+    fs::create_dir_all(&dir)?;
+    let stamp = Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+    let backup_path = dir.join(format!("{}.{}.bak", file_name, stamp));
+    match fs::remove_file(&backup_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // Backups of auth.json hold credentials for every provider
+    let mut file = create_private(&backup_path)?;
+    file.write_all(content)?;
+
+    prune_backups(file_name)?;
+    Ok(backup_path)
+}
+
+/// List backups of `path`, newest first
+pub fn list_backups(path: &Path) -> io::Result<Vec<BackupInfo>> {
+    let file_name = file_name_of(path);
+    let mut backups: Vec<BackupInfo> = backup_entries(&file_name)?
+        .into_iter()
+        .map(|(name, size)| {
+            let created_at = name
+                .strip_prefix(&format!("{}.", file_name))
+                .and_then(|rest| rest.strip_suffix(".bak"))
+                .and_then(|stamp| {
+                    NaiveDateTime::parse_from_str(stamp, BACKUP_TIMESTAMP_FORMAT).ok()
+                })
+                .map(|dt| dt.and_utc());
+            BackupInfo {
+                name,
+                created_at,
+                size,
+            }
+        })
+        .collect();
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Restore `path` from the named backup. The current version is backed up first.
+pub fn restore_backup(path: &Path, backup_name: &str) -> io::Result<()> {
+    let file_name = file_name_of(path);
+    if !backup_name.starts_with(&format!("{}.", file_name))
+        || !backup_name.ends_with(".bak")
+        || backup_name.contains(['/', '\\'])
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a backup of {}", backup_name, file_name),
+        ));
+    }
+
+    let content = fs::read(backup_dir()?.join(backup_name))?;
+    write_with_backup(path, &content)?;
+    log::info!("Restored {} from {}", path.display(), backup_name);
+    Ok(())
+}
+
+/// Delete every backup of `path`. Returns how many there were.
+pub fn remove_backups(path: &Path) -> io::Result<usize> {
+    let entries = backup_entries(&file_name_of(path))?;
+    let dir = backup_dir()?;
+    for (name, _) in &entries {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(entries.len())
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// (name, size) of every backup of `file_name`
+fn backup_entries(file_name: &str) -> io::Result<Vec<(String, u64)>> {
+    let dir = backup_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}.", file_name);
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name.ends_with(".bak") {
+            entries.push((name, entry.metadata().map(|m| m.len()).unwrap_or(0)));
+        }
+    }
+    Ok(entries)
+}
+
+fn prune_backups(file_name: &str) -> io::Result<()> {
+    let mut names: Vec<String> = backup_entries(file_name)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    if names.len() <= MAX_BACKUPS {
+        return Ok(());
+    }
+
+    names.sort();
+    let dir = backup_dir()?;
+    for name in &names[..names.len() - MAX_BACKUPS] {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dymium-files-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn new_file_is_private() {
+        let dir = scratch_dir("new");
+        let path = dir.join("auth.json");
+        write_atomic(&path, b"{}").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"{}");
+        assert_eq!(mode(&path), 0o600);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn existing_permissions_are_kept() {
+        let dir = scratch_dir("existing");
+        let path = dir.join("opencode.json");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(mode(&path), 0o644);
+        let leftovers = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(leftovers, 1, "temp file left behind");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn private_files_are_never_shared() {
+        let dir = scratch_dir("private");
+        let path = dir.join("backup.bak");
+        let mut file = create_private(&path).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        file.write_all(b"secret").unwrap();
+        // An existing file, e.g. one planted to read what is written, is refused
+        assert!(create_private(&path).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn lock_within_gives_up_on_a_held_lock() {
+        let dir = scratch_dir("lock");
+        let path = dir.join("auth.json");
+        let held = lock(&path).unwrap();
+        let err = lock_within(&path, Duration::from_millis(100))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        drop(held);
+        assert!(lock_within(&path, Duration::from_millis(100)).is_ok());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,141 @@
+//! Endpoint health
+//!
+//! Remembers the outcome of the last live request to Keycloak (token grant)
+//! and to the LLM endpoint (`/v1/models` verification) for the settings
+//! window's health dashboard. Dry runs from `test_llm_endpoint` and
+//! `validate_oauth_credentials` are not recorded.
+//!
+//! Each upstream also keeps a rolling average round-trip time. The LLM
+//! endpoint check only lists models, so a high average there means a slow
+//! network or gateway rather than a slow model.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Requests the rolling average covers
+const LATENCY_SAMPLES: usize = 10;
+
+/// Which endpoint a check was made against
+#[derive(Debug, Clone, Copy)]
+pub enum EndpointKind {
+    Keycloak,
+    LlmEndpoint,
+}
+
+/// Outcome of the last request to one upstream
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointHealth {
+    pub healthy: bool,
+    /// HTTP status, if a response was received
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// Average round-trip time of the last requests that got a response
+    pub average_latency_ms: Option<u64>,
+    pub checked_at: DateTime<Utc>,
+    /// Why the check failed
+    pub error: Option<String>,
+}
+
+/// Last known health of both upstreams; `None` until first checked
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub keycloak: Option<EndpointHealth>,
+    pub llm_endpoint: Option<EndpointHealth>,
+}
+
+/// Recent round-trip times per upstream, newest last
+#[derive(Default)]
+struct Latencies {
+    keycloak: VecDeque<u64>,
+    llm_endpoint: VecDeque<u64>,
+}
+
+impl Latencies {
+    fn of(&mut self, kind: EndpointKind) -> &mut VecDeque<u64> {
+        match kind {
+            EndpointKind::Keycloak => &mut self.keycloak,
+            EndpointKind::LlmEndpoint => &mut self.llm_endpoint,
+        }
+    }
+}
+
+/// Shared record of the last check per upstream
+#[derive(Clone, Default)]
+pub struct HealthLog {
+    report: Arc<Mutex<HealthReport>>,
+    latencies: Arc<Mutex<Latencies>>,
+}
+
+impl HealthLog {
+    /// Record a request to `kind` that started at `started`
+    pub fn record(
+        &self,
+        kind: EndpointKind,
+        started: Instant,
+        status: Option<u16>,
+        error: Option<String>,
+    ) {
+        let latency_ms = started.elapsed().as_millis() as u64;
+        // Failures without a response (timeouts, refused connections) would
+        // only skew the average
+        let average_latency_ms = self.latencies.lock().ok().and_then(|mut latencies| {
+            let samples = latencies.of(kind);
+            if status.is_some() {
+                if samples.len() == LATENCY_SAMPLES {
+                    samples.pop_front();
+                }
+                samples.push_back(latency_ms);
+            }
+            average(samples)
+        });
+        let health = EndpointHealth {
+            healthy: error.is_none(),
+            status,
+            latency_ms,
+            average_latency_ms,
+            checked_at: Utc::now(),
+            error,
+        };
+        if let Ok(mut report) = self.report.lock() {
+            match kind {
+                EndpointKind::Keycloak => report.keycloak = Some(health),
+                EndpointKind::LlmEndpoint => report.llm_endpoint = Some(health),
+            }
+        }
+    }
+
+    /// Latest results
+    pub fn report(&self) -> HealthReport {
+        self.report.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// Rolling average round-trip time to `kind`, once it has answered
+    pub fn average_latency(&self, kind: EndpointKind) -> Option<u64> {
+        self.latencies
+            .lock()
+            .ok()
+            .and_then(|mut latencies| average(latencies.of(kind)))
+    }
+
+    /// Forget previous results, e.g. after the endpoint settings change
+    pub fn clear(&self) {
+        if let Ok(mut report) = self.report.lock() {
+            *report = HealthReport::default();
+        }
+        if let Ok(mut latencies) = self.latencies.lock() {
+            *latencies = Latencies::default();
+        }
+    }
+}
+
+fn average(samples: &VecDeque<u64>) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    Some(samples.iter().sum::<u64>() / samples.len() as u64)
+}
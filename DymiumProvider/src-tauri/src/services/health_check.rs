@@ -0,0 +1,78 @@
+//! Health-check endpoint
+//!
+//! With `healthCheck.enabled`, serves two routes on 127.0.0.1 (port
+//! `healthCheck.port`, default 4142) so wrapper scripts can wait for
+//! authentication before starting an agent job:
+//!
+//! - `GET /healthz` → 200 while the app is running, with the token state
+//! - `GET /readyz` → 200 once a verified token exists and hasn't expired,
+//!   503 otherwise
+//!
+//! Both answer `{"ready": bool, "state": {...}}`, the state as in
+//! `get_state_public` (no token).
+
+use crate::services::config::{TokenState, TokenStatePublic};
+use crate::services::token::SharedTokenService;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::Serialize;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Body of both routes
+#[derive(Serialize)]
+struct HealthResponse {
+    ready: bool,
+    state: TokenStatePublic,
+}
+
+/// Service for the health-check endpoint
+pub struct HealthCheckService;
+
+impl HealthCheckService {
+    /// Serve on 127.0.0.1:`port` until the listener fails
+    pub async fn serve(
+        port: u16,
+        token_service: Arc<SharedTokenService>,
+    ) -> Result<(), std::io::Error> {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .with_state(token_service);
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        log::info!("Health check listening on http://127.0.0.1:{}", port);
+        axum::serve(listener, app).await
+    }
+}
+
+async fn healthz(State(ts): State<Arc<SharedTokenService>>) -> Json<HealthResponse> {
+    Json(health(&ts).await)
+}
+
+async fn readyz(State(ts): State<Arc<SharedTokenService>>) -> (StatusCode, Json<HealthResponse>) {
+    let health = health(&ts).await;
+    let status = if health.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health))
+}
+
+async fn health(ts: &SharedTokenService) -> HealthResponse {
+    let service = ts.lock().await;
+    let state = service.state();
+    // Logins only reach Authenticated once the LLM endpoint accepted the token
+    let ready = matches!(
+        state,
+        TokenState::Authenticated { expires_at, .. } if *expires_at > Utc::now()
+    );
+    HealthResponse {
+        ready,
+        state: state.into(),
+    }
+}
@@ -0,0 +1,47 @@
+//! Host name overrides
+//!
+//! `hostOverrides` maps host names to IP addresses for requests to Keycloak
+//! and the LLM endpoint, like entries in /etc/hosts but without admin rights,
+//! for labs whose names aren't in DNS. Requests keep the host name for TLS
+//! (SNI and certificate checks) and the `Host` header; only the address
+//! lookup is replaced. Requests through an egress proxy are resolved by the
+//! proxy instead.
+
+use reqwest::ClientBuilder;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Parsed `hostOverrides`
+#[derive(Debug, Clone, Default)]
+pub struct HostOverrides {
+    addresses: Vec<(String, IpAddr)>,
+}
+
+impl HostOverrides {
+    /// Parse `hosts` (name → IP); invalid addresses are logged and skipped
+    pub fn parse(hosts: &BTreeMap<String, String>) -> Self {
+        let addresses = hosts
+            .iter()
+            .filter_map(|(host, ip)| match ip.trim().parse::<IpAddr>() {
+                Ok(ip) => Some((host.trim().to_ascii_lowercase(), ip)),
+                Err(_) => {
+                    log::error!(
+                        "Ignoring host override {} → {}: not an IP address",
+                        host,
+                        ip
+                    );
+                    None
+                }
+            })
+            .collect();
+        Self { addresses }
+    }
+
+    /// Resolve the overridden names on `builder`'s connections
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        self.addresses.iter().fold(builder, |builder, (host, ip)| {
+            // The port is ignored; the URL's port is used
+            builder.resolve(host, SocketAddr::new(*ip, 0))
+        })
+    }
+}
@@ -0,0 +1,127 @@
+//! Managed integrations
+//!
+//! Besides OpenCode (always managed, see `opencode`), other tools can be kept
+//! pointed at GhostLLM with the current token. Each one implements
+//! [`Integration`] and is listed in [`REGISTRY`]; it is enabled by setting its
+//! ID to `true` under `integrations` in config.json.
+
+use crate::services::claude_code::ClaudeCodeService;
+use crate::services::codex::CodexService;
+use crate::services::config::{AppConfig, ModelEntry};
+use crate::services::continue_dev::ContinueService;
+use crate::services::cursor::CursorService;
+use crate::services::env_file::EnvFileService;
+use crate::services::jetbrains::JetBrainsService;
+use crate::services::neovim::NeovimService;
+use crate::services::opencode::OpenCodeService;
+use crate::services::roo_code::RooCodeService;
+use crate::services::shell::ShellService;
+use crate::services::zed::ZedService;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// What an integration gets to work with on each sync
+pub struct SyncContext<'a> {
+    pub config: &'a AppConfig,
+    /// Current access token (or static API key)
+    pub token: &'a str,
+    /// Catalog models, or those the endpoint listed
+    pub models: &'a [ModelEntry],
+}
+
+impl SyncContext<'_> {
+    pub fn model_ids(&self) -> Vec<String> {
+        self.models.iter().map(|m| m.id.clone()).collect()
+    }
+}
+
+/// A tool whose configuration the app keeps up to date
+pub trait Integration: Sync {
+    /// Key under `integrations` in config.json
+    fn id(&self) -> &'static str;
+    /// Display name
+    fn name(&self) -> &'static str;
+    /// Files the integration writes
+    fn target_files(&self, config: &AppConfig) -> Vec<PathBuf>;
+    /// What the integration writes into its files
+    fn template(&self) -> &'static str;
+    /// Bring the target files up to date. Must not rewrite unchanged files.
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()>;
+    /// Remove what `sync` wrote
+    fn clear(&self, config: &AppConfig);
+    /// Whether the managed entries are unmistakably ours, so they can be
+    /// cleaned up even while the integration is disabled. Integrations that
+    /// write settings the user may have set themselves return false.
+    fn owns_entries(&self) -> bool {
+        true
+    }
+}
+
+/// Every optional integration
+pub static REGISTRY: &[&dyn Integration] = &[
+    &ContinueService,
+    &RooCodeService,
+    &ClaudeCodeService,
+    &CursorService,
+    &ZedService,
+    &EnvFileService,
+    &ShellService,
+    &CodexService,
+    &NeovimService,
+    &JetBrainsService,
+];
+
+/// Look up an integration by ID
+pub fn find(id: &str) -> Option<&'static dyn Integration> {
+    REGISTRY.iter().copied().find(|i| i.id() == id)
+}
+
+/// A file the app writes to
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagedFile {
+    /// Integration ID ("opencode" for the built-in OpenCode integration)
+    pub integration: String,
+    pub name: String,
+    pub path: String,
+    /// What the app manages in the file
+    pub managed: String,
+    pub enabled: bool,
+    pub exists: bool,
+}
+
+/// Every file the app touches, for enabled and disabled integrations alike
+pub fn managed_files(config: &AppConfig) -> Vec<ManagedFile> {
+    let entry = |id: &str, name: &str, path: PathBuf, managed: &str, enabled: bool| ManagedFile {
+        integration: id.to_string(),
+        name: name.to_string(),
+        exists: path.exists(),
+        path: path.display().to_string(),
+        managed: managed.to_string(),
+        enabled,
+    };
+
+    let mut files = Vec::new();
+    if let Ok(paths) = OpenCodeService::managed_paths() {
+        let managed = [
+            "provider.dymium and the auth plugin registration",
+            "the dymium token entry",
+        ];
+        for (path, managed) in paths.into_iter().zip(managed) {
+            files.push(entry("opencode", "OpenCode", path, managed, true));
+        }
+    }
+    for integration in REGISTRY {
+        let enabled = config.integrations.is_enabled(integration.id());
+        for path in integration.target_files(config) {
+            files.push(entry(
+                integration.id(),
+                integration.name(),
+                path,
+                integration.template(),
+                enabled,
+            ));
+        }
+    }
+    files
+}
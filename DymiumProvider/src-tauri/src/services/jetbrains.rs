@@ -0,0 +1,388 @@
+//! JetBrains AI Assistant integration
+//!
+//! AI Assistant's OpenAI-compatible provider keeps its API key in the IDE's
+//! password safe (OS keychain or KeePass database), which other programs
+//! can't update reliably. So instead of the token, each IDE is pointed at the
+//! local proxy (see `proxy`), which adds the current token to every request:
+//! the provider URL becomes `http://127.0.0.1:<port>/v1` and the key a fixed
+//! placeholder, neither of which changes when the token rotates.
+//!
+//! The settings are options of one component in
+//! `<config dir>/JetBrains/<IDE><version>/options/`, for every IDE and
+//! version found. Only those options are edited, as text; the rest of the file
+//! is left as it was. The IDE reads them at startup and writes its own copy
+//! back on exit, so restart it after enabling the integration.
+
+use crate::services::config::AppConfig;
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Options file, under each IDE's `options` directory
+const OPTIONS_FILE: &str = "AIAssistantThirdPartyProviders.xml";
+/// Component holding the third-party provider settings
+const COMPONENT: &str = "AIAssistantThirdPartyProviders";
+const ENABLED_OPTION: &str = "openAICompatibleEnabled";
+const URL_OPTION: &str = "openAICompatibleUrl";
+const API_KEY_OPTION: &str = "openAICompatibleApiKey";
+/// Key sent to the proxy, which replaces it with the real token
+const PLACEHOLDER_KEY: &str = "dymium-proxy";
+
+#[derive(Error, Debug)]
+pub enum JetBrainsError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("{0} is not an IDE options file")]
+    NotAnOptionsFile(String),
+    #[error("The local proxy is off; set proxy.enabled in config.json")]
+    ProxyDisabled,
+    #[error("Config directory not found")]
+    NoConfigDir,
+}
+
+/// Service for pointing JetBrains AI Assistant at the local proxy
+pub struct JetBrainsService;
+
+impl JetBrainsService {
+    /// JetBrains' config root (<config dir>/JetBrains)
+    fn config_root() -> Result<PathBuf, JetBrainsError> {
+        dirs::config_dir()
+            .map(|p| p.join("JetBrains"))
+            .ok_or(JetBrainsError::NoConfigDir)
+    }
+
+    /// The options file of every installed IDE version, e.g.
+    /// ~/.config/JetBrains/PyCharm2025.2/options/<file>
+    pub fn options_paths() -> Result<Vec<PathBuf>, JetBrainsError> {
+        let root = Self::config_root()?;
+        let entries = match fs::read_dir(&root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| Self::is_ide_dir(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path().join("options"))
+            .filter(|options| options.is_dir())
+            .map(|options| options.join(OPTIONS_FILE))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// IDE config directories are named after the product and its version,
+    /// e.g. "IntelliJIdea2025.2"; others (consent settings, Toolbox) aren't
+    fn is_ide_dir(name: &str) -> bool {
+        let Some(pos) = name.find(|c: char| c.is_ascii_digit()) else {
+            return false;
+        };
+        let (product, version) = name.split_at(pos);
+        let mut parts = version.split('.');
+        !product.is_empty()
+            && parts.next().is_some_and(|year| year.len() == 4)
+            && parts.next().is_some()
+            && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+    }
+
+    /// Point every IDE's OpenAI-compatible provider at the local proxy
+    pub fn sync(config: &AppConfig) -> Result<(), JetBrainsError> {
+        if !config.proxy.enabled {
+            return Err(JetBrainsError::ProxyDisabled);
+        }
+        let url = config.proxy.local_base_url();
+        let options = [
+            (ENABLED_OPTION, Some("true")),
+            (URL_OPTION, Some(url.as_str())),
+            (API_KEY_OPTION, Some(PLACEHOLDER_KEY)),
+        ];
+        for path in Self::options_paths()? {
+            if Self::update(&path, &options)? {
+                log::info!(
+                    "Pointed AI Assistant at the local proxy in {}",
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the provider settings, where they still point at the proxy
+    pub fn clear(config: &AppConfig) {
+        let url = config.proxy.local_base_url();
+        let options = [
+            (ENABLED_OPTION, None),
+            (URL_OPTION, None),
+            (API_KEY_OPTION, None),
+        ];
+        let result = Self::options_paths().and_then(|paths| {
+            for path in paths {
+                let Some(content) = files::read_optional(&path)? else {
+                    continue;
+                };
+                // The user may have pointed the provider elsewhere since
+                if Self::option(&content, URL_OPTION).as_deref() != Some(url.as_str()) {
+                    continue;
+                }
+                if Self::update(&path, &options)? {
+                    log::info!("Cleared Dymium settings from {}", path.display());
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("Failed to clear JetBrains settings: {}", e);
+        }
+    }
+
+    /// Apply `options` to the file at `path`. Returns whether it was written.
+    fn update(path: &Path, options: &[(&str, Option<&str>)]) -> Result<bool, JetBrainsError> {
+        let original = files::read_optional(path)?;
+        let Some(content) = Self::set_options(original.as_deref(), options)
+            .ok_or_else(|| JetBrainsError::NotAnOptionsFile(path.display().to_string()))?
+        else {
+            return Ok(false);
+        };
+        if original.as_deref() == Some(content.as_str()) {
+            return Ok(false);
+        }
+        Ok(files::write_with_backup(path, content.as_bytes())?)
+    }
+
+    /// Set (`Some`) or remove (`None`) options of the component in an
+    /// options file's text. Returns `Some(None)` when there is no file and
+    /// nothing to set, and `None` if the text isn't an options file.
+    fn set_options(
+        original: Option<&str>,
+        options: &[(&str, Option<&str>)],
+    ) -> Option<Option<String>> {
+        let original = match original.filter(|c| !c.trim().is_empty()) {
+            Some(original) => original,
+            None if options.iter().all(|(_, value)| value.is_none()) => return Some(None),
+            None => "<application>\n</application>\n",
+        };
+        let mut content = original.to_string();
+
+        let open_tag = format!("<component name=\"{}\"", COMPONENT);
+        let start = match content.find(&open_tag) {
+            Some(start) => start,
+            None => {
+                if options.iter().all(|(_, value)| value.is_none()) {
+                    return Some(Some(content));
+                }
+                let end = content.rfind("</application>")?;
+                let line_start = content[..end].rfind('\n').map_or(0, |p| p + 1);
+                content.insert_str(line_start, &format!("  {}>\n  </component>\n", open_tag));
+                line_start + 2
+            }
+        };
+        // `<component name="..." />` gets a body
+        let tag_end = start + content[start..].find('>')?;
+        if content[..tag_end].ends_with('/') {
+            let slash = content[..tag_end].trim_end_matches('/').trim_end().len();
+            content.replace_range(slash..=tag_end, ">\n  </component>");
+        }
+        let body_start = start + content[start..].find('>')? + 1;
+        let body_end = body_start + content[body_start..].find("</component>")?;
+
+        let mut body = content[body_start..body_end].to_string();
+        for (name, value) in options {
+            body = Self::set_option(&body, name, *value);
+        }
+        content.replace_range(body_start..body_end, &body);
+        Some(Some(content))
+    }
+
+    /// Set or remove one `<option name=".." value=".." />` in a component body
+    fn set_option(body: &str, name: &str, value: Option<&str>) -> String {
+        let tag = format!("<option name=\"{}\"", name);
+        let new_tag = value.map(|value| format!("{} value=\"{}\" />", tag, escape(value)));
+        let mut body = body.to_string();
+        match (body.find(&tag), new_tag) {
+            (Some(start), Some(new_tag)) => {
+                if let Some(len) = body[start..].find("/>") {
+                    body.replace_range(start..start + len + 2, &new_tag);
+                }
+            }
+            (Some(start), None) => {
+                if let Some(len) = body[start..].find("/>") {
+                    // Take the whole line with it
+                    let line_start = body[..start].rfind('\n').unwrap_or(start);
+                    body.replace_range(line_start..start + len + 2, "");
+                }
+            }
+            (None, Some(new_tag)) => {
+                // Before the closing tag's own indentation
+                let at = body.rfind('\n').unwrap_or(body.len());
+                body.insert_str(at, &format!("\n    {}", new_tag));
+            }
+            (None, None) => {}
+        }
+        body
+    }
+
+    /// Value of an option of the component, if set
+    fn option(content: &str, name: &str) -> Option<String> {
+        let start = content.find(&format!("<component name=\"{}\"", COMPONENT))?;
+        let body = &content[start..start + content[start..].find("</component>")?];
+        let tag = &body[body.find(&format!("<option name=\"{}\"", name))?..];
+        let tag = &tag[..tag.find("/>")?];
+        let value = &tag[tag.find("value=\"")? + 7..];
+        Some(unescape(&value[..value.find('"')?]))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+impl Integration for JetBrainsService {
+    fn id(&self) -> &'static str {
+        "jetbrains"
+    }
+
+    fn name(&self) -> &'static str {
+        "JetBrains AI Assistant"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::options_paths().unwrap_or_default()
+    }
+
+    fn template(&self) -> &'static str {
+        "the OpenAI-compatible provider, pointed at the local proxy with a placeholder key"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config)?)
+    }
+
+    fn clear(&self, config: &AppConfig) {
+        Self::clear(config)
+    }
+
+    /// The provider may be the user's own
+    fn owns_entries(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SET: &[(&str, Option<&str>)] = &[
+        (ENABLED_OPTION, Some("true")),
+        (URL_OPTION, Some("http://127.0.0.1:4141/v1")),
+        (API_KEY_OPTION, Some(PLACEHOLDER_KEY)),
+    ];
+    const REMOVE: &[(&str, Option<&str>)] = &[
+        (ENABLED_OPTION, None),
+        (URL_OPTION, None),
+        (API_KEY_OPTION, None),
+    ];
+
+    fn set(original: Option<&str>, options: &[(&str, Option<&str>)]) -> String {
+        JetBrainsService::set_options(original, options)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn new_file_gets_the_component() {
+        assert_eq!(
+            set(None, SET),
+            "<application>
+  <component name=\"AIAssistantThirdPartyProviders\">
+    <option name=\"openAICompatibleEnabled\" value=\"true\" />
+    <option name=\"openAICompatibleUrl\" value=\"http://127.0.0.1:4141/v1\" />
+    <option name=\"openAICompatibleApiKey\" value=\"dymium-proxy\" />
+  </component>
+</application>
+"
+        );
+        assert_eq!(JetBrainsService::set_options(None, REMOVE), Some(None));
+    }
+
+    #[test]
+    fn other_settings_are_kept() {
+        let original = "<application>
+  <component name=\"AIAssistantThirdPartyProviders\">
+    <option name=\"ollamaEnabled\" value=\"true\" />
+    <option name=\"openAICompatibleUrl\" value=\"http://localhost:1234/v1\" />
+  </component>
+  <component name=\"Other\" />
+</application>
+";
+        let synced = set(Some(original), SET);
+        assert_eq!(
+            synced,
+            "<application>
+  <component name=\"AIAssistantThirdPartyProviders\">
+    <option name=\"ollamaEnabled\" value=\"true\" />
+    <option name=\"openAICompatibleUrl\" value=\"http://127.0.0.1:4141/v1\" />
+    <option name=\"openAICompatibleEnabled\" value=\"true\" />
+    <option name=\"openAICompatibleApiKey\" value=\"dymium-proxy\" />
+  </component>
+  <component name=\"Other\" />
+</application>
+"
+        );
+        assert_eq!(set(Some(&synced), SET), synced);
+        assert_eq!(
+            JetBrainsService::option(&synced, URL_OPTION).as_deref(),
+            Some("http://127.0.0.1:4141/v1")
+        );
+
+        let cleared = set(Some(&synced), REMOVE);
+        assert_eq!(
+            cleared,
+            "<application>
+  <component name=\"AIAssistantThirdPartyProviders\">
+    <option name=\"ollamaEnabled\" value=\"true\" />
+  </component>
+  <component name=\"Other\" />
+</application>
+"
+        );
+    }
+
+    #[test]
+    fn empty_component_and_other_files() {
+        let original = "<application>\n  <component name=\"AIAssistantThirdPartyProviders\" />\n</application>\n";
+        let synced = set(Some(original), &SET[1..2]);
+        assert_eq!(
+            synced,
+            "<application>
+  <component name=\"AIAssistantThirdPartyProviders\">
+    <option name=\"openAICompatibleUrl\" value=\"http://127.0.0.1:4141/v1\" />
+  </component>
+</application>
+"
+        );
+        assert_eq!(JetBrainsService::set_options(Some("{}"), SET), None);
+    }
+
+    #[test]
+    fn ide_directories() {
+        assert!(JetBrainsService::is_ide_dir("IntelliJIdea2025.2"));
+        assert!(JetBrainsService::is_ide_dir("PyCharmCE2024.3"));
+        assert!(!JetBrainsService::is_ide_dir("consentOptions"));
+        assert!(!JetBrainsService::is_ide_dir("Toolbox"));
+        assert!(!JetBrainsService::is_ide_dir("2025.2"));
+    }
+}
@@ -0,0 +1,244 @@
+//! Edit-in-place for JSON-with-comments files
+//!
+//! Round-tripping a user's config through `serde_json::Value` drops comments
+//! and reformats everything. Instead, the desired document is computed as a
+//! `Value` as usual, and only the properties the app manages are spliced into
+//! the original text; comments, formatting and key order elsewhere survive.
+
+use crate::services::files;
+use jsonc_parser::cst::{CstInputValue, CstObject, CstRootNode};
+use jsonc_parser::ParseOptions;
+use serde_json::{Map, Value};
+use std::io;
+use std::path::Path;
+
+/// Rewrite the properties at `paths` in `original` to match `updated`.
+///
+/// Each path is a list of object keys from the root. Properties present in
+/// `updated` are replaced (or appended); properties missing from it are
+/// removed. Paths whose value is unchanged are left untouched, formatting
+/// included. Returns `None` if `original` can't be parsed as JSONC.
+pub fn patch(original: &str, updated: &Value, paths: &[&[&str]]) -> Option<String> {
+    let root = CstRootNode::parse(original, &ParseOptions::default()).ok()?;
+    let current: Option<Value> = jsonc_parser::parse_to_serde_value(original, &Default::default())
+        .ok()
+        .flatten();
+
+    for path in paths {
+        let pointer = to_pointer(path);
+        let desired = updated.pointer(&pointer);
+        if desired == current.as_ref().and_then(|c| c.pointer(&pointer)) {
+            continue;
+        }
+
+        let Some((last, parents)) = path.split_last() else {
+            continue;
+        };
+        let mut parent: CstObject = root.object_value_or_set();
+        for key in parents {
+            parent = parent.object_value_or_set(key);
+        }
+
+        match (desired, parent.get(last)) {
+            (Some(value), Some(prop)) => prop.set_value(to_cst(value)),
+            (Some(value), None) => {
+                parent.append(last, to_cst(value));
+            }
+            (None, Some(prop)) => prop.remove(),
+            (None, None) => {}
+        }
+    }
+
+    Some(root.to_string())
+}
+
+/// Set (`Some`) or remove (`None`) properties in a JSONC file on disk, keeping
+/// the rest of the file as it was. A missing file is only created if something
+/// is set. Returns whether the file was written.
+pub fn update_file(path: &Path, edits: &[(&[&str], Option<Value>)]) -> io::Result<bool> {
+    let original = files::read_optional(path)?;
+    if original.is_none() && edits.iter().all(|(_, v)| v.is_none()) {
+        return Ok(false);
+    }
+
+    let text = original.clone().unwrap_or_else(|| "{}".to_string());
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a JSON object", path.display()),
+        )
+    };
+    let mut doc = jsonc_parser::parse_to_serde_value(&text, &Default::default())
+        .map_err(|_| invalid())?
+        .unwrap_or_else(|| Value::Object(Map::new()));
+    if !doc.is_object() {
+        return Err(invalid());
+    }
+
+    for (keys, value) in edits {
+        set_path(&mut doc, keys, value.clone());
+    }
+    let paths: Vec<&[&str]> = edits.iter().map(|(keys, _)| *keys).collect();
+    let content = patch(&text, &doc, &paths).ok_or_else(invalid)?;
+    if original.as_deref() == Some(content.as_str()) {
+        return Ok(false);
+    }
+
+    files::write_with_backup(path, content.as_bytes())?;
+    Ok(true)
+}
+
+/// Set or remove the property at `keys`, creating intermediate objects
+fn set_path(doc: &mut Value, keys: &[&str], value: Option<Value>) {
+    let Some((last, parents)) = keys.split_last() else {
+        return;
+    };
+    let mut current = doc;
+    for key in parents {
+        let Some(obj) = current.as_object_mut() else {
+            return;
+        };
+        let child = obj
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !child.is_object() {
+            if value.is_none() {
+                return;
+            }
+            *child = Value::Object(Map::new());
+        }
+        current = child;
+    }
+
+    if let Some(obj) = current.as_object_mut() {
+        match value {
+            Some(v) => {
+                obj.insert(last.to_string(), v);
+            }
+            None => {
+                obj.remove(*last);
+            }
+        }
+    }
+}
+
+fn to_pointer(path: &[&str]) -> String {
+    path.iter()
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn to_cst(value: &Value) -> CstInputValue {
+    match value {
+        Value::Null => CstInputValue::Null,
+        Value::Bool(b) => CstInputValue::Bool(*b),
+        Value::Number(n) => CstInputValue::Number(n.to_string()),
+        Value::String(s) => CstInputValue::String(s.clone()),
+        Value::Array(items) => CstInputValue::Array(items.iter().map(to_cst).collect()),
+        Value::Object(map) => {
+            CstInputValue::Object(map.iter().map(|(k, v)| (k.clone(), to_cst(v))).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CONFIG: &str = r#"{
+  // My OpenCode setup
+  "$schema": "https://opencode.ai/config.json",
+  "theme":   "tokyonight", /* spacing kept */
+  "provider": {
+    "openai": {
+      "options": { "apiKey": "sk-mine" }, // not ours
+    },
+    "dymium": {
+      "name": "Dymium",
+      "options": {
+        "baseURL": "http://old/v1",
+        "apiKey": "old-token", // rotated by the app
+      },
+    },
+  },
+}
+"#;
+
+    fn parse(text: &str) -> Value {
+        jsonc_parser::parse_to_serde_value(text, &Default::default())
+            .unwrap()
+            .unwrap()
+    }
+
+    const API_KEY: &[&str] = &["provider", "dymium", "options", "apiKey"];
+    const BASE_URL: &[&str] = &["provider", "dymium", "options", "baseURL"];
+
+    #[test]
+    fn changed_value_is_replaced_in_place() {
+        let mut updated = parse(CONFIG);
+        updated["provider"]["dymium"]["options"]["apiKey"] = json!("new-token");
+        let patched = patch(CONFIG, &updated, &[API_KEY, BASE_URL]).unwrap();
+        // Every other byte, comments and trailing commas included, is kept
+        assert_eq!(patched, CONFIG.replace("\"old-token\"", "\"new-token\""));
+    }
+
+    #[test]
+    fn unchanged_values_leave_the_text_alone() {
+        let patched = patch(CONFIG, &parse(CONFIG), &[API_KEY, BASE_URL]).unwrap();
+        assert_eq!(patched, CONFIG);
+    }
+
+    #[test]
+    fn new_nested_provider_is_appended() {
+        let mut updated = parse(CONFIG);
+        updated["provider"]["dymium-app"] = json!({ "name": "App", "options": { "apiKey": "t" } });
+        let patched = patch(CONFIG, &updated, &[&["provider", "dymium-app"]]).unwrap();
+        let (before, after) = CONFIG.split_at(CONFIG.rfind("  },\n}").unwrap());
+        assert!(patched.starts_with(before));
+        assert!(patched.ends_with(after));
+        assert_eq!(parse(&patched), updated);
+    }
+
+    #[test]
+    fn missing_value_is_removed() {
+        let mut updated = parse(CONFIG);
+        updated["provider"]["dymium"]["options"]
+            .as_object_mut()
+            .unwrap()
+            .remove("apiKey");
+        let patched = patch(CONFIG, &updated, &[API_KEY]).unwrap();
+        assert_eq!(
+            patched,
+            CONFIG.replace(
+                "        \"apiKey\": \"old-token\", // rotated by the app\n",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_text_is_not_patched() {
+        assert_eq!(patch("{ \"provider\": ", &json!({}), &[API_KEY]), None);
+    }
+
+    #[test]
+    fn update_file_creates_only_when_setting() {
+        let dir = std::env::temp_dir().join(format!("dymium-jsonc-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("opencode.json");
+
+        assert!(!update_file(&path, &[(API_KEY, None)]).unwrap());
+        assert!(!path.exists());
+
+        assert!(update_file(&path, &[(API_KEY, Some(json!("t")))]).unwrap());
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            parse(&written),
+            json!({ "provider": { "dymium": { "options": { "apiKey": "t" } } } })
+        );
+        // Nothing to change, nothing written
+        assert!(!update_file(&path, &[(API_KEY, Some(json!("t")))]).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,55 @@
+//! Keycloak failover
+//!
+//! With `keycloakFallbackUrls` set (e.g. a DR replica of the realm), token
+//! requests go to `keycloakUrl` first and then to each fallback in order
+//! whenever a Keycloak can't be reached, times out or answers 5xx. A
+//! Keycloak that failed is remembered as down for `RETRY_AFTER` and tried
+//! after the others until then, so refreshes during a regional outage don't
+//! wait for the dead primary each time. Answers such as a rejected password
+//! or refresh token are final and never fail over.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a failed Keycloak goes to the back of the queue
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Shared memory of which token endpoints recently failed
+#[derive(Clone, Default)]
+pub struct KeycloakFailover {
+    down_since: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl KeycloakFailover {
+    /// `urls` (primary first) in the order to try them: those not known to
+    /// be down keep their configured order, recently failed ones go last
+    pub fn order(&self, urls: Vec<String>) -> Vec<String> {
+        let Ok(down_since) = self.down_since.lock() else {
+            return urls;
+        };
+        let is_down = |url: &String| {
+            down_since
+                .get(url)
+                .is_some_and(|since| since.elapsed() < RETRY_AFTER)
+        };
+        let (down, up): (Vec<String>, Vec<String>) = urls.into_iter().partition(is_down);
+        up.into_iter().chain(down).collect()
+    }
+
+    /// `url` failed; try it last for a while
+    pub fn report_down(&self, url: &str) {
+        if let Ok(mut down_since) = self.down_since.lock() {
+            down_since.insert(url.to_string(), Instant::now());
+        }
+    }
+
+    /// `url` answered
+    pub fn report_up(&self, url: &str) {
+        if let Ok(mut down_since) = self.down_since.lock() {
+            if down_since.remove(url).is_some() {
+                log::info!("Keycloak {} is reachable again", url);
+            }
+        }
+    }
+}
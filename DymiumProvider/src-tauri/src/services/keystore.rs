@@ -4,8 +4,16 @@
 //! - macOS: Keychain
 //! - Linux: Secret Service (GNOME Keyring, KWallet)
 //! - Windows: Credential Manager
+//! - iOS and Android: stores registered by `mobile`
+//!
+//! Credential Manager holds at most 2560 bytes per secret, stored as UTF-16,
+//! which a long Keycloak refresh token can exceed. Larger secrets go to
+//! ~/.dymium/keystore/<name>.dpapi instead, encrypted with DPAPI for the
+//! current user, and are looked for there first.
 
+use crate::services::config::{ConfigError, Secrets};
 use keyring::Entry;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 const SERVICE_NAME: &str = "io.dymium.provider";
@@ -14,22 +22,60 @@ const SERVICE_NAME: &str = "io.dymium.provider";
 pub enum KeystoreError {
     #[error("Keyring error: {0}")]
     KeyringError(#[from] keyring::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[cfg(windows)]
+    #[error("DPAPI error: {0}")]
+    Dpapi(#[from] windows::core::Error),
 }
 
 /// Keys for storing secrets
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum CredentialKey {
     ClientSecret,
     Password,
     RefreshToken,
+    StaticApiKey,
 }
 
 impl CredentialKey {
-    fn as_str(&self) -> &'static str {
+    /// Every key the app stores
+    pub const ALL: [CredentialKey; 4] = [
+        Self::ClientSecret,
+        Self::Password,
+        Self::RefreshToken,
+        Self::StaticApiKey,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
         match self {
             Self::ClientSecret => "client_secret",
             Self::Password => "password",
             Self::RefreshToken => "refresh_token",
+            Self::StaticApiKey => "static_api_key",
+        }
+    }
+
+    /// The matching field of a [`Secrets`] set
+    pub fn field<'a>(&self, secrets: &'a Secrets) -> &'a Option<String> {
+        match self {
+            Self::ClientSecret => &secrets.client_secret,
+            Self::Password => &secrets.password,
+            Self::RefreshToken => &secrets.refresh_token,
+            Self::StaticApiKey => &secrets.static_api_key,
+        }
+    }
+
+    /// The matching field of a [`Secrets`] set, for writing
+    pub fn field_mut<'a>(&self, secrets: &'a mut Secrets) -> &'a mut Option<String> {
+        match self {
+            Self::ClientSecret => &mut secrets.client_secret,
+            Self::Password => &mut secrets.password,
+            Self::RefreshToken => &mut secrets.refresh_token,
+            Self::StaticApiKey => &mut secrets.static_api_key,
         }
     }
 }
@@ -40,15 +86,45 @@ pub struct KeystoreService;
 impl KeystoreService {
     /// Save a secret to the system keystore
     pub fn save(key: CredentialKey, value: &str) -> Result<(), KeystoreError> {
-        let entry = Entry::new(SERVICE_NAME, key.as_str())?;
-        entry.set_password(value)?;
-        log::debug!("Saved {} to keystore", key.as_str());
-        Ok(())
+        Self::save_entry(key.as_str(), value)
     }
 
     /// Load a secret from the system keystore
     pub fn load(key: CredentialKey) -> Result<Option<String>, KeystoreError> {
-        let entry = Entry::new(SERVICE_NAME, key.as_str())?;
+        Self::load_entry(key.as_str())
+    }
+
+    /// Delete a secret from the system keystore
+    pub fn delete(key: CredentialKey) -> Result<(), KeystoreError> {
+        Self::delete_entry(key.as_str())
+    }
+
+    /// Save a secret that isn't part of the login credentials, such as the
+    /// egress proxy password
+    pub fn save_entry(name: &str, value: &str) -> Result<(), KeystoreError> {
+        #[cfg(windows)]
+        if dpapi::too_large(value) {
+            dpapi::save(name, value)?;
+            // Don't leave an older copy that loading could fall back to
+            Self::delete_keyring_entry(name)?;
+            log::debug!("Saved {} to a DPAPI file", name);
+            return Ok(());
+        }
+        let entry = Entry::new(SERVICE_NAME, name)?;
+        entry.set_password(value)?;
+        #[cfg(windows)]
+        dpapi::delete(name)?;
+        log::debug!("Saved {} to keystore", name);
+        Ok(())
+    }
+
+    /// Load a secret saved with [`Self::save_entry`]
+    pub fn load_entry(name: &str) -> Result<Option<String>, KeystoreError> {
+        #[cfg(windows)]
+        if let Some(value) = dpapi::load(name)? {
+            return Ok(Some(value));
+        }
+        let entry = Entry::new(SERVICE_NAME, name)?;
         match entry.get_password() {
             Ok(password) => Ok(Some(password)),
             Err(keyring::Error::NoEntry) => Ok(None),
@@ -56,12 +132,18 @@ impl KeystoreService {
         }
     }
 
-    /// Delete a secret from the system keystore
-    pub fn delete(key: CredentialKey) -> Result<(), KeystoreError> {
-        let entry = Entry::new(SERVICE_NAME, key.as_str())?;
+    /// Delete a secret saved with [`Self::save_entry`]
+    pub fn delete_entry(name: &str) -> Result<(), KeystoreError> {
+        #[cfg(windows)]
+        dpapi::delete(name)?;
+        Self::delete_keyring_entry(name)
+    }
+
+    fn delete_keyring_entry(name: &str) -> Result<(), KeystoreError> {
+        let entry = Entry::new(SERVICE_NAME, name)?;
         match entry.delete_credential() {
             Ok(_) => {
-                log::debug!("Deleted {} from keystore", key.as_str());
+                log::debug!("Deleted {} from keystore", name);
                 Ok(())
             }
             Err(keyring::Error::NoEntry) => Ok(()), // Already deleted
@@ -73,4 +155,133 @@ impl KeystoreService {
     pub fn exists(key: CredentialKey) -> bool {
         Self::load(key).map(|v| v.is_some()).unwrap_or(false)
     }
+
+    /// Store every secret that is set. Entries for unset ones are deleted if
+    /// `delete_unset`; pass false when they may just have failed to load.
+    pub fn save_secrets(secrets: &Secrets, delete_unset: bool) -> Result<(), KeystoreError> {
+        for key in CredentialKey::ALL {
+            match key.field(secrets).as_deref().filter(|s| !s.is_empty()) {
+                Some(value) => Self::save(key, value)?,
+                None if delete_unset => Self::delete(key)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Load every secret from the keystore
+    pub fn load_secrets() -> Result<Secrets, KeystoreError> {
+        let mut secrets = Secrets::default();
+        for key in CredentialKey::ALL {
+            *key.field_mut(&mut secrets) = Self::load(key)?;
+        }
+        Ok(secrets)
+    }
+}
+
+#[cfg(windows)]
+mod dpapi {
+    use super::KeystoreError;
+    use crate::services::config::AppConfig;
+    use crate::services::files::restrict_to_owner;
+    use std::fs;
+    use std::path::PathBuf;
+    use windows::core::w;
+    use windows::Win32::Foundation::{LocalFree, HLOCAL};
+    use windows::Win32::Security::Cryptography::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    /// CRED_MAX_CREDENTIAL_BLOB_SIZE
+    const MAX_BLOB_SIZE: usize = 5 * 512;
+
+    /// Whether `value` is too large for Credential Manager
+    pub fn too_large(value: &str) -> bool {
+        value.encode_utf16().count() * 2 > MAX_BLOB_SIZE
+    }
+
+    fn path(name: &str) -> Result<PathBuf, KeystoreError> {
+        Ok(AppConfig::config_dir()?
+            .join("keystore")
+            .join(format!("{}.dpapi", name)))
+    }
+
+    pub fn save(name: &str, value: &str) -> Result<(), KeystoreError> {
+        let path = path(name)?;
+        let data = protect(value.as_bytes())?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, data)?;
+        restrict_to_owner(&path)?;
+        Ok(())
+    }
+
+    pub fn load(name: &str) -> Result<Option<String>, KeystoreError> {
+        let data = match fs::read(path(name)?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let value = String::from_utf8(unprotect(&data)?)
+            .map_err(|e| keyring::Error::BadEncoding(e.into_bytes()))?;
+        Ok(Some(value))
+    }
+
+    pub fn delete(name: &str) -> Result<(), KeystoreError> {
+        match fs::remove_file(path(name)?) {
+            Ok(()) => {
+                log::debug!("Deleted DPAPI file of {}", name);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+        CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        }
+    }
+
+    /// Copy out a blob DPAPI allocated, and free it
+    unsafe fn take(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+        let data = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+        LocalFree(Some(HLOCAL(blob.pbData.cast())));
+        data
+    }
+
+    fn protect(data: &[u8]) -> windows::core::Result<Vec<u8>> {
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        unsafe {
+            CryptProtectData(
+                &blob(data),
+                w!("Dymium Provider"),
+                None,
+                None,
+                None,
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )?;
+            Ok(take(output))
+        }
+    }
+
+    fn unprotect(data: &[u8]) -> windows::core::Result<Vec<u8>> {
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        unsafe {
+            CryptUnprotectData(
+                &blob(data),
+                None,
+                None,
+                None,
+                None,
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )?;
+            Ok(take(output))
+        }
+    }
 }
@@ -0,0 +1,103 @@
+//! Last known token state
+//!
+//! The last settled `TokenState`, without the token, is kept in
+//! ~/.dymium/last-state.json so that right after a restart the tray can show
+//! "Connected until 14:32 (refreshing...)" while the first authentication
+//! runs. Transient states (authenticating, verifying) are never written.
+
+use crate::services::config::{AppConfig, TokenState};
+use crate::services::error_code::FailureKind;
+use crate::services::files;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Settled status, as recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LastStatus {
+    Idle,
+    Authenticated,
+    Failed,
+    Locked,
+}
+
+/// A settled token state without the token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastState {
+    pub status: LastStatus,
+    /// When the token expires, if authenticated
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Why it failed, if it did
+    #[serde(default)]
+    pub error: Option<String>,
+    /// What kind of failure that was
+    #[serde(default)]
+    pub kind: Option<FailureKind>,
+    /// When the token was last obtained
+    #[serde(default)]
+    pub last_refresh: Option<DateTime<Utc>>,
+}
+
+impl LastState {
+    /// `state` if it is settled. Idle with credentials only means the first
+    /// authentication hasn't started yet.
+    pub fn settled(
+        state: &TokenState,
+        has_credentials: bool,
+        last_refresh: Option<DateTime<Utc>>,
+    ) -> Option<Self> {
+        let (status, expires_at, error, kind) = match state {
+            TokenState::Idle if has_credentials => return None,
+            TokenState::Idle => (LastStatus::Idle, None, None, None),
+            TokenState::Authenticating | TokenState::Verifying => return None,
+            TokenState::Authenticated { expires_at, .. } => {
+                (LastStatus::Authenticated, Some(*expires_at), None, None)
+            }
+            TokenState::Failed { error, kind, .. } => {
+                (LastStatus::Failed, None, Some(error.clone()), Some(*kind))
+            }
+            TokenState::Locked => (LastStatus::Locked, None, None, None),
+        };
+        Some(Self {
+            status,
+            expires_at,
+            error,
+            kind,
+            last_refresh,
+        })
+    }
+
+    fn path() -> Option<PathBuf> {
+        AppConfig::config_dir()
+            .ok()
+            .map(|dir| dir.join("last-state.json"))
+    }
+
+    /// The state recorded by the previous run, if any
+    pub fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path()?).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                log::warn!("Ignoring unreadable last-state.json: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Record this state. Failures are logged only.
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(self)
+            .map_err(std::io::Error::other)
+            .and_then(|content| files::write_atomic(&path, &content));
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
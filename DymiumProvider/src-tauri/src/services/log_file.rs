@@ -0,0 +1,144 @@
+//! Rotating log files
+//!
+//! The lines kept in the in-memory log (see `logs`) are also appended to
+//! ~/.dymium/logs/dymium.log, one JSON object or key=value line each, so the
+//! history survives a crash or a closed terminal. The file is rotated to
+//! `dymium-<timestamp>.log` when it reaches `maxSizeMb` or the UTC day
+//! changes; old files beyond `maxFiles` or `maxAgeDays` are deleted.
+
+use crate::services::config::{AppConfig, ConfigError, LogFileConfig, LogFormat};
+use crate::services::logs::LogEntry;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the file being written
+const CURRENT: &str = "dymium.log";
+
+#[derive(Error, Debug)]
+pub enum LogFileError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+}
+
+/// The open log file
+pub struct LogFile {
+    dir: PathBuf,
+    config: LogFileConfig,
+    file: File,
+    /// Bytes in the current file
+    size: u64,
+    /// UTC day the current file was started
+    day: NaiveDate,
+}
+
+impl LogFile {
+    /// Open ~/.dymium/logs/dymium.log for appending, rotating it first if
+    /// it is already due
+    pub fn open(config: LogFileConfig) -> Result<Self, LogFileError> {
+        let dir = AppConfig::logs_dir()?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(CURRENT);
+        let (size, day) = match fs::metadata(&path) {
+            Ok(meta) => {
+                let modified: DateTime<Utc> = meta.modified()?.into();
+                (meta.len(), modified.date_naive())
+            }
+            Err(_) => (0, Utc::now().date_naive()),
+        };
+        let mut log_file = Self {
+            file: open_append(&path)?,
+            dir,
+            config,
+            size,
+            day,
+        };
+        if log_file.due(Utc::now(), 0) {
+            log_file.rotate()?;
+        }
+        Ok(log_file)
+    }
+
+    /// Append one line, rotating first if it wouldn't fit
+    pub fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        let mut line = match self.config.format {
+            LogFormat::Json => serde_json::to_string(entry)?,
+            LogFormat::KeyValue => format!(
+                "ts={} level={} target={} msg={:?}",
+                entry.timestamp.to_rfc3339(),
+                entry.level,
+                entry.target,
+                entry.message
+            ),
+        };
+        line.push('\n');
+        if self.due(entry.timestamp, line.len() as u64) {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Whether a line of `len` bytes written at `now` belongs in a new file
+    fn due(&self, now: DateTime<Utc>, len: u64) -> bool {
+        let max_size = self.config.max_size_mb * 1024 * 1024;
+        let too_big = max_size > 0 && self.size > 0 && self.size + len > max_size;
+        let new_day = self.config.rotate_daily && self.size > 0 && now.date_naive() != self.day;
+        too_big || new_day
+    }
+
+    /// Move the current file aside, start a new one and apply retention
+    fn rotate(&mut self) -> io::Result<()> {
+        let now = Utc::now();
+        let current = self.dir.join(CURRENT);
+        let rotated = self
+            .dir
+            .join(format!("dymium-{}.log", now.format("%Y%m%d-%H%M%S%.3f")));
+        fs::rename(&current, rotated)?;
+        self.file = open_append(&current)?;
+        self.size = 0;
+        self.day = now.date_naive();
+        self.prune(now)
+    }
+
+    /// Delete rotated files beyond `max_files` or older than `max_age_days`
+    fn prune(&self, now: DateTime<Utc>) -> io::Result<()> {
+        let mut rotated: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| is_rotated(path))
+            .collect();
+        // Timestamped names sort oldest first
+        rotated.sort();
+        rotated.reverse();
+        for (index, path) in rotated.iter().enumerate() {
+            let expired = self.config.max_age_days > 0
+                && fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .map(|modified| {
+                        let modified: DateTime<Utc> = modified.into();
+                        (now - modified).num_days() >= self.config.max_age_days as i64
+                    })
+                    .unwrap_or(false);
+            if index >= self.config.max_files || expired {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `dymium-<timestamp>.log`
+fn is_rotated(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("dymium-") && name.ends_with(".log"))
+}
@@ -0,0 +1,194 @@
+//! In-memory application log
+//!
+//! Wraps env_logger: lines still go to stderr as filtered by RUST_LOG, and
+//! the app's own lines at the current level (info unless `logLevel` or
+//! `set_log_level` say otherwise) are also kept in a ring buffer the settings
+//! window pages through with `get_logs`, so users don't need a terminal to
+//! see them. The same lines go to the rotating files in ~/.dymium/logs
+//! (see `log_file`) unless `logFile.enabled` is off. Every line, including
+//! library output, has tokens, keys and passwords masked first (see
+//! `redact`).
+
+use crate::services::config::LogFileConfig;
+use crate::services::log_file::LogFile;
+use crate::services::redact::redact_secrets;
+use chrono::{DateTime, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Lines kept in memory
+const CAPACITY: usize = 2000;
+/// Level used unless the user picks another
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// One log line
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    /// "error", "warn", "info", "debug" or "trace"
+    pub level: String,
+    /// Module that logged the line
+    pub target: String,
+    pub message: String,
+}
+
+/// A page of log lines, newest first
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// Matching lines in the buffer, across all pages
+    pub total: usize,
+}
+
+/// Shared ring buffer of recent log lines
+#[derive(Clone)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<(Level, LogEntry)>>>,
+    /// Current level, as a `LevelFilter` discriminant
+    level: Arc<AtomicUsize>,
+    /// RUST_LOG's level for stderr
+    stderr_level: LevelFilter,
+}
+
+impl LogBuffer {
+    /// Install the logger, writing to the log files as configured in `file`;
+    /// call once at startup
+    pub fn init(file: &LogFileConfig) -> Self {
+        let inner = env_logger::Builder::from_default_env().build();
+        let buffer = Self {
+            entries: Arc::default(),
+            level: Arc::new(AtomicUsize::new(DEFAULT_LEVEL as usize)),
+            stderr_level: inner.filter(),
+        };
+        let (file, file_error) = match file.enabled.then(|| LogFile::open(file.clone())) {
+            Some(Ok(file)) => (Some(Mutex::new(file)), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+        let logger = BufferedLogger {
+            inner,
+            buffer: buffer.clone(),
+            file,
+        };
+        if log::set_boxed_logger(Box::new(logger)).is_ok() {
+            buffer.set_level(DEFAULT_LEVEL);
+        }
+        if let Some(e) = file_error {
+            log::warn!("Not writing log files: {}", e);
+        }
+        buffer
+    }
+
+    /// Level of the app's lines kept in the buffer
+    pub fn level(&self) -> LevelFilter {
+        LevelFilter::iter()
+            .nth(self.level.load(Ordering::Relaxed))
+            .unwrap_or(DEFAULT_LEVEL)
+    }
+
+    /// Change the level without a restart
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.store(level as usize, Ordering::Relaxed);
+        log::set_max_level(self.stderr_level.max(level));
+    }
+
+    /// Lines at `level` or more severe (all if unset), newest first
+    pub fn page(
+        &self,
+        level: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<LogPage, String> {
+        let min = match level {
+            Some(level) => {
+                Level::from_str(level).map_err(|_| format!("Unknown log level {}", level))?
+            }
+            None => Level::Trace,
+        };
+        let entries = self.entries.lock().map_err(|e| e.to_string())?;
+        let matching = entries.iter().rev().filter(|(l, _)| *l <= min);
+        let total = matching.clone().count();
+        let entries = matching
+            .skip(offset)
+            .take(limit)
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        Ok(LogPage { entries, total })
+    }
+
+    fn push(&self, level: Level, entry: LogEntry) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back((level, entry));
+        }
+    }
+}
+
+/// env_logger plus the in-memory buffer and the log file
+struct BufferedLogger {
+    inner: env_logger::Logger,
+    buffer: LogBuffer,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl BufferedLogger {
+    fn buffers(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.buffer.level() && !is_dependency(metadata.target())
+    }
+}
+
+impl Log for BufferedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || self.buffers(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let to_stderr = self.inner.matches(record);
+        let to_buffer = self.buffers(record.metadata());
+        if !to_stderr && !to_buffer {
+            return;
+        }
+        let message = redact_secrets(&record.args().to_string());
+        if to_stderr {
+            self.inner.log(
+                &Record::builder()
+                    .args(format_args!("{}", message))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            );
+        }
+        if to_buffer {
+            let entry = LogEntry {
+                timestamp: Utc::now(),
+                level: record.level().as_str().to_lowercase(),
+                target: record.target().to_string(),
+                message,
+            };
+            if let Some(Ok(mut file)) = self.file.as_ref().map(|f| f.lock()) {
+                // Nowhere to report a failed write; the buffer still has it
+                let _ = file.write(&entry);
+            }
+            self.buffer.push(record.level(), entry);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Lines from libraries (hyper, rustls, tao, ...) are left to RUST_LOG
+fn is_dependency(target: &str) -> bool {
+    !target.starts_with("dymium_provider")
+}
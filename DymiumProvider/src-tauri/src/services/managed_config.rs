@@ -0,0 +1,210 @@
+//! Signed managed config
+//!
+//! With `managedConfig.url` and `managedConfig.publicKey` set, the app fetches
+//! an org config (same format as the org file, `"locked"` included) from the
+//! URL every `managedConfig.intervalMinutes`, with its minisign signature
+//! from the same URL plus ".minisig". Only a config signed with the key is
+//! kept, in ~/.dymium/managed-config.json; a compromised file server can't
+//! repoint anyone's traffic without the signing key. The cached copy is
+//! verified again on every load and layered over the org file.
+//!
+//! A fetched config signed earlier than the cached one, going by the
+//! `timestamp:` minisign writes into the trusted comment, is refused, so an
+//! old signed config can't be replayed to roll settings back.
+//!
+//! The key is taken from the org file if it sets one, so users can't swap
+//! it, and from config.json otherwise.
+
+use crate::services::config::{AppConfig, ConfigError, ManagedConfig};
+use crate::services::files;
+use crate::services::minisign::{self, MinisignError, PublicKey};
+use crate::services::org_config::OrgConfig;
+use crate::services::tls_trust::{HttpClients, PlainHttpRefused};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+const PUBLIC_KEY: &str = "/managedConfig/publicKey";
+
+#[derive(Error, Debug)]
+pub enum ManagedConfigError {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("{0}")]
+    PlainHttp(#[from] PlainHttpRefused),
+    #[error("Signature check failed: {0}")]
+    Signature(#[from] MinisignError),
+    #[error("Managed config is not a JSON object")]
+    NotAnObject,
+    #[error("managedConfig.publicKey is not set")]
+    NoPublicKey,
+    #[error("Managed config was signed before the cached one ({0} < {1})")]
+    Outdated(u64, u64),
+}
+
+/// Service for the signed config fetched from `managedConfig.url`
+pub struct ManagedConfigService;
+
+impl ManagedConfigService {
+    fn cache_path() -> Result<PathBuf, ManagedConfigError> {
+        Ok(AppConfig::config_dir()?.join("managed-config.json"))
+    }
+
+    fn signature_path() -> Result<PathBuf, ManagedConfigError> {
+        Ok(AppConfig::config_dir()?.join("managed-config.json.minisig"))
+    }
+
+    /// The public key to verify with: the org file's, else config.json's
+    fn public_key(org: &Map<String, Value>) -> Option<String> {
+        let from_org = Value::Object(org.clone())
+            .pointer(PUBLIC_KEY)
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        from_org.or_else(|| {
+            let content = fs::read_to_string(AppConfig::config_path().ok()?).ok()?;
+            serde_json::from_str::<Value>(&content)
+                .ok()?
+                .pointer(PUBLIC_KEY)?
+                .as_str()
+                .map(str::to_string)
+        })
+    }
+
+    /// The cached config, if there is one and it verifies. `org` is the org
+    /// file's settings, which may hold the key.
+    pub fn cached(org: &Map<String, Value>) -> Option<Map<String, Value>> {
+        let data = fs::read(Self::cache_path().ok()?).ok()?;
+        let result = Self::public_key(org)
+            .ok_or(ManagedConfigError::NoPublicKey)
+            .and_then(|key| {
+                let signature = fs::read_to_string(Self::signature_path()?)?;
+                Self::verify(&key, &data, &signature).map(|(settings, _)| settings)
+            });
+        match result {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                log::error!("Ignoring cached managed config: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Check `data` against `signature` and parse it. Also returns when it
+    /// was signed, if the trusted comment says.
+    fn verify(
+        public_key: &str,
+        data: &[u8],
+        signature: &str,
+    ) -> Result<(Map<String, Value>, Option<u64>), ManagedConfigError> {
+        let trusted_comment = PublicKey::parse(public_key)?.verify(data, signature)?;
+        match serde_json::from_slice(data) {
+            Ok(Value::Object(settings)) => Ok((settings, minisign::signed_at(&trusted_comment))),
+            _ => Err(ManagedConfigError::NotAnObject),
+        }
+    }
+
+    /// When the cached config was signed, if it verifies and says
+    fn cached_signed_at(public_key: &str) -> Option<u64> {
+        let data = fs::read(Self::cache_path().ok()?).ok()?;
+        let signature = fs::read_to_string(Self::signature_path().ok()?).ok()?;
+        Self::verify(public_key, &data, &signature).ok()?.1
+    }
+
+    /// Refuse a config signed before the cached one. One without a
+    /// timestamp counts as older than any cached one that has one.
+    fn ensure_not_older(
+        signed: Option<u64>,
+        cached: Option<u64>,
+    ) -> Result<(), ManagedConfigError> {
+        match cached {
+            Some(cached) if signed.unwrap_or(0) < cached => {
+                Err(ManagedConfigError::Outdated(signed.unwrap_or(0), cached))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Download and verify the config and cache it. Returns whether the
+    /// cache changed; the caller then reloads the config. With no URL set,
+    /// the cache is removed.
+    pub async fn fetch(
+        clients: &HttpClients,
+        settings: &ManagedConfig,
+    ) -> Result<bool, ManagedConfigError> {
+        let Some(url) = settings.url.as_deref().filter(|u| !u.trim().is_empty()) else {
+            return Self::clear();
+        };
+        let public_key = Self::public_key(OrgConfig::load_file().settings())
+            .ok_or(ManagedConfigError::NoPublicKey)?;
+        let signature_url = format!("{}.minisig", url);
+        let data = clients
+            .for_url(url)?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let signature = clients
+            .for_url(&signature_url)?
+            .get(&signature_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let (_, signed_at) = Self::verify(&public_key, &data, &signature)?;
+
+        let cache_path = Self::cache_path()?;
+        if fs::read(&cache_path).is_ok_and(|cached| cached == data.as_ref()) {
+            return Ok(false);
+        }
+        Self::ensure_not_older(signed_at, Self::cached_signed_at(&public_key))?;
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        files::write_atomic(&cache_path, &data)?;
+        files::write_atomic(&Self::signature_path()?, signature.as_bytes())?;
+        log::info!("Managed config from {} updated", url);
+        Ok(true)
+    }
+
+    /// Remove the cached config. Returns whether there was one.
+    fn clear() -> Result<bool, ManagedConfigError> {
+        let removed = match fs::remove_file(Self::cache_path()?) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        match fs::remove_file(Self::signature_path()?) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_configs_are_refused() {
+        let check = ManagedConfigService::ensure_not_older;
+        assert!(check(Some(1760000000), None).is_ok());
+        assert!(check(Some(1760000000), Some(1760000000)).is_ok());
+        assert!(check(Some(1760000001), Some(1760000000)).is_ok());
+        assert!(matches!(
+            check(Some(1750000000), Some(1760000000)),
+            Err(ManagedConfigError::Outdated(1750000000, 1760000000))
+        ));
+        assert!(check(None, Some(1760000000)).is_err());
+        assert!(check(None, None).is_ok());
+    }
+}
@@ -0,0 +1,111 @@
+//! One-time migration of plaintext secrets
+//!
+//! Older versions kept the password, client secret, refresh token and static
+//! API key directly in ~/.dymium/config.json. On startup, any found there are
+//! moved into the system keystore and scrubbed from the file.
+//!
+//! The order of operations guarantees the user is never left without
+//! credentials:
+//! 1. write every secret to the keystore and read it back
+//! 2. only then rewrite config.json without the secrets
+//!
+//! If step 1 fails, entries written so far are removed again and config.json is
+//! left untouched, so the next launch simply retries.
+
+use crate::services::audit::AuditLog;
+use crate::services::config::{AppConfig, Secrets};
+use crate::services::keystore::{CredentialKey, KeystoreService};
+
+/// Result of a migration attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationOutcome {
+    /// No plaintext secrets found (or the vault manages them)
+    NotNeeded,
+    /// Secrets moved into the keystore
+    Migrated(Vec<&'static str>),
+    /// Nothing was changed; secrets remain in config.json
+    Failed(String),
+}
+
+/// Service for moving plaintext secrets into the keystore
+pub struct MigrationService;
+
+impl MigrationService {
+    /// Move plaintext secrets from `config` into the keystore and persist a
+    /// scrubbed config. `config` keeps its secrets in memory either way.
+    pub fn migrate_plaintext_secrets(config: &mut AppConfig) -> MigrationOutcome {
+        if config.vault_enabled {
+            return MigrationOutcome::NotNeeded;
+        }
+
+        let secrets = Secrets::from_config(config);
+        if secrets.is_empty() {
+            return MigrationOutcome::NotNeeded;
+        }
+
+        let keys: Vec<CredentialKey> = CredentialKey::ALL
+            .into_iter()
+            .filter(|k| k.field(&secrets).as_deref().is_some_and(|s| !s.is_empty()))
+            .collect();
+        let names: Vec<&'static str> = keys.iter().map(|k| k.as_str()).collect();
+        log::info!(
+            "Migrating plaintext secrets from config.json to keystore: {}",
+            names.join(", ")
+        );
+
+        // Step 1: keystore writes, verified by reading back
+        let mut written = Vec::new();
+        for key in &keys {
+            let value = key.field(&secrets).as_deref().unwrap_or_default();
+            let result = KeystoreService::save(*key, value).and_then(|_| {
+                match KeystoreService::load(*key)? {
+                    Some(stored) if stored == value => Ok(()),
+                    _ => Err(keyring::Error::NoEntry.into()),
+                }
+            });
+
+            match result {
+                Ok(()) => written.push(*key),
+                Err(e) => {
+                    log::error!("Keystore migration failed at {}: {}", key.as_str(), e);
+                    for done in written {
+                        if let Err(e) = KeystoreService::delete(done) {
+                            log::warn!("Failed to roll back {}: {}", done.as_str(), e);
+                        }
+                    }
+                    let reason = format!("keystore write failed for {}: {}", key.as_str(), e);
+                    AuditLog::record("migrate_secrets", Some(config.auth_mode), "failed", &reason);
+                    return MigrationOutcome::Failed(reason);
+                }
+            }
+        }
+
+        // Step 2: scrub config.json
+        let mut scrubbed = config.clone();
+        Secrets::scrub(&mut scrubbed);
+        scrubbed.secrets_in_keystore = true;
+        if let Err(e) = scrubbed.save() {
+            // Secrets are now in both places; config.json still has them and
+            // the flag is unset, so the next launch retries the scrub.
+            log::error!("Failed to scrub secrets from config.json: {}", e);
+            let reason = format!("config.json rewrite failed: {}", e);
+            AuditLog::record(
+                "migrate_secrets",
+                Some(config.auth_mode),
+                "partial",
+                &reason,
+            );
+            return MigrationOutcome::Failed(reason);
+        }
+
+        config.secrets_in_keystore = true;
+        log::info!("Moved {} secret(s) into the keystore", names.len());
+        AuditLog::record(
+            "migrate_secrets",
+            Some(config.auth_mode),
+            "success",
+            &names.join(","),
+        );
+        MigrationOutcome::Migrated(names)
+    }
+}
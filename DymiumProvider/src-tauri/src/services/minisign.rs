@@ -0,0 +1,205 @@
+//! minisign signature verification
+//!
+//! Verifies detached signatures made with `minisign -S` (BLAKE2b-prehashed,
+//! the default) or `minisign -S -l` (legacy, over the whole file). Both the
+//! signature and its trusted comment must verify, and the key ID in the
+//! signature must match the public key's.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::{Blake2b512, Digest};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use thiserror::Error;
+
+const KEY_ID_LEN: usize = 8;
+const PUBLIC_KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+#[derive(Error, Debug)]
+pub enum MinisignError {
+    #[error("Malformed public key")]
+    MalformedKey,
+    #[error("Malformed signature")]
+    MalformedSignature,
+    #[error("Signed with another key (ID {0})")]
+    WrongKey(String),
+    #[error("Signature doesn't match the data")]
+    BadSignature,
+}
+
+/// A minisign public key
+pub struct PublicKey {
+    key_id: [u8; KEY_ID_LEN],
+    key: [u8; PUBLIC_KEY_LEN],
+}
+
+impl PublicKey {
+    /// Parse the base64 key, e.g. "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3",
+    /// or the contents of a minisign.pub file
+    pub fn parse(key: &str) -> Result<Self, MinisignError> {
+        let line = key
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+            .ok_or(MinisignError::MalformedKey)?;
+        let bytes = STANDARD
+            .decode(line)
+            .map_err(|_| MinisignError::MalformedKey)?;
+        if bytes.len() != 2 + KEY_ID_LEN + PUBLIC_KEY_LEN || &bytes[..2] != b"Ed" {
+            return Err(MinisignError::MalformedKey);
+        }
+        let mut key_id = [0; KEY_ID_LEN];
+        key_id.copy_from_slice(&bytes[2..2 + KEY_ID_LEN]);
+        let mut key = [0; PUBLIC_KEY_LEN];
+        key.copy_from_slice(&bytes[2 + KEY_ID_LEN..]);
+        Ok(Self { key_id, key })
+    }
+
+    /// Check `signature` (the contents of a .minisig file) over `data`.
+    /// Returns the trusted comment.
+    pub fn verify(&self, data: &[u8], signature: &str) -> Result<String, MinisignError> {
+        let mut lines = signature.lines().map(|l| l.trim_end_matches('\r'));
+        let _untrusted = lines.next();
+        let signature_line = lines.next().ok_or(MinisignError::MalformedSignature)?;
+        let trusted_comment = lines
+            .next()
+            .and_then(|l| l.strip_prefix(TRUSTED_COMMENT))
+            .ok_or(MinisignError::MalformedSignature)?;
+        let global_line = lines.next().ok_or(MinisignError::MalformedSignature)?;
+
+        let bytes = STANDARD
+            .decode(signature_line.trim())
+            .map_err(|_| MinisignError::MalformedSignature)?;
+        if bytes.len() != 2 + KEY_ID_LEN + SIGNATURE_LEN {
+            return Err(MinisignError::MalformedSignature);
+        }
+        let prehashed = match &bytes[..2] {
+            b"ED" => true,
+            b"Ed" => false,
+            _ => return Err(MinisignError::MalformedSignature),
+        };
+        let key_id = &bytes[2..2 + KEY_ID_LEN];
+        if key_id != self.key_id {
+            // minisign shows key IDs as little-endian hex
+            let id: String = key_id.iter().rev().map(|b| format!("{:02X}", b)).collect();
+            return Err(MinisignError::WrongKey(id));
+        }
+        let sig = &bytes[2 + KEY_ID_LEN..];
+
+        let key = UnparsedPublicKey::new(&ED25519, &self.key);
+        let verified = if prehashed {
+            key.verify(&Blake2b512::digest(data), sig)
+        } else {
+            key.verify(data, sig)
+        };
+        verified.map_err(|_| MinisignError::BadSignature)?;
+
+        let global = STANDARD
+            .decode(global_line.trim())
+            .map_err(|_| MinisignError::MalformedSignature)?;
+        let mut signed_comment = sig.to_vec();
+        signed_comment.extend_from_slice(trusted_comment.as_bytes());
+        key.verify(&signed_comment, &global)
+            .map_err(|_| MinisignError::BadSignature)?;
+        Ok(trusted_comment.to_string())
+    }
+}
+
+/// The `timestamp:` field minisign puts in the default trusted comment
+/// ("timestamp:<unix time>\tfile:<name>[\thashed]")
+pub fn signed_at(trusted_comment: &str) -> Option<u64> {
+    trusted_comment
+        .split('\t')
+        .find_map(|field| field.strip_prefix("timestamp:"))
+        .and_then(|time| time.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Generated in minisign's formats (`minisign -S`, and `-S -l` for the
+    // legacy signature) with a test key from the Ed25519 seed 00 01 .. 1f
+    // and key ID 8F2E6C0B7A1D4953
+    const PUBLIC_KEY: &str = "untrusted comment: minisign public key 53491D7A0B6C2E8F
+RWSPLmwLeh1JUwOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4
+";
+    const OTHER_KEY: &str = "RWQRIjNEqrvM3Xm1Vi6P5lT5QHixEuipi6eQH4U65pW+1+DjkQutBJZk";
+    const DATA: &[u8] = b"{\"llmEndpoint\": \"https://ghostllm.example.com/v1\"}\n";
+    const PREHASHED: &str = "untrusted comment: signature from minisign secret key
+RUSPLmwLeh1JU1wVWzYIA0+Wj8sysF/iDNspmezYLqPGcQ7aCIcYeY7qULZ3rBFlcFM2lgarFHTNBEcmorXTvcuOG92jnTzzFAo=
+trusted comment: timestamp:1760000000\tfile:managed-config.json\thashed
+2xmfrRUOzpsSonNZkvuVGS9AFn/ZmwdekTcRRLzIhwtl+hlwBeWvuiPr/qifwanh6nLy1M3VjQ0/ItrfYdUCAw==
+";
+    const LEGACY: &str = "untrusted comment: signature from minisign secret key
+RWSPLmwLeh1JUxCglVkgE0S0S8qSs7PjYyXwhHBYIlIMN4oR4tuG9ex1dYV2tXPkt5IvNvyNC4XLWPQxpxZxnuS5YaSBh58U9QQ=
+trusted comment: timestamp:1760000000\tfile:managed-config.json
+Di77/vo+262jWMN3GWn4JVKKoyheHe+t31C5OTswWJ2IVt0CPNEyz78h7odu2W+7g1FdzJ3Xg6ST3194Z9PUAg==
+";
+
+    fn key() -> PublicKey {
+        PublicKey::parse(PUBLIC_KEY).unwrap()
+    }
+
+    #[test]
+    fn prehashed_and_legacy_signatures_verify() {
+        let comment = key().verify(DATA, PREHASHED).unwrap();
+        assert_eq!(
+            comment,
+            "timestamp:1760000000\tfile:managed-config.json\thashed"
+        );
+        assert_eq!(signed_at(&comment), Some(1760000000));
+
+        let comment = key().verify(DATA, LEGACY).unwrap();
+        assert_eq!(signed_at(&comment), Some(1760000000));
+        // CRLF line endings, as a file edited on Windows may have
+        assert!(key().verify(DATA, &PREHASHED.replace('\n', "\r\n")).is_ok());
+    }
+
+    #[test]
+    fn another_key_is_refused() {
+        let other = PublicKey::parse(OTHER_KEY).unwrap();
+        for signature in [PREHASHED, LEGACY] {
+            match other.verify(DATA, signature) {
+                Err(MinisignError::WrongKey(id)) => assert_eq!(id, "53491D7A0B6C2E8F"),
+                result => panic!("expected WrongKey, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_trusted_comment_is_refused() {
+        for signature in [PREHASHED, LEGACY] {
+            let tampered = signature.replace("timestamp:1760000000", "timestamp:1860000000");
+            assert!(matches!(
+                key().verify(DATA, &tampered),
+                Err(MinisignError::BadSignature)
+            ));
+        }
+    }
+
+    #[test]
+    fn tampered_body_is_refused() {
+        let tampered = b"{\"llmEndpoint\": \"https://attacker.example.com/v1\"}\n";
+        for signature in [PREHASHED, LEGACY] {
+            assert!(matches!(
+                key().verify(tampered, signature),
+                Err(MinisignError::BadSignature)
+            ));
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_refused() {
+        assert!(matches!(
+            PublicKey::parse("not a key"),
+            Err(MinisignError::MalformedKey)
+        ));
+        let truncated: String = PREHASHED.lines().take(3).collect::<Vec<_>>().join("\n");
+        assert!(matches!(
+            key().verify(DATA, &truncated),
+            Err(MinisignError::MalformedSignature)
+        ));
+    }
+}
@@ -0,0 +1,549 @@
+//! iOS and Android support
+//!
+//! On a phone the setup window is the whole app (there is no tray), and:
+//! - `~` is moved into the app's private storage, so ~/.dymium works as on
+//!   desktop: `Library` in the iOS container, `Context.getFilesDir()` on
+//!   Android, where `$HOME` isn't set at all.
+//! - Secrets go through `keyring` as everywhere else, but to a store
+//!   registered here: the iOS Keychain (readable after the first unlock,
+//!   never synced off the device), or on Android files under
+//!   ~/.dymium/keystore encrypted with an AES key that never leaves the
+//!   Android Keystore.
+//! - On Android the refresh loop keeps running in the background under a
+//!   foreground service (`io.dymium.provider.RefreshService`, see the
+//!   README), started while there's a token to refresh. iOS suspends the
+//!   app in the background; the loop catches up when it's reopened.
+
+use crate::services::config::ConfigError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MobileError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Malformed keystore entry")]
+    Malformed,
+    #[cfg(target_os = "android")]
+    #[error("JNI error: {0}")]
+    Jni(#[from] jni::errors::Error),
+    #[cfg(target_os = "ios")]
+    #[error("Keychain error {0}")]
+    Keychain(i32),
+}
+
+/// Whether the foreground service was last asked to run
+static KEEPING_ALIVE: AtomicBool = AtomicBool::new(false);
+
+/// Service for the platform parts of the mobile app
+pub struct MobileService;
+
+impl MobileService {
+    /// Point `~` at app storage and register the keystore. Must run before
+    /// anything reads the config.
+    pub fn init() {
+        match platform::home() {
+            Ok(home) => std::env::set_var("HOME", home),
+            Err(e) => log::error!("App storage not found: {}", e),
+        }
+        keyring::set_default_credential_builder(Box::new(platform::KeystoreBuilder));
+    }
+
+    /// Run (`true`) or stop the foreground service that keeps the refresh
+    /// loop alive. Only acts when the answer changes.
+    pub fn keep_alive(enabled: bool) {
+        if KEEPING_ALIVE.swap(enabled, Ordering::SeqCst) == enabled {
+            return;
+        }
+        if let Err(e) = platform::keep_alive(enabled) {
+            log::warn!("Failed to update the refresh service: {}", e);
+        }
+    }
+}
+
+/// Turn a backend failure into the `keyring` error for it
+fn platform_failure(e: MobileError) -> keyring::Error {
+    keyring::Error::PlatformFailure(Box::new(e))
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    use super::{platform_failure, MobileError};
+    use crate::services::config::AppConfig;
+    use crate::services::files::restrict_to_owner;
+    use jni::objects::{JByteArray, JClass, JObject, JString, JValue};
+    use jni::{JNIEnv, JavaVM};
+    use keyring::credential::{
+        Credential, CredentialApi, CredentialBuilderApi, CredentialPersistence,
+    };
+    use std::any::Any;
+    use std::path::PathBuf;
+
+    /// Alias of the AES key in the Android Keystore
+    const KEY_ALIAS: &str = "io.dymium.provider";
+    /// Foreground service in the Android project
+    const SERVICE_CLASS: &str = "io.dymium.provider.RefreshService";
+    /// GCM IV length chosen by the Keystore
+    const IV_LEN: usize = 12;
+
+    // KeyProperties.PURPOSE_* and Cipher.*_MODE
+    const PURPOSE_ENCRYPT_DECRYPT: i32 = 1 | 2;
+    const ENCRYPT_MODE: i32 = 1;
+    const DECRYPT_MODE: i32 = 2;
+
+    /// Run `f` with a JNI environment and the app's `Context`
+    fn with_context<T>(
+        f: impl for<'local> FnOnce(&mut JNIEnv<'local>, &JObject<'local>) -> jni::errors::Result<T>,
+    ) -> Result<T, MobileError> {
+        let ctx = ndk_context::android_context();
+        let vm = unsafe { JavaVM::from_raw(ctx.vm().cast()) }?;
+        let mut env = vm.attach_current_thread()?;
+        let context = unsafe { JObject::from_raw(ctx.context().cast()) };
+        let result = env.with_local_frame(16, |env| f(env, &context));
+        if env.exception_check().unwrap_or(false) {
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+        Ok(result?)
+    }
+
+    pub fn home() -> Result<PathBuf, MobileError> {
+        with_context(|env, context| {
+            let dir = env
+                .call_method(context, "getFilesDir", "()Ljava/io/File;", &[])?
+                .l()?;
+            let path = env
+                .call_method(&dir, "getAbsolutePath", "()Ljava/lang/String;", &[])?
+                .l()?;
+            let path: String = env.get_string(&JString::from(path))?.into();
+            Ok(PathBuf::from(path))
+        })
+    }
+
+    pub fn keep_alive(enabled: bool) -> Result<(), MobileError> {
+        with_context(|env, context| {
+            // App classes aren't visible to FindClass from a native thread
+            let loader = env
+                .call_method(context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?
+                .l()?;
+            let name = env.new_string(SERVICE_CLASS)?;
+            let class = env
+                .call_method(
+                    &loader,
+                    "loadClass",
+                    "(Ljava/lang/String;)Ljava/lang/Class;",
+                    &[JValue::Object(&name)],
+                )?
+                .l()?;
+            let method = if enabled { "start" } else { "stop" };
+            env.call_static_method(
+                JClass::from(class),
+                method,
+                "(Landroid/content/Context;)V",
+                &[JValue::Object(context)],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// The app's AES key in the Android Keystore, created on first use
+    fn secret_key<'local>(env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+        let provider = env.new_string("AndroidKeyStore")?;
+        let store = env
+            .call_static_method(
+                "java/security/KeyStore",
+                "getInstance",
+                "(Ljava/lang/String;)Ljava/security/KeyStore;",
+                &[JValue::Object(&provider)],
+            )?
+            .l()?;
+        env.call_method(
+            &store,
+            "load",
+            "(Ljava/security/KeyStore$LoadStoreParameter;)V",
+            &[JValue::Object(&JObject::null())],
+        )?;
+        let alias = env.new_string(KEY_ALIAS)?;
+        let key = env
+            .call_method(
+                &store,
+                "getKey",
+                "(Ljava/lang/String;[C)Ljava/security/Key;",
+                &[JValue::Object(&alias), JValue::Object(&JObject::null())],
+            )?
+            .l()?;
+        if !key.is_null() {
+            return Ok(key);
+        }
+
+        let builder = env.new_object(
+            "android/security/keystore/KeyGenParameterSpec$Builder",
+            "(Ljava/lang/String;I)V",
+            &[JValue::Object(&alias), JValue::Int(PURPOSE_ENCRYPT_DECRYPT)],
+        )?;
+        let modes = string_array(env, "GCM")?;
+        env.call_method(
+            &builder,
+            "setBlockModes",
+            "([Ljava/lang/String;)Landroid/security/keystore/KeyGenParameterSpec$Builder;",
+            &[JValue::Object(&modes)],
+        )?;
+        let paddings = string_array(env, "NoPadding")?;
+        env.call_method(
+            &builder,
+            "setEncryptionPaddings",
+            "([Ljava/lang/String;)Landroid/security/keystore/KeyGenParameterSpec$Builder;",
+            &[JValue::Object(&paddings)],
+        )?;
+        let spec = env
+            .call_method(
+                &builder,
+                "build",
+                "()Landroid/security/keystore/KeyGenParameterSpec;",
+                &[],
+            )?
+            .l()?;
+        let algorithm = env.new_string("AES")?;
+        let generator = env
+            .call_static_method(
+                "javax/crypto/KeyGenerator",
+                "getInstance",
+                "(Ljava/lang/String;Ljava/lang/String;)Ljavax/crypto/KeyGenerator;",
+                &[JValue::Object(&algorithm), JValue::Object(&provider)],
+            )?
+            .l()?;
+        env.call_method(
+            &generator,
+            "init",
+            "(Ljava/security/spec/AlgorithmParameterSpec;)V",
+            &[JValue::Object(&spec)],
+        )?;
+        log::info!("Created keystore key {}", KEY_ALIAS);
+        env.call_method(&generator, "generateKey", "()Ljavax/crypto/SecretKey;", &[])?
+            .l()
+    }
+
+    fn string_array<'local>(
+        env: &mut JNIEnv<'local>,
+        value: &str,
+    ) -> jni::errors::Result<JObject<'local>> {
+        let value = env.new_string(value)?;
+        Ok(env.new_object_array(1, "java/lang/String", &value)?.into())
+    }
+
+    /// An AES/GCM cipher with the Keystore key; decrypting when `iv` is given
+    fn cipher<'local>(
+        env: &mut JNIEnv<'local>,
+        iv: Option<&[u8]>,
+    ) -> jni::errors::Result<JObject<'local>> {
+        let key = secret_key(env)?;
+        let transformation = env.new_string("AES/GCM/NoPadding")?;
+        let cipher = env
+            .call_static_method(
+                "javax/crypto/Cipher",
+                "getInstance",
+                "(Ljava/lang/String;)Ljavax/crypto/Cipher;",
+                &[JValue::Object(&transformation)],
+            )?
+            .l()?;
+        match iv {
+            None => env.call_method(
+                &cipher,
+                "init",
+                "(ILjava/security/Key;)V",
+                &[JValue::Int(ENCRYPT_MODE), JValue::Object(&key)],
+            )?,
+            Some(iv) => {
+                let iv = env.byte_array_from_slice(iv)?;
+                let spec = env.new_object(
+                    "javax/crypto/spec/GCMParameterSpec",
+                    "(I[B)V",
+                    &[JValue::Int(128), JValue::Object(&iv)],
+                )?;
+                env.call_method(
+                    &cipher,
+                    "init",
+                    "(ILjava/security/Key;Ljava/security/spec/AlgorithmParameterSpec;)V",
+                    &[
+                        JValue::Int(DECRYPT_MODE),
+                        JValue::Object(&key),
+                        JValue::Object(&spec),
+                    ],
+                )?
+            }
+        };
+        Ok(cipher)
+    }
+
+    fn do_final(
+        env: &mut JNIEnv<'_>,
+        cipher: &JObject<'_>,
+        data: &[u8],
+    ) -> jni::errors::Result<Vec<u8>> {
+        let input = env.byte_array_from_slice(data)?;
+        let output = env
+            .call_method(cipher, "doFinal", "([B)[B", &[JValue::Object(&input)])?
+            .l()?;
+        env.convert_byte_array(JByteArray::from(output))
+    }
+
+    /// IV followed by the ciphertext
+    fn seal(secret: &[u8]) -> Result<Vec<u8>, MobileError> {
+        with_context(|env, _| {
+            let cipher = cipher(env, None)?;
+            let iv = env.call_method(&cipher, "getIV", "()[B", &[])?.l()?;
+            let mut sealed = env.convert_byte_array(JByteArray::from(iv))?;
+            sealed.extend(do_final(env, &cipher, secret)?);
+            Ok(sealed)
+        })
+    }
+
+    fn open(sealed: &[u8]) -> Result<Vec<u8>, MobileError> {
+        if sealed.len() <= IV_LEN {
+            return Err(MobileError::Malformed);
+        }
+        let (iv, ciphertext) = sealed.split_at(IV_LEN);
+        with_context(|env, _| {
+            let cipher = cipher(env, Some(iv))?;
+            do_final(env, &cipher, ciphertext)
+        })
+    }
+
+    /// A secret in ~/.dymium/keystore
+    #[derive(Debug)]
+    struct StoredSecret {
+        path: PathBuf,
+    }
+
+    impl CredentialApi for StoredSecret {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            let sealed = seal(secret).map_err(platform_failure)?;
+            let write = || -> std::io::Result<()> {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&self.path, sealed)?;
+                restrict_to_owner(&self.path)
+            };
+            write().map_err(|e| platform_failure(e.into()))
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            match std::fs::read(&self.path) {
+                Ok(sealed) => open(&sealed).map_err(platform_failure),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(keyring::Error::NoEntry),
+                Err(e) => Err(platform_failure(e.into())),
+            }
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            match std::fs::remove_file(&self.path) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(keyring::Error::NoEntry),
+                Err(e) => Err(platform_failure(e.into())),
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct KeystoreBuilder;
+
+    impl CredentialBuilderApi for KeystoreBuilder {
+        fn build(
+            &self,
+            _target: Option<&str>,
+            _service: &str,
+            user: &str,
+        ) -> keyring::Result<Box<Credential>> {
+            let dir = AppConfig::config_dir().map_err(|e| platform_failure(e.into()))?;
+            Ok(Box::new(StoredSecret {
+                path: dir.join("keystore").join(user),
+            }))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn persistence(&self) -> CredentialPersistence {
+            CredentialPersistence::UntilDelete
+        }
+    }
+}
+
+#[cfg(target_os = "ios")]
+mod platform {
+    use super::{platform_failure, MobileError};
+    use core_foundation::base::{CFType, CFTypeRef, TCFType};
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::data::{CFData, CFDataRef};
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::string::{CFString, CFStringRef};
+    use keyring::credential::{
+        Credential, CredentialApi, CredentialBuilderApi, CredentialPersistence,
+    };
+    use std::any::Any;
+    use std::path::PathBuf;
+
+    #[link(name = "Security", kind = "framework")]
+    unsafe extern "C" {
+        static kSecClass: CFStringRef;
+        static kSecClassGenericPassword: CFStringRef;
+        static kSecAttrService: CFStringRef;
+        static kSecAttrAccount: CFStringRef;
+        static kSecAttrAccessible: CFStringRef;
+        static kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly: CFStringRef;
+        static kSecValueData: CFStringRef;
+        static kSecReturnData: CFStringRef;
+
+        fn SecItemAdd(attributes: CFDictionaryRef, result: *mut CFTypeRef) -> i32;
+        fn SecItemCopyMatching(query: CFDictionaryRef, result: *mut CFTypeRef) -> i32;
+        fn SecItemUpdate(query: CFDictionaryRef, attributes: CFDictionaryRef) -> i32;
+        fn SecItemDelete(query: CFDictionaryRef) -> i32;
+    }
+
+    const ERR_SEC_SUCCESS: i32 = 0;
+    const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+    const ERR_SEC_DUPLICATE_ITEM: i32 = -25299;
+
+    pub fn home() -> Result<PathBuf, MobileError> {
+        // The container root isn't writable; Library is private and backed up
+        std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join("Library"))
+            .ok_or(MobileError::ConfigError(
+                crate::services::config::ConfigError::NoDirError,
+            ))
+    }
+
+    pub fn keep_alive(_enabled: bool) -> Result<(), MobileError> {
+        Ok(())
+    }
+
+    fn key(name: CFStringRef) -> CFType {
+        unsafe { CFString::wrap_under_get_rule(name) }.as_CFType()
+    }
+
+    fn keychain_error(status: i32) -> keyring::Error {
+        platform_failure(MobileError::Keychain(status))
+    }
+
+    /// A generic password item in the Keychain
+    #[derive(Debug)]
+    struct KeychainItem {
+        service: String,
+        account: String,
+    }
+
+    impl KeychainItem {
+        fn query(&self, extra: &[(CFType, CFType)]) -> CFDictionary<CFType, CFType> {
+            let mut pairs = unsafe {
+                vec![
+                    (key(kSecClass), key(kSecClassGenericPassword)),
+                    (
+                        key(kSecAttrService),
+                        CFString::new(&self.service).as_CFType(),
+                    ),
+                    (
+                        key(kSecAttrAccount),
+                        CFString::new(&self.account).as_CFType(),
+                    ),
+                ]
+            };
+            pairs.extend_from_slice(extra);
+            CFDictionary::from_CFType_pairs(&pairs)
+        }
+    }
+
+    impl CredentialApi for KeychainItem {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            let data = CFData::from_buffer(secret).as_CFType();
+            let update =
+                unsafe { CFDictionary::from_CFType_pairs(&[(key(kSecValueData), data.clone())]) };
+            let status = unsafe {
+                SecItemUpdate(
+                    self.query(&[]).as_concrete_TypeRef(),
+                    update.as_concrete_TypeRef(),
+                )
+            };
+            if status != ERR_SEC_ITEM_NOT_FOUND {
+                return match status {
+                    ERR_SEC_SUCCESS => Ok(()),
+                    status => Err(keychain_error(status)),
+                };
+            }
+            // Readable by the refresh loop while the phone is locked
+            let attributes = unsafe {
+                self.query(&[
+                    (key(kSecValueData), data),
+                    (
+                        key(kSecAttrAccessible),
+                        key(kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly),
+                    ),
+                ])
+            };
+            match unsafe { SecItemAdd(attributes.as_concrete_TypeRef(), std::ptr::null_mut()) } {
+                ERR_SEC_SUCCESS | ERR_SEC_DUPLICATE_ITEM => Ok(()),
+                status => Err(keychain_error(status)),
+            }
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            let query = unsafe {
+                self.query(&[(key(kSecReturnData), CFBoolean::true_value().as_CFType())])
+            };
+            let mut result: CFTypeRef = std::ptr::null();
+            match unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) } {
+                ERR_SEC_SUCCESS if !result.is_null() => {
+                    let data = unsafe { CFData::wrap_under_create_rule(result as CFDataRef) };
+                    Ok(data.bytes().to_vec())
+                }
+                ERR_SEC_SUCCESS => Err(platform_failure(MobileError::Malformed)),
+                ERR_SEC_ITEM_NOT_FOUND => Err(keyring::Error::NoEntry),
+                status => Err(keychain_error(status)),
+            }
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            match unsafe { SecItemDelete(self.query(&[]).as_concrete_TypeRef()) } {
+                ERR_SEC_SUCCESS => Ok(()),
+                ERR_SEC_ITEM_NOT_FOUND => Err(keyring::Error::NoEntry),
+                status => Err(keychain_error(status)),
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct KeystoreBuilder;
+
+    impl CredentialBuilderApi for KeystoreBuilder {
+        fn build(
+            &self,
+            _target: Option<&str>,
+            service: &str,
+            user: &str,
+        ) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(KeychainItem {
+                service: service.to_string(),
+                account: user.to_string(),
+            }))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn persistence(&self) -> CredentialPersistence {
+            CredentialPersistence::UntilDelete
+        }
+    }
+}
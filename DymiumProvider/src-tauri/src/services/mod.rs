@@ -1,6 +1,64 @@
 //! Services module
 
+pub mod activity;
+pub mod audit;
+pub mod autostart;
+pub mod claude_code;
+pub mod codex;
 pub mod config;
+pub mod config_template;
+pub mod continue_dev;
+pub mod crash;
+pub mod cursor;
+pub mod egress_proxy;
+pub mod env_file;
+pub mod error_code;
+pub mod failover;
+pub mod files;
+pub mod health;
+pub mod health_check;
+pub mod host_overrides;
+pub mod integrations;
+pub mod jetbrains;
+pub mod jsonc;
+pub mod keycloak_failover;
 pub mod keystore;
+pub mod last_state;
+pub mod log_file;
+pub mod logs;
+pub mod managed_config;
+pub mod migration;
+pub mod minisign;
+#[cfg(mobile)]
+pub mod mobile;
+pub mod neovim;
+pub mod notifications;
 pub mod opencode;
+pub mod org_config;
+pub mod os_auth;
+pub mod power;
+pub mod proxy;
+pub mod proxy_log;
+pub mod proxy_tls;
+pub mod rate_limit;
+pub mod redact;
+pub mod revocation;
+pub mod roo_code;
+pub mod screen_lock;
+pub mod secret_scan;
+pub mod shell;
+pub mod systemd;
+pub mod telemetry;
+pub mod tls_trust;
 pub mod token;
+pub mod token_socket;
+pub mod translate;
+pub mod tray_badge;
+pub mod tray_support;
+pub mod updater;
+pub mod updates;
+pub mod usage;
+pub mod vault;
+pub mod watcher;
+pub mod workspaces;
+pub mod zed;
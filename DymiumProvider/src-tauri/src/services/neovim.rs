@@ -0,0 +1,160 @@
+//! Neovim integration
+//!
+//! Writes a `dymium` Lua module onto Neovim's default runtimepath
+//! (<data dir>/nvim/site/lua/dymium.lua) plus the same data as
+//! ~/.dymium/neovim.json, for plugins such as avante.nvim and codecompanion.
+//! Neither file holds the token: `require("dymium").api_key()` reads the token
+//! file on each call, so rotation needs no reload.
+
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// First line of the Lua module; a file without it is never deleted
+const HEADER: &str =
+    "-- Managed by Dymium Provider; regenerated when the endpoint or models change";
+
+#[derive(Error, Debug)]
+pub enum NeovimError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Data directory not found")]
+    NoDataDir,
+}
+
+/// Service for the Neovim Lua module and JSON file
+pub struct NeovimService;
+
+impl NeovimService {
+    /// Lua module path (stdpath("data")/site/lua/dymium.lua)
+    pub fn lua_path() -> Result<PathBuf, NeovimError> {
+        let data = if cfg!(windows) {
+            dirs::data_local_dir().map(|p| p.join("nvim-data"))
+        } else {
+            dirs::home_dir().map(|p| p.join(".local/share/nvim"))
+        };
+        data.map(|p| p.join("site").join("lua").join("dymium.lua"))
+            .ok_or(NeovimError::NoDataDir)
+    }
+
+    /// JSON file with the same data (~/.dymium/neovim.json)
+    pub fn json_path() -> Result<PathBuf, NeovimError> {
+        Ok(AppConfig::config_dir()?.join("neovim.json"))
+    }
+
+    /// Regenerate both files if the endpoint or models changed
+    pub fn sync(config: &AppConfig, model_ids: &[String]) -> Result<(), NeovimError> {
+        let base_url = OpenCodeService::compute_base_url(config);
+        let token_file = AppConfig::token_path()?.display().to_string();
+
+        let models: Vec<String> = model_ids.iter().map(|m| lua_string(m)).collect();
+        let lua = format!(
+            "{header}\n\
+             -- Usage: local dymium = require(\"dymium\")\n\
+             local M = {{\n\
+             \x20 base_url = {base_url},\n\
+             \x20 token_file = {token_file},\n\
+             \x20 models = {{ {models} }},\n\
+             }}\n\
+             \n\
+             --- Current GhostLLM token, read fresh on every call\n\
+             function M.api_key()\n\
+             \x20 local f = io.open(M.token_file, \"r\")\n\
+             \x20 if not f then\n\
+             \x20   return nil\n\
+             \x20 end\n\
+             \x20 local token = f:read(\"*a\"):gsub(\"%s+$\", \"\")\n\
+             \x20 f:close()\n\
+             \x20 return token\n\
+             end\n\
+             \n\
+             return M\n",
+            header = HEADER,
+            base_url = lua_string(&base_url),
+            token_file = lua_string(&token_file),
+            models = models.join(", "),
+        );
+        let json = serde_json::to_string_pretty(&json!({
+            "baseUrl": base_url,
+            "tokenFile": token_file,
+            "models": model_ids,
+        }))?;
+
+        for (path, content) in [(Self::lua_path()?, lua), (Self::json_path()?, json)] {
+            if files::read_optional(&path)?.as_deref() != Some(content.as_str()) {
+                files::write_atomic(&path, content.as_bytes())?;
+                log::info!("Updated {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete both files if we wrote them (on log out / mode switch)
+    pub fn clear() {
+        if let Err(e) = Self::do_clear() {
+            log::error!("Failed to remove Neovim files: {}", e);
+        }
+    }
+
+    fn do_clear() -> Result<(), NeovimError> {
+        let lua_path = Self::lua_path()?;
+        let ours = files::read_optional(&lua_path)?.is_some_and(|c| c.starts_with(HEADER));
+        if ours {
+            fs::remove_file(&lua_path)?;
+            log::info!("Removed {}", lua_path.display());
+        }
+        let json_path = Self::json_path()?;
+        if json_path.exists() {
+            fs::remove_file(&json_path)?;
+            log::info!("Removed {}", json_path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Double-quoted Lua string literal
+fn lua_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+impl Integration for NeovimService {
+    fn id(&self) -> &'static str {
+        "neovim"
+    }
+
+    fn name(&self) -> &'static str {
+        "Neovim"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        [Self::lua_path(), Self::json_path()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "the dymium Lua module / JSON: base URL, token file path and models (whole file)"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, &ctx.model_ids())?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
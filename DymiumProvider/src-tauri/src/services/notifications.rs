@@ -0,0 +1,77 @@
+//! Notification throttling
+//!
+//! Keeps a flaky connection from producing a notification storm: an auth
+//! failure is announced once per failure streak, further streaks within
+//! `notifications.failureCooldownMinutes` stay silent, and recovery is only
+//! announced for a failure the user was told about. Non-critical
+//! notifications are suppressed during `notifications.quietHours`.
+
+use crate::services::config::NotificationConfig;
+use chrono::Local;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How much a notification matters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    /// Something is broken and needs the user; shown during quiet hours
+    Critical,
+    /// Informational, e.g. recovery; held back during quiet hours
+    Normal,
+}
+
+#[derive(Default)]
+struct GateState {
+    /// Inside a failure streak
+    failing: bool,
+    /// The current streak's failure was announced
+    announced: bool,
+    last_failure_notice: Option<Instant>,
+}
+
+/// Decides which state-change notifications to show
+#[derive(Default)]
+pub struct NotificationGate {
+    state: Mutex<GateState>,
+}
+
+impl NotificationGate {
+    /// Whether a notification may be shown right now
+    pub fn allows(config: &NotificationConfig, urgency: Urgency) -> bool {
+        urgency == Urgency::Critical || !config.is_quiet_at(Local::now().time())
+    }
+
+    /// Authentication failed; whether to announce it
+    pub fn failure(&self, config: &NotificationConfig) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.failing {
+            return false;
+        }
+        state.failing = true;
+        let cooldown = Duration::from_secs(config.failure_cooldown_minutes * 60);
+        let cooling = state
+            .last_failure_notice
+            .is_some_and(|at| at.elapsed() < cooldown);
+        state.announced = !cooling;
+        if state.announced {
+            state.last_failure_notice = Some(Instant::now());
+        }
+        state.announced
+    }
+
+    /// Authentication succeeded; whether to announce the recovery
+    pub fn recovery(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let announce = state.failing && state.announced;
+        state.failing = false;
+        state.announced = false;
+        announce
+    }
+
+    /// Back to idle (logged out, locked); ends any failure streak quietly
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.failing = false;
+        state.announced = false;
+    }
+}
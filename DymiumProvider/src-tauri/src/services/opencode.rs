@@ -3,10 +3,16 @@
 //! Handles updating the OpenCode config (~/.config/opencode/opencode.json)
 //! and auth file (~/.local/share/opencode/auth.json)
 
-use crate::services::config::AppConfig;
+use crate::services::config::{AppConfig, ModelEntry};
+use crate::services::files::{self, BackupInfo};
+use crate::services::jsonc;
+use crate::services::workspaces::WorkspaceService;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use similar::TextDiff;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,6 +27,67 @@ pub enum OpenCodeError {
     NoHomeDir,
 }
 
+/// Context window assumed when `/v1/models` doesn't report one
+pub const DEFAULT_CONTEXT_LIMIT: u64 = 200000;
+/// Output limit assumed when `/v1/models` doesn't report one
+pub const DEFAULT_OUTPUT_LIMIT: u64 = 16384;
+/// Times to re-merge auth.json if OpenCode keeps rewriting it mid-update
+const AUTH_WRITE_ATTEMPTS: usize = 3;
+/// Wait before re-reading an auth.json that didn't parse, e.g. because
+/// OpenCode was halfway through writing it
+const AUTH_PARSE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+/// Longest wait for another writer's lock on auth.json
+const AUTH_LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Provider ids for `ghostllmExtraApps` are this plus the app name
+const APP_PROVIDER_PREFIX: &str = "dymium-";
+
+/// API key OpenCode sends to the local proxy in `socketOnly` mode; the proxy
+/// replaces it with the real token
+const PROXY_API_KEY: &str = "dymium-proxy";
+
+/// auth.json entry fields holding a credential
+const AUTH_SECRET_FIELDS: &[&str] = &["key", "access", "refresh"];
+
+/// A model advertised by the GhostLLM `/v1/models` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredModel {
+    pub id: String,
+    #[serde(
+        default,
+        alias = "context_window",
+        alias = "max_context_length",
+        rename(serialize = "contextLength")
+    )]
+    pub context_length: Option<u64>,
+    #[serde(default, alias = "max_tokens", rename(serialize = "maxOutputTokens"))]
+    pub max_output_tokens: Option<u64>,
+}
+
+/// OpenCode files the app writes (and backs up)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpenCodeFile {
+    /// ~/.config/opencode/opencode.json
+    Config,
+    /// ~/.local/share/opencode/auth.json
+    Auth,
+}
+
+/// New contents for a managed file, alongside what is on disk now
+struct PlannedWrite {
+    path: PathBuf,
+    original: Option<String>,
+    content: String,
+}
+
+/// opencode.json and auth.json contents computed ahead of a token change,
+/// written by `OpenCodeService::commit_staged`
+pub struct StagedSync {
+    config: Option<PlannedWrite>,
+    auth: PlannedWrite,
+    token: String,
+}
+
 /// Service for managing OpenCode configuration
 pub struct OpenCodeService;
 
@@ -28,7 +95,7 @@ impl OpenCodeService {
     /// Get the OpenCode config path
     /// OpenCode (Node.js) uses ~/.config/opencode/ on all platforms (XDG convention),
     /// NOT the platform-native config dir (~/Library/Application Support on macOS).
-    fn config_path() -> Result<PathBuf, OpenCodeError> {
+    pub fn config_path() -> Result<PathBuf, OpenCodeError> {
         dirs::home_dir()
             .map(|p| p.join(".config/opencode/opencode.json"))
             .ok_or(OpenCodeError::NoHomeDir)
@@ -37,25 +104,233 @@ impl OpenCodeService {
     /// Get the OpenCode auth path
     /// OpenCode (Node.js) uses ~/.local/share/opencode/ on all platforms (XDG convention),
     /// NOT the platform-native data dir (~/Library/Application Support on macOS).
-    fn auth_path() -> Result<PathBuf, OpenCodeError> {
+    pub fn auth_path() -> Result<PathBuf, OpenCodeError> {
         dirs::home_dir()
             .map(|p| p.join(".local/share/opencode/auth.json"))
             .ok_or(OpenCodeError::NoHomeDir)
     }
 
+    fn file_path(file: OpenCodeFile) -> Result<PathBuf, OpenCodeError> {
+        match file {
+            OpenCodeFile::Config => Self::config_path(),
+            OpenCodeFile::Auth => Self::auth_path(),
+        }
+    }
+
+    /// Directory of the auth plugin's debug.log (same XDG convention)
+    pub fn plugin_log_dir() -> Result<PathBuf, OpenCodeError> {
+        dirs::home_dir()
+            .map(|p| p.join(".local/share/dymium-opencode-plugin"))
+            .ok_or(OpenCodeError::NoHomeDir)
+    }
+
+    /// List backups of an OpenCode file, newest first
+    pub fn list_backups(file: OpenCodeFile) -> Result<Vec<BackupInfo>, OpenCodeError> {
+        Ok(files::list_backups(&Self::file_path(file)?)?)
+    }
+
+    /// Restore an OpenCode file from a backup
+    pub fn restore_backup(file: OpenCodeFile, backup_name: &str) -> Result<(), OpenCodeError> {
+        Ok(files::restore_backup(&Self::file_path(file)?, backup_name)?)
+    }
+
+    /// Files the app manages (opencode.json and auth.json)
+    pub fn managed_paths() -> Result<Vec<PathBuf>, OpenCodeError> {
+        Ok(vec![Self::config_path()?, Self::auth_path()?])
+    }
+
+    /// Managed sections that are missing or unreadable on disk, e.g. after
+    /// OpenCode or the user rewrote a file. Empty when everything is intact.
+    pub fn missing_managed_sections(config: &AppConfig) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+
+        let opencode_config = Self::config_path()
+            .ok()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| Self::parse_json_like(&content).ok());
+        match opencode_config {
+            Some(value) => {
+                if !value
+                    .pointer("/provider/dymium")
+                    .is_some_and(Value::is_object)
+                {
+                    missing.push("opencode.json provider.dymium");
+                }
+                let has_plugin = match value.get("plugin") {
+                    Some(Value::Array(plugins)) => plugins
+                        .iter()
+                        .any(|p| p.as_str().is_some_and(|s| s.contains("dymium-auth-plugin"))),
+                    Some(Value::String(s)) => s.contains("dymium-auth-plugin"),
+                    _ => false,
+                };
+                if !has_plugin && !config.skip_opencode_plugin {
+                    missing.push("opencode.json plugin");
+                }
+            }
+            None => missing.push("opencode.json"),
+        }
+
+        // auth.json only matters once there is a token to put in it
+        if let Ok(token) = Self::resolve_token(config) {
+            let stored_key = Self::auth_path()
+                .ok()
+                .and_then(|p| fs::read_to_string(p).ok())
+                .and_then(|content| Self::parse_json_like(&content).ok())
+                .and_then(|auth| {
+                    auth.pointer("/dymium/key")
+                        .and_then(|k| k.as_str())
+                        .map(str::to_owned)
+                });
+            if stored_key.as_deref() != Some(token.as_str()) {
+                missing.push("auth.json dymium");
+            }
+        }
+
+        missing
+    }
+
     /// Ensure the dymium provider is configured in opencode.json
     pub fn ensure_dymium_provider(config: &AppConfig) -> Result<(), OpenCodeError> {
-        let config_path = Self::config_path()?;
+        if let Some(planned) = Self::plan_config(config)? {
+            if files::write_with_backup(&planned.path, planned.content.as_bytes())? {
+                log::info!("Updated {}", planned.path.display());
+            }
+        }
+
+        // Update auth.json
+        Self::update_auth_json(config)?;
+
+        WorkspaceService::sync(config);
+        Ok(())
+    }
+
+    /// Compute what `ensure_dymium_provider` will write once `token` is
+    /// stored, without writing anything, so the work can overlap with
+    /// checking the token
+    pub fn stage(config: &AppConfig, token: &str) -> Result<StagedSync, OpenCodeError> {
+        let token = if config.socket_only {
+            PROXY_API_KEY.to_string()
+        } else {
+            token.to_string()
+        };
+        Ok(StagedSync {
+            config: Self::plan_config(config)?,
+            auth: Self::plan_auth(config, &token)?,
+            token,
+        })
+    }
+
+    /// Write staged contents. A file that changed on disk since it was
+    /// staged is planned again, so edits made in the meantime are kept.
+    pub fn commit_staged(config: &AppConfig, staged: StagedSync) -> Result<(), OpenCodeError> {
+        let planned = match staged.config {
+            Some(planned) if files::read_optional(&planned.path)? == planned.original => {
+                Some(planned)
+            }
+            Some(_) => Self::plan_config(config)?,
+            None => None,
+        };
+        if let Some(planned) = planned {
+            if files::write_with_backup(&planned.path, planned.content.as_bytes())? {
+                log::info!("Updated {}", planned.path.display());
+            }
+        }
+
+        Self::write_auth_json(config, &staged.token, Some(&staged.auth))?;
+
+        WorkspaceService::sync(config);
+        Ok(())
+    }
+
+    /// Unified diff of what `ensure_dymium_provider` would write versus the
+    /// current opencode.json and auth.json. Empty when nothing would change.
+    /// Credentials are masked, including other providers'.
+    pub fn preview_changes(config: &AppConfig) -> Result<String, OpenCodeError> {
+        let mut planned: Vec<PlannedWrite> = Self::plan_config(config)?.into_iter().collect();
+        let token = Self::resolve_token(config).ok();
+        if let Some(ref token) = token {
+            planned.push(Self::plan_auth(config, token)?);
+        }
+
+        Ok(Self::render_preview(planned, token))
+    }
+
+    /// Unified diff of `planned` writes with every credential masked: the
+    /// Dymium `token`, each provider's `options.apiKey` and header values, and
+    /// each auth.json entry's key and tokens, whether the line changes or is
+    /// only context
+    fn render_preview(planned: Vec<PlannedWrite>, token: Option<String>) -> String {
+        let mut secrets: Vec<String> = token.into_iter().collect();
+        let mut diff = String::new();
+        for write in planned {
+            let original = write.original.unwrap_or_default();
+            if original == write.content {
+                continue;
+            }
+            Self::collect_secrets(&original, &mut secrets);
+            Self::collect_secrets(&write.content, &mut secrets);
+            let path = write.path.display().to_string();
+            diff.push_str(
+                &TextDiff::from_lines(&original, &write.content)
+                    .unified_diff()
+                    .header(&path, &path)
+                    .to_string(),
+            );
+        }
 
-        // Ensure config directory exists
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)?;
+        // Longest first, so a secret containing another is masked whole
+        secrets.retain(|s| !s.is_empty());
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        secrets.dedup();
+        for secret in &secrets {
+            diff = diff.replace(secret.as_str(), "<redacted>");
         }
+        diff
+    }
+
+    /// Credential values in opencode.json or auth.json contents
+    fn collect_secrets(content: &str, secrets: &mut Vec<String>) {
+        let Ok(Value::Object(root)) = Self::parse_json_like(content) else {
+            return;
+        };
+        let strings = |value: Option<&Value>| {
+            value
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .into_iter()
+        };
+        if let Some(providers) = root.get("provider").and_then(Value::as_object) {
+            for provider in providers.values() {
+                secrets.extend(strings(provider.pointer("/options/apiKey")));
+                if let Some(headers) = provider
+                    .pointer("/options/headers")
+                    .and_then(Value::as_object)
+                {
+                    secrets.extend(headers.values().flat_map(|v| strings(Some(v))));
+                }
+            }
+        }
+        // auth.json entries
+        for entry in root.values() {
+            for field in AUTH_SECRET_FIELDS {
+                secrets.extend(strings(entry.get(*field)));
+            }
+        }
+    }
+
+    /// Compute the opencode.json contents `ensure_dymium_provider` would
+    /// write. Returns `None` when the file is already up to date.
+    fn plan_config(config: &AppConfig) -> Result<Option<PlannedWrite>, OpenCodeError> {
+        let config_path = Self::config_path()?;
 
         // Read existing config (accept JSON with comments/trailing commas) or create new
-        let mut opencode_config: Value = if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            Self::parse_json_like(&content)?
+        let original = if config_path.exists() {
+            Some(fs::read_to_string(&config_path)?)
+        } else {
+            None
+        };
+        let mut opencode_config: Value = if let Some(ref content) = original {
+            Self::parse_json_like(content)?
         } else {
             json!({
                 "$schema": "https://opencode.ai/config.json"
@@ -83,8 +358,16 @@ impl OpenCodeService {
             changed = true;
         }
 
-        // Resolve the API key to write into options.apiKey
-        let api_key = Self::resolve_token(config).ok();
+        // Resolve the API key to write into options.apiKey, unless the auth
+        // plugin supplies it
+        let api_key = if config.skip_opencode_api_key {
+            if config.skip_opencode_plugin {
+                log::warn!("skipOpencodeApiKey without the auth plugin: OpenCode only reads auth.json at startup");
+            }
+            None
+        } else {
+            Self::resolve_token(config).ok()
+        };
 
         // Compute the effective baseURL, injecting the app path when configured.
         // GhostLLM routes: /{app}/v1/chat/completions (preferred, required for OIDC)
@@ -92,7 +375,7 @@ impl OpenCodeService {
         //
         // User enters endpoint like: http://host:9090/v1
         // With ghostllm_app "myapp": http://host:9090/myapp/v1
-        let effective_base_url = Self::compute_base_url(config);
+        let effective_base_url = Self::provider_base_url(config);
 
         // Add or update dymium provider
         let providers_map = providers.as_object_mut().unwrap();
@@ -147,7 +430,13 @@ impl OpenCodeService {
                 );
             }
 
+            Self::apply_headers(opts, &config.llm_headers, &mut changed);
+
             // Update apiKey if changed (this is how OpenCode actually reads auth)
+            if config.skip_opencode_api_key && opts.remove("apiKey").is_some() {
+                changed = true;
+                log::info!("Removed dymium provider apiKey from opencode.json");
+            }
             if let Some(ref key) = api_key {
                 let current_key = opts
                     .get("apiKey")
@@ -170,6 +459,12 @@ impl OpenCodeService {
                     .unwrap()
                     .insert("apiKey".to_string(), json!(key));
             }
+            if !config.llm_headers.is_empty() {
+                options
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("headers".to_string(), json!(&config.llm_headers));
+            }
 
             providers_map.insert(
                 "dymium".to_string(),
@@ -178,109 +473,367 @@ impl OpenCodeService {
                     "name": "Dymium",
                     "api": &effective_base_url,
                     "options": options,
-                    "models": {
-                        "claude-opus-4-5": {
-                            "name": "Claude Opus 4.5 (via Dymium)",
-                            "tool_call": true,
-                            "temperature": true,
-                            "attachment": true,
-                            "reasoning": true,
-                            "interleaved": { "field": "reasoning_content" },
-                            "limit": {
-                                "context": 200000,
-                                "output": 16384
-                            }
-                        },
-                        "claude-sonnet-4": {
-                            "name": "Claude Sonnet 4 (via Dymium)",
-                            "tool_call": true,
-                            "temperature": true,
-                            "attachment": true,
-                            "reasoning": false,
-                            "limit": {
-                                "context": 200000,
-                                "output": 16384
-                            }
-                        }
-                    }
+                    // Populated from /v1/models once the endpoint is verified
+                    "models": {}
                 }),
             );
             changed = true;
             log::info!("Added dymium provider to opencode.json");
         }
 
-        // Ensure all configured Dymium models surface interleaved reasoning deltas.
+        // Apply the curated catalog, then ensure all configured Dymium models
+        // surface interleaved reasoning deltas.
         if let Some(dymium_obj) = providers_map
             .get_mut("dymium")
             .and_then(|v| v.as_object_mut())
         {
+            Self::apply_model_catalog(dymium_obj, &config.model_catalog, &mut changed);
             Self::ensure_model_interleaving(dymium_obj, &mut changed);
         }
+        let app_providers = Self::sync_app_providers(config, providers_map, &mut changed);
 
-        // Ensure plugin is registered via npm
-        let npm_plugin = "dymium-auth-plugin@latest";
-        let plugins_value = opencode_config
-            .as_object_mut()
-            .unwrap()
-            .entry("plugin")
-            .or_insert_with(|| json!([]));
-        let plugins_array = match plugins_value {
-            Value::Array(arr) => arr,
-            Value::String(s) => {
-                let moved = s.clone();
-                *plugins_value = json!([moved]);
-                changed = true;
-                plugins_value.as_array_mut().unwrap()
-            }
-            Value::Null => {
-                *plugins_value = json!([]);
+        // Ensure plugin is registered via npm, unless the user opted out
+        if !config.skip_opencode_plugin {
+            let npm_plugin = "dymium-auth-plugin@latest";
+            let plugins_value = opencode_config
+                .as_object_mut()
+                .unwrap()
+                .entry("plugin")
+                .or_insert_with(|| json!([]));
+            let plugins_array = match plugins_value {
+                Value::Array(arr) => arr,
+                Value::String(s) => {
+                    let moved = s.clone();
+                    *plugins_value = json!([moved]);
+                    changed = true;
+                    plugins_value.as_array_mut().unwrap()
+                }
+                Value::Null => {
+                    *plugins_value = json!([]);
+                    changed = true;
+                    plugins_value.as_array_mut().unwrap()
+                }
+                _ => {
+                    log::warn!(
+                        "opencode.json 'plugin' key was not an array/string; replacing with array"
+                    );
+                    *plugins_value = json!([]);
+                    changed = true;
+                    plugins_value.as_array_mut().unwrap()
+                }
+            };
+
+            // Remove any stale file:// plugin entries
+            let old_len = plugins_array.len();
+            plugins_array.retain(|p| {
+                !p.as_str()
+                    .map(|s| s.contains("dymium-opencode-plugin"))
+                    .unwrap_or(false)
+            });
+            if plugins_array.len() != old_len {
                 changed = true;
-                plugins_value.as_array_mut().unwrap()
+                log::info!("Removed stale file:// dymium plugin entry");
             }
-            _ => {
-                log::warn!(
-                    "opencode.json 'plugin' key was not an array/string; replacing with array"
-                );
-                *plugins_value = json!([]);
+
+            // Add npm plugin if not already present
+            if !plugins_array.iter().any(|p| {
+                p.as_str()
+                    .map(|s| s.contains("dymium-auth-plugin"))
+                    .unwrap_or(false)
+            }) {
+                plugins_array.push(json!(npm_plugin));
                 changed = true;
-                plugins_value.as_array_mut().unwrap()
+                log::info!("Registered dymium auth plugin via npm: {}", npm_plugin);
             }
+        }
+
+        if !changed {
+            return Ok(None);
+        }
+
+        // Touch only the sections we manage
+        let mut paths: Vec<Vec<&str>> = vec![vec!["provider", "dymium"]];
+        paths.extend(app_providers.iter().map(|id| vec!["provider", id.as_str()]));
+        if !config.skip_opencode_plugin {
+            paths.push(vec!["plugin"]);
+        }
+        let paths: Vec<&[&str]> = paths.iter().map(Vec::as_slice).collect();
+        let content = Self::render_config(original.as_deref(), &opencode_config, &paths)?;
+        Ok(Some(PlannedWrite {
+            path: config_path,
+            original,
+            content,
+        }))
+    }
+
+    /// Replace the dymium `models` block with the models the endpoint serves.
+    /// Entries for models that are still served keep any user customizations;
+    /// models no longer served are dropped so OpenCode doesn't offer them.
+    /// Skipped when the user curates a model catalog.
+    pub fn sync_discovered_models(
+        config: &AppConfig,
+        models: &[DiscoveredModel],
+    ) -> Result<(), OpenCodeError> {
+        if !config.model_catalog.is_empty() {
+            log::debug!("Model catalog configured; ignoring /v1/models for opencode.json");
+            return Ok(());
+        }
+        if models.is_empty() {
+            log::warn!("LLM endpoint listed no models; leaving opencode.json models unchanged");
+            return Ok(());
+        }
+        if Self::replace_models(models)? {
+            log::info!("Synced {} dymium model(s) from /v1/models", models.len());
+        }
+        Ok(())
+    }
+
+    /// Put the discovered models back after the catalog was emptied, so
+    /// models removed from it don't linger. With nothing discovered yet the
+    /// block is emptied until the endpoint is next verified.
+    pub fn restore_discovered_models(models: &[DiscoveredModel]) -> Result<(), OpenCodeError> {
+        if Self::replace_models(models)? {
+            log::info!(
+                "Model catalog emptied; restored {} discovered dymium model(s)",
+                models.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Write `models` as the dymium and app providers' `models` blocks.
+    /// Returns whether opencode.json changed.
+    fn replace_models(models: &[DiscoveredModel]) -> Result<bool, OpenCodeError> {
+        let config_path = Self::config_path()?;
+        if !config_path.exists() {
+            return Ok(false);
+        }
+        let original = fs::read_to_string(&config_path)?;
+        let mut opencode_config = Self::parse_json_like(&original)?;
+
+        let Some(dymium_obj) = opencode_config
+            .pointer_mut("/provider/dymium")
+            .and_then(|v| v.as_object_mut())
+        else {
+            return Ok(false);
         };
 
-        // Remove any stale file:// plugin entries
-        let old_len = plugins_array.len();
-        plugins_array.retain(|p| {
-            !p.as_str()
-                .map(|s| s.contains("dymium-opencode-plugin"))
-                .unwrap_or(false)
-        });
-        if plugins_array.len() != old_len {
-            changed = true;
-            log::info!("Removed stale file:// dymium plugin entry");
+        let existing = dymium_obj
+            .get("models")
+            .and_then(|v| v.as_object())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut discovered = serde_json::Map::new();
+        for model in models {
+            let entry = existing
+                .get(&model.id)
+                .cloned()
+                .unwrap_or_else(|| Self::default_model_entry(model));
+            discovered.insert(model.id.clone(), entry);
         }
 
-        // Add npm plugin if not already present
-        if !plugins_array.iter().any(|p| {
-            p.as_str()
-                .map(|s| s.contains("dymium-auth-plugin"))
-                .unwrap_or(false)
-        }) {
-            plugins_array.push(json!(npm_plugin));
-            changed = true;
-            log::info!("Registered dymium auth plugin via npm: {}", npm_plugin);
+        let mut changed = discovered != existing;
+        dymium_obj.insert("models".to_string(), Value::Object(discovered));
+        Self::ensure_model_interleaving(dymium_obj, &mut changed);
+        let model_entries = dymium_obj
+            .get("models")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        // Providers for further apps offer the same models
+        let mut app_providers = Vec::new();
+        if let Some(providers) = opencode_config
+            .get_mut("provider")
+            .and_then(|v| v.as_object_mut())
+        {
+            for (id, provider) in providers.iter_mut() {
+                if id.starts_with(APP_PROVIDER_PREFIX) && provider.is_object() {
+                    if provider.get("models") != Some(&model_entries) {
+                        provider["models"] = model_entries.clone();
+                        changed = true;
+                    }
+                    app_providers.push(id.clone());
+                }
+            }
         }
 
-        // Write config if changed
         if changed {
-            let content = serde_json::to_string_pretty(&opencode_config)?;
-            fs::write(&config_path, content)?;
-            log::info!("Updated {}", config_path.display());
+            let mut paths: Vec<Vec<&str>> = vec![vec!["provider", "dymium", "models"]];
+            paths.extend(
+                app_providers
+                    .iter()
+                    .map(|id| vec!["provider", id.as_str(), "models"]),
+            );
+            let paths: Vec<&[&str]> = paths.iter().map(Vec::as_slice).collect();
+            let content = Self::render_config(Some(&original), &opencode_config, &paths)?;
+            files::write_with_backup(&config_path, content.as_bytes())?;
         }
 
-        // Update auth.json
-        Self::update_auth_json(config)?;
+        Ok(changed)
+    }
 
-        Ok(())
+    /// Make the dymium `models` block match the curated catalog exactly.
+    /// Fields the catalog leaves unset keep their current values.
+    fn apply_model_catalog(
+        dymium_obj: &mut serde_json::Map<String, Value>,
+        catalog: &[ModelEntry],
+        changed: &mut bool,
+    ) {
+        if catalog.is_empty() {
+            return;
+        }
+
+        let existing = dymium_obj
+            .get("models")
+            .and_then(|v| v.as_object())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut models = serde_json::Map::new();
+        for entry in catalog {
+            let mut value = existing
+                .get(&entry.id)
+                .filter(|v| v.is_object())
+                .cloned()
+                .unwrap_or_else(|| {
+                    Self::default_model_entry(&DiscoveredModel {
+                        id: entry.id.clone(),
+                        context_length: None,
+                        max_output_tokens: None,
+                    })
+                });
+            let obj = value.as_object_mut().unwrap();
+
+            if let Some(ref name) = entry.name {
+                obj.insert("name".to_string(), json!(name));
+            }
+            if let Some(reasoning) = entry.reasoning {
+                obj.insert("reasoning".to_string(), json!(reasoning));
+            }
+            if entry.context_limit.is_some() || entry.output_limit.is_some() {
+                let limit = obj.entry("limit").or_insert_with(|| json!({}));
+                if !limit.is_object() {
+                    *limit = json!({});
+                }
+                let limit = limit.as_object_mut().unwrap();
+                if let Some(context) = entry.context_limit {
+                    limit.insert("context".to_string(), json!(context));
+                }
+                if let Some(output) = entry.output_limit {
+                    limit.insert("output".to_string(), json!(output));
+                }
+            }
+
+            models.insert(entry.id.clone(), value);
+        }
+
+        if models != existing {
+            dymium_obj.insert("models".to_string(), Value::Object(models));
+            *changed = true;
+            log::info!(
+                "Applied model catalog ({} models) to opencode.json",
+                catalog.len()
+            );
+        }
+    }
+
+    /// OpenCode model entry for a newly discovered model
+    /// Make the `dymium-<app>` providers match `ghostllmExtraApps` (OAuth
+    /// mode only): each is a copy of the dymium provider with the app's base
+    /// URL. Providers for apps no longer listed are removed. Returns the ids
+    /// added, updated or removed.
+    fn sync_app_providers(
+        config: &AppConfig,
+        providers: &mut serde_json::Map<String, Value>,
+        changed: &mut bool,
+    ) -> Vec<String> {
+        let primary = providers
+            .get("dymium")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let mut wanted = BTreeMap::new();
+        // The proxy serves one app, so there are no per-app providers to
+        // point at it
+        if config.is_oauth_mode() && !config.socket_only {
+            let primary_app = config.ghostllm_app.as_deref().map(str::trim);
+            for app in config.ghostllm_extra_apps.iter().map(|a| a.trim()) {
+                if app.is_empty() || Some(app) == primary_app {
+                    continue;
+                }
+                let mut app_config = config.clone();
+                app_config.ghostllm_app = Some(app.to_string());
+                let base_url = Self::compute_base_url(&app_config);
+                let mut entry = primary.clone();
+                entry["name"] = json!(format!("Dymium ({})", app));
+                entry["api"] = json!(&base_url);
+                entry["options"]["baseURL"] = json!(&base_url);
+                wanted.insert(format!("{}{}", APP_PROVIDER_PREFIX, app), entry);
+            }
+        }
+
+        let mut touched = Vec::new();
+        let stale: Vec<String> = providers
+            .keys()
+            .filter(|id| id.starts_with(APP_PROVIDER_PREFIX) && !wanted.contains_key(*id))
+            .cloned()
+            .collect();
+        for id in stale {
+            providers.remove(&id);
+            log::info!("Removed {} provider from opencode.json", id);
+            touched.push(id);
+        }
+        for (id, entry) in wanted {
+            if providers.get(&id) != Some(&entry) {
+                log::info!("Updated {} provider in opencode.json", id);
+                providers.insert(id.clone(), entry);
+                touched.push(id);
+            }
+        }
+        *changed |= !touched.is_empty();
+        touched
+    }
+
+    /// Set `llmHeaders` in `options.headers`, keeping headers added by hand
+    fn apply_headers(
+        opts: &mut serde_json::Map<String, Value>,
+        headers: &BTreeMap<String, String>,
+        changed: &mut bool,
+    ) {
+        if headers.is_empty() {
+            return;
+        }
+        let existing = opts.entry("headers").or_insert_with(|| json!({}));
+        if !existing.is_object() {
+            log::warn!("opencode.json provider.dymium.options.headers was not an object; replacing with object");
+            *existing = json!({});
+            *changed = true;
+        }
+        let existing = existing.as_object_mut().unwrap();
+        for (name, value) in headers {
+            if existing.get(name).and_then(Value::as_str) != Some(value.as_str()) {
+                existing.insert(name.clone(), json!(value));
+                *changed = true;
+                log::info!("Updated dymium provider header {} in opencode.json", name);
+            }
+        }
+    }
+
+    fn default_model_entry(model: &DiscoveredModel) -> Value {
+        let id = model.id.to_lowercase();
+        let reasoning = id.contains("opus") || id.contains("reason") || id.contains("thinking");
+        json!({
+            "name": format!("{} (via Dymium)", model.id),
+            "tool_call": true,
+            "temperature": true,
+            "attachment": true,
+            "reasoning": reasoning,
+            "interleaved": { "field": "reasoning_content" },
+            "limit": {
+                "context": model.context_length.unwrap_or(DEFAULT_CONTEXT_LIMIT),
+                "output": model.max_output_tokens.unwrap_or(DEFAULT_OUTPUT_LIMIT)
+            }
+        })
     }
 
     fn ensure_model_interleaving(
@@ -318,6 +871,23 @@ impl OpenCodeService {
         }
     }
 
+    /// Serialize an updated opencode.json. When the file already exists, only
+    /// the managed `paths` are rewritten so the user's comments, formatting and
+    /// key order survive; otherwise the whole document is pretty-printed.
+    fn render_config(
+        original: Option<&str>,
+        updated: &Value,
+        paths: &[&[&str]],
+    ) -> Result<String, OpenCodeError> {
+        if let Some(original) = original {
+            if let Some(patched) = jsonc::patch(original, updated, paths) {
+                return Ok(patched);
+            }
+            log::warn!("opencode.json could not be edited in place; rewriting whole file");
+        }
+        Ok(serde_json::to_string_pretty(updated)?)
+    }
+
     fn parse_json_like(content: &str) -> Result<Value, OpenCodeError> {
         match serde_json::from_str(content) {
             Ok(v) => Ok(v),
@@ -334,11 +904,15 @@ impl OpenCodeService {
     fn update_auth_json(config: &AppConfig) -> Result<(), OpenCodeError> {
         // Resolve the token: try the token file first, fall back to static key from config
         let token = Self::resolve_token(config)?;
-        Self::write_auth_json(config, &token)
+        Self::write_auth_json(config, &token, None)
     }
 
     /// Resolve the current token from available sources
     fn resolve_token(config: &AppConfig) -> Result<String, OpenCodeError> {
+        if config.socket_only {
+            return Ok(PROXY_API_KEY.to_string());
+        }
+
         // Try reading from the token file first
         if let Ok(token_path) = AppConfig::token_path() {
             if let Ok(content) = fs::read_to_string(&token_path) {
@@ -364,6 +938,16 @@ impl OpenCodeService {
         )))
     }
 
+    /// baseURL of the dymium provider: the local proxy in `socketOnly` mode,
+    /// otherwise `compute_base_url`
+    fn provider_base_url(config: &AppConfig) -> String {
+        if config.socket_only {
+            config.proxy.local_base_url()
+        } else {
+            Self::compute_base_url(config)
+        }
+    }
+
     /// Compute the effective baseURL for the OpenCode provider.
     ///
     /// For OIDC auth, the app name MUST be in the URL path:
@@ -409,22 +993,132 @@ impl OpenCodeService {
         endpoint.to_string()
     }
 
-    /// Write the dymium entry to auth.json
-    fn write_auth_json(config: &AppConfig, token: &str) -> Result<(), OpenCodeError> {
+    /// Write the dymium entry to auth.json. `staged` contents are used as
+    /// they are if auth.json hasn't changed since they were computed.
+    fn write_auth_json(
+        config: &AppConfig,
+        token: &str,
+        staged: Option<&PlannedWrite>,
+    ) -> Result<(), OpenCodeError> {
         let auth_path = Self::auth_path()?;
+        let written = Self::modify_auth_json(&auth_path, |original| {
+            let content = match staged.filter(|s| s.original.as_deref() == original) {
+                Some(staged) => staged.content.clone(),
+                None => Self::auth_content(config, token, original)?,
+            };
+            // Formatting differences alone don't warrant a write
+            let unchanged = original
+                .and_then(|original| Self::parse_json_like(original).ok())
+                .zip(serde_json::from_str::<Value>(&content).ok())
+                .is_some_and(|(before, after)| before == after);
+            Ok((!unchanged).then_some(content))
+        })?;
+        if !written {
+            return Ok(());
+        }
+        log::info!(
+            "Updated dymium token in {} (mode: {})",
+            auth_path.display(),
+            if config.is_static_key_mode() {
+                "static"
+            } else {
+                "oauth"
+            }
+        );
 
-        // Ensure directory exists
-        if let Some(parent) = auth_path.parent() {
-            fs::create_dir_all(parent)?;
+        Ok(())
+    }
+
+    /// Read-modify-write auth.json. `edit` maps the current contents to the new
+    /// contents, or `None` to leave the file alone. Returns whether it wrote.
+    ///
+    /// OpenCode rewrites auth.json when users log into other providers. Our own
+    /// writers are serialized by an advisory lock; since OpenCode doesn't take
+    /// that lock, the file is re-read before writing and, if it changed in the
+    /// meantime, `edit` is re-applied to the new contents so their entries
+    /// aren't clobbered. A file that doesn't parse is read again a few times,
+    /// in case it was caught mid-write, and then reported rather than replaced.
+    ///
+    /// Callers run on runtime workers, usually with the token service locked,
+    /// so waiting for the lock is bounded and runs through [`files::blocking`].
+    fn modify_auth_json<F>(auth_path: &Path, edit: F) -> Result<bool, OpenCodeError>
+    where
+        F: Fn(Option<&str>) -> Result<Option<String>, OpenCodeError>,
+    {
+        files::blocking(|| Self::modify_auth_json_locked(auth_path, edit))
+    }
+
+    fn modify_auth_json_locked<F>(auth_path: &Path, edit: F) -> Result<bool, OpenCodeError>
+    where
+        F: Fn(Option<&str>) -> Result<Option<String>, OpenCodeError>,
+    {
+        let _lock = files::lock_within(auth_path, AUTH_LOCK_TIMEOUT)?;
+
+        let mut parse_error = None;
+        for _ in 0..AUTH_WRITE_ATTEMPTS {
+            let original = files::read_optional(auth_path)?;
+            let content = match edit(original.as_deref()) {
+                Ok(Some(content)) => content,
+                Ok(None) => return Ok(false),
+                Err(e @ OpenCodeError::ParseError(_)) => {
+                    log::warn!("auth.json is unreadable ({}); reading it again", e);
+                    parse_error = Some(e);
+                    std::thread::sleep(AUTH_PARSE_RETRY_DELAY);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if files::read_optional(auth_path)? != original {
+                log::warn!("auth.json changed while updating it; merging again");
+                continue;
+            }
+            files::write_with_backup(auth_path, content.as_bytes())?;
+            return Ok(true);
         }
 
-        // Read existing auth or create new
-        let mut auth: Value = if auth_path.exists() {
-            let content = fs::read_to_string(&auth_path)?;
-            Self::parse_json_like(&content).unwrap_or_else(|_| json!({}))
-        } else {
-            json!({})
+        // Never start over from an empty object: that would drop the other
+        // providers' credentials
+        Err(parse_error.unwrap_or_else(|| {
+            OpenCodeError::IoError(std::io::Error::other(
+                "auth.json kept changing while updating it",
+            ))
+        }))
+    }
+
+    /// Parse auth.json. A missing or empty file is an empty object; anything
+    /// else that isn't a JSON object is an error.
+    fn parse_auth(original: Option<&str>) -> Result<Value, OpenCodeError> {
+        let Some(content) = original.filter(|c| !c.trim().is_empty()) else {
+            return Ok(json!({}));
         };
+        let auth = Self::parse_json_like(content)?;
+        if !auth.is_object() {
+            return Err(OpenCodeError::ParseError(
+                "auth.json root is not an object".to_string(),
+            ));
+        }
+        Ok(auth)
+    }
+
+    /// Compute auth.json with the dymium entry set to `token`
+    fn plan_auth(config: &AppConfig, token: &str) -> Result<PlannedWrite, OpenCodeError> {
+        let auth_path = Self::auth_path()?;
+        let original = files::read_optional(&auth_path)?;
+        let content = Self::auth_content(config, token, original.as_deref())?;
+        Ok(PlannedWrite {
+            path: auth_path,
+            original,
+            content,
+        })
+    }
+
+    /// `original` auth.json contents with the dymium entry set to `token`
+    fn auth_content(
+        config: &AppConfig,
+        token: &str,
+        original: Option<&str>,
+    ) -> Result<String, OpenCodeError> {
+        let mut auth = Self::parse_auth(original)?;
 
         // Determine auth type based on config mode
         let auth_type = if config.is_static_key_mode() {
@@ -454,22 +1148,50 @@ impl OpenCodeService {
             .unwrap()
             .insert("dymium".to_string(), dymium_auth);
 
-        fs::write(&auth_path, serde_json::to_string_pretty(&auth)?)?;
-        log::info!(
-            "Updated dymium token in {} (mode: {})",
-            auth_path.display(),
-            auth_type
-        );
-
-        Ok(())
+        Ok(serde_json::to_string_pretty(&auth)?)
     }
 
-    /// Clear the dymium entry from auth.json
+    /// Clear the dymium entry from auth.json and the dymium providers'
+    /// `options.apiKey` from opencode.json
     /// Called when switching auth modes to prevent stale credentials
     pub fn clear_dymium_auth() {
         if let Err(e) = Self::do_clear_dymium_auth() {
             log::error!("Failed to clear dymium auth: {}", e);
         }
+        if let Err(e) = Self::clear_api_keys() {
+            log::error!("Failed to clear dymium apiKey from opencode.json: {}", e);
+        }
+    }
+
+    fn clear_api_keys() -> Result<(), OpenCodeError> {
+        let config_path = Self::config_path()?;
+        let Some(content) = files::read_optional(&config_path)? else {
+            return Ok(());
+        };
+        let ids: Vec<String> = Self::parse_json_like(&content)?
+            .get("provider")
+            .and_then(Value::as_object)
+            .map(|providers| {
+                providers
+                    .iter()
+                    .filter(|(id, p)| {
+                        (id.as_str() == "dymium" || id.starts_with(APP_PROVIDER_PREFIX))
+                            && p.pointer("/options/apiKey").is_some()
+                    })
+                    .map(|(id, _)| id.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let paths: Vec<[&str; 4]> = ids
+            .iter()
+            .map(|id| ["provider", id.as_str(), "options", "apiKey"])
+            .collect();
+        let edits: Vec<(&[&str], Option<Value>)> =
+            paths.iter().map(|path| (&path[..], None)).collect();
+        if jsonc::update_file(&config_path, &edits)? {
+            log::info!("Cleared dymium apiKey from opencode.json");
+        }
+        Ok(())
     }
 
     fn do_clear_dymium_auth() -> Result<(), OpenCodeError> {
@@ -479,17 +1201,88 @@ impl OpenCodeService {
             return Ok(());
         }
 
-        let content = fs::read_to_string(&auth_path)?;
-        let mut auth: Value = Self::parse_json_like(&content).unwrap_or_else(|_| json!({}));
-
         // Remove the dymium entry
-        if let Some(obj) = auth.as_object_mut() {
-            if obj.remove("dymium").is_some() {
-                fs::write(&auth_path, serde_json::to_string_pretty(&auth)?)?;
-                log::info!("Cleared dymium entry from auth.json");
+        let cleared = Self::modify_auth_json(&auth_path, |original| {
+            let mut auth = Self::parse_auth(original)?;
+            let removed = auth
+                .as_object_mut()
+                .is_some_and(|obj| obj.remove("dymium").is_some());
+            if removed {
+                Ok(Some(serde_json::to_string_pretty(&auth)?))
+            } else {
+                Ok(None)
             }
+        })?;
+        if cleared {
+            log::info!("Cleared dymium entry from auth.json");
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(name: &str, original: &str, content: &str) -> PlannedWrite {
+        PlannedWrite {
+            path: PathBuf::from(name),
+            original: Some(original.to_string()),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn preview_masks_every_credential() {
+        let auth_before = r#"{
+  "openai": { "type": "api", "key": "sk-openai-secret" },
+  "anthropic": { "type": "oauth", "access": "anthropic-access", "refresh": "anthropic-refresh" },
+  "dymium": { "type": "api", "key": "old-dymium-token" }
+}
+"#;
+        let auth_after = auth_before.replace("old-dymium-token", "new-dymium-token");
+        let config_before = r#"{
+  "provider": {
+    "groq": { "options": { "apiKey": "groq-secret" } },
+    "dymium": { "options": { "baseURL": "https://old.example/v1" } }
+  }
+}
+"#;
+        let config_after = r#"{
+  "provider": {
+    "groq": { "options": { "apiKey": "groq-secret" } },
+    "dymium": { "options": { "baseURL": "https://new.example/v1", "headers": { "CF-Access-Client-Secret": "cf-secret" } } }
+  }
+}
+"#;
+        let diff = OpenCodeService::render_preview(
+            vec![
+                planned("opencode.json", config_before, config_after),
+                planned("auth.json", auth_before, &auth_after),
+            ],
+            Some("new-dymium-token".to_string()),
+        );
+
+        for secret in [
+            "sk-openai-secret",
+            "anthropic-access",
+            "anthropic-refresh",
+            "old-dymium-token",
+            "new-dymium-token",
+            "groq-secret",
+            "cf-secret",
+        ] {
+            assert!(!diff.contains(secret), "{} leaked:\n{}", secret, diff);
+        }
+        assert!(diff.contains("https://new.example/v1"));
+        assert!(diff.contains("-  \"dymium\": { \"type\": \"api\", \"key\": \"<redacted>\" }"));
+    }
+
+    #[test]
+    fn preview_skips_unchanged_files() {
+        let same = r#"{ "openai": { "key": "sk-openai-secret" } }"#;
+        let diff = OpenCodeService::render_preview(vec![planned("auth.json", same, same)], None);
+        assert!(diff.is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Organization-wide base configuration
+//!
+//! IT can ship a base config.json, by hand or through MDM, that sits under
+//! the user's ~/.dymium/config.json:
+//!
+//! 1. Built-in defaults
+//! 2. The org file
+//! 3. The user's config.json
+//! 4. Keys the org file lists in `"locked"`, which the user can't override
+//!
+//! Objects are merged key by key, so the org file can set `proxy.port` and
+//! the user the rest of `proxy`. The org file comes from `DYMIUM_ORG_CONFIG`,
+//! or the first that exists of /etc/dymium/config.json (Linux),
+//! /Library/Application Support/Dymium/config.json (macOS) and
+//! %ProgramData%\Dymium\config.json (Windows). Values taken from it are never
+//! copied into the user's file, so later changes to it still apply.
+//!
+//! A verified managed config (see `managed_config`) is layered over the org
+//! file, between steps 2 and 3.
+
+use crate::services::managed_config::ManagedConfigService;
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+
+/// Environment variable naming the org file
+pub const ORG_CONFIG_ENV: &str = "DYMIUM_ORG_CONFIG";
+
+/// Key in the org file listing the keys users can't override
+const LOCKED_KEY: &str = "locked";
+
+/// The org file's settings and locked keys
+#[derive(Debug, Clone, Default)]
+pub struct OrgConfig {
+    settings: Map<String, Value>,
+    locked: Vec<String>,
+}
+
+impl OrgConfig {
+    /// Where the org file is looked for, in order
+    fn candidates() -> Vec<PathBuf> {
+        if let Ok(path) = std::env::var(ORG_CONFIG_ENV) {
+            return vec![PathBuf::from(path)];
+        }
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(|dir| PathBuf::from(dir).join("Dymium").join("config.json"))
+                .into_iter()
+                .collect()
+        } else if cfg!(target_os = "macos") {
+            vec![PathBuf::from(
+                "/Library/Application Support/Dymium/config.json",
+            )]
+        } else {
+            vec![PathBuf::from("/etc/dymium/config.json")]
+        }
+    }
+
+    /// Path of the org file in use, if there is one
+    pub fn path() -> Option<PathBuf> {
+        Self::candidates().into_iter().find(|p| p.is_file())
+    }
+
+    /// Read the org file and the managed config over it
+    pub fn load() -> Self {
+        let mut org = Self::load_file();
+        if let Some(managed) = ManagedConfigService::cached(&org.settings) {
+            let managed = Self::from_settings(managed);
+            let mut settings = Value::Object(std::mem::take(&mut org.settings));
+            merge_into(&mut settings, Value::Object(managed.settings));
+            if let Value::Object(settings) = settings {
+                org.settings = settings;
+            }
+            for key in managed.locked {
+                if !org.locked.contains(&key) {
+                    org.locked.push(key);
+                }
+            }
+        }
+        org
+    }
+
+    /// Read the org file alone. A missing file is an empty layer; an
+    /// unreadable one is logged and ignored so the app still starts.
+    pub fn load_file() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<Value>(&content).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(Value::Object(settings)) => Self::from_settings(settings),
+            Ok(_) => {
+                log::error!("Ignoring {}: not a JSON object", path.display());
+                Self::default()
+            }
+            Err(e) => {
+                log::error!("Ignoring {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Split `"locked"` off a layer's settings
+    fn from_settings(mut settings: Map<String, Value>) -> Self {
+        let locked = match settings.remove(LOCKED_KEY) {
+            Some(Value::Array(keys)) => keys
+                .into_iter()
+                .filter_map(|k| k.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Self { settings, locked }
+    }
+
+    /// Whether there are no org settings at all
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty() && self.locked.is_empty()
+    }
+
+    /// The org settings, without the locked keys
+    pub fn settings(&self) -> &Map<String, Value> {
+        &self.settings
+    }
+
+    /// Keys the user can't override
+    pub fn locked(&self) -> &[String] {
+        &self.locked
+    }
+
+    /// The user's settings on top of the org file, locked keys excepted
+    pub fn merge(&self, user: Value) -> Value {
+        let mut merged = Value::Object(self.settings.clone());
+        let Value::Object(mut user) = user else {
+            return merged;
+        };
+        for key in &self.locked {
+            if user.remove(key).is_some() {
+                log::warn!("Ignoring {} in config.json: set by the organization", key);
+            }
+        }
+        merge_into(&mut merged, Value::Object(user));
+        merged
+    }
+
+    /// `config` without the values that come from the org file, for writing
+    /// to the user's config.json
+    pub fn strip(&self, config: Value) -> Value {
+        let Value::Object(mut config) = config else {
+            return config;
+        };
+        for key in &self.locked {
+            config.remove(key);
+        }
+        strip_from(&mut config, &self.settings);
+        Value::Object(config)
+    }
+}
+
+/// Merge `overlay` into `base`, object by object
+fn merge_into(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_into(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Remove from `config` the values equal to those in `org`
+fn strip_from(config: &mut Map<String, Value>, org: &Map<String, Value>) {
+    for (key, org_value) in org {
+        let Some(value) = config.get_mut(key) else {
+            continue;
+        };
+        if value == org_value {
+            config.remove(key);
+        } else if let (Value::Object(value), Value::Object(org_value)) = (value, org_value) {
+            strip_from(value, org_value);
+            if value.is_empty() {
+                config.remove(key);
+            }
+        }
+    }
+}
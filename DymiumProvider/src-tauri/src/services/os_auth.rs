@@ -0,0 +1,175 @@
+//! OS-level user confirmation
+//!
+//! Gates access to raw secrets behind the platform's local authentication:
+//! - macOS: LocalAuthentication (Touch ID, falling back to the login password)
+//! - Windows: Windows Hello (UserConsentVerifier)
+//! - Linux: polkit (`pkcheck` with user interaction), with the app's own
+//!   action, which asks for the user's password rather than an admin's
+//!
+//! All calls block until the user responds, so run them off the async runtime.
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OsAuthError {
+    #[error("Authentication was cancelled or failed: {0}")]
+    Denied(String),
+    #[error("OS authentication is not available: {0}")]
+    Unavailable(String),
+}
+
+/// Service for confirming the user's identity with the operating system
+pub struct OsAuthService;
+
+impl OsAuthService {
+    /// Ask the user to confirm their identity. `reason` completes the sentence
+    /// shown by the OS prompt, e.g. "reveal the Dymium access token".
+    pub fn confirm(reason: &str) -> Result<(), OsAuthError> {
+        log::info!("Requesting OS authentication to {}", reason);
+        let result = platform::confirm(reason);
+        match &result {
+            Ok(()) => log::info!("OS authentication succeeded"),
+            Err(e) => log::warn!("OS authentication failed: {}", e),
+        }
+        result
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::OsAuthError;
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_foundation::{NSError, NSString};
+    use objc2_local_authentication::{LAContext, LAPolicy};
+    use std::sync::mpsc;
+
+    pub fn confirm(reason: &str) -> Result<(), OsAuthError> {
+        let context = unsafe { LAContext::new() };
+        let policy = LAPolicy::DeviceOwnerAuthentication;
+
+        if let Err(e) = unsafe { context.canEvaluatePolicy_error(policy) } {
+            return Err(OsAuthError::Unavailable(
+                e.localizedDescription().to_string(),
+            ));
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let reply = RcBlock::new(move |success: Bool, error: *mut NSError| {
+            let result = if success.as_bool() {
+                Ok(())
+            } else {
+                let message = unsafe { error.as_ref() }
+                    .map(|e| e.localizedDescription().to_string())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                Err(OsAuthError::Denied(message))
+            };
+            let _ = tx.send(result);
+        });
+
+        let reason = NSString::from_str(reason);
+        unsafe { context.evaluatePolicy_localizedReason_reply(policy, &reason, &reply) };
+
+        rx.recv().map_err(|_| {
+            OsAuthError::Unavailable("LocalAuthentication did not reply".to_string())
+        })?
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::OsAuthError;
+    use windows::core::HSTRING;
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    pub fn confirm(reason: &str) -> Result<(), OsAuthError> {
+        let availability = UserConsentVerifier::CheckAvailabilityAsync()
+            .and_then(|op| op.get())
+            .map_err(|e| OsAuthError::Unavailable(e.message().to_string()))?;
+        if availability != UserConsentVerifierAvailability::Available {
+            return Err(OsAuthError::Unavailable(
+                "Windows Hello is not set up on this device".to_string(),
+            ));
+        }
+
+        let message = HSTRING::from(format!("Dymium Provider wants to {}", reason));
+        let result = UserConsentVerifier::RequestVerificationAsync(&message)
+            .and_then(|op| op.get())
+            .map_err(|e| OsAuthError::Unavailable(e.message().to_string()))?;
+
+        if result == UserConsentVerificationResult::Verified {
+            Ok(())
+        } else {
+            Err(OsAuthError::Denied(format!("{:?}", result)))
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::OsAuthError;
+    use std::process::Command;
+
+    /// The app's polkit action (polkit/io.dymium.provider.policy, installed
+    /// by the .deb and .rpm): the user's own password, not an admin's
+    const POLKIT_ACTION: &str = "io.dymium.provider.confirm";
+
+    /// Where packages install polkit actions
+    const POLKIT_ACTIONS_DIR: &str = "/usr/share/polkit-1/actions";
+
+    /// `pid,start-time` of this process. The start time (field 22 of
+    /// /proc/self/stat) keeps polkit from authorizing another process that
+    /// reused the pid.
+    fn subject() -> Result<String, OsAuthError> {
+        let stat = std::fs::read_to_string("/proc/self/stat")
+            .map_err(|e| OsAuthError::Unavailable(format!("/proc/self/stat: {}", e)))?;
+        // The command name (field 2) may contain spaces; fields 3 on follow
+        // the last ')'
+        let start_time = stat
+            .rsplit_once(')')
+            .and_then(|(_, fields)| fields.split_whitespace().nth(19))
+            .ok_or_else(|| OsAuthError::Unavailable("unexpected /proc/self/stat".to_string()))?;
+        Ok(format!("{},{}", std::process::id(), start_time))
+    }
+
+    pub fn confirm(reason: &str) -> Result<(), OsAuthError> {
+        log::debug!("Asking polkit to authorize: {}", reason);
+        // An unknown action is just "not authorized" to pkcheck
+        let registered = Command::new("pkaction")
+            .arg("--action-id")
+            .arg(POLKIT_ACTION)
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !registered {
+            return Err(OsAuthError::Unavailable(format!(
+                "polkit action {} is not installed (copy io.dymium.provider.policy to {})",
+                POLKIT_ACTION, POLKIT_ACTIONS_DIR
+            )));
+        }
+
+        let status = Command::new("pkcheck")
+            .arg("--action-id")
+            .arg(POLKIT_ACTION)
+            .arg("--process")
+            .arg(subject()?)
+            .arg("--allow-user-interaction")
+            .status()
+            .map_err(|e| OsAuthError::Unavailable(format!("pkcheck: {}", e)))?;
+
+        // pkcheck exit codes: 0 authorized, 1 not authorized, 2 dismissed,
+        // 3 error, 4 challenge required
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(1) | Some(2) => Err(OsAuthError::Denied("polkit denied the request".to_string())),
+            Some(code) => Err(OsAuthError::Unavailable(format!(
+                "pkcheck exited with status {}",
+                code
+            ))),
+            None => Err(OsAuthError::Unavailable(
+                "pkcheck was terminated".to_string(),
+            )),
+        }
+    }
+}
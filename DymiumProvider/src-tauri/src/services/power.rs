@@ -0,0 +1,263 @@
+//! Battery and metered connection detection
+//!
+//! A laptop left idle on battery, or tethered to a phone, shouldn't wake its
+//! radio every minute. While either is detected the refresh loop waits
+//! `powerSaving.intervalMultiplier` times longer between refreshes, but always
+//! refreshes with time to spare before the token expires. It also leaves out
+//! checks that only re-confirm what is known: the endpoint check for a token
+//! refreshed soon after the last one was accepted, and the proxy's probes of
+//! the primary endpoint while failed over.
+//!
+//! Battery comes from /sys/class/power_supply (Linux), `pmset` (macOS) or
+//! `GetSystemPowerStatus` (Windows); metered connections from NetworkManager
+//! (Linux) or the connection cost (Windows). macOS has no metered flag
+//! outside of apps' own network paths, and the mobile OSes schedule
+//! background work themselves, so neither is detected there. Anything that
+//! can't be read counts as mains power on an unmetered network.
+
+use crate::services::config::PowerSavingConfig;
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a detected state is reused before asking the OS again
+const DETECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time left before expiry that a stretched interval never eats into
+const EXPIRY_MARGIN_SECS: u64 = 120;
+
+/// How long after the endpoint last accepted a token a refreshed one is
+/// trusted without checking
+const RECHECK_AFTER_MINUTES: i64 = 60;
+
+/// Last detected state and when it was detected
+static LAST: Mutex<Option<(Instant, PowerState)>> = Mutex::new(None);
+
+/// What the machine is running on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+impl PowerState {
+    /// Whether refreshes should be spaced out
+    pub fn constrained(&self) -> bool {
+        self.on_battery || self.metered
+    }
+
+    fn describe(&self) -> &'static str {
+        match (self.on_battery, self.metered) {
+            (true, true) => "on battery and a metered connection",
+            (true, false) => "on battery",
+            (false, true) => "on a metered connection",
+            (false, false) => "on mains power and an unmetered connection",
+        }
+    }
+}
+
+/// Service for power-aware refresh scheduling
+pub struct PowerService;
+
+impl PowerService {
+    /// Current power and network state, detected at most every 30 seconds
+    pub fn current() -> PowerState {
+        let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, state)) = *last {
+            if at.elapsed() < DETECT_INTERVAL {
+                return state;
+            }
+        }
+        let state = PowerState {
+            on_battery: platform::on_battery(),
+            metered: platform::metered(),
+        };
+        let previous = last.map(|(_, s)| s).unwrap_or_default();
+        if state != previous {
+            log::info!("Running {}", state.describe());
+        }
+        *last = Some((Instant::now(), state));
+        state
+    }
+
+    /// Whether power saving applies right now
+    pub fn saving(config: &PowerSavingConfig) -> bool {
+        config.enabled && Self::current().constrained()
+    }
+
+    /// Whether non-essential verification should be left out right now
+    pub fn skip_verification(config: &PowerSavingConfig) -> bool {
+        config.skip_verification && Self::saving(config)
+    }
+
+    /// Whether a refreshed token can be handed out without asking the
+    /// endpoint first, given when it last accepted one. It came from the same
+    /// grant, so the check mostly confirms the endpoint is up.
+    pub fn skip_endpoint_check(
+        config: &PowerSavingConfig,
+        last_accepted: Option<DateTime<Utc>>,
+    ) -> bool {
+        last_accepted
+            .is_some_and(|at| Utc::now() - at < chrono::Duration::minutes(RECHECK_AFTER_MINUTES))
+            && Self::skip_verification(config)
+    }
+
+    /// Seconds until the next refresh: `base`, or stretched while power
+    /// saving, but ending at least two minutes before a token expiring in
+    /// `expires_in` seconds. Never shorter than `base`.
+    pub fn refresh_interval(base: u64, config: &PowerSavingConfig, expires_in: Option<i64>) -> u64 {
+        if !Self::saving(config) {
+            return base;
+        }
+        let stretched = base.saturating_mul(config.interval_multiplier.max(1));
+        let limit = expires_in
+            .map(|secs| u64::try_from(secs).unwrap_or(0))
+            .map(|secs| secs.saturating_sub(EXPIRY_MARGIN_SECS))
+            .unwrap_or(stretched);
+        stretched.min(limit).max(base)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::path::Path;
+    use std::process::Command;
+
+    /// NetworkManager's NM_METERED_YES and NM_METERED_GUESS_YES
+    const METERED: [u32; 2] = [1, 3];
+
+    fn read(dir: &Path, name: &str) -> Option<String> {
+        std::fs::read_to_string(dir.join(name))
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    /// On battery when a system battery is discharging and no AC adapter is
+    /// plugged in. Mice and headsets report batteries too, with scope Device.
+    pub fn on_battery() -> bool {
+        let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+            return false;
+        };
+        let mut discharging = false;
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            match read(&dir, "type").as_deref() {
+                Some("Mains") | Some("USB") if read(&dir, "online").as_deref() == Some("1") => {
+                    return false;
+                }
+                Some("Battery")
+                    if read(&dir, "scope").as_deref() != Some("Device")
+                        && read(&dir, "status").as_deref() == Some("Discharging") =>
+                {
+                    discharging = true;
+                }
+                _ => {}
+            }
+        }
+        discharging
+    }
+
+    /// NetworkManager's `Metered` property, asked with `dbus-send` or
+    /// `gdbus`. Both print it as "uint32 <n>".
+    pub fn metered() -> bool {
+        let dbus_send = Command::new("dbus-send")
+            .args([
+                "--system",
+                "--print-reply",
+                "--dest=org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.DBus.Properties.Get",
+                "string:org.freedesktop.NetworkManager",
+                "string:Metered",
+            ])
+            .output();
+        let output = match dbus_send {
+            Ok(output) if output.status.success() => output,
+            _ => match Command::new("gdbus")
+                .args([
+                    "call",
+                    "--system",
+                    "--dest",
+                    "org.freedesktop.NetworkManager",
+                    "--object-path",
+                    "/org/freedesktop/NetworkManager",
+                    "--method",
+                    "org.freedesktop.DBus.Properties.Get",
+                    "org.freedesktop.NetworkManager",
+                    "Metered",
+                ])
+                .output()
+            {
+                Ok(output) if output.status.success() => output,
+                _ => return false,
+            },
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .split("uint32")
+            .nth(1)
+            .and_then(|rest| {
+                let digits: String = rest
+                    .trim_start()
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+                digits.parse::<u32>().ok()
+            })
+            .is_some_and(|value| METERED.contains(&value))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+
+    /// `pmset -g batt` starts with "Now drawing from 'Battery Power'"
+    pub fn on_battery() -> bool {
+        Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+    }
+
+    pub fn metered() -> bool {
+        false
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    /// AC line status 0 is offline; 1 is online and 255 unknown
+    pub fn on_battery() -> bool {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        unsafe { GetSystemPowerStatus(&mut status) }.is_ok() && status.ACLineStatus == 0
+    }
+
+    /// Metered when the connection is charged by use or has a cap, is
+    /// roaming or is over its data limit
+    pub fn metered() -> bool {
+        let cost = NetworkInformation::GetInternetConnectionProfile()
+            .and_then(|profile| profile.GetConnectionCost());
+        let Ok(cost) = cost else {
+            return false;
+        };
+        let charged = cost
+            .NetworkCostType()
+            .is_ok_and(|kind| kind == NetworkCostType::Fixed || kind == NetworkCostType::Variable);
+        charged || cost.Roaming().unwrap_or(false) || cost.OverDataLimit().unwrap_or(false)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    pub fn on_battery() -> bool {
+        false
+    }
+
+    pub fn metered() -> bool {
+        false
+    }
+}
@@ -0,0 +1,629 @@
+//! Local authenticating reverse proxy
+//!
+//! Listens on 127.0.0.1 (`proxy.port` in config.json; HTTPS with `proxy.tls`,
+//! see `proxy_tls`) and forwards
+//! OpenAI-compatible requests to GhostLLM, replacing the Authorization header
+//! with the current token. Clients point at `http://127.0.0.1:<port>/v1` with
+//! any API key and never see or store the real one. Responses, SSE streams
+//! included, are passed through as they arrive. Requests can be recorded for
+//! debugging (see `proxy_log`) and limited in rate (see `rate_limit`). Model
+//! names can be aliased, and models routed to other GhostLLM apps, so tools
+//! with hardcoded model names work unchanged. Anthropic Messages API clients
+//! are served through OpenAI-compatible upstreams and vice versa (see
+//! `translate`). Requests fail over across endpoints (see `failover`).
+
+use crate::services::config::{AppConfig, PowerSavingConfig, ProxyConfig, UpstreamApi};
+use crate::services::failover::Failover;
+use crate::services::power::PowerService;
+use crate::services::proxy_log::{ProxyLog, RequestTap};
+use crate::services::proxy_tls::{ProxyTls, ProxyTlsError};
+use crate::services::rate_limit::RateLimiter;
+use crate::services::tls_trust::{certificate_error, HttpClients, TlsTrustError};
+use crate::services::translate::{Direction, StreamTranslator, Translation};
+use crate::services::usage::UsageMeter;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use futures_util::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpListener;
+
+/// Largest request body accepted (images in chat requests can be large)
+const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Anthropic API version sent to Anthropic upstreams when the client sets none
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// How often the primary endpoint is probed while failed over
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Headers that describe a single connection and must not be forwarded
+const HOP_BY_HOP: &[HeaderName] = &[
+    header::CONNECTION,
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("TLS error: {0}")]
+    Tls(#[from] ProxyTlsError),
+    #[error("TLS trust error: {0}")]
+    Trust(#[from] TlsTrustError),
+}
+
+/// Where requests go and with which token
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    /// Effective GhostLLM base URL (see `OpenCodeService::compute_base_url`)
+    pub base_url: String,
+    /// GhostLLM app the base URL points at, for usage metering
+    pub app: Option<String>,
+    /// Base URL of each app named in `proxy.routes`
+    pub app_base_urls: BTreeMap<String, String>,
+    /// Base URLs for `proxy.fallbackEndpoints`, in order
+    pub fallback_base_urls: Vec<String>,
+    pub token: String,
+    /// `llmHeaders`, added to every forwarded request
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Upstream {
+    /// The primary base URL followed by the fallbacks
+    pub fn base_urls(&self) -> Vec<String> {
+        std::iter::once(self.base_url.clone())
+            .chain(self.fallback_base_urls.iter().cloned())
+            .collect()
+    }
+}
+
+/// Current upstream, published by the token service and read per request.
+/// `None` while not authenticated.
+pub type SharedUpstream = Arc<RwLock<Option<Upstream>>>;
+
+/// Where a request goes
+struct Target {
+    base_url: String,
+    app: Option<String>,
+    api: UpstreamApi,
+    /// Whether this is the default upstream, which fails over
+    failover: bool,
+}
+
+struct ProxyState {
+    config: ProxyConfig,
+    upstream: SharedUpstream,
+    clients: HttpClients,
+    log: ProxyLog,
+    meter: UsageMeter,
+    limiter: RateLimiter,
+    failover: Failover,
+}
+
+/// Service for the local reverse proxy
+pub struct ProxyService;
+
+impl ProxyService {
+    /// Serve until the listener fails. Requests made while not authenticated
+    /// get a 503.
+    pub async fn serve(
+        app_config: AppConfig,
+        upstream: SharedUpstream,
+        log: ProxyLog,
+        meter: UsageMeter,
+        failover: Failover,
+    ) -> Result<(), ProxyError> {
+        // Same trust policy, host overrides and `http.llm` profile as the
+        // token service. No overall timeout, as streams run long.
+        let clients = HttpClients::for_streaming(&app_config)?;
+        let power_saving = app_config.power_saving;
+        let config = app_config.proxy;
+        let port = config.port;
+        let tls = if config.tls {
+            Some(ProxyTls::server_config(&config)?)
+        } else {
+            None
+        };
+        let limiter = RateLimiter::new(config.requests_per_minute, config.max_concurrent_requests);
+        if !config.fallback_endpoints.is_empty() {
+            tokio::spawn(check_primary(
+                clients.clone(),
+                upstream.clone(),
+                failover.clone(),
+                power_saving,
+            ));
+        }
+        let app = Router::new()
+            .fallback(forward)
+            .with_state(Arc::new(ProxyState {
+                config,
+                upstream,
+                clients,
+                log,
+                meter,
+                limiter,
+                failover,
+            }));
+
+        match tls {
+            Some(tls) => {
+                let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+                log::info!("Proxy listening on https://{}", addr);
+                axum_server::bind_rustls(addr, RustlsConfig::from_config(tls))
+                    .serve(app.into_make_service())
+                    .await?;
+            }
+            None => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+                log::info!("Proxy listening on http://{}", listener.local_addr()?);
+                axum::serve(listener, app).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// While failed over, probe the primary and fail back once it answers.
+/// Power saving stays on the fallback, which works, instead of probing.
+async fn check_primary(
+    clients: HttpClients,
+    upstream: SharedUpstream,
+    failover: Failover,
+    power_saving: PowerSavingConfig,
+) {
+    loop {
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        let upstream = upstream.read().map(|u| u.clone()).unwrap_or(None);
+        let Some(upstream) = upstream else {
+            continue;
+        };
+        let count = upstream.fallback_base_urls.len() + 1;
+        if failover.active(count) == 0 || PowerService::skip_verification(&power_saving) {
+            continue;
+        }
+        let url = upstream_url(&upstream.base_url, "/v1/models", None);
+        let Ok(client) = clients.for_url(&url) else {
+            continue;
+        };
+        let healthy = client
+            .get(&url)
+            .bearer_auth(&upstream.token)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .is_ok_and(|r| !r.status().is_server_error());
+        if healthy {
+            failover.fail_back(&upstream.base_url);
+        }
+    }
+}
+
+async fn forward(State(state): State<Arc<ProxyState>>, request: Request) -> Response {
+    if !is_local_request(&request) {
+        log::warn!(
+            "Proxy: {} {} refused: not addressed to localhost or sent from a web page elsewhere",
+            request.method(),
+            request.uri().path()
+        );
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Only local clients may use the Dymium proxy",
+        );
+    }
+
+    let upstream = state.upstream.read().map(|u| u.clone()).unwrap_or(None);
+    let Some(upstream) = upstream else {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Dymium Provider is not authenticated",
+        );
+    };
+
+    let admission = match state.limiter.admit() {
+        Ok(admission) => admission,
+        Err(rejection) => {
+            log::warn!(
+                "Proxy: {} {} rejected: {}",
+                request.method(),
+                request.uri().path(),
+                rejection.message()
+            );
+            let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, rejection.message());
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(rejection.retry_after()),
+            );
+            return response;
+        }
+    };
+
+    let (parts, body) = request.into_parts();
+    let body: Bytes = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    let (body, model) = apply_alias(&state.config, body);
+    let target = route(&state.config, &upstream, model.as_deref());
+
+    let direction = match (parts.uri.path(), target.api) {
+        ("/v1/messages", UpstreamApi::OpenAi) => Some(Direction::AnthropicToOpenAi),
+        ("/v1/chat/completions", UpstreamApi::Anthropic) => Some(Direction::OpenAiToAnthropic),
+        _ => None,
+    };
+    let (translation, body, path) = match direction.and_then(|d| Translation::request(d, &body)) {
+        Some((translation, translated)) => {
+            let path = translation.upstream_path().to_string();
+            (Some(translation), Bytes::from(translated), path)
+        }
+        None => (None, body, parts.uri.path().to_string()),
+    };
+
+    let mut tap = RequestTap::new(
+        state.config.log_requests.then(|| state.log.clone()),
+        state.meter.clone(),
+        target.app.clone(),
+        parts.method.as_str(),
+        &path,
+        &body,
+        state.config.log_bodies,
+    );
+
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    // reqwest sets Host and Content-Length for the upstream request
+    headers.remove(header::HOST);
+    headers.remove(header::CONTENT_LENGTH);
+    // Ask for an uncompressed body so its usage block can be read
+    headers.remove(header::ACCEPT_ENCODING);
+    // The client's placeholder key, as sent by Anthropic clients
+    headers.remove("x-api-key");
+    let Ok(token) = HeaderValue::from_str(&upstream.token) else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Invalid token");
+    };
+    let Ok(bearer) = HeaderValue::from_str(&format!("Bearer {}", upstream.token)) else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Invalid token");
+    };
+    headers.insert(header::AUTHORIZATION, bearer);
+    for (name, value) in &upstream.headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => log::warn!("Proxy: skipping invalid llmHeaders entry {}", name),
+        }
+    }
+    match target.api {
+        UpstreamApi::Anthropic => {
+            headers.insert("x-api-key", token);
+            if !headers.contains_key("anthropic-version") {
+                headers.insert(
+                    "anthropic-version",
+                    HeaderValue::from_static(ANTHROPIC_VERSION),
+                );
+            }
+        }
+        UpstreamApi::OpenAi => {
+            headers.remove("anthropic-version");
+            headers.remove("anthropic-beta");
+        }
+    }
+
+    // Requests to the default upstream fail over across the endpoints once
+    let base_urls = upstream.base_urls();
+    let failover = target.failover && base_urls.len() > 1;
+    let mut index = state.failover.active(base_urls.len());
+    let mut retried = false;
+    let (url, response) = loop {
+        let base_url = if failover {
+            &base_urls[index]
+        } else {
+            &target.base_url
+        };
+        let url = upstream_url(base_url, &path, parts.uri.query());
+        let client = match state.clients.for_url(&url) {
+            Ok(client) => client,
+            Err(e) => {
+                log::warn!("Proxy request to {} refused: {}", url, e);
+                tap.failed(StatusCode::FORBIDDEN.as_u16(), &e.to_string());
+                return error_response(StatusCode::FORBIDDEN, &e.to_string());
+            }
+        };
+        let response = client
+            .request(parts.method.clone(), &url)
+            .headers(headers.clone())
+            .body(body.clone())
+            .send()
+            .await;
+        let failed = match &response {
+            Ok(response) => response.status().is_server_error(),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+        if failover && failed && !retried {
+            index = state.failover.report_failure(index, &base_urls);
+            retried = true;
+            continue;
+        }
+        break (url, response);
+    };
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            let message = certificate_error(&e)
+                .map(|failure| failure.to_string())
+                .unwrap_or_else(|| e.to_string());
+            log::warn!("Proxy request to {} failed: {}", url, message);
+            tap.failed(StatusCode::BAD_GATEWAY.as_u16(), &message);
+            return error_response(
+                StatusCode::BAD_GATEWAY,
+                &format!("Upstream error: {}", message),
+            );
+        }
+    };
+
+    let status = response.status();
+    let mut headers = response.headers().clone();
+    strip_hop_by_hop(&mut headers);
+    tap.response(status.as_u16());
+    // The tap and admission live as long as the stream: the request counts as
+    // in flight until then, and is recorded when it ends
+    let stream = response.bytes_stream().inspect(move |chunk| {
+        let _ = &admission;
+        if let Ok(chunk) = chunk {
+            tap.chunk(chunk);
+        }
+    });
+
+    let body = match translation {
+        None => Body::from_stream(stream),
+        Some(translation) => {
+            headers.remove(header::CONTENT_LENGTH);
+            if translation.is_stream() && status.is_success() {
+                let translator = translation.stream_translator();
+                Body::from_stream(translate_stream(stream, translator))
+            } else {
+                let mut stream = Box::pin(stream);
+                let mut buffer = Vec::new();
+                while let Some(chunk) = stream.next().await {
+                    match chunk {
+                        Ok(chunk) => buffer.extend_from_slice(&chunk),
+                        Err(e) => {
+                            return error_response(
+                                StatusCode::BAD_GATEWAY,
+                                &format!("Upstream error: {}", e),
+                            )
+                        }
+                    }
+                }
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                Body::from(translation.response(status.is_success(), &buffer))
+            }
+        }
+    };
+
+    let mut out = Response::new(body);
+    *out.status_mut() = status;
+    *out.headers_mut() = headers;
+    out
+}
+
+/// Run a streamed response through a translator, flushing it at the end
+fn translate_stream<S>(
+    stream: S,
+    translator: StreamTranslator,
+) -> impl Stream<Item = Result<Bytes, reqwest::Error>>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+{
+    futures_util::stream::unfold(Some((Box::pin(stream), translator)), |state| async move {
+        let (mut stream, mut translator) = state?;
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                let translated = Bytes::from(translator.feed(&chunk));
+                Some((Ok(translated), Some((stream, translator))))
+            }
+            Some(Err(e)) => Some((Err(e), None)),
+            None => Some((Ok(Bytes::from(translator.finish())), None)),
+        }
+    })
+}
+
+/// Rewrite an aliased `model` in a JSON request body. Returns the body to
+/// forward and the model it asks for.
+fn apply_alias(config: &ProxyConfig, body: Bytes) -> (Bytes, Option<String>) {
+    let Ok(mut request) = serde_json::from_slice::<Value>(&body) else {
+        return (body, None);
+    };
+    let Some(model) = request.get("model").and_then(Value::as_str) else {
+        return (body, None);
+    };
+    let Some(alias) = config.model_aliases.get(model) else {
+        let model = model.to_string();
+        return (body, Some(model));
+    };
+
+    log::debug!("Proxy: model {} -> {}", model, alias);
+    request["model"] = Value::String(alias.clone());
+    match serde_json::to_vec(&request) {
+        Ok(rewritten) => (Bytes::from(rewritten), Some(alias.clone())),
+        Err(_) => (body, Some(alias.clone())),
+    }
+}
+
+/// Base URL, GhostLLM app and API for a request, from the first matching route
+fn route(config: &ProxyConfig, upstream: &Upstream, model: Option<&str>) -> Target {
+    let default = Target {
+        base_url: upstream.base_url.clone(),
+        app: upstream.app.clone(),
+        api: UpstreamApi::OpenAi,
+        failover: true,
+    };
+    let Some(route) = model.and_then(|m| config.routes.iter().find(|r| r.matches(m))) else {
+        return default;
+    };
+    let base_url = route.base_url.clone().or_else(|| {
+        route
+            .app
+            .as_ref()
+            .and_then(|a| upstream.app_base_urls.get(a).cloned())
+    });
+    match base_url {
+        Some(base_url) => Target {
+            base_url,
+            app: route.app.clone(),
+            api: route.api,
+            failover: false,
+        },
+        None => Target {
+            api: route.api,
+            ..default
+        },
+    }
+}
+
+/// Upstream URL for a local request path. Clients use `/v1/...` paths, so a
+/// trailing `/v1` on the base URL is dropped before appending.
+fn upstream_url(base_url: &str, path: &str, query: Option<&str>) -> String {
+    let base = base_url.trim_end_matches('/');
+    let base = base.strip_suffix("/v1").unwrap_or(base);
+    match query {
+        Some(query) => format!("{}{}?{}", base, path, query),
+        None => format!("{}{}", base, path),
+    }
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP {
+        headers.remove(name);
+    }
+}
+
+/// Whether `request` is addressed to 127.0.0.1 or localhost and, if a browser
+/// sent it, comes from a local page. Otherwise any web page could point its
+/// own name at 127.0.0.1 (DNS rebinding) and use the proxy, and the user's
+/// token, while reading the responses.
+fn is_local_request(request: &Request) -> bool {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().authority().map(|a| a.as_str()));
+    if !host.is_some_and(is_local_authority) {
+        return false;
+    }
+    match request.headers().get(header::ORIGIN) {
+        None => true,
+        Some(origin) => origin
+            .to_str()
+            .ok()
+            .and_then(|origin| origin.split_once("://"))
+            .is_some_and(|(scheme, authority)| {
+                matches!(scheme, "http" | "https") && is_local_authority(authority)
+            }),
+    }
+}
+
+/// `127.0.0.1` or `localhost`, with or without a port
+fn is_local_authority(authority: &str) -> bool {
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    host == "127.0.0.1" || host.eq_ignore_ascii_case("localhost")
+}
+
+/// Error in the OpenAI error shape, so clients surface the message
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = json!({ "error": { "message": message, "type": "dymium_proxy_error" } });
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: Option<&str>, origin: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri("/v1/models");
+        if let Some(host) = host {
+            builder = builder.header(header::HOST, host);
+        }
+        if let Some(origin) = origin {
+            builder = builder.header(header::ORIGIN, origin);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn local_authorities() {
+        assert!(is_local_authority("127.0.0.1"));
+        assert!(is_local_authority("127.0.0.1:8787"));
+        assert!(is_local_authority("localhost"));
+        assert!(is_local_authority("LocalHost:8787"));
+        assert!(!is_local_authority("127.0.0.1.evil.com"));
+        assert!(!is_local_authority("127.0.0.1.evil.com:8787"));
+        assert!(!is_local_authority("localhost.evil.com"));
+        assert!(!is_local_authority("evil.com:8787"));
+        assert!(!is_local_authority("127.0.0.1:port"));
+        assert!(!is_local_authority(""));
+    }
+
+    #[test]
+    fn local_host_without_origin_is_allowed() {
+        assert!(is_local_request(&request(Some("localhost:8787"), None)));
+        assert!(is_local_request(&request(Some("127.0.0.1:8787"), None)));
+    }
+
+    #[test]
+    fn non_local_host_is_refused() {
+        assert!(!is_local_request(&request(Some("evil.com:8787"), None)));
+        assert!(!is_local_request(&request(
+            Some("127.0.0.1.evil.com"),
+            None
+        )));
+        assert!(!is_local_request(&request(None, None)));
+    }
+
+    #[test]
+    fn origin_must_be_local() {
+        assert!(is_local_request(&request(
+            Some("127.0.0.1:8787"),
+            Some("http://localhost:1420")
+        )));
+        assert!(!is_local_request(&request(
+            Some("127.0.0.1:8787"),
+            Some("https://evil.com")
+        )));
+        assert!(!is_local_request(&request(
+            Some("127.0.0.1:8787"),
+            Some("http://127.0.0.1.evil.com")
+        )));
+        assert!(!is_local_request(&request(
+            Some("127.0.0.1:8787"),
+            Some("null")
+        )));
+    }
+}
@@ -0,0 +1,227 @@
+//! Request log for the local proxy
+//!
+//! With `proxy.logRequests` set, every proxied request is recorded (model,
+//! status, latency, token usage) in an in-memory ring buffer the UI reads via
+//! `get_proxy_log`. Prompt and completion content are left out unless
+//! `proxy.logBodies` is also set. Usage is metered (see `usage`) either way.
+
+use crate::services::usage::UsageMeter;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Entries kept in memory
+const CAPACITY: usize = 200;
+/// Most response bytes kept for usage parsing and body logging
+const MAX_CAPTURE: usize = 1024 * 1024;
+
+/// Token counts from the response's `usage` block
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// One proxied request
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
+    /// `model` from the request body
+    pub model: Option<String>,
+    pub stream: bool,
+    /// Upstream status (502 if GhostLLM could not be reached)
+    pub status: u16,
+    /// Time until the response headers arrived
+    pub latency_ms: u64,
+    /// Time until the response body was fully sent (or the client hung up)
+    pub duration_ms: u64,
+    pub usage: Option<Usage>,
+    pub error: Option<String>,
+    /// Only with `proxy.logBodies`
+    pub request_body: Option<String>,
+    /// Only with `proxy.logBodies`
+    pub response_body: Option<String>,
+}
+
+/// Shared ring buffer of recent requests
+#[derive(Clone, Default)]
+pub struct ProxyLog {
+    entries: Arc<Mutex<VecDeque<ProxyLogEntry>>>,
+}
+
+impl ProxyLog {
+    /// Recent entries, oldest first
+    pub fn entries(&self) -> Vec<ProxyLogEntry> {
+        self.entries
+            .lock()
+            .map(|e| e.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop all entries
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    fn push(&self, entry: ProxyLogEntry) {
+        log::info!(
+            "Proxy: {} {} model={} status={} {}ms{}",
+            entry.method,
+            entry.path,
+            entry.model.as_deref().unwrap_or("-"),
+            entry.status,
+            entry.duration_ms,
+            entry
+                .usage
+                .map(|u| format!(" tokens={}+{}", u.prompt_tokens, u.completion_tokens))
+                .unwrap_or_default()
+        );
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() == CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+}
+
+/// A request being recorded. Feed it the response body as it streams; usage
+/// is metered and the entry logged when the tap is dropped, i.e. once the body
+/// is done.
+pub struct RequestTap {
+    /// `None` unless `proxy.logRequests` is set
+    log: Option<ProxyLog>,
+    meter: UsageMeter,
+    app: Option<String>,
+    entry: ProxyLogEntry,
+    started: Instant,
+    log_bodies: bool,
+    captured: Vec<u8>,
+    truncated: bool,
+}
+
+impl RequestTap {
+    /// Start recording a request
+    pub fn new(
+        log: Option<ProxyLog>,
+        meter: UsageMeter,
+        app: Option<String>,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        log_bodies: bool,
+    ) -> Self {
+        let log_bodies = log_bodies && log.is_some();
+        let request: Option<Value> = serde_json::from_slice(body).ok();
+        let field = |key: &str| request.as_ref().and_then(|r| r.get(key));
+        Self {
+            log,
+            meter,
+            app,
+            entry: ProxyLogEntry {
+                timestamp: Utc::now(),
+                method: method.to_string(),
+                path: path.to_string(),
+                model: field("model").and_then(Value::as_str).map(str::to_string),
+                stream: field("stream").and_then(Value::as_bool).unwrap_or(false),
+                status: 0,
+                latency_ms: 0,
+                duration_ms: 0,
+                usage: None,
+                error: None,
+                request_body: log_bodies.then(|| String::from_utf8_lossy(body).into_owned()),
+                response_body: None,
+            },
+            started: Instant::now(),
+            log_bodies,
+            captured: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// The response headers arrived
+    pub fn response(&mut self, status: u16) {
+        self.entry.status = status;
+        self.entry.latency_ms = self.started.elapsed().as_millis() as u64;
+    }
+
+    /// The request failed before a response arrived
+    pub fn failed(&mut self, status: u16, error: &str) {
+        self.response(status);
+        self.entry.error = Some(error.to_string());
+    }
+
+    /// A chunk of the response body was sent to the client
+    pub fn chunk(&mut self, chunk: &Bytes) {
+        if self.captured.len() + chunk.len() <= MAX_CAPTURE {
+            self.captured.extend_from_slice(chunk);
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl Drop for RequestTap {
+    fn drop(&mut self) {
+        self.entry.duration_ms = self.started.elapsed().as_millis() as u64;
+        let body = String::from_utf8_lossy(&self.captured);
+        if !self.truncated {
+            self.entry.usage = parse_usage(&body);
+        }
+        let success = self.entry.error.is_none() && self.entry.status < 400;
+        self.meter.record(
+            self.app.as_deref(),
+            self.entry.model.as_deref(),
+            success,
+            self.entry.usage.unwrap_or_default(),
+        );
+
+        if let Some(log) = &self.log {
+            if self.log_bodies {
+                self.entry.response_body = Some(body.into_owned());
+            }
+            log.push(self.entry.clone());
+        }
+    }
+}
+
+/// `usage` from a JSON response, or from the last SSE event that carries one
+/// (sent when the client asks for `stream_options.include_usage`)
+fn parse_usage(body: &str) -> Option<Usage> {
+    let from_json = |v: &Value| {
+        let usage = v.get("usage")?;
+        let count = |key: &str| usage.get(key).and_then(Value::as_u64);
+        // OpenAI names, or Anthropic's for routes that speak its API
+        let prompt = count("prompt_tokens")
+            .or_else(|| count("input_tokens"))
+            .unwrap_or(0);
+        let completion = count("completion_tokens")
+            .or_else(|| count("output_tokens"))
+            .unwrap_or(0);
+        Some(Usage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: count("total_tokens").unwrap_or(prompt + completion),
+        })
+    };
+
+    if let Ok(value) = serde_json::from_str::<Value>(body) {
+        return from_json(&value);
+    }
+    body.lines()
+        .rev()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .find_map(|event| from_json(&event))
+}
@@ -0,0 +1,171 @@
+//! HTTPS for the local proxy
+//!
+//! Some IDE clients refuse plain-HTTP endpoints, even on localhost. With
+//! `proxy.tls` set, the proxy serves HTTPS using either the certificate and key
+//! at `proxy.tlsCertPath` / `proxy.tlsKeyPath`, or a self-signed certificate
+//! for localhost / 127.0.0.1 generated into ~/.dymium/proxy-cert.pem (key in
+//! proxy-key.pem, mode 0600). `trust_certificate` adds the generated
+//! certificate to the user's trust store.
+
+use crate::services::config::{AppConfig, ConfigError, ProxyConfig};
+use crate::services::files;
+use chrono::{Datelike, Utc};
+use rcgen::{date_time_ymd, CertificateParams, DnType, KeyPair};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Validity of generated certificates. macOS rejects server certificates
+/// valid for longer than 825 days.
+const VALIDITY_DAYS: i64 = 825;
+/// Generated certificates are replaced when older than this
+const RENEW_AFTER_DAYS: u64 = 800;
+
+#[derive(Error, Debug)]
+pub enum ProxyTlsError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Certificate generation failed: {0}")]
+    GenerateError(#[from] rcgen::Error),
+    #[error("Invalid certificate or key: {0}")]
+    PemError(#[from] rustls::pki_types::pem::Error),
+    #[error("TLS error: {0}")]
+    TlsError(#[from] rustls::Error),
+    #[error("Set both tlsCertPath and tlsKeyPath, or neither")]
+    IncompletePaths,
+    #[error("Failed to trust certificate: {0}")]
+    TrustFailed(String),
+}
+
+/// Service for the proxy's TLS certificate
+pub struct ProxyTls;
+
+impl ProxyTls {
+    /// Generated certificate path (~/.dymium/proxy-cert.pem)
+    pub fn cert_path() -> Result<PathBuf, ProxyTlsError> {
+        Ok(AppConfig::config_dir()?.join("proxy-cert.pem"))
+    }
+
+    /// Generated key path (~/.dymium/proxy-key.pem)
+    pub fn key_path() -> Result<PathBuf, ProxyTlsError> {
+        Ok(AppConfig::config_dir()?.join("proxy-key.pem"))
+    }
+
+    /// rustls config for the configured or generated certificate
+    pub fn server_config(config: &ProxyConfig) -> Result<Arc<ServerConfig>, ProxyTlsError> {
+        let (cert_path, key_path) = match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+            (None, None) => Self::ensure_generated()?,
+            _ => return Err(ProxyTlsError::IncompletePaths),
+        };
+
+        let certs = CertificateDer::pem_file_iter(&cert_path)?.collect::<Result<Vec<_>, _>>()?;
+        let key = PrivateKeyDer::from_pem_file(&key_path)?;
+        // Both ring (via reqwest) and aws-lc-rs are compiled in, so there is no
+        // process-wide default provider to fall back on
+        let server_config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_no_client_auth()
+                .with_single_cert(certs, key)?;
+        Ok(Arc::new(server_config))
+    }
+
+    /// Generated certificate and key, created (or renewed) if needed
+    fn ensure_generated() -> Result<(PathBuf, PathBuf), ProxyTlsError> {
+        let cert_path = Self::cert_path()?;
+        let key_path = Self::key_path()?;
+        let fresh = fs::metadata(&cert_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < Duration::from_secs(RENEW_AFTER_DAYS * 24 * 60 * 60));
+        if fresh && key_path.exists() {
+            return Ok((cert_path, key_path));
+        }
+
+        let mut params =
+            CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Dymium Provider local proxy");
+        let date =
+            |d: chrono::DateTime<Utc>| date_time_ymd(d.year(), d.month() as u8, d.day() as u8);
+        let now = Utc::now();
+        params.not_before = date(now - chrono::Duration::days(1));
+        params.not_after = date(now + chrono::Duration::days(VALIDITY_DAYS));
+
+        let key_pair = KeyPair::generate()?;
+        let cert = params.self_signed(&key_pair)?;
+
+        files::write_atomic(&key_path, key_pair.serialize_pem().as_bytes())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600))?;
+        }
+        files::write_atomic(&cert_path, cert.pem().as_bytes())?;
+        log::info!("Generated proxy certificate {}", cert_path.display());
+        Ok((cert_path, key_path))
+    }
+
+    /// Add the generated certificate to the user's trust store. The OS asks
+    /// the user to confirm.
+    pub fn trust_certificate() -> Result<(), ProxyTlsError> {
+        let (cert_path, _) = Self::ensure_generated()?;
+        let cert = cert_path.display().to_string();
+
+        let mut command = if cfg!(target_os = "macos") {
+            let keychain = dirs::home_dir()
+                .map(|h| h.join("Library/Keychains/login.keychain-db"))
+                .ok_or_else(|| ProxyTlsError::TrustFailed("Home directory not found".into()))?;
+            let mut c = Command::new("security");
+            c.args(["add-trusted-cert", "-r", "trustRoot", "-k"])
+                .arg(keychain)
+                .arg(&cert);
+            c
+        } else if cfg!(windows) {
+            let mut c = Command::new("certutil");
+            c.args(["-user", "-addstore", "Root"]).arg(&cert);
+            c
+        } else {
+            // No per-user system store on Linux; the NSS database covers
+            // Chromium- and Electron-based clients
+            let nssdb = dirs::home_dir()
+                .map(|h| format!("sql:{}", h.join(".pki/nssdb").display()))
+                .ok_or_else(|| ProxyTlsError::TrustFailed("Home directory not found".into()))?;
+            let mut c = Command::new("certutil");
+            c.args([
+                "-d",
+                &nssdb,
+                "-A",
+                "-t",
+                "C,,",
+                "-n",
+                "Dymium Provider local proxy",
+                "-i",
+            ])
+            .arg(&cert);
+            c
+        };
+
+        let output = command
+            .output()
+            .map_err(|e| ProxyTlsError::TrustFailed(e.to_string()))?;
+        if !output.status.success() {
+            return Err(ProxyTlsError::TrustFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        log::info!("Trusted proxy certificate {}", cert);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! Client-side rate limiting for the local proxy
+//!
+//! Caps requests per minute (sliding window) and requests in flight, so a
+//! runaway agent loop is turned away locally with a 429 before it burns
+//! through GhostLLM quotas.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy)]
+pub enum Rejection {
+    /// Per-minute limit reached; retry after this many seconds
+    RequestsPerMinute { retry_after: u64 },
+    /// Too many requests in flight
+    Concurrency,
+}
+
+impl Rejection {
+    /// Seconds for the Retry-After header
+    pub fn retry_after(&self) -> u64 {
+        match self {
+            Self::RequestsPerMinute { retry_after } => *retry_after,
+            Self::Concurrency => 1,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::RequestsPerMinute { .. } => "Local rate limit reached (proxy.requestsPerMinute)",
+            Self::Concurrency => "Too many concurrent requests (proxy.maxConcurrentRequests)",
+        }
+    }
+}
+
+/// Held for the lifetime of an admitted request
+pub struct Admission {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Request limits; 0 disables a limit
+pub struct RateLimiter {
+    requests_per_minute: usize,
+    recent: Mutex<VecDeque<Instant>>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, max_concurrent: u32) -> Self {
+        Self {
+            requests_per_minute: requests_per_minute as usize,
+            recent: Mutex::new(VecDeque::new()),
+            in_flight: (max_concurrent > 0)
+                .then(|| Arc::new(Semaphore::new(max_concurrent as usize))),
+        }
+    }
+
+    /// Admit a request, or say why not. Keep the admission until the response
+    /// body is done so it counts as in flight.
+    pub fn admit(&self) -> Result<Admission, Rejection> {
+        let permit = match &self.in_flight {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| Rejection::Concurrency)?,
+            ),
+            None => None,
+        };
+
+        if self.requests_per_minute > 0 {
+            let now = Instant::now();
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            while recent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+            {
+                recent.pop_front();
+            }
+            if recent.len() >= self.requests_per_minute {
+                let oldest = recent.front().copied().unwrap_or(now);
+                let wait = WINDOW.saturating_sub(now.duration_since(oldest));
+                return Err(Rejection::RequestsPerMinute {
+                    retry_after: wait.as_secs_f64().ceil().max(1.0) as u64,
+                });
+            }
+            recent.push_back(now);
+        }
+
+        Ok(Admission { _permit: permit })
+    }
+}
@@ -0,0 +1,237 @@
+//! Secret scrubbing
+//!
+//! Masks JWTs, bearer tokens, long key-like strings and the values of
+//! `password=`/`"secret": ...` style pairs. `redact_secrets` keeps URLs
+//! (minus credentials and query strings) so log lines stay useful;
+//! `redact` also hides URLs, for text that leaves the machine. Best effort:
+//! a safety net, not a guarantee.
+
+/// Words whose value follows a `=` or `:`
+const SECRET_KEYS: &[&str] = &[
+    "password",
+    "secret",
+    "token",
+    "apikey",
+    "api_key",
+    "authorization",
+];
+
+/// Keys common in prose ("refresh token: IO error"); their `:` only counts
+/// when quoted, as in JSON
+const GENERIC_KEYS: &[&str] = &["token", "secret"];
+
+/// Key-like strings at least this long are redacted
+const MIN_KEY_LEN: usize = 32;
+
+/// What the previous word implies for the next one
+#[derive(Clone, Copy, PartialEq)]
+enum Pending {
+    None,
+    /// After "Bearer"
+    Value,
+    /// After a secret key, until a `=` or (where allowed) `:` assigns it
+    Key {
+        assigned: bool,
+        colon: bool,
+    },
+}
+
+/// `text` with secrets and URLs replaced, for reports sent elsewhere
+pub fn redact(text: &str) -> String {
+    scrub(text, false)
+}
+
+/// `text` with secrets replaced, keeping URLs without credentials or query
+pub fn redact_secrets(text: &str) -> String {
+    scrub(text, true)
+}
+
+fn scrub(text: &str, keep_urls: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut pending = Pending::None;
+    let mut quoted = false;
+    while let Some(c) = rest.chars().next() {
+        if let Some(len) = url_len(rest) {
+            if keep_urls {
+                out.push_str(&strip_url(&rest[..len]));
+            } else {
+                out.push_str("[url]");
+            }
+            rest = &rest[len..];
+            pending = Pending::None;
+            continue;
+        }
+        if !is_word_char(c) {
+            pending = match pending {
+                Pending::Key { colon, .. } if c == '=' || (c == ':' && colon) => Pending::Key {
+                    assigned: true,
+                    colon,
+                },
+                Pending::Key { .. } | Pending::Value
+                    if c.is_whitespace() || c == '"' || c == '\'' =>
+                {
+                    pending
+                }
+                _ => Pending::None,
+            };
+            quoted = c == '"' || c == '\'';
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+        let word = &rest[..len];
+        rest = &rest[len..];
+        if matches!(
+            pending,
+            Pending::Value | Pending::Key { assigned: true, .. }
+        ) {
+            // "Authorization: Bearer <token>": the scheme isn't the secret
+            if matches!(pending, Pending::Key { .. }) && word.eq_ignore_ascii_case("bearer") {
+                out.push_str(word);
+                pending = Pending::Value;
+                continue;
+            }
+            out.push_str("[redacted]");
+            pending = Pending::None;
+            continue;
+        }
+
+        if let Some((key, value)) = word.split_once('=') {
+            if !value.is_empty() && is_secret_key(&key.to_ascii_lowercase()) {
+                out.push_str(key);
+                out.push_str("=[redacted]");
+                pending = Pending::None;
+                continue;
+            }
+        }
+        if looks_like_secret(word) {
+            out.push_str("[redacted]");
+            pending = Pending::None;
+            continue;
+        }
+
+        out.push_str(word);
+        let lower = word.to_ascii_lowercase();
+        pending = if lower == "bearer" {
+            Pending::Value
+        } else if is_secret_key(&lower) {
+            Pending::Key {
+                assigned: false,
+                colon: quoted || !GENERIC_KEYS.contains(&lower.as_str()),
+            }
+        } else {
+            Pending::None
+        };
+    }
+    out
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | '/' | '=' | '~')
+}
+
+fn is_secret_key(word: &str) -> bool {
+    SECRET_KEYS.iter().any(|key| word.ends_with(key))
+}
+
+/// JWTs and long mixed letter/digit strings such as API keys
+fn looks_like_secret(word: &str) -> bool {
+    let word = word.trim_end_matches('.');
+    if word.starts_with("eyJ") && word.matches('.').count() >= 2 {
+        return true;
+    }
+    word.len() >= MIN_KEY_LEN
+        && !word.contains('/')
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// Length of the URL `text` starts with, if any
+fn url_len(text: &str) -> Option<usize> {
+    let scheme = text
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|&len| len > 0)?;
+    if !text[scheme..].starts_with("://") {
+        return None;
+    }
+    let end = text[scheme + 3..]
+        .find(|c: char| {
+            c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | ')' | ']' | '}' | ',')
+        })
+        .map_or(text.len(), |len| scheme + 3 + len);
+    Some(end)
+}
+
+/// `url` without `user:password@` and with the query replaced
+fn strip_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, _)) => (rest, "?[redacted]"),
+        None => (rest, ""),
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let rest = match rest[..authority_end].rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    format!("{}://{}{}", scheme, rest, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_assigned_values() {
+        assert_eq!(redact("password=hunter2 ok"), "password=[redacted] ok");
+        assert_eq!(redact("db_password: hunter2"), "db_password: [redacted]");
+        assert_eq!(
+            redact(r#"{"secret": "abc", "name": "x"}"#),
+            r#"{"secret": "[redacted]", "name": "x"}"#
+        );
+    }
+
+    #[test]
+    fn masks_bearer_tokens() {
+        assert_eq!(redact("Bearer abc123"), "Bearer [redacted]");
+        assert_eq!(
+            redact("Authorization: Bearer abc123"),
+            "Authorization: Bearer [redacted]"
+        );
+    }
+
+    #[test]
+    fn masks_jwts_and_keys() {
+        assert_eq!(
+            redact("got eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln"),
+            "got [redacted]"
+        );
+        assert_eq!(
+            redact("key sk1234567890abcdefghijklmnopqrstuvwxyz"),
+            "key [redacted]"
+        );
+    }
+
+    #[test]
+    fn leaves_prose_alone() {
+        let text = "refresh token: IO error at café ✓ (retry 3)";
+        assert_eq!(redact(text), text);
+        assert_eq!(
+            redact_secrets("/usr/local/share/dymium-provider"),
+            "/usr/local/share/dymium-provider"
+        );
+    }
+
+    #[test]
+    fn urls_are_stripped_or_hidden() {
+        let text = "GET https://user:pw@host.example/v1/models?code=xyz failed";
+        assert_eq!(
+            redact_secrets(text),
+            "GET https://host.example/v1/models?[redacted] failed"
+        );
+        assert_eq!(redact(text), "GET [url] failed");
+    }
+}
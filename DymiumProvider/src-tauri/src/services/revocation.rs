@@ -0,0 +1,126 @@
+//! Remote kill switch
+//!
+//! With `revocation.url` set, the app asks that URL every
+//! `revocation.intervalMinutes` whether this client has been disabled, e.g.
+//! because the laptop was lost. The request is a `GET` with the current
+//! token as `Authorization: Bearer` (when there is one) and this install's
+//! ID as `X-Dymium-Client-Id`; the ID is generated once into
+//! ~/.dymium/client-id. An answer of `{"disabled": true, "message": "..."}`
+//! wipes the tokens and blocks authentication until a later answer says
+//! otherwise. The block is remembered in ~/.dymium/revoked, so restarting
+//! the app doesn't lift it. Failed checks change nothing.
+
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::tls_trust::{HttpClients, PlainHttpRefused};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RevocationError {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("{0}")]
+    PlainHttp(#[from] PlainHttpRefused),
+    #[error("Revocation check answered HTTP {0}")]
+    Status(u16),
+}
+
+/// Answer of the revocation URL
+#[derive(Debug, Deserialize)]
+struct Verdict {
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Service for polling the kill switch
+pub struct RevocationService;
+
+impl RevocationService {
+    fn client_id_path() -> Result<PathBuf, RevocationError> {
+        Ok(AppConfig::config_dir()?.join("client-id"))
+    }
+
+    fn marker_path() -> Result<PathBuf, RevocationError> {
+        Ok(AppConfig::config_dir()?.join("revoked"))
+    }
+
+    /// This install's ID, generated on first use
+    pub fn client_id() -> Result<String, RevocationError> {
+        let path = Self::client_id_path()?;
+        if let Ok(id) = fs::read_to_string(&path) {
+            let id = id.trim();
+            if !id.is_empty() {
+                return Ok(id.to_string());
+            }
+        }
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let id: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &id)?;
+        log::info!("Generated client ID {}", id);
+        Ok(id)
+    }
+
+    /// Why this client is blocked, if it is
+    pub fn blocked() -> Option<String> {
+        let path = Self::marker_path().ok()?;
+        fs::read_to_string(path).ok()
+    }
+
+    /// Remember (`Some`) or lift (`None`) the block
+    pub fn set_blocked(message: Option<&str>) -> Result<(), RevocationError> {
+        let path = Self::marker_path()?;
+        match message {
+            Some(message) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, message)?;
+            }
+            None => match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+
+    /// Ask `url` whether this client is disabled. Returns the admin's message
+    /// if it is.
+    pub async fn check(
+        clients: &HttpClients,
+        url: &str,
+        token: Option<&str>,
+    ) -> Result<Option<String>, RevocationError> {
+        let mut request = clients
+            .for_url(url)?
+            .get(url)
+            .header("X-Dymium-Client-Id", Self::client_id()?);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(RevocationError::Status(response.status().as_u16()));
+        }
+        let verdict: Verdict = response.json().await?;
+        Ok(verdict.disabled.then(|| {
+            verdict
+                .message
+                .unwrap_or_else(|| "This client was disabled by your administrator".to_string())
+        }))
+    }
+}
@@ -0,0 +1,147 @@
+//! Roo Code integration
+//!
+//! Roo Code keeps provider credentials in VS Code's secret storage, which other
+//! processes can't write. It does, however, import a settings export at
+//! startup from the path in its `roo-cline.autoImportSettingsPath` setting. We
+//! keep such an export at ~/.dymium/roo-code-settings.json — a "dymium"
+//! OpenAI-compatible profile with the current token — and point the setting at
+//! it. Cline has no equivalent hook, so it isn't supported.
+
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::jsonc;
+use crate::services::opencode::OpenCodeService;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// VS Code setting naming the file Roo Code imports at startup
+const IMPORT_SETTING: &str = "roo-cline.autoImportSettingsPath";
+/// Provider profile name in Roo Code
+const PROFILE_NAME: &str = "dymium";
+
+#[derive(Error, Debug)]
+pub enum RooCodeError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Config directory not found")]
+    NoConfigDir,
+}
+
+/// Service for managing the Roo Code settings import
+pub struct RooCodeService;
+
+impl RooCodeService {
+    /// Settings export Roo Code imports (~/.dymium/roo-code-settings.json)
+    pub fn export_path() -> Result<PathBuf, RooCodeError> {
+        Ok(AppConfig::config_dir()?.join("roo-code-settings.json"))
+    }
+
+    /// VS Code user settings (platform config dir/Code/User/settings.json)
+    pub fn vscode_settings_path() -> Result<PathBuf, RooCodeError> {
+        dirs::config_dir()
+            .map(|p| p.join("Code").join("User").join("settings.json"))
+            .ok_or(RooCodeError::NoConfigDir)
+    }
+
+    /// Write the Dymium profile with the current token and make sure VS Code
+    /// points Roo Code at it. The first model is used as the profile's model.
+    pub fn sync(config: &AppConfig, token: &str, model_ids: &[String]) -> Result<(), RooCodeError> {
+        let Some(model_id) = model_ids.first() else {
+            log::debug!("No GhostLLM models known yet; leaving Roo Code settings alone");
+            return Ok(());
+        };
+
+        let export_path = Self::export_path()?;
+        let export = json!({
+            "providerProfiles": {
+                "currentApiConfigName": PROFILE_NAME,
+                "apiConfigs": {
+                    PROFILE_NAME: {
+                        "id": PROFILE_NAME,
+                        "apiProvider": "openai",
+                        "openAiBaseUrl": OpenCodeService::compute_base_url(config),
+                        "openAiApiKey": token,
+                        "openAiModelId": model_id,
+                    }
+                }
+            }
+        });
+        let content = serde_json::to_string_pretty(&export)?;
+        if files::read_optional(&export_path)?.as_deref() != Some(content.as_str()) {
+            files::write_atomic(&export_path, content.as_bytes())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&export_path, fs::Permissions::from_mode(0o600))?;
+            }
+            log::info!("Updated {}", export_path.display());
+        }
+
+        let path = export_path.to_string_lossy().into_owned();
+        Self::set_import_setting(Some(&path))
+    }
+
+    /// Remove the settings export and the VS Code setting (on log out / mode switch)
+    pub fn clear() {
+        if let Err(e) = Self::do_clear() {
+            log::error!("Failed to clear Roo Code settings: {}", e);
+        }
+    }
+
+    fn do_clear() -> Result<(), RooCodeError> {
+        let export_path = Self::export_path()?;
+        if export_path.exists() {
+            fs::remove_file(&export_path)?;
+            log::info!("Removed {}", export_path.display());
+        }
+        Self::set_import_setting(None)
+    }
+
+    /// Set (or with `None`, remove) the import setting in VS Code's settings.json
+    fn set_import_setting(value: Option<&str>) -> Result<(), RooCodeError> {
+        let settings_path = Self::vscode_settings_path()?;
+        if jsonc::update_file(
+            &settings_path,
+            &[(&[IMPORT_SETTING], value.map(|v| json!(v)))],
+        )? {
+            log::info!("Updated {} in {}", IMPORT_SETTING, settings_path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Integration for RooCodeService {
+    fn id(&self) -> &'static str {
+        "rooCode"
+    }
+
+    fn name(&self) -> &'static str {
+        "Roo Code"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        [Self::export_path(), Self::vscode_settings_path()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "the dymium provider profile and roo-cline.autoImportSettingsPath"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.token, &ctx.model_ids())?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
@@ -0,0 +1,60 @@
+//! Screen lock detection
+//!
+//! Polled by the idle lock (`idleLock.onScreenLock`):
+//! - macOS: `CGSSessionScreenIsLocked` in the `ioreg` root entry
+//! - Windows: whether the lock screen (LogonUI.exe) is running
+//! - Linux: the session's `LockedHint` from systemd-logind
+//!
+//! Anything that can't be determined counts as unlocked.
+
+use std::process::Command;
+
+/// Service for checking whether the user's screen is locked
+pub struct ScreenLock;
+
+impl ScreenLock {
+    /// Whether the screen is locked right now
+    pub fn is_locked() -> bool {
+        platform::is_locked()
+    }
+}
+
+/// Stdout of `program args`, if it ran successfully
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    pub fn is_locked() -> bool {
+        super::output("ioreg", &["-n", "Root", "-d1"])
+            .is_some_and(|out| out.contains("\"CGSSessionScreenIsLocked\"=Yes"))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    pub fn is_locked() -> bool {
+        super::output(
+            "tasklist",
+            &["/FI", "IMAGENAME eq LogonUI.exe", "/FO", "CSV", "/NH"],
+        )
+        .is_some_and(|out| out.contains("LogonUI.exe"))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    pub fn is_locked() -> bool {
+        let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+        super::output(
+            "loginctl",
+            &["show-session", &session, "--property=LockedHint"],
+        )
+        .is_some_and(|out| out.trim() == "LockedHint=yes")
+    }
+}
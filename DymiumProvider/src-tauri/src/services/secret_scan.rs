@@ -0,0 +1,154 @@
+//! Plaintext secret detection
+//!
+//! On startup the files the app writes are checked for credentials stored in
+//! the clear: secrets left in config.json (e.g. when the keystore was
+//! unavailable for the migration), `options.apiKey` values in opencode.json
+//! and keys or tokens in OpenCode's auth.json, and the same in the app's
+//! backups of those files (~/.dymium/backups). The findings are shown as a
+//! security advisory; `TokenService::harden_secrets` fixes the ones the app
+//! owns, deleting backups that hold credentials.
+
+use crate::services::config::AppConfig;
+use crate::services::files;
+use crate::services::opencode::OpenCodeService;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Secret fields of config.json
+const CONFIG_SECRETS: &[&str] = &["clientSecret", "password", "refreshToken", "staticApiKey"];
+
+/// Secret fields of an auth.json entry
+const AUTH_SECRETS: &[&str] = &["key", "access", "refresh"];
+
+/// Credentials stored in the clear in one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretFinding {
+    pub path: String,
+    /// Where in the file, e.g. "password" or "provider.dymium.options.apiKey"
+    pub entries: Vec<String>,
+    /// Whether `harden_secrets` can remove them
+    pub fixable: bool,
+}
+
+/// Service for finding plaintext credentials
+pub struct SecretScan;
+
+impl SecretScan {
+    /// Files holding plaintext credentials, given the current settings
+    pub fn scan(config: &AppConfig) -> Vec<SecretFinding> {
+        let mut findings = Vec::new();
+
+        if let Ok(path) = AppConfig::config_path() {
+            let entries = read_json(&path)
+                .map(|value| {
+                    CONFIG_SECRETS
+                        .iter()
+                        .filter(|key| is_set(value.get(**key)))
+                        .map(|key| key.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            push(&mut findings, &path, entries, true);
+        }
+
+        if let Ok(path) = OpenCodeService::config_path() {
+            let (ours, others): (Vec<_>, Vec<_>) = read_json(&path)
+                .map(|value| provider_keys(&value))
+                .unwrap_or_default()
+                .into_iter()
+                .partition(|(id, _)| id == "dymium" || id.starts_with("dymium-"));
+            let ours = ours.into_iter().map(|(_, entry)| entry).collect();
+            let others = others.into_iter().map(|(_, entry)| entry).collect();
+            // The apiKey can only be left out while the auth plugin supplies it
+            push(&mut findings, &path, ours, !config.skip_opencode_plugin);
+            push(&mut findings, &path, others, false);
+        }
+
+        if let Ok(path) = OpenCodeService::auth_path() {
+            let entries = read_json(&path)
+                .map(|value| auth_entries(&value))
+                .unwrap_or_default()
+                .into_iter()
+                // In socket-only mode the dymium key is a placeholder
+                .filter(|id| !(config.socket_only && id == "dymium"))
+                .collect();
+            // OpenCode reads credentials from here; only socket-only mode
+            // keeps the real token out
+            push(&mut findings, &path, entries, false);
+        }
+
+        findings.extend(Self::scan_backups());
+        findings
+    }
+
+    /// Backups in ~/.dymium/backups holding credentials, read as either
+    /// file. Backups that aren't JSON (other tools' configs) aren't checked.
+    pub fn scan_backups() -> Vec<SecretFinding> {
+        let mut findings = Vec::new();
+        let Ok(entries) = files::backup_dir().and_then(std::fs::read_dir) else {
+            return findings;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(value) = read_json(&path) else {
+                continue;
+            };
+            let mut found: Vec<String> = provider_keys(&value)
+                .into_iter()
+                .map(|(_, entry)| entry)
+                .collect();
+            if found.is_empty() {
+                found = auth_entries(&value);
+            }
+            push(&mut findings, &path, found, true);
+        }
+        findings
+    }
+}
+
+/// (provider id, entry) for each opencode.json provider with an apiKey
+fn provider_keys(value: &Value) -> Vec<(String, String)> {
+    let Some(Value::Object(providers)) = value.get("provider") else {
+        return Vec::new();
+    };
+    providers
+        .iter()
+        .filter(|(_, provider)| is_set(provider.pointer("/options/apiKey")))
+        .map(|(id, _)| (id.clone(), format!("provider.{}.options.apiKey", id)))
+        .collect()
+}
+
+/// Ids of auth.json entries holding a key or token
+fn auth_entries(value: &Value) -> Vec<String> {
+    let Value::Object(entries) = value else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter(|(_, entry)| AUTH_SECRETS.iter().any(|k| is_set(entry.get(*k))))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    jsonc_parser::parse_to_serde_value(&content, &Default::default())
+        .ok()
+        .flatten()
+}
+
+fn is_set(value: Option<&Value>) -> bool {
+    value.and_then(Value::as_str).is_some_and(|s| !s.is_empty())
+}
+
+fn push(findings: &mut Vec<SecretFinding>, path: &Path, entries: Vec<String>, fixable: bool) {
+    if !entries.is_empty() {
+        findings.push(SecretFinding {
+            path: path.display().to_string(),
+            entries,
+            fixable,
+        });
+    }
+}
@@ -0,0 +1,133 @@
+//! Shell and direnv integration
+//!
+//! Generates `export` snippets for .bashrc/.envrc, and keeps a direnv library
+//! file (~/.config/direnv/lib/dymium.sh) defining `use_dymium`, so an .envrc
+//! only needs `use dymium`. The library reads the token file at load time
+//! and watches it, so direnv reloads whenever the token rotates.
+
+use crate::services::codex::API_KEY_ENV;
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::opencode::OpenCodeService;
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// First line of the direnv library; a file without it is never deleted
+const HEADER: &str = "# Managed by Dymium Provider; regenerated when the endpoint changes";
+
+#[derive(Error, Debug)]
+pub enum ShellError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Config directory not found")]
+    NoConfigDir,
+}
+
+/// Service for shell export snippets and the direnv helper
+pub struct ShellService;
+
+impl ShellService {
+    /// direnv loads every file in ~/.config/direnv/lib
+    pub fn direnv_lib_path() -> Result<PathBuf, ShellError> {
+        dirs::home_dir()
+            .map(|p| p.join(".config/direnv/lib/dymium.sh"))
+            .ok_or(ShellError::NoConfigDir)
+    }
+
+    /// POSIX shell `export` lines for the GhostLLM endpoint. With `token`, the
+    /// key is inlined; otherwise it is read from the token file when sourced.
+    pub fn snippet(config: &AppConfig, token: Option<&str>) -> Result<String, ShellError> {
+        let api_key = match token {
+            Some(token) => quote(token),
+            None => format!("\"$(cat {})\"", quote(&token_path()?)),
+        };
+        Ok(format!(
+            "export OPENAI_BASE_URL={}\nexport OPENAI_API_KEY={}\nexport {}=\"$OPENAI_API_KEY\"\n",
+            quote(&OpenCodeService::compute_base_url(config)),
+            api_key,
+            API_KEY_ENV
+        ))
+    }
+
+    /// Rewrite the direnv library if the endpoint changed
+    pub fn sync(config: &AppConfig) -> Result<(), ShellError> {
+        let path = Self::direnv_lib_path()?;
+        let token_file = quote(&token_path()?);
+        let content = format!(
+            "{header}\n\
+             # Usage in .envrc: use dymium\n\
+             use_dymium() {{\n\
+             \x20 watch_file {token_file}\n\
+             \x20 export OPENAI_BASE_URL={base_url}\n\
+             \x20 export OPENAI_API_KEY=\"$(cat {token_file})\"\n\
+             \x20 export {key_env}=\"$OPENAI_API_KEY\"\n\
+             }}\n",
+            header = HEADER,
+            token_file = token_file,
+            base_url = quote(&OpenCodeService::compute_base_url(config)),
+            key_env = API_KEY_ENV,
+        );
+        if files::read_optional(&path)?.as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+
+        files::write_atomic(&path, content.as_bytes())?;
+        log::info!("Updated {}", path.display());
+        Ok(())
+    }
+
+    /// Delete the direnv library if we wrote it (on log out / mode switch)
+    pub fn clear() {
+        let result = Self::direnv_lib_path().and_then(|path| {
+            let ours =
+                files::read_optional(&path)?.is_some_and(|content| content.starts_with(HEADER));
+            if ours {
+                fs::remove_file(&path)?;
+                log::info!("Removed {}", path.display());
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("Failed to remove direnv helper: {}", e);
+        }
+    }
+}
+
+fn token_path() -> Result<String, ShellError> {
+    Ok(AppConfig::token_path()?.display().to_string())
+}
+
+/// Single-quote for POSIX shells
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+impl Integration for ShellService {
+    fn id(&self) -> &'static str {
+        "direnv"
+    }
+
+    fn name(&self) -> &'static str {
+        "direnv"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::direnv_lib_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "the use_dymium direnv function (whole file)"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config)?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
@@ -0,0 +1,159 @@
+//! systemd integration
+//!
+//! When headless mode runs as a `Type=notify` service, systemd passes
+//! `NOTIFY_SOCKET` (and `WATCHDOG_USEC` with `WatchdogSec=`); the daemon
+//! reports readiness, status and watchdog pings over it (the sd_notify
+//! protocol). Also writes a user unit for `systemctl --user`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Name of the user unit
+pub const UNIT_NAME: &str = "dymium-provider.service";
+
+#[derive(Error, Debug)]
+pub enum SystemdError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config directory not found")]
+    NoDirError,
+    #[error("systemd units are only supported on Linux")]
+    Unsupported,
+}
+
+/// Connection to the service manager's notification socket
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(unix)]
+    path: String,
+    /// How often systemd expects a watchdog ping
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// The notifier systemd asked for, if running under it
+    pub fn from_env() -> Option<Self> {
+        #[cfg(unix)]
+        {
+            let path = std::env::var("NOTIFY_SOCKET").ok()?;
+            let socket = std::os::unix::net::UnixDatagram::unbound().ok()?;
+            Some(Self {
+                socket,
+                path,
+                watchdog: watchdog_interval(),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Startup is done
+    pub fn ready(&self) {
+        self.notify("READY=1");
+    }
+
+    /// Shutting down
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    /// One-line status shown by `systemctl status`
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={}", status.replace('\n', " ")));
+    }
+
+    /// Tell the watchdog we're alive
+    pub fn watchdog(&self) {
+        if self.watchdog.is_some() {
+            self.notify("WATCHDOG=1");
+        }
+    }
+
+    /// How often to ping: half of `WatchdogSec`, if the watchdog is on
+    pub fn watchdog_period(&self) -> Option<Duration> {
+        self.watchdog.map(|interval| interval / 2)
+    }
+
+    #[cfg(unix)]
+    fn notify(&self, message: &str) {
+        let result = match self.path.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                std::os::unix::net::SocketAddr::from_abstract_name(name)
+                    .and_then(|addr| self.socket.send_to_addr(message.as_bytes(), &addr))
+            }
+            _ => self.socket.send_to(message.as_bytes(), &self.path),
+        };
+        if let Err(e) = result {
+            log::warn!("sd_notify {} failed: {}", message, e);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn notify(&self, _message: &str) {}
+}
+
+/// `WATCHDOG_USEC`, if it is meant for this process
+#[cfg(unix)]
+fn watchdog_interval() -> Option<Duration> {
+    let pid_matches = std::env::var("WATCHDOG_PID")
+        .map(|pid| pid == std::process::id().to_string())
+        .unwrap_or(true);
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (pid_matches && usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Service for the user unit file
+pub struct SystemdService;
+
+impl SystemdService {
+    /// ~/.config/systemd/user/dymium-provider.service
+    pub fn unit_path() -> Result<PathBuf, SystemdError> {
+        dirs::config_dir()
+            .map(|p| p.join("systemd").join("user").join(UNIT_NAME))
+            .ok_or(SystemdError::NoDirError)
+    }
+
+    /// Unit that runs this binary in headless mode
+    pub fn unit() -> Result<String, SystemdError> {
+        let exe = std::env::current_exe()?;
+        Ok(format!(
+            "[Unit]
+Description=Dymium Provider (GhostLLM token refresh)
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=notify
+ExecStart=\"{}\" --headless
+Restart=on-failure
+RestartSec=10
+WatchdogSec=120
+Environment=RUST_LOG=info
+
+[Install]
+WantedBy=default.target
+",
+            exe.display()
+        ))
+    }
+
+    /// Write the unit, replacing any previous one, and return its path
+    pub fn install() -> Result<PathBuf, SystemdError> {
+        if !cfg!(target_os = "linux") {
+            return Err(SystemdError::Unsupported);
+        }
+        let path = Self::unit_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, Self::unit()?)?;
+        log::info!("Wrote {}", path.display());
+        Ok(path)
+    }
+}
@@ -0,0 +1,186 @@
+//! Opt-in anonymous telemetry
+//!
+//! With `telemetry.enabled`, authentication outcomes are counted in
+//! ~/.dymium/telemetry.json: attempts per auth mode, successes, and failures
+//! per error class (see `error_code`). Once a day the totals, with the app
+//! version and platform, are POSTed to `telemetry.endpoint` and reset. No
+//! identifiers, endpoints, usernames or messages are collected; the pending
+//! report can be inspected with `get_telemetry_report` before it is sent.
+
+use crate::services::config::{AppConfig, AuthMode, ConfigError, TelemetryConfig};
+use crate::services::error_code::ErrorCode;
+use crate::services::files;
+use crate::services::token::TokenError;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// How much history goes into one report
+const REPORT_PERIOD_HOURS: i64 = 24;
+/// How often `send_loop` checks whether a report is due
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Telemetry endpoint returned {0}")]
+    Rejected(u16),
+}
+
+/// Counts since the last report, exactly as they are sent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TelemetryReport {
+    pub app_version: String,
+    /// "macos", "windows", "linux", ...
+    pub os: String,
+    pub arch: String,
+    /// When counting started; unset until the first event
+    pub period_start: Option<DateTime<Utc>>,
+    /// Authentication attempts per auth mode ("oauth", "static")
+    pub auth_modes: BTreeMap<String, u64>,
+    pub successes: u64,
+    /// Failures per error class, e.g. "auth_failed"
+    pub failures: BTreeMap<String, u64>,
+}
+
+/// Service for counting and sending telemetry
+pub struct Telemetry;
+
+impl Telemetry {
+    /// Get the pending counts path (~/.dymium/telemetry.json)
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        Ok(AppConfig::config_dir()?.join("telemetry.json"))
+    }
+
+    /// Count one authentication or refresh, if the user opted in
+    pub fn record(config: &TelemetryConfig, mode: AuthMode, result: &Result<(), TokenError>) {
+        if !config.enabled {
+            return;
+        }
+        let result = Self::update(|report| {
+            report.period_start.get_or_insert_with(Utc::now);
+            let mode = match mode {
+                AuthMode::OAuth => "oauth",
+                AuthMode::StaticKey => "static",
+            };
+            *report.auth_modes.entry(mode.to_string()).or_default() += 1;
+            match result {
+                Ok(()) => report.successes += 1,
+                Err(e) => {
+                    let class = ErrorCode::from(e).name().to_string();
+                    *report.failures.entry(class).or_default() += 1;
+                }
+            }
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to update telemetry counts: {}", e);
+        }
+    }
+
+    /// The report that would be sent next
+    pub fn report() -> Result<TelemetryReport, TelemetryError> {
+        let mut report = match files::read_optional(&Self::path()?)? {
+            Some(content) => serde_json::from_str(&content)?,
+            None => TelemetryReport::default(),
+        };
+        report.app_version = env!("CARGO_PKG_VERSION").to_string();
+        report.os = std::env::consts::OS.to_string();
+        report.arch = std::env::consts::ARCH.to_string();
+        Ok(report)
+    }
+
+    /// Forget pending counts, e.g. after opting out
+    pub fn clear() -> Result<(), TelemetryError> {
+        match std::fs::remove_file(Self::path()?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Send and reset the counts if a day has passed since counting started.
+    /// Returns whether a report was sent.
+    pub async fn send_if_due(config: &TelemetryConfig) -> Result<bool, TelemetryError> {
+        let Some(endpoint) = config.endpoint.as_deref().filter(|_| config.enabled) else {
+            return Ok(false);
+        };
+        let report = Self::report()?;
+        let due = report
+            .period_start
+            .is_some_and(|start| Utc::now() - start >= Duration::hours(REPORT_PERIOD_HOURS));
+        if !due {
+            return Ok(false);
+        }
+
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .build()?;
+        let response = client
+            .post(endpoint)
+            .header(
+                "User-Agent",
+                concat!("dymium-provider/", env!("CARGO_PKG_VERSION")),
+            )
+            .json(&report)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(TelemetryError::Rejected(response.status().as_u16()));
+        }
+
+        // Events counted while the request was in flight go into the next report
+        Self::update(|pending| {
+            for (mode, count) in &report.auth_modes {
+                let left = pending.auth_modes.entry(mode.clone()).or_default();
+                *left = left.saturating_sub(*count);
+            }
+            for (class, count) in &report.failures {
+                let left = pending.failures.entry(class.clone()).or_default();
+                *left = left.saturating_sub(*count);
+            }
+            pending.auth_modes.retain(|_, count| *count > 0);
+            pending.failures.retain(|_, count| *count > 0);
+            pending.successes = pending.successes.saturating_sub(report.successes);
+            let empty = pending.auth_modes.is_empty() && pending.successes == 0;
+            pending.period_start = (!empty).then(Utc::now);
+        })?;
+        log::info!("Sent telemetry report");
+        Ok(true)
+    }
+
+    /// Send reports as they come due, for as long as the app runs. Settings
+    /// are re-read each time, so opting in or out needs no restart.
+    pub async fn send_loop() {
+        loop {
+            let config = AppConfig::load().telemetry;
+            if let Err(e) = Self::send_if_due(&config).await {
+                log::warn!("Telemetry report not sent: {}", e);
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Apply `change` to the saved counts under the file lock
+    fn update(change: impl FnOnce(&mut TelemetryReport)) -> Result<(), TelemetryError> {
+        let path = Self::path()?;
+        // The app and the CLI may count at the same time
+        let _lock = files::lock(&path)?;
+        let mut report: TelemetryReport = match files::read_optional(&path)? {
+            Some(content) => serde_json::from_str(&content).unwrap_or_default(),
+            None => TelemetryReport::default(),
+        };
+        change(&mut report);
+        files::write_atomic(&path, serde_json::to_string_pretty(&report)?.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+//! TLS trust for outgoing requests
+//!
+//! Certificates are verified against the bundled Mozilla roots, the system
+//! CA bundle (Unix; `SSL_CERT_FILE` overrides it) and any `tlsTrust.extraCaCerts`.
+//! Hosts in `tlsTrust.insecureHosts` (e.g. a development Keycloak with a
+//! self-signed certificate) skip verification; `"*"` restores the old
+//! accept-anything behaviour. Rejected certificates are reported with the
+//! host and reason instead of a bare connection error.
+//!
+//! Hosts in `tlsTrust.pins` must also present a certificate whose public key
+//! (SPKI) hashes to one of the listed SHA-256 values, so a CA trusted by the
+//! machine can't be used to intercept the token. Only the server's own
+//! certificate counts: intermediates are sent by the server and could be
+//! copied by an interceptor. The handshake is aborted before anything is
+//! sent if no pin matches.
+//!
+//! With `tlsTrust.httpsOnly`, `http://` URLs are refused before anything is
+//! sent, except for hosts in `tlsTrust.plainHttpHosts`.
+
+use crate::services::config::{AppConfig, HttpProfile, HttpVersion, TlsTrustConfig};
+use crate::services::egress_proxy::{EgressProxy, EgressRoute};
+use crate::services::host_overrides::HostOverrides;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::{Client, ClientBuilder, Url};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{VerifierBuilderError, WebPkiServerVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::error::Error as _;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// CA bundles of common distributions, first match wins
+#[cfg(unix)]
+const SYSTEM_BUNDLES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+/// How a pin mismatch starts in the handshake error, to recognise it later
+const PIN_MISMATCH: &str = "server key sha256/";
+
+#[derive(Error, Debug)]
+pub enum TlsTrustError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("TLS error: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("Certificate verifier error: {0}")]
+    Verifier(#[from] VerifierBuilderError),
+}
+
+/// A plain HTTP URL refused by `httpsOnly`
+#[derive(Error, Debug)]
+#[error(
+    "Refusing to send credentials to {0} over plain HTTP; use https:// or list the host in tlsTrust.plainHttpHosts"
+)]
+pub struct PlainHttpRefused(pub String);
+
+/// Why a request failed at the TLS handshake
+#[derive(Debug, Clone)]
+pub enum CertificateFailure {
+    /// The certificate isn't trusted
+    Untrusted(String),
+    /// The certificate is trusted but matches none of the host's pins
+    PinMismatch(String),
+}
+
+impl fmt::Display for CertificateFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Untrusted(message) | Self::PinMismatch(message) => f.write_str(message),
+        }
+    }
+}
+
+/// A verifying client and, for insecure hosts, a non-verifying one
+#[derive(Clone)]
+pub struct HttpClients {
+    verified: Client,
+    unverified: Client,
+    insecure_hosts: Vec<String>,
+    https_only: bool,
+    plain_http_hosts: Vec<String>,
+}
+
+impl HttpClients {
+    /// Build both clients from `base`, which sets timeouts and the like.
+    /// Unreadable CA files and malformed pins are logged and skipped.
+    pub fn new(
+        trust: &TlsTrustConfig,
+        base: impl Fn() -> ClientBuilder,
+    ) -> Result<Self, TlsTrustError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let pins = parse_pins(trust);
+        let roots = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(root_store(trust)),
+            provider.clone(),
+        )
+        .build()?;
+        let tls = |chain: Option<Arc<WebPkiServerVerifier>>| {
+            let verifier = PinningVerifier {
+                chain,
+                pins: pins.clone(),
+                provider: provider.clone(),
+            };
+            Ok::<_, rustls::Error>(
+                ClientConfig::builder_with_provider(provider.clone())
+                    .with_safe_default_protocol_versions()?
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(verifier))
+                    .with_no_client_auth(),
+            )
+        };
+        Ok(Self {
+            verified: base().use_preconfigured_tls(tls(Some(roots))?).build()?,
+            unverified: base().use_preconfigured_tls(tls(None)?).build()?,
+            insecure_hosts: normalize_hosts(trust.insecure_hosts.iter()),
+            https_only: trust.https_only,
+            plain_http_hosts: normalize_hosts(trust.plain_http_hosts.iter()),
+        })
+    }
+
+    /// Clients for Keycloak and for the LLM endpoint, with the connection
+    /// settings of their `http` profiles
+    pub fn from_profiles(config: &AppConfig) -> Result<(Self, Self), TlsTrustError> {
+        let route = EgressProxy::resolve(&config.egress_proxy);
+        Ok((
+            Self::with_profile(config, &config.http.keycloak, &route, true)?,
+            Self::with_profile(config, &config.http.llm, &route, true)?,
+        ))
+    }
+
+    /// Clients for streamed LLM responses: the `http.llm` profile without
+    /// its request timeout
+    pub fn for_streaming(config: &AppConfig) -> Result<Self, TlsTrustError> {
+        let route = EgressProxy::resolve(&config.egress_proxy);
+        Self::with_profile(config, &config.http.llm, &route, false)
+    }
+
+    /// Both clients with `profile`'s settings, through its own egress proxy
+    /// if it sets one and `route` otherwise
+    fn with_profile(
+        config: &AppConfig,
+        profile: &HttpProfile,
+        route: &EgressRoute,
+        request_timeout: bool,
+    ) -> Result<Self, TlsTrustError> {
+        let route = match &profile.egress_proxy {
+            Some(proxy) => EgressProxy::resolve(proxy),
+            None => route.clone(),
+        };
+        let hosts = HostOverrides::parse(&config.host_overrides);
+        Self::new(&config.tls_trust, || {
+            let mut builder = Client::builder()
+                .connect_timeout(Duration::from_secs(profile.connect_timeout_secs))
+                .pool_idle_timeout(Duration::from_secs(profile.idle_timeout_secs));
+            if request_timeout && profile.timeout_secs > 0 {
+                builder = builder.timeout(Duration::from_secs(profile.timeout_secs));
+            }
+            if profile.idle_timeout_secs == 0 {
+                builder = builder.pool_max_idle_per_host(0);
+            }
+            if profile.tcp_keepalive_secs > 0 {
+                builder = builder.tcp_keepalive(Duration::from_secs(profile.tcp_keepalive_secs));
+            }
+            builder = match profile.http_version {
+                HttpVersion::Http1 => builder.http1_only(),
+                HttpVersion::Auto => builder,
+                HttpVersion::Http2 => builder.http2_prior_knowledge(),
+            };
+            hosts.apply(route.apply(builder))
+        })
+    }
+
+    /// The client to use for `url`, unless `httpsOnly` refuses it
+    pub fn for_url(&self, url: &str) -> Result<&Client, PlainHttpRefused> {
+        let parsed = Url::parse(url).ok();
+        let host = parsed
+            .as_ref()
+            .and_then(|u| u.host_str().map(str::to_ascii_lowercase));
+        let matches = |patterns: &[String]| {
+            host.as_deref()
+                .is_some_and(|host| patterns.iter().any(|p| host_matches(p, host)))
+        };
+        if self.https_only
+            && parsed.as_ref().is_some_and(|u| u.scheme() == "http")
+            && !matches(&self.plain_http_hosts)
+        {
+            return Err(PlainHttpRefused(host.unwrap_or_else(|| url.to_string())));
+        }
+        if matches(&self.insecure_hosts) {
+            Ok(&self.unverified)
+        } else {
+            Ok(&self.verified)
+        }
+    }
+}
+
+/// If `e` failed because the server's certificate was rejected, a message
+/// naming the host and the reason, with how to fix it
+pub fn certificate_error(e: &reqwest::Error) -> Option<CertificateFailure> {
+    let host = || {
+        e.url()
+            .and_then(|u| u.host_str())
+            .unwrap_or("the server")
+            .to_string()
+    };
+    let mut source = e.source();
+    while let Some(cause) = source {
+        let reason = cause.to_string();
+        if let Some(start) = reason.find(PIN_MISMATCH) {
+            return Some(CertificateFailure::PinMismatch(format!(
+                "Certificate of {} doesn't match tlsTrust.pins ({}). The connection may be intercepted; if the server's key was replaced on purpose, update the pin",
+                host(),
+                &reason[start..]
+            )));
+        }
+        if reason.contains("certificate") {
+            return Some(CertificateFailure::Untrusted(format!(
+                "TLS certificate of {} was rejected ({}). Add its CA to tlsTrust.extraCaCerts, or list the host in tlsTrust.insecureHosts if it uses a self-signed certificate",
+                host(),
+                reason
+            )));
+        }
+        source = cause.source();
+    }
+    None
+}
+
+/// Checks pins, then the chain unless the host is insecure
+#[derive(Debug)]
+struct PinningVerifier {
+    /// Chain verification; `None` accepts any chain
+    chain: Option<Arc<WebPkiServerVerifier>>,
+    /// Host pattern and accepted SPKI hashes
+    pins: Vec<(String, Vec<[u8; 32]>)>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let host = server_name.to_str().to_ascii_lowercase();
+        if let Some((_, pins)) = self
+            .pins
+            .iter()
+            .find(|(pattern, _)| host_matches(pattern, &host))
+        {
+            let presented = spki_sha256(end_entity);
+            if !presented.is_some_and(|hash| pins.contains(&hash)) {
+                let key = presented
+                    .map_or_else(|| "unreadable".to_string(), |hash| STANDARD.encode(hash));
+                return Err(rustls::Error::General(format!(
+                    "{}{} is not pinned",
+                    PIN_MISMATCH, key
+                )));
+            }
+        }
+        match &self.chain {
+            Some(chain) => {
+                chain.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            }
+            None => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// SHA-256 of a certificate's SubjectPublicKeyInfo, as `openssl x509 -pubkey
+/// | openssl pkey -pubin -outform der | openssl dgst -sha256` computes it
+fn spki_sha256(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    Some(Sha256::digest(cert.subject_public_key_info().as_ref()).into())
+}
+
+/// Pins per host pattern; entries that aren't a base64 (or hex) SHA-256,
+/// optionally prefixed "sha256/", are skipped. A host left with no valid
+/// pin rejects every certificate rather than falling back to none.
+fn parse_pins(trust: &TlsTrustConfig) -> Vec<(String, Vec<[u8; 32]>)> {
+    trust
+        .pins
+        .iter()
+        .filter_map(|(host, pins)| {
+            let pattern = normalize_hosts(std::iter::once(host)).pop()?;
+            let hashes = pins
+                .iter()
+                .filter_map(|pin| {
+                    let value = pin.trim();
+                    let value = value.strip_prefix("sha256/").unwrap_or(value);
+                    let decoded = if value.len() == 64 {
+                        hex_decode(value)
+                    } else {
+                        STANDARD.decode(value).ok()
+                    };
+                    let hash = decoded.and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+                    if hash.is_none() {
+                        log::error!("Ignoring malformed pin for {}: {}", host, pin);
+                    }
+                    hash
+                })
+                .collect();
+            Some((pattern, hashes))
+        })
+        .collect()
+}
+
+fn hex_decode(value: &str) -> Option<Vec<u8>> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn normalize_hosts<'a>(hosts: impl Iterator<Item = &'a String>) -> Vec<String> {
+    hosts
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+/// Whether `host` matches an exact name, `*.domain` or `*`
+fn host_matches(pattern: &str, host: &str) -> bool {
+    pattern == "*"
+        || pattern == host
+        || pattern
+            .strip_prefix("*.")
+            .is_some_and(|domain| host.ends_with(&format!(".{}", domain)))
+}
+
+/// Bundled roots plus system and extra CA certificates
+fn root_store(trust: &TlsTrustConfig) -> RootCertStore {
+    let mut store = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let mut paths: Vec<String> = Vec::new();
+    if let Ok(path) = std::env::var("SSL_CERT_FILE") {
+        paths.push(path);
+    } else {
+        #[cfg(unix)]
+        if let Some(bundle) = SYSTEM_BUNDLES.iter().find(|p| Path::new(p).exists()) {
+            paths.push(bundle.to_string());
+        }
+    }
+    paths.extend(trust.extra_ca_certs.iter().cloned());
+
+    for path in paths {
+        let loaded = CertificateDer::pem_file_iter(Path::new(&path))
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>());
+        match loaded {
+            Ok(certs) => {
+                let (_, ignored) = store.add_parsable_certificates(certs);
+                if ignored > 0 {
+                    log::warn!("Ignored {} unusable CA certificate(s) in {}", ignored, path);
+                }
+            }
+            Err(e) => log::error!("Ignoring CA certificates in {}: {}", path, e),
+        }
+    }
+    store
+}
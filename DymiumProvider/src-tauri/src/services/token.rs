@@ -2,14 +2,46 @@
 //!
 //! Handles OAuth authentication with Keycloak and token management
 
-use crate::services::config::{AppConfig, AuthMode, TokenState};
+use crate::services::activity::ActivityLog;
+use crate::services::audit::AuditLog;
+use crate::services::autostart::AutostartService;
+use crate::services::config::{
+    AppConfig, AuthMode, EgressProxyConfig, ModelEntry, Profile, SecretKind, Secrets, TokenState,
+    UpdateChannel,
+};
+use crate::services::egress_proxy::{EgressProxy, EgressRoute};
+use crate::services::error_code::ErrorCode;
+use crate::services::failover::{ActiveEndpoint, Failover};
+use crate::services::files;
+use crate::services::health::{EndpointKind, HealthLog};
+use crate::services::integrations::{self, SyncContext};
+use crate::services::keycloak_failover::KeycloakFailover;
 use crate::services::keystore::{CredentialKey, KeystoreService};
-use crate::services::opencode::OpenCodeService;
+use crate::services::last_state::LastState;
+use crate::services::managed_config::{ManagedConfigError, ManagedConfigService};
+use crate::services::migration::{MigrationOutcome, MigrationService};
+use crate::services::opencode::{DiscoveredModel, OpenCodeService};
+use crate::services::power::PowerService;
+use crate::services::proxy::{SharedUpstream, Upstream};
+use crate::services::redact::redact_secrets;
+use crate::services::revocation::RevocationService;
+use crate::services::secret_scan::{SecretFinding, SecretScan};
+use crate::services::telemetry::Telemetry;
+use crate::services::tls_trust::{
+    certificate_error, CertificateFailure, HttpClients, PlainHttpRefused,
+};
+use crate::services::vault::{Vault, VaultError};
+use crate::services::workspaces::{WorkspaceError, WorkspaceService};
 use chrono::{Duration, Utc};
-use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
+use tokio::sync::{watch, Mutex, MutexGuard};
 
 #[derive(Error, Debug)]
 pub enum TokenError {
@@ -23,14 +55,151 @@ pub enum TokenError {
     InvalidResponse,
     #[error("Auth failed ({status}): {body}")]
     AuthFailed { status: u16, body: String },
+    #[error("{message}")]
+    EndpointRejected { status: u16, message: String },
+    #[error("{0}")]
+    EndpointUnreachable(String),
+    #[error("{0}")]
+    EndpointTimeout(String),
+    #[error("{0}")]
+    CertificateRejected(String),
+    #[error("{0}")]
+    CertificatePinMismatch(String),
+    #[error("{0}")]
+    PlainHttpRefused(#[from] PlainHttpRefused),
     #[error("HTTP error: {0}")]
     HttpError(#[from] reqwest::Error),
     #[error("Config error: {0}")]
     ConfigError(String),
+    #[error("Failed to update OpenCode config: {0}")]
+    OpenCodeWrite(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("Keystore error: {0}")]
     KeystoreError(#[from] crate::services::keystore::KeystoreError),
+    #[error("Locked after inactivity; unlock to continue")]
+    IdleLocked,
+    #[error("Disabled by your administrator: {0}")]
+    ClientDisabled(String),
+    #[error("Vault error: {0}")]
+    VaultError(#[from] VaultError),
+}
+
+impl TokenError {
+    /// HTTP status of the response that caused the error, if any
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::AuthFailed { status, .. } | Self::EndpointRejected { status, .. } => {
+                Some(*status)
+            }
+            Self::HttpError(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+impl From<CertificateFailure> for TokenError {
+    fn from(failure: CertificateFailure) -> Self {
+        match failure {
+            CertificateFailure::Untrusted(message) => Self::CertificateRejected(message),
+            CertificateFailure::PinMismatch(message) => Self::CertificatePinMismatch(message),
+        }
+    }
+}
+
+/// The current access token, as returned by `get_token`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessToken {
+    pub token: String,
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+/// Outcome of `test_llm_endpoint`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTest {
+    /// URL that was checked (with the app path, if any)
+    pub base_url: String,
+    /// Model IDs the endpoint lists
+    pub models: Vec<String>,
+}
+
+/// Outcome of `list_models`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelList {
+    /// Models the endpoint lists
+    pub models: Vec<DiscoveredModel>,
+    /// When the endpoint was asked
+    pub fetched_at: chrono::DateTime<Utc>,
+}
+
+/// OAuth setup values to validate, as entered in the setup form
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthSettings {
+    pub keycloak_url: String,
+    pub realm: String,
+    pub client_id: String,
+    pub username: String,
+    pub llm_endpoint: String,
+    #[serde(default)]
+    pub ghostllm_app: Option<String>,
+    pub client_secret: String,
+    pub password: String,
+}
+
+/// Settings and credentials for a one-off login, e.g. from CI environment
+/// variables. Unset fields keep their configured values.
+#[derive(Debug, Clone, Default)]
+pub struct SessionLogin {
+    pub auth_mode: Option<AuthMode>,
+    pub llm_endpoint: Option<String>,
+    pub ghostllm_app: Option<String>,
+    pub keycloak_url: Option<String>,
+    pub realm: Option<String>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub secrets: Secrets,
+}
+
+/// Outcome of one step of `validate_oauth_credentials`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StepStatus {
+    Passed,
+    Failed,
+    /// An earlier step failed
+    Skipped,
+}
+
+/// One step of `validate_oauth_credentials`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationStep {
+    /// "keycloak" (password grant) or "endpoint" (`/v1/models` check)
+    pub step: &'static str,
+    pub status: StepStatus,
+    pub detail: String,
+}
+
+impl ValidationStep {
+    fn passed(step: &'static str, detail: String) -> Self {
+        Self {
+            step,
+            status: StepStatus::Passed,
+            detail,
+        }
+    }
+
+    fn failed(step: &'static str, detail: String) -> Self {
+        Self {
+            step,
+            status: StepStatus::Failed,
+            detail,
+        }
+    }
 }
 
 /// Response from Keycloak token endpoint
@@ -41,30 +210,101 @@ struct KeycloakTokenResponse {
     refresh_token: Option<String>,
     refresh_expires_in: Option<i64>,
     token_type: String,
+    /// HTTP status Keycloak answered with
+    #[serde(skip)]
+    status: u16,
+}
+
+/// Response from the LLM endpoint's OpenAI-compatible `/v1/models`
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    #[serde(default)]
+    data: Vec<DiscoveredModel>,
 }
 
 /// Token service for managing authentication
 pub struct TokenService {
     config: AppConfig,
     state: TokenState,
-    client: Client,
+    /// Clients for Keycloak and the other control-plane servers
+    clients: HttpClients,
+    /// Clients for the LLM endpoint
+    llm_clients: HttpClients,
     last_refresh: Option<chrono::DateTime<Utc>>,
+    vault: Vault,
+    /// Models the endpoint listed at the last verification
+    discovered_models: Vec<DiscoveredModel>,
+    /// When `discovered_models` was fetched
+    models_fetched_at: Option<chrono::DateTime<Utc>>,
+    /// Endpoint and token the local proxy forwards with
+    upstream: SharedUpstream,
+    /// Recent events for the tray
+    activity: ActivityLog,
+    /// Last Keycloak and endpoint check results
+    health: HealthLog,
+    /// Which Keycloaks recently failed
+    keycloak_failover: KeycloakFailover,
+    /// Which of `llmEndpoint` and its fallbacks is in use
+    endpoint_failover: Failover,
+    /// Credentials came from `use_session_login` and are never written
+    session_only: bool,
+    /// The idle lock purged the token and is waiting for an unlock
+    idle_locked: bool,
+    /// Why an administrator disabled this client, while they have
+    blocked: Option<String>,
+    /// Loading secrets from the keystore failed at startup, so unset ones
+    /// may still be stored and mustn't be deleted on save
+    keystore_unreadable: bool,
 }
 
 impl TokenService {
     pub fn new() -> Self {
-        // Create HTTP client that accepts self-signed certificates
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
+        // With the vault enabled, credentials stay encrypted until the user
+        // unlocks it with the master password.
+        let mut config = AppConfig::load();
+        let mut keystore_unreadable = false;
+        let state = if config.vault_enabled {
+            Secrets::scrub(&mut config);
+            TokenState::Locked
+        } else {
+            // Move plaintext secrets left by older versions into the keystore
+            let outcome = MigrationService::migrate_plaintext_secrets(&mut config);
+            if outcome == MigrationOutcome::NotNeeded && config.secrets_in_keystore {
+                match KeystoreService::load_secrets() {
+                    Ok(secrets) => secrets.apply(&mut config),
+                    Err(e) => {
+                        log::error!("Failed to load secrets from keystore: {}", e);
+                        keystore_unreadable = true;
+                    }
+                }
+            }
+            TokenState::Idle
+        };
+        let blocked = RevocationService::blocked();
+        let state = match &blocked {
+            Some(message) => TokenState::failed(&TokenError::ClientDisabled(message.clone())),
+            None => state,
+        };
 
+        let (clients, llm_clients) = http_clients(&config);
         Self {
-            config: AppConfig::load(),
-            state: TokenState::Idle,
-            client,
+            clients,
+            llm_clients,
+            config,
+            state,
             last_refresh: None,
+            vault: Vault::new(),
+            discovered_models: Vec::new(),
+            models_fetched_at: None,
+            upstream: SharedUpstream::default(),
+            activity: ActivityLog::default(),
+            health: HealthLog::default(),
+            keycloak_failover: KeycloakFailover::default(),
+            endpoint_failover: Failover::new().0,
+            session_only: false,
+            idle_locked: false,
+            blocked,
+            keystore_unreadable,
         }
     }
 
@@ -78,23 +318,469 @@ impl TokenService {
         &self.config
     }
 
+    /// Handle the local proxy reads its upstream from
+    pub fn upstream(&self) -> SharedUpstream {
+        self.upstream.clone()
+    }
+
+    /// Receiver for LLM endpoint failovers and fail-backs
+    pub fn endpoint_switches(&self) -> watch::Receiver<Option<ActiveEndpoint>> {
+        self.endpoint_failover.subscribe()
+    }
+
+    /// `config` with `llm_endpoint` set to the endpoint in use
+    fn live_config(&self) -> Cow<'_, AppConfig> {
+        let endpoints = self.config.llm_endpoints();
+        match self.endpoint_failover.active(endpoints.len()) {
+            0 => Cow::Borrowed(&self.config),
+            active => {
+                let mut config = self.config.clone();
+                config.llm_endpoint = endpoints[active].clone();
+                Cow::Owned(config)
+            }
+        }
+    }
+
+    /// Handle to the recent activity history
+    pub fn activity(&self) -> ActivityLog {
+        self.activity.clone()
+    }
+
+    /// Last Keycloak and endpoint check results
+    pub fn health(&self) -> HealthLog {
+        self.health.clone()
+    }
+
+    /// Point the local proxy at the current token, or stop it forwarding
+    fn publish_upstream(&self, token: Option<&str>) {
+        let live = self.live_config();
+        let upstream = token.map(|token| {
+            // Base URLs for the apps proxy routes point at
+            let app_base_urls = self
+                .config
+                .proxy
+                .routes
+                .iter()
+                .filter_map(|route| route.app.clone())
+                .map(|app| {
+                    let mut config = live.as_ref().clone();
+                    config.ghostllm_app = Some(app.clone());
+                    (app, OpenCodeService::compute_base_url(&config))
+                })
+                .collect();
+            let fallback_base_urls = self
+                .config
+                .proxy
+                .fallback_endpoints
+                .iter()
+                .map(|endpoint| {
+                    let mut config = live.as_ref().clone();
+                    config.llm_endpoint = endpoint.clone();
+                    OpenCodeService::compute_base_url(&config)
+                })
+                .collect();
+            Upstream {
+                base_url: OpenCodeService::compute_base_url(&live),
+                app: live.ghostllm_app.clone(),
+                app_base_urls,
+                fallback_base_urls,
+                token: token.to_string(),
+                headers: live.llm_headers.clone(),
+            }
+        });
+        if let Ok(mut current) = self.upstream.write() {
+            *current = upstream;
+        }
+    }
+
+    /// Get a raw secret. Callers must have confirmed the user's identity first.
+    pub fn reveal_secret(&self, kind: SecretKind) -> Option<String> {
+        let value = match kind {
+            SecretKind::AccessToken => match &self.state {
+                TokenState::Authenticated { token, .. } => Some(token.clone()),
+                _ => None,
+            },
+            SecretKind::StaticApiKey => self.config.static_api_key.clone(),
+            SecretKind::ClientSecret => self.config.client_secret.clone(),
+            SecretKind::Password => self.config.password.clone(),
+        };
+        let value = value.filter(|s| !s.is_empty());
+        let outcome = if value.is_some() { "success" } else { "empty" };
+        AuditLog::record(
+            "reveal_secret",
+            Some(self.config.auth_mode),
+            outcome,
+            kind.label(),
+        );
+        value
+    }
+
+    /// Record a credential operation's outcome in the audit log
+    fn audit(&self, action: &str, result: &Result<(), TokenError>) {
+        let (outcome, detail) = match result {
+            Ok(()) => ("success", ""),
+            Err(e) => ("failed", ErrorCode::from(e).name()),
+        };
+        AuditLog::record(action, Some(self.config.auth_mode), outcome, detail);
+    }
+
+    /// The current access token and its expiry, if `tokenCommand` allows it
+    pub fn access_token(&self) -> Result<AccessToken, TokenError> {
+        if !self.config.token_command {
+            return Err(TokenError::ConfigError(
+                "get_token is disabled; set tokenCommand in config.json".to_string(),
+            ));
+        }
+        match &self.state {
+            TokenState::Authenticated { token, expires_at } => Ok(AccessToken {
+                token: token.clone(),
+                expires_at: *expires_at,
+            }),
+            _ => Err(TokenError::ConfigError("Not authenticated".to_string())),
+        }
+    }
+
     /// Reload config from disk
     pub fn reload_config(&mut self) {
+        let secrets = Secrets::from_config(&self.config);
         self.config = AppConfig::load();
+        // Secrets may live in the vault or keystore, or nowhere on disk for a
+        // session login — keep the ones in memory
+        secrets.apply(&mut self.config);
+        (self.clients, self.llm_clients) = http_clients(&self.config);
+    }
+
+    /// HTTP clients with the TLS settings applied, for long downloads that
+    /// shouldn't hold the service lock
+    pub fn clients(&self) -> HttpClients {
+        self.clients.clone()
+    }
+
+    /// Fetch the signed managed config and, if it changed, reload the
+    /// settings and rewrite OpenCode's config. Returns whether it changed.
+    pub async fn sync_managed_config(&mut self) -> Result<bool, ManagedConfigError> {
+        if !ManagedConfigService::fetch(&self.clients, &self.config.managed_config).await? {
+            return Ok(false);
+        }
+        self.reload_config();
+        if let Err(e) = OpenCodeService::ensure_dymium_provider(&self.live_config()) {
+            log::warn!("Failed to update OpenCode config: {}", e);
+        }
+        self.activity.record("Managed settings updated");
+        Ok(true)
+    }
+
+    /// Persist config to disk, routing secrets into the vault when enabled
+    fn save_config(&self) -> Result<(), TokenError> {
+        let secrets = Secrets::from_config(&self.config);
+        if self.session_only {
+            // Only the settings are saved
+        } else if self.config.vault_enabled {
+            self.vault.store(&secrets)?;
+        } else if self.config.secrets_in_keystore {
+            if self.keystore_unreadable {
+                log::warn!("Keystore couldn't be read at startup; keeping entries not loaded");
+            }
+            KeystoreService::save_secrets(&secrets, !self.keystore_unreadable)?;
+        } else {
+            return self
+                .config
+                .save()
+                .map_err(|e| TokenError::ConfigError(e.to_string()));
+        }
+
+        let mut scrubbed = self.config.clone();
+        Secrets::scrub(&mut scrubbed);
+        scrubbed
+            .save()
+            .map_err(|e| TokenError::ConfigError(e.to_string()))
+    }
+
+    /// Store newly entered credentials in the keystore from the start rather
+    /// than in config.json, unless the vault or a session login holds them.
+    /// They replace all stored ones, so unset entries may be deleted. If the
+    /// keystore is unavailable they go to config.json, and are migrated at a
+    /// later launch.
+    fn prefer_keystore(&mut self) {
+        self.keystore_unreadable = false;
+        if self.session_only || self.config.vault_enabled || self.config.secrets_in_keystore {
+            return;
+        }
+        match KeystoreService::save_secrets(&Secrets::from_config(&self.config), true) {
+            Ok(()) => self.config.secrets_in_keystore = true,
+            Err(e) => log::error!(
+                "Keystore unavailable, saving credentials to config.json: {}",
+                e
+            ),
+        }
+    }
+
+    /// Fail early when the vault is enabled but locked
+    fn ensure_unlocked(&self) -> Result<(), TokenError> {
+        if let Some(message) = &self.blocked {
+            Err(TokenError::ClientDisabled(message.clone()))
+        } else if self.idle_locked {
+            Err(TokenError::IdleLocked)
+        } else if self.is_locked() {
+            Err(TokenError::VaultError(VaultError::Locked))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether credentials are waiting behind a locked vault
+    pub fn is_locked(&self) -> bool {
+        !self.session_only && self.config.vault_enabled && !self.vault.is_unlocked()
+    }
+
+    /// Record user activity for the vault auto-lock timer
+    pub fn record_activity(&mut self) {
+        self.vault.touch();
+    }
+
+    /// Whether the unlocked vault has been idle past the auto-lock timeout
+    pub fn auto_lock_due(&self) -> bool {
+        let minutes = self.config.vault_auto_lock_minutes;
+        self.config.vault_enabled
+            && self.vault.is_unlocked()
+            && minutes > 0
+            && self.vault.idle_for() >= std::time::Duration::from_secs(minutes * 60)
+    }
+
+    /// Whether the idle lock should engage: `idleLock` is on, there is
+    /// something to lock and the app has been idle past `idleHours` (or the
+    /// screen is locked, with `onScreenLock`)
+    pub fn idle_lock_due(&self, screen_locked: bool) -> bool {
+        let policy = &self.config.idle_lock;
+        policy.enabled
+            && !self.idle_locked
+            && !self.state.is_locked()
+            && self.has_credentials()
+            && ((policy.on_screen_lock && screen_locked)
+                || (policy.idle_hours > 0
+                    && self.vault.idle_for()
+                        >= std::time::Duration::from_secs(policy.idle_hours * 3600)))
+    }
+
+    /// Whether the idle lock is engaged
+    pub fn is_idle_locked(&self) -> bool {
+        self.idle_locked
+    }
+
+    /// Purge the access token from memory and disk and stop refreshing until
+    /// `unlock_idle`
+    pub fn idle_lock(&mut self) {
+        self.clear_cached_credentials();
+        if self.config.vault_enabled {
+            self.vault.lock();
+            Secrets::scrub(&mut self.config);
+        }
+        self.idle_locked = true;
+        self.state = TokenState::Locked;
+        self.last_refresh = None;
+        self.audit("idle_lock", &Ok(()));
+        self.activity.record("Locked after inactivity");
+        log::info!("Idle lock engaged; access token purged");
+    }
+
+    /// Release the idle lock. With the vault enabled this needs the master
+    /// password; otherwise the caller confirms the user with the OS first.
+    /// The caller then re-authenticates with `start_refresh_loop`.
+    pub fn unlock_idle(&mut self, master_password: Option<&str>) -> Result<(), TokenError> {
+        if !self.idle_locked {
+            return Ok(());
+        }
+        if self.config.vault_enabled {
+            let password = master_password.ok_or(TokenError::VaultError(VaultError::Locked))?;
+            if let Err(e) = self
+                .vault
+                .unlock(password)
+                .map(|s| s.apply(&mut self.config))
+            {
+                let result = Err(e.into());
+                self.audit("idle_unlock", &result);
+                return result;
+            }
+        }
+        self.idle_locked = false;
+        self.state = TokenState::Idle;
+        self.vault.touch();
+        self.audit("idle_unlock", &Ok(()));
+        self.activity.record("Unlocked");
+        Ok(())
+    }
+
+    /// Why an administrator disabled this client, if they did
+    pub fn blocked(&self) -> Option<&str> {
+        self.blocked.as_deref()
+    }
+
+    /// Ask `revocation.url` whether this client is disabled and apply the
+    /// answer. Returns whether the block was set or lifted; after a lift the
+    /// caller re-authenticates with `start_refresh_loop`. Failed checks are
+    /// logged and change nothing.
+    pub async fn check_revocation(&mut self) -> bool {
+        let Some(url) = self.config.revocation.url.clone() else {
+            return false;
+        };
+        let token = match &self.state {
+            TokenState::Authenticated { token, .. } => Some(token.as_str()),
+            _ => None,
+        };
+        let verdict = match RevocationService::check(&self.clients, &url, token).await {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                log::warn!("Revocation check failed: {}", e);
+                return false;
+            }
+        };
+        match (verdict, self.blocked.is_some()) {
+            (Some(message), false) => {
+                self.disable(message);
+                true
+            }
+            (None, true) => {
+                self.enable();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Wipe the tokens and refuse to authenticate until `enable`
+    fn disable(&mut self, message: String) {
+        self.clear_cached_credentials();
+        self.config.refresh_token = None;
+        self.config.static_api_key = None;
+        if !self.is_locked() {
+            if let Err(e) = self.save_config() {
+                log::error!("Failed to remove stored tokens: {}", e);
+            }
+        }
+        if let Err(e) = RevocationService::set_blocked(Some(&message)) {
+            log::error!("Failed to remember the block: {}", e);
+        }
+        self.state = TokenState::failed(&TokenError::ClientDisabled(message.clone()));
+        self.last_refresh = None;
+        self.blocked = Some(message);
+        self.audit("remote_disable", &Ok(()));
+        self.activity.record("Disabled by your administrator");
+        log::warn!("Client disabled remotely; tokens wiped");
+    }
+
+    /// Lift the block set by `disable`
+    fn enable(&mut self) {
+        if let Err(e) = RevocationService::set_blocked(None) {
+            log::error!("Failed to forget the block: {}", e);
+        }
+        self.blocked = None;
+        self.state = TokenState::Idle;
+        self.audit("remote_enable", &Ok(()));
+        self.activity.record("Re-enabled by your administrator");
+        log::info!("Client re-enabled remotely");
+    }
+
+    /// Protect stored credentials with a master password
+    pub fn enable_vault(&mut self, master_password: &str) -> Result<(), TokenError> {
+        if self.config.vault_enabled {
+            return Err(TokenError::ConfigError(
+                "Master password is already enabled".to_string(),
+            ));
+        }
+
+        self.vault
+            .create(master_password, &Secrets::from_config(&self.config))?;
+        let was_in_keystore = self.config.secrets_in_keystore;
+        self.config.vault_enabled = true;
+        self.config.secrets_in_keystore = false;
+        if let Err(e) = self.save_config() {
+            // Roll back so the secrets stay where they were
+            self.config.vault_enabled = false;
+            self.config.secrets_in_keystore = was_in_keystore;
+            self.vault.lock();
+            let _ = Vault::destroy();
+            let result = Err(e);
+            self.audit("enable_vault", &result);
+            return result;
+        }
+        if was_in_keystore {
+            for key in CredentialKey::ALL {
+                let _ = KeystoreService::delete(key);
+            }
+        }
+
+        self.audit("enable_vault", &Ok(()));
+        log::info!("Master-password vault enabled");
+        Ok(())
+    }
+
+    /// Remove the master password and store credentials in config.json again
+    pub fn disable_vault(&mut self, master_password: &str) -> Result<(), TokenError> {
+        if !self.config.vault_enabled {
+            return Ok(());
+        }
+
+        let secrets = self.vault.unlock(master_password)?;
+        secrets.apply(&mut self.config);
+        self.config.vault_enabled = false;
+        self.config.secrets_in_keystore = false;
+        self.save_config()?;
+        self.vault.lock();
+        Vault::destroy()?;
+
+        // Secrets are back in config.json; move them on to the keystore
+        MigrationService::migrate_plaintext_secrets(&mut self.config);
+
+        self.audit("disable_vault", &Ok(()));
+        log::info!("Master-password vault disabled");
+        Ok(())
+    }
+
+    /// Unlock the vault and load the stored credentials
+    pub fn unlock_vault(&mut self, master_password: &str) -> Result<(), TokenError> {
+        let secrets = self.vault.unlock(master_password)?;
+        secrets.apply(&mut self.config);
+        if self.state.is_locked() {
+            self.state = TokenState::Idle;
+        }
+        Ok(())
+    }
+
+    /// Lock the vault and drop credentials from memory
+    pub fn lock_vault(&mut self) {
+        if !self.config.vault_enabled {
+            return;
+        }
+        self.vault.lock();
+        Secrets::scrub(&mut self.config);
+        self.publish_upstream(None);
+        self.state = TokenState::Locked;
+        self.last_refresh = None;
+    }
+
+    /// Report `Authenticating` ahead of a `start_refresh_loop` that is about
+    /// to run, so the status doesn't read "not configured" until it does
+    pub fn begin_authentication(&mut self) {
+        if matches!(self.state, TokenState::Idle) && self.has_credentials() && !self.is_locked() {
+            self.state = TokenState::Authenticating;
+        }
     }
 
     /// Start the token refresh loop (or just set static key)
     pub async fn start_refresh_loop(&mut self) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
         let result = if self.config.is_static_key_mode() {
             self.setup_static_api_key().await
         } else {
             self.authenticate().await
         };
+        self.audit("authenticate", &result);
+        Telemetry::record(&self.config.telemetry, self.config.auth_mode, &result);
 
         if let Err(ref e) = result {
-            self.state = TokenState::Failed {
-                error: e.to_string(),
-            };
+            self.activity
+                .record(format!("Authentication failed: {}", e));
+            self.state = TokenState::failed(e);
         }
 
         result
@@ -110,22 +796,12 @@ impl TokenService {
             .ok_or_else(|| TokenError::ConfigError("No static API key configured".to_string()))?
             .clone();
 
-        self.state = TokenState::Authenticating;
-
-        // Write the static API key as the token
-        self.write_token(&api_key)?;
-        log::info!("Static API key written to token file");
-
-        // Ensure OpenCode config and update auth.json
-        OpenCodeService::ensure_dymium_provider(&self.config).map_err(|e| {
-            TokenError::ConfigError(format!("Failed to update OpenCode config: {}", e))
-        })?;
-
-        log::info!("Updated opencode.json with static API key");
-
-        // Verify the endpoint actually works before declaring success
         self.state = TokenState::Verifying;
-        self.verify_endpoint(&api_key).await?;
+        let models = self.verify_and_commit(&api_key).await?;
+        log::info!("Updated token file and opencode.json with static API key");
+        self.sync_models(models);
+        self.sync_integrations(&api_key);
+        self.publish_upstream(Some(&api_key));
 
         // Static keys don't expire, so use a far-future date
         let far_future = Utc::now() + Duration::days(365);
@@ -188,7 +864,7 @@ impl TokenService {
         // Store refresh token if we got one
         if let Some(ref refresh_token) = response.refresh_token {
             self.config.refresh_token = Some(refresh_token.clone());
-            if let Err(e) = self.config.save() {
+            if let Err(e) = self.save_config() {
                 log::error!("Failed to save refresh token: {}", e);
             }
             if let Some(expires_in) = response.refresh_expires_in {
@@ -196,23 +872,15 @@ impl TokenService {
             }
         }
 
-        // Write access token to disk
-        self.write_token(&response.access_token)?;
-        log::info!("Access token written to token file");
-
-        // Ensure OpenCode config and update auth.json
-        OpenCodeService::ensure_dymium_provider(&self.config).map_err(|e| {
-            TokenError::ConfigError(format!("Failed to update OpenCode config: {}", e))
-        })?;
-
+        self.state = TokenState::Verifying;
+        let models = self.verify_and_commit(&response.access_token).await?;
         log::info!(
-            "Updated opencode.json with OAuth token, expires at {}",
+            "Updated token file and opencode.json with OAuth token, expires at {}",
             expires_at
         );
-
-        // Verify the endpoint actually works
-        self.state = TokenState::Verifying;
-        self.verify_endpoint(&response.access_token).await?;
+        self.sync_models(models);
+        self.sync_integrations(&response.access_token);
+        self.publish_upstream(Some(&response.access_token));
 
         self.state = TokenState::Authenticated {
             token: response.access_token,
@@ -223,10 +891,300 @@ impl TokenService {
         Ok(())
     }
 
+    /// Re-apply opencode.json / auth.json sections removed or corrupted outside
+    /// the app. Returns the sections that were restored (empty if none were).
+    pub fn repair_opencode_config(&self) -> Result<Vec<&'static str>, TokenError> {
+        if !self.has_credentials() {
+            return Ok(Vec::new());
+        }
+        let missing = OpenCodeService::missing_managed_sections(&self.config);
+        if missing.is_empty() {
+            return Ok(missing);
+        }
+
+        log::warn!(
+            "Managed OpenCode config changed externally ({}); re-applying",
+            missing.join(", ")
+        );
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?;
+        Ok(missing)
+    }
+
+    /// Write the models served by the endpoint into opencode.json.
+    /// Failures are logged only — the token itself is fine.
+    fn sync_models(&mut self, models: Vec<DiscoveredModel>) {
+        if let Err(e) = OpenCodeService::sync_discovered_models(&self.live_config(), &models) {
+            log::warn!("Failed to sync models into opencode.json: {}", e);
+        }
+        self.discovered_models = models;
+        self.models_fetched_at = Some(Utc::now());
+    }
+
+    /// Models the endpoint lists. The last answer is reused for
+    /// `modelsCacheTtlMinutes` unless `force_refresh` is set.
+    pub async fn list_models(&mut self, force_refresh: bool) -> Result<ModelList, TokenError> {
+        let ttl = Duration::minutes(self.config.models_cache_ttl_minutes as i64);
+        if let Some(fetched_at) = self.models_fetched_at {
+            if !force_refresh && Utc::now() - fetched_at < ttl {
+                return Ok(ModelList {
+                    models: self.discovered_models.clone(),
+                    fetched_at,
+                });
+            }
+        }
+
+        let TokenState::Authenticated { token, .. } = &self.state else {
+            return Err(TokenError::ConfigError("Not authenticated".to_string()));
+        };
+        let token = token.clone();
+        let effective_url = OpenCodeService::compute_base_url(&self.live_config());
+        let (_, models) = self.check_endpoint(&effective_url, &token).await?;
+        self.sync_models(models);
+        Ok(ModelList {
+            models: self.discovered_models.clone(),
+            fetched_at: Utc::now(),
+        })
+    }
+
+    /// Models offered to other tools: the catalog if curated, otherwise
+    /// whatever the endpoint listed
+    fn models(&self) -> Vec<ModelEntry> {
+        if self.config.model_catalog.is_empty() {
+            self.discovered_models
+                .iter()
+                .map(|m| ModelEntry {
+                    id: m.id.clone(),
+                    name: None,
+                    context_limit: m.context_length,
+                    output_limit: m.max_output_tokens,
+                    reasoning: None,
+                })
+                .collect()
+        } else {
+            self.config.model_catalog.clone()
+        }
+    }
+
+    /// Remove Dymium credentials from integrations. Those whose entries are
+    /// unmistakably ours are cleaned up even when disabled; the rest only when
+    /// enabled, since their settings may be the user's own.
+    fn clear_integrations(&self) {
+        for integration in integrations::REGISTRY {
+            if integration.owns_entries() || self.config.integrations.is_enabled(integration.id()) {
+                integration.clear(&self.config);
+            }
+        }
+    }
+
+    /// Delete the backups of OpenCode's and the integrations' files, which
+    /// hold tokens and keys, once those have been cleared from the files
+    fn remove_credential_backups(&self) {
+        let mut paths: Vec<PathBuf> =
+            [OpenCodeService::config_path(), OpenCodeService::auth_path()]
+                .into_iter()
+                .flatten()
+                .collect();
+        for integration in integrations::REGISTRY {
+            paths.extend(integration.target_files(&self.config));
+        }
+        for path in paths {
+            match files::remove_backups(&path) {
+                Ok(0) => {}
+                Ok(count) => log::info!("Deleted {} backup(s) of {}", count, path.display()),
+                Err(e) => log::warn!("Failed to delete backups of {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Push the current token into the enabled integrations.
+    /// Failures are logged only — OpenCode is the primary consumer.
+    fn sync_integrations(&self, token: &str) {
+        if self.config.socket_only {
+            log::debug!("Socket-only mode; not writing the token into other tools");
+            return;
+        }
+        let models = self.models();
+        let config = self.live_config();
+        let ctx = SyncContext {
+            config: &config,
+            token,
+            models: &models,
+        };
+        for integration in integrations::REGISTRY {
+            if self.config.integrations.is_enabled(integration.id()) {
+                if let Err(e) = integration.sync(&ctx) {
+                    log::warn!("Failed to update {} config: {}", integration.name(), e);
+                }
+            }
+        }
+    }
+
+    /// Enable or disable an integration. Enabling syncs it right away when
+    /// authenticated; disabling removes what it wrote.
+    pub fn set_integration_enabled(&mut self, id: &str, enabled: bool) -> Result<(), TokenError> {
+        let integration = integrations::find(id)
+            .ok_or_else(|| TokenError::ConfigError(format!("Unknown integration: {}", id)))?;
+        self.config.integrations.set_enabled(id, enabled);
+        self.save_config()?;
+
+        if !enabled {
+            integration.clear(&self.config);
+        } else if let TokenState::Authenticated { token, .. } = &self.state {
+            self.sync_integrations(token);
+        }
+        log::info!(
+            "{} integration {}",
+            integration.name(),
+            if enabled { "enabled" } else { "disabled" }
+        );
+        Ok(())
+    }
+
     /// Verify the LLM endpoint is reachable and accepts our token.
     /// Uses the same effective URL that OpenCode will use (with app path for OIDC).
-    async fn verify_endpoint(&self, token: &str) -> Result<(), TokenError> {
-        let effective_url = OpenCodeService::compute_base_url(&self.config);
+    /// Tries `llmEndpoint` first, then each fallback while the previous one
+    /// is down, and switches OpenCode to whichever answered. Returns the
+    /// models the endpoint lists.
+    async fn verify_endpoint(&self, token: &str) -> Result<Vec<DiscoveredModel>, TokenError> {
+        let endpoints = self.config.llm_endpoints();
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            let mut config = self.config.clone();
+            config.llm_endpoint = endpoint.clone();
+            let effective_url = OpenCodeService::compute_base_url(&config);
+            let started = Instant::now();
+            let result = self.check_endpoint(&effective_url, token).await;
+            match &result {
+                Ok((status, _)) => {
+                    self.health
+                        .record(EndpointKind::LlmEndpoint, started, Some(*status), None)
+                }
+                Err(e) => self.health.record(
+                    EndpointKind::LlmEndpoint,
+                    started,
+                    e.status(),
+                    Some(e.to_string()),
+                ),
+            }
+            match result {
+                Ok((_, models)) => {
+                    if self.endpoint_failover.switch_to(index, endpoint) {
+                        self.switched_endpoint(index, endpoint)?;
+                    }
+                    self.activity
+                        .record(format!("Endpoint verified ({} models)", models.len()));
+                    return Ok(models);
+                }
+                Err(e) if is_outage(&e) && index + 1 < endpoints.len() => {
+                    log::warn!(
+                        "LLM endpoint {} is down ({}), trying {}",
+                        endpoint,
+                        e,
+                        endpoints[index + 1]
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        // `llm_endpoints` always has at least `llm_endpoint`
+        Err(TokenError::InvalidUrl)
+    }
+
+    /// Point OpenCode at the endpoint now in use
+    fn switched_endpoint(&self, index: usize, endpoint: &str) -> Result<(), TokenError> {
+        let message = if index == 0 {
+            let message = format!("Primary endpoint recovered: {}", endpoint);
+            log::info!("{}", message);
+            message
+        } else {
+            let message = format!("Switched to fallback endpoint {}", endpoint);
+            log::warn!("{}", message);
+            message
+        };
+        self.activity.record(message);
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))
+    }
+
+    /// Run the endpoint verification against unsaved values. With `app`, the
+    /// app path is inserted into the URL as in OAuth mode.
+    pub async fn test_endpoint(
+        &self,
+        endpoint: &str,
+        key: &str,
+        app: Option<String>,
+    ) -> Result<EndpointTest, TokenError> {
+        let app = app.filter(|a| !a.trim().is_empty());
+        let mut config = self.config.clone();
+        config.auth_mode = if app.is_some() {
+            AuthMode::OAuth
+        } else {
+            AuthMode::StaticKey
+        };
+        config.llm_endpoint = endpoint.trim().to_string();
+        config.ghostllm_app = app;
+        let base_url = OpenCodeService::compute_base_url(&config);
+        let (_, models) = self.check_endpoint(&base_url, key).await?;
+        Ok(EndpointTest {
+            base_url,
+            models: models.into_iter().map(|m| m.id).collect(),
+        })
+    }
+
+    /// Dry run of OAuth setup: password grant, then endpoint verification,
+    /// with the supplied values. Nothing is saved or written.
+    pub async fn validate_oauth(&self, settings: OAuthSettings) -> Vec<ValidationStep> {
+        let mut config = self.config.clone();
+        config.auth_mode = AuthMode::OAuth;
+        config.keycloak_url = settings.keycloak_url;
+        config.realm = settings.realm;
+        config.client_id = settings.client_id;
+        config.username = settings.username;
+        config.llm_endpoint = settings.llm_endpoint;
+        config.ghostllm_app = settings.ghostllm_app;
+        config.client_secret = Some(settings.client_secret);
+        config.password = Some(settings.password);
+
+        let mut steps = Vec::new();
+        let token = match self.password_grant(&config).await {
+            Ok(response) => {
+                steps.push(ValidationStep::passed(
+                    "keycloak",
+                    format!("Token issued, valid for {}s", response.expires_in),
+                ));
+                Some(response.access_token)
+            }
+            Err(e) => {
+                steps.push(ValidationStep::failed("keycloak", e.to_string()));
+                None
+            }
+        };
+
+        let base_url = OpenCodeService::compute_base_url(&config);
+        steps.push(match token {
+            Some(token) => match self.check_endpoint(&base_url, &token).await {
+                Ok((_, models)) => ValidationStep::passed(
+                    "endpoint",
+                    format!("{} lists {} model(s)", base_url, models.len()),
+                ),
+                Err(e) => ValidationStep::failed("endpoint", e.to_string()),
+            },
+            None => ValidationStep {
+                step: "endpoint",
+                status: StepStatus::Skipped,
+                detail: "Needs a token from Keycloak".to_string(),
+            },
+        });
+        steps
+    }
+
+    /// GET `/v1/models` under `effective_url` with `token`. Returns the
+    /// response status and the listed models.
+    async fn check_endpoint(
+        &self,
+        effective_url: &str,
+        token: &str,
+    ) -> Result<(u16, Vec<DiscoveredModel>), TokenError> {
         let effective_trimmed = effective_url.trim_end_matches('/');
 
         // Build the models URL from the effective base
@@ -238,57 +1196,103 @@ impl TokenService {
 
         log::info!("Verifying endpoint: GET {}", models_url);
 
-        let response = self
-            .client
+        let mut request = self
+            .llm_clients
+            .for_url(&models_url)?
             .get(&models_url)
             .header("Authorization", format!("Bearer {}", token))
-            .header("Host", extract_hostname(&models_url))
-            .send()
-            .await
-            .map_err(|e| {
-                let msg = if e.is_connect() {
-                    format!("Cannot reach LLM endpoint ({})", effective_trimmed)
-                } else if e.is_timeout() {
-                    format!("LLM endpoint timed out ({})", effective_trimmed)
-                } else {
-                    format!("LLM endpoint error: {}", e)
-                };
-                TokenError::ConfigError(msg)
-            })?;
+            .header("Host", extract_hostname(&models_url));
+        for (name, value) in &self.config.llm_headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.map_err(|e| {
+            if let Some(failure) = certificate_error(&e) {
+                failure.into()
+            } else if e.is_connect() {
+                TokenError::EndpointUnreachable(format!(
+                    "Cannot reach LLM endpoint ({})",
+                    effective_trimmed
+                ))
+            } else if e.is_timeout() {
+                TokenError::EndpointTimeout(format!(
+                    "LLM endpoint timed out ({})",
+                    effective_trimmed
+                ))
+            } else {
+                TokenError::ConfigError(format!("LLM endpoint error: {}", e))
+            }
+        })?;
 
         let status = response.status();
         if status.is_success() {
             log::info!("Endpoint verified: {} returned {}", models_url, status);
-            Ok(())
+            match response.json::<ModelsResponse>().await {
+                Ok(models) => {
+                    log::info!("Endpoint lists {} model(s)", models.data.len());
+                    Ok((status.as_u16(), models.data))
+                }
+                Err(e) => {
+                    log::warn!("Could not parse model list from {}: {}", models_url, e);
+                    Ok((status.as_u16(), Vec::new()))
+                }
+            }
         } else if status.as_u16() == 401 {
-            let body = response.text().await.unwrap_or_default();
+            let body = redact_secrets(&response.text().await.unwrap_or_default());
             log::warn!("Endpoint rejected token: {} {}", status, body);
-            Err(TokenError::ConfigError(
-                "LLM endpoint rejected the API key (401 Unauthorized)".to_string(),
-            ))
+            Err(TokenError::EndpointRejected {
+                status: 401,
+                message: "LLM endpoint rejected the API key (401 Unauthorized)".to_string(),
+            })
         } else {
-            let body = response.text().await.unwrap_or_default();
+            let body = redact_secrets(&response.text().await.unwrap_or_default());
             log::warn!("Endpoint returned {}: {}", status, body);
-            Err(TokenError::ConfigError(format!(
-                "LLM endpoint returned {} — check endpoint URL",
-                status
-            )))
+            Err(TokenError::EndpointRejected {
+                status: status.as_u16(),
+                message: format!("LLM endpoint returned {} — check endpoint URL", status),
+            })
         }
     }
 
     /// Perform password grant authentication
     async fn perform_password_grant(&self) -> Result<KeycloakTokenResponse, TokenError> {
-        let url = &self.config.token_endpoint_url();
+        let started = Instant::now();
+        let result = self.password_grant(&self.config).await;
+        self.record_keycloak_health(started, &result);
+        result
+    }
 
-        let client_secret = self
-            .config
+    /// Remember how a live token request to Keycloak went
+    fn record_keycloak_health(
+        &self,
+        started: Instant,
+        result: &Result<KeycloakTokenResponse, TokenError>,
+    ) {
+        match result {
+            Ok(response) => {
+                self.health
+                    .record(EndpointKind::Keycloak, started, Some(response.status), None)
+            }
+            Err(e) => self.health.record(
+                EndpointKind::Keycloak,
+                started,
+                e.status(),
+                Some(e.to_string()),
+            ),
+        }
+    }
+
+    /// Password grant with the Keycloak settings and credentials in `config`
+    async fn password_grant(
+        &self,
+        config: &AppConfig,
+    ) -> Result<KeycloakTokenResponse, TokenError> {
+        let client_secret = config
             .client_secret
             .as_ref()
             .filter(|s| !s.is_empty())
             .ok_or(TokenError::MissingClientSecret)?;
 
-        let password = self
-            .config
+        let password = config
             .password
             .as_ref()
             .filter(|s| !s.is_empty())
@@ -296,37 +1300,91 @@ impl TokenService {
 
         let params = [
             ("grant_type", "password"),
-            ("client_id", &self.config.client_id),
+            ("client_id", &config.client_id),
             ("client_secret", client_secret),
-            ("username", &self.config.username),
+            ("username", &config.username),
             ("password", password),
         ];
 
-        let response = self.client.post(url).form(&params).send().await?;
+        let response = self.send_token_request(config, &params).await?;
 
         let status = response.status();
         if !status.is_success() {
-            let body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+            // Keycloak may echo parts of the request back
+            let body = redact_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string()),
+            );
             return Err(TokenError::AuthFailed {
                 status: status.as_u16(),
                 body,
             });
         }
 
-        let token_response: KeycloakTokenResponse = response.json().await?;
+        let mut token_response: KeycloakTokenResponse = response.json().await?;
+        token_response.status = status.as_u16();
         Ok(token_response)
     }
 
+    /// POST `params` to the token endpoint, failing over to the next
+    /// Keycloak in `config` while one can't be reached or answers 5xx
+    async fn send_token_request(
+        &self,
+        config: &AppConfig,
+        params: &[(&str, &str)],
+    ) -> Result<reqwest::Response, TokenError> {
+        let urls = self.keycloak_failover.order(config.token_endpoint_urls());
+        let mut urls = urls.iter().peekable();
+        while let Some(url) = urls.next() {
+            let result = self
+                .clients
+                .for_url(url)?
+                .post(url)
+                .form(params)
+                .send()
+                .await;
+            let failure = match &result {
+                Ok(response) if response.status().is_server_error() => {
+                    format!("status {}", response.status().as_u16())
+                }
+                // Certificate problems are not outages; don't route around them
+                Err(e) if (e.is_connect() || e.is_timeout()) && certificate_error(e).is_none() => {
+                    e.to_string()
+                }
+                _ => {
+                    if result.is_ok() {
+                        self.keycloak_failover.report_up(url);
+                    }
+                    return result.map_err(keycloak_error);
+                }
+            };
+            self.keycloak_failover.report_down(url);
+            match urls.peek() {
+                Some(next) => log::warn!("Keycloak {} failed ({}), trying {}", url, failure, next),
+                None => return result.map_err(keycloak_error),
+            }
+        }
+        Err(TokenError::InvalidUrl)
+    }
+
     /// Perform refresh token grant
     async fn perform_refresh_token_grant(
         &mut self,
         refresh_token: String,
     ) -> Result<KeycloakTokenResponse, TokenError> {
-        let url = &self.config.token_endpoint_url();
+        let started = Instant::now();
+        let result = self.refresh_token_grant(refresh_token).await;
+        self.record_keycloak_health(started, &result);
+        result
+    }
 
+    /// Exchange the refresh token; drops it if Keycloak rejects it outright
+    async fn refresh_token_grant(
+        &mut self,
+        refresh_token: String,
+    ) -> Result<KeycloakTokenResponse, TokenError> {
         let client_secret = self
             .config
             .client_secret
@@ -341,14 +1399,17 @@ impl TokenService {
             ("refresh_token", &refresh_token),
         ];
 
-        let response = self.client.post(url).form(&params).send().await?;
+        let response = self.send_token_request(&self.config, &params).await?;
 
         let status = response.status();
         if !status.is_success() {
-            let body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+            // Keycloak may echo parts of the request back
+            let body = redact_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string()),
+            );
             log::warn!(
                 "Refresh token grant failed with status {}: {}",
                 status.as_u16(),
@@ -359,7 +1420,7 @@ impl TokenService {
             if status.as_u16() == 400 || status.as_u16() == 401 {
                 log::info!("Clearing invalid refresh token");
                 self.config.refresh_token = None;
-                let _ = self.config.save();
+                let _ = self.save_config();
             }
 
             return Err(TokenError::AuthFailed {
@@ -368,14 +1429,64 @@ impl TokenService {
             });
         }
 
-        let token_response: KeycloakTokenResponse = response.json().await?;
+        let mut token_response: KeycloakTokenResponse = response.json().await?;
+        token_response.status = status.as_u16();
         Ok(token_response)
     }
 
+    /// Verify the endpoint accepts `token`, then make it the current one.
+    /// The OpenCode files are staged while the endpoint is asked, but only
+    /// written once it accepted the token.
+    async fn verify_and_commit(&mut self, token: &str) -> Result<Vec<DiscoveredModel>, TokenError> {
+        let config = self.live_config().into_owned();
+        let staged_endpoint = config.llm_endpoint.clone();
+        let staged_token = token.to_string();
+        let stage =
+            tokio::task::spawn_blocking(move || OpenCodeService::stage(&config, &staged_token));
+        let (verified, staged) = tokio::join!(self.verify_endpoint(token), stage);
+        let models = verified?;
+        let staged = staged
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?;
+        if self.live_config().llm_endpoint == staged_endpoint {
+            self.write_token(token)?;
+            OpenCodeService::commit_staged(&self.live_config(), staged)
+                .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?;
+        } else {
+            // Verification failed over; the staged files name the old endpoint
+            self.commit_token(token)?;
+        }
+        Ok(models)
+    }
+
+    /// Hand a token the endpoint accepted to OpenCode: the token file, then
+    /// opencode.json and auth.json. Callers verify first, so a failed
+    /// rotation leaves the previous, working token everywhere.
+    fn commit_token(&self, token: &str) -> Result<(), TokenError> {
+        self.write_token(token)?;
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))
+    }
+
     /// Write token to disk
     fn write_token(&self, token: &str) -> Result<(), TokenError> {
         let path = AppConfig::token_path().map_err(|e| TokenError::ConfigError(e.to_string()))?;
 
+        if self.config.socket_only {
+            // Served from memory only; drop any file left from before
+            match fs::remove_file(&path) {
+                Ok(()) => log::info!("Removed {} (socket-only mode)", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            if !self.config.proxy.enabled {
+                log::warn!(
+                    "Socket-only mode without the local proxy: OpenCode can't reach GhostLLM"
+                );
+            }
+            return Ok(());
+        }
+
         // Ensure directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
@@ -407,6 +1518,18 @@ impl TokenService {
             return Ok(());
         }
 
+        let result = self.refresh_access_token().await;
+        self.audit("refresh_token", &result);
+        Telemetry::record(&self.config.telemetry, self.config.auth_mode, &result);
+        match &result {
+            Ok(()) => self.activity.record("Token refreshed"),
+            Err(e) => self.activity.record(format!("Refresh failed: {}", e)),
+        }
+        result
+    }
+
+    /// Get a new access token and hand it to OpenCode and the integrations
+    async fn refresh_access_token(&mut self) -> Result<(), TokenError> {
         log::info!("Periodic token refresh tick");
 
         // Try refresh token first, fall back to password grant
@@ -433,16 +1556,23 @@ impl TokenService {
         // Store refresh token if we got a new one
         if let Some(ref refresh_token) = response.refresh_token {
             self.config.refresh_token = Some(refresh_token.clone());
-            if let Err(e) = self.config.save() {
+            if let Err(e) = self.save_config() {
                 log::error!("Failed to save refresh token: {}", e);
             }
         }
 
-        // Write new access token to disk files
-        self.write_token(&response.access_token)?;
-        OpenCodeService::ensure_dymium_provider(&self.config).map_err(|e| {
-            TokenError::ConfigError(format!("Failed to update OpenCode config: {}", e))
-        })?;
+        // Until the endpoint accepts the new token, OpenCode keeps the
+        // current one, which is still valid until it expires. Power saving
+        // leaves the check out while the last accepted token is recent.
+        if PowerService::skip_endpoint_check(&self.config.power_saving, self.models_fetched_at) {
+            log::info!("Power saving: not checking the refreshed token with the endpoint");
+            self.commit_token(&response.access_token)?;
+        } else {
+            let models = self.verify_and_commit(&response.access_token).await?;
+            self.sync_models(models);
+        }
+        self.sync_integrations(&response.access_token);
+        self.publish_upstream(Some(&response.access_token));
 
         self.state = TokenState::Authenticated {
             token: response.access_token,
@@ -456,16 +1586,19 @@ impl TokenService {
 
     /// Manually trigger a refresh
     pub async fn manual_refresh(&mut self) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
         let result = if self.config.is_static_key_mode() {
             self.setup_static_api_key().await
         } else {
             self.authenticate().await
         };
+        self.audit("manual_refresh", &result);
+        Telemetry::record(&self.config.telemetry, self.config.auth_mode, &result);
 
         if let Err(ref e) = result {
-            self.state = TokenState::Failed {
-                error: e.to_string(),
-            };
+            self.activity
+                .record(format!("Authentication failed: {}", e));
+            self.state = TokenState::failed(e);
         }
 
         result
@@ -473,19 +1606,29 @@ impl TokenService {
 
     /// Log out - clear all stored credentials and tokens
     pub fn log_out(&mut self) -> Result<(), TokenError> {
+        let result = self.wipe_credentials();
+        self.audit("log_out", &result);
+        result
+    }
+
+    fn wipe_credentials(&mut self) -> Result<(), TokenError> {
         // Clear credentials from config
         self.config.client_secret = None;
         self.config.password = None;
         self.config.refresh_token = None;
         self.config.static_api_key = None;
-        self.config
-            .save()
-            .map_err(|e| TokenError::ConfigError(e.to_string()))?;
+        if self.is_locked() {
+            // Can't re-encrypt without the master password; the vault only
+            // held the credentials being wiped, so drop it entirely.
+            self.config.vault_enabled = false;
+            Vault::destroy()?;
+        }
+        self.save_config()?;
 
         // Delete keystore entries
-        let _ = KeystoreService::delete(CredentialKey::ClientSecret);
-        let _ = KeystoreService::delete(CredentialKey::Password);
-        let _ = KeystoreService::delete(CredentialKey::RefreshToken);
+        for key in CredentialKey::ALL {
+            let _ = KeystoreService::delete(key);
+        }
 
         // Delete token file
         if let Ok(path) = AppConfig::token_path() {
@@ -494,6 +1637,10 @@ impl TokenService {
 
         // Remove only dymium credentials from OpenCode auth.json
         OpenCodeService::clear_dymium_auth();
+        self.clear_integrations();
+        self.remove_credential_backups();
+        self.publish_upstream(None);
+        self.health.clear();
 
         // Reset state
         self.state = TokenState::Idle;
@@ -503,6 +1650,37 @@ impl TokenService {
         Ok(())
     }
 
+    /// Forget one stored secret, e.g. after rotating it. Endpoint settings and
+    /// the other secrets are kept. If the remaining credentials can no longer
+    /// authenticate, the cached token is cleared as well.
+    pub fn delete_credential(&mut self, key: CredentialKey) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+        let mut secrets = Secrets::from_config(&self.config);
+        *key.field_mut(&mut secrets) = None;
+        secrets.apply(&mut self.config);
+        self.save_config()?;
+        if self.config.secrets_in_keystore && self.keystore_unreadable {
+            // save_config leaves unset entries alone in this case
+            KeystoreService::delete(key)?;
+        }
+        AuditLog::record(
+            "delete_credential",
+            Some(self.config.auth_mode),
+            "success",
+            key.as_str(),
+        );
+
+        if !self.has_credentials() {
+            self.clear_cached_credentials();
+            self.state = TokenState::Idle;
+            self.last_refresh = None;
+        }
+        self.activity
+            .record(format!("Deleted {}", key.as_str().replace('_', " ")));
+        log::info!("Deleted stored {}", key.as_str());
+        Ok(())
+    }
+
     /// Save OAuth configuration
     pub fn save_oauth_setup(
         &mut self,
@@ -515,6 +1693,8 @@ impl TokenService {
         client_secret: String,
         password: String,
     ) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+
         // Clear old credentials immediately when switching modes
         self.clear_cached_credentials();
 
@@ -530,11 +1710,13 @@ impl TokenService {
         self.config.refresh_token = None; // Clear old refresh token
         self.config.static_api_key = None;
 
-        self.config
-            .save()
-            .map_err(|e| TokenError::ConfigError(e.to_string()))?;
+        self.prefer_keystore();
+        let result = self.save_config();
+        self.audit("save_credentials", &result);
+        result?;
         self.state = TokenState::Idle;
         self.last_refresh = None;
+        self.activity.record("OAuth settings saved");
         log::info!("OAuth configuration saved");
         Ok(())
     }
@@ -546,6 +1728,8 @@ impl TokenService {
         static_api_key: String,
         ghostllm_app: Option<String>,
     ) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+
         // Clear old credentials immediately when switching modes
         self.clear_cached_credentials();
 
@@ -557,15 +1741,160 @@ impl TokenService {
         self.config.password = None;
         self.config.refresh_token = None;
 
-        self.config
-            .save()
-            .map_err(|e| TokenError::ConfigError(e.to_string()))?;
+        self.prefer_keystore();
+        let result = self.save_config();
+        self.audit("save_credentials", &result);
+        result?;
         self.state = TokenState::Idle;
         self.last_refresh = None;
+        self.activity.record("Static API key settings saved");
         log::info!("Static API key configuration saved");
         Ok(())
     }
 
+    /// Authenticate with `login` instead of the stored credentials. Changed
+    /// settings are saved; the credentials stay in memory, so nothing needs
+    /// the keystore or the vault.
+    pub fn use_session_login(&mut self, login: SessionLogin) -> Result<(), TokenError> {
+        let previous = Profile::from_config(&self.config);
+        let config = &mut self.config;
+        if let Some(auth_mode) = login.auth_mode {
+            config.auth_mode = auth_mode;
+        }
+        if let Some(llm_endpoint) = login.llm_endpoint {
+            config.llm_endpoint = llm_endpoint;
+        }
+        if let Some(ghostllm_app) = login.ghostllm_app {
+            config.ghostllm_app = Some(ghostllm_app);
+        }
+        if let Some(keycloak_url) = login.keycloak_url {
+            config.keycloak_url = keycloak_url;
+        }
+        if let Some(realm) = login.realm {
+            config.realm = realm;
+        }
+        if let Some(client_id) = login.client_id {
+            config.client_id = client_id;
+        }
+        if let Some(username) = login.username {
+            config.username = username;
+        }
+        let mut secrets = Secrets::from_config(config);
+        let mut supplied = login.secrets;
+        for key in CredentialKey::ALL {
+            if let Some(value) = key.field_mut(&mut supplied).take() {
+                *key.field_mut(&mut secrets) = Some(value);
+            }
+        }
+        secrets.apply(config);
+        if !Profile::from_config(&self.config).same_identity(&previous) {
+            self.config.refresh_token = None;
+        }
+
+        self.session_only = true;
+        self.state = TokenState::Idle;
+        let result = self.save_config();
+        self.audit("session_login", &result);
+        result
+    }
+
+    /// Save the current connection settings as a profile and make it active
+    pub fn save_profile(&mut self, name: &str) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TokenError::ConfigError(
+                "Profile name is required".to_string(),
+            ));
+        }
+        self.config
+            .profiles
+            .insert(name.to_string(), Profile::from_config(&self.config));
+        self.config.active_profile = Some(name.to_string());
+        self.save_config()?;
+        log::info!("Saved profile {}", name);
+        Ok(())
+    }
+
+    /// Remove a saved profile. The current settings are left as they are.
+    pub fn delete_profile(&mut self, name: &str) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+        if self.config.profiles.remove(name).is_none() {
+            return Err(TokenError::ConfigError(format!(
+                "No profile named {}",
+                name
+            )));
+        }
+        if self.config.active_profile.as_deref() == Some(name) {
+            self.config.active_profile = None;
+        }
+        self.save_config()?;
+        log::info!("Deleted profile {}", name);
+        Ok(())
+    }
+
+    /// Point `dir`'s project-local opencode.json at `profile`, or with
+    /// `None` stop managing it and remove the dymium provider there
+    pub fn set_workspace_profile(
+        &mut self,
+        dir: &str,
+        profile: Option<&str>,
+    ) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+        let dir = dir.trim();
+        let workspace_error = |e: WorkspaceError| TokenError::ConfigError(e.to_string());
+        match profile {
+            Some(profile) => {
+                WorkspaceService::write(&self.config, dir, profile).map_err(workspace_error)?;
+                self.config
+                    .workspaces
+                    .insert(dir.to_string(), profile.to_string());
+                self.activity
+                    .record(format!("{} uses profile {}", dir, profile));
+            }
+            None => {
+                if self.config.workspaces.remove(dir).is_none() {
+                    return Err(TokenError::ConfigError(format!(
+                        "No workspace profile for {}",
+                        dir
+                    )));
+                }
+                WorkspaceService::clear(dir).map_err(workspace_error)?;
+            }
+        }
+        self.save_config()
+    }
+
+    /// Load a profile's connection settings. The caller re-authenticates
+    /// with `start_refresh_loop`, which resyncs the managed files.
+    pub fn switch_profile(&mut self, name: &str) -> Result<(), TokenError> {
+        self.ensure_unlocked()?;
+        let profile = self
+            .config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| TokenError::ConfigError(format!("No profile named {}", name)))?;
+
+        // Don't keep serving the old endpoint's token
+        self.clear_cached_credentials();
+
+        if !profile.same_identity(&Profile::from_config(&self.config)) {
+            // Issued for another Keycloak client or user
+            self.config.refresh_token = None;
+        }
+        profile.apply(&mut self.config);
+        self.config.active_profile = Some(name.to_string());
+
+        self.save_config()?;
+        self.state = TokenState::Idle;
+        self.last_refresh = None;
+        self.activity
+            .record(format!("Switched to profile {}", name));
+        log::info!("Switched to profile {}", name);
+        Ok(())
+    }
+
     /// Clear cached credentials (token file and auth.json)
     /// Called when switching auth modes to prevent stale credentials from being used
     fn clear_cached_credentials(&self) {
@@ -582,6 +1911,196 @@ impl TokenService {
 
         // Clear dymium entry from auth.json
         OpenCodeService::clear_dymium_auth();
+        self.clear_integrations();
+        self.remove_credential_backups();
+        self.publish_upstream(None);
+        self.health.clear();
+    }
+
+    /// Curated models written to opencode.json
+    pub fn model_catalog(&self) -> &[ModelEntry] {
+        &self.config.model_catalog
+    }
+
+    /// Add a model to the catalog (`replace = false`) or edit an existing one
+    /// (`replace = true`), then rewrite opencode.json
+    pub fn upsert_catalog_model(
+        &mut self,
+        mut entry: ModelEntry,
+        replace: bool,
+    ) -> Result<(), TokenError> {
+        entry.id = entry.id.trim().to_string();
+        if entry.id.is_empty() {
+            return Err(TokenError::ConfigError(
+                "Model ID must not be empty".to_string(),
+            ));
+        }
+
+        let position = self
+            .config
+            .model_catalog
+            .iter()
+            .position(|m| m.id == entry.id);
+        match (position, replace) {
+            (Some(i), true) => self.config.model_catalog[i] = entry,
+            (None, false) => self.config.model_catalog.push(entry),
+            (Some(_), false) => {
+                return Err(TokenError::ConfigError(format!(
+                    "Model {} is already in the catalog",
+                    entry.id
+                )))
+            }
+            (None, true) => {
+                return Err(TokenError::ConfigError(format!(
+                    "Model {} is not in the catalog",
+                    entry.id
+                )))
+            }
+        }
+
+        self.apply_model_catalog()
+    }
+
+    /// Remove a model from the catalog, then rewrite opencode.json
+    pub fn remove_catalog_model(&mut self, id: &str) -> Result<(), TokenError> {
+        let before = self.config.model_catalog.len();
+        self.config.model_catalog.retain(|m| m.id != id);
+        if self.config.model_catalog.len() == before {
+            return Err(TokenError::ConfigError(format!(
+                "Model {} is not in the catalog",
+                id
+            )));
+        }
+        self.apply_model_catalog()
+    }
+
+    /// Stop (or resume) registering the Dymium plugin in opencode.json.
+    /// An existing plugin entry is left in place either way.
+    pub fn set_skip_opencode_plugin(&mut self, skip: bool) -> Result<(), TokenError> {
+        self.config.skip_opencode_plugin = skip;
+        self.save_config()?;
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))
+    }
+
+    /// Start the app at login (or stop doing so)
+    pub fn set_launch_at_login(&mut self, enabled: bool) -> Result<(), TokenError> {
+        AutostartService::set(enabled).map_err(|e| TokenError::ConfigError(e.to_string()))?;
+        self.config.launch_at_login = enabled;
+        self.save_config()
+    }
+
+    /// Take updates from `channel`
+    pub fn set_update_channel(&mut self, channel: UpdateChannel) -> Result<(), TokenError> {
+        self.config.update_channel = channel;
+        self.save_config()
+    }
+
+    /// Keep the token out of opencode.json (or write it there again)
+    pub fn set_skip_opencode_api_key(&mut self, skip: bool) -> Result<(), TokenError> {
+        self.config.skip_opencode_api_key = skip;
+        self.save_config()?;
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))
+    }
+
+    /// Files holding plaintext credentials
+    pub fn secret_advisories(&self) -> Vec<SecretFinding> {
+        SecretScan::scan(&self.config)
+    }
+
+    /// Move config.json secrets into the keystore and keep the token out of
+    /// opencode.json (while the auth plugin can supply it). Returns what is
+    /// left in the clear.
+    pub fn harden_secrets(&mut self) -> Result<Vec<SecretFinding>, TokenError> {
+        self.ensure_unlocked()?;
+        let result = self.do_harden_secrets();
+        self.audit("harden_secrets", &result);
+        result?;
+        self.activity.record("Stored credentials hardened");
+        Ok(self.secret_advisories())
+    }
+
+    fn do_harden_secrets(&mut self) -> Result<(), TokenError> {
+        if self.config.vault_enabled || self.session_only {
+            // Already kept elsewhere; saving scrubs config.json
+            self.save_config()?;
+        } else if let MigrationOutcome::Failed(reason) =
+            MigrationService::migrate_plaintext_secrets(&mut self.config)
+        {
+            return Err(TokenError::ConfigError(reason));
+        }
+        if !self.config.skip_opencode_plugin && !self.config.skip_opencode_api_key {
+            self.set_skip_opencode_api_key(true)?;
+        }
+        for finding in SecretScan::scan_backups() {
+            fs::remove_file(&finding.path)?;
+            log::info!("Deleted {}, which held credentials", finding.path);
+        }
+        Ok(())
+    }
+
+    /// Change the outgoing proxy, and its password unless `password` is
+    /// `None` (an empty one removes it)
+    pub fn set_egress_proxy(
+        &mut self,
+        egress_proxy: EgressProxyConfig,
+        password: Option<String>,
+    ) -> Result<(), TokenError> {
+        if let Some(password) = password {
+            EgressProxy::set_password(Some(&password))?;
+        }
+        self.config.egress_proxy = egress_proxy;
+        self.save_config()?;
+        (self.clients, self.llm_clients) = http_clients(&self.config);
+        Ok(())
+    }
+
+    /// Where outgoing requests currently go
+    pub fn egress_route(&self) -> EgressRoute {
+        EgressProxy::resolve(&self.config.egress_proxy)
+    }
+
+    /// Opt in to or out of telemetry; opting out drops unsent counts
+    pub fn set_telemetry(
+        &mut self,
+        enabled: bool,
+        endpoint: Option<String>,
+    ) -> Result<(), TokenError> {
+        self.config.telemetry.enabled = enabled;
+        self.config.telemetry.endpoint = endpoint.filter(|e| !e.trim().is_empty());
+        self.save_config()?;
+        if !enabled {
+            Telemetry::clear().map_err(|e| TokenError::ConfigError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Persist the log level (None for the default) and when it reverts
+    pub fn set_log_level(
+        &mut self,
+        level: Option<String>,
+        until: Option<chrono::DateTime<Utc>>,
+    ) -> Result<(), TokenError> {
+        self.config.log_level = level;
+        self.config.log_level_until = until;
+        self.save_config()
+    }
+
+    /// Persist the catalog and push it into opencode.json
+    fn apply_model_catalog(&mut self) -> Result<(), TokenError> {
+        self.save_config()?;
+        OpenCodeService::ensure_dymium_provider(&self.live_config())
+            .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?;
+        if self.config.model_catalog.is_empty() {
+            OpenCodeService::restore_discovered_models(&self.discovered_models)
+                .map_err(|e| TokenError::OpenCodeWrite(e.to_string()))?;
+        }
+        log::info!(
+            "Model catalog updated ({} models)",
+            self.config.model_catalog.len()
+        );
+        Ok(())
     }
 
     /// Whether the periodic refresh loop should run (OAuth mode with credentials)
@@ -589,9 +2108,20 @@ impl TokenService {
         !self.config.is_static_key_mode() && self.has_credentials() && self.state.is_authenticated()
     }
 
-    /// Refresh interval from config
+    /// Refresh interval from config, stretched on battery or a metered
+    /// connection (see `PowerService`)
     pub fn refresh_interval_secs(&self) -> u64 {
-        self.config.refresh_interval_seconds
+        let expires_in = match &self.state {
+            TokenState::Authenticated { expires_at, .. } => {
+                Some((*expires_at - Utc::now()).num_seconds())
+            }
+            _ => None,
+        };
+        PowerService::refresh_interval(
+            self.config.refresh_interval_seconds,
+            &self.config.power_saving,
+            expires_in,
+        )
     }
 
     /// Check if credentials are configured
@@ -618,6 +2148,107 @@ impl TokenService {
     }
 }
 
+/// What the read-only commands show, as of the last time the service lock
+/// was released
+#[derive(Debug, Clone)]
+pub struct ServiceSnapshot {
+    pub state: TokenState,
+    pub config: AppConfig,
+    pub has_credentials: bool,
+    /// Most recent settled state, from this run or the previous one
+    pub last_known: Option<LastState>,
+}
+
+impl ServiceSnapshot {
+    fn new(service: &TokenService, last_known: Option<LastState>) -> Self {
+        Self {
+            state: service.state.clone(),
+            config: service.config.clone(),
+            has_credentials: service.has_credentials(),
+            last_known,
+        }
+    }
+}
+
+/// The token service behind its lock. Every release of the lock publishes a
+/// fresh `ServiceSnapshot`, so the UI and tray can read the state and config
+/// while a slow verification or refresh holds the lock.
+pub struct SharedTokenService {
+    service: Mutex<TokenService>,
+    snapshot: watch::Sender<Arc<ServiceSnapshot>>,
+}
+
+impl SharedTokenService {
+    pub fn new(service: TokenService) -> Self {
+        let snapshot = ServiceSnapshot::new(&service, LastState::load());
+        let (snapshot, _) = watch::channel(Arc::new(snapshot));
+        Self {
+            service: Mutex::new(service),
+            snapshot,
+        }
+    }
+
+    /// Wait for the service
+    pub async fn lock(&self) -> TokenServiceGuard<'_> {
+        TokenServiceGuard {
+            service: self.service.lock().await,
+            snapshot: &self.snapshot,
+        }
+    }
+
+    /// The service, if nothing else holds it
+    pub fn try_lock(&self) -> Option<TokenServiceGuard<'_>> {
+        let service = self.service.try_lock().ok()?;
+        Some(TokenServiceGuard {
+            service,
+            snapshot: &self.snapshot,
+        })
+    }
+
+    /// State and config as of the last release of the lock, without waiting
+    pub fn snapshot(&self) -> Arc<ServiceSnapshot> {
+        self.snapshot.borrow().clone()
+    }
+}
+
+/// Locked token service; publishes a snapshot, and records a newly settled
+/// state, when dropped
+pub struct TokenServiceGuard<'a> {
+    service: MutexGuard<'a, TokenService>,
+    snapshot: &'a watch::Sender<Arc<ServiceSnapshot>>,
+}
+
+impl Deref for TokenServiceGuard<'_> {
+    type Target = TokenService;
+
+    fn deref(&self) -> &TokenService {
+        &self.service
+    }
+}
+
+impl DerefMut for TokenServiceGuard<'_> {
+    fn deref_mut(&mut self) -> &mut TokenService {
+        &mut self.service
+    }
+}
+
+impl Drop for TokenServiceGuard<'_> {
+    fn drop(&mut self) {
+        let service = &*self.service;
+        let previous = self.snapshot.borrow().last_known.clone();
+        let settled = LastState::settled(
+            &service.state,
+            service.has_credentials(),
+            service.last_refresh,
+        );
+        if let Some(settled) = settled.as_ref().filter(|s| previous.as_ref() != Some(*s)) {
+            settled.save();
+        }
+        let snapshot = ServiceSnapshot::new(service, settled.or(previous));
+        self.snapshot.send_replace(Arc::new(snapshot));
+    }
+}
+
 /// Extract hostname from a URL string for the Host header.
 /// Returns just the hostname without port (for Istio VirtualService matching).
 fn extract_hostname(url: &str) -> String {
@@ -630,3 +2261,26 @@ fn extract_hostname(url: &str) -> String {
         .unwrap_or("localhost");
     host.to_string()
 }
+
+/// Clients for Keycloak and for the LLM endpoint
+fn http_clients(config: &AppConfig) -> (HttpClients, HttpClients) {
+    HttpClients::from_profiles(config).expect("Failed to create HTTP client")
+}
+
+/// Whether `e` means the endpoint is down rather than refusing us
+fn is_outage(e: &TokenError) -> bool {
+    match e {
+        TokenError::EndpointUnreachable(_) | TokenError::EndpointTimeout(_) => true,
+        TokenError::EndpointRejected { status, .. } => *status >= 500,
+        TokenError::HttpError(e) => e.is_connect() || e.is_timeout(),
+        _ => false,
+    }
+}
+
+/// A failed Keycloak request, naming the certificate if that was the cause
+fn keycloak_error(e: reqwest::Error) -> TokenError {
+    match certificate_error(&e) {
+        Some(failure) => failure.into(),
+        None => TokenError::HttpError(e),
+    }
+}
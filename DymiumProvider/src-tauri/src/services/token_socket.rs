@@ -0,0 +1,300 @@
+//! Token socket
+//!
+//! Serves the current token over a Unix socket (~/.dymium/token.sock, mode
+//! 0600) or, on Windows, a per-user named pipe (\\.\pipe\dymium-provider-<user>)
+//! that rejects remote clients. Local tools can fetch a fresh token on demand
+//! instead of reading the token file:
+//!
+//! - `GET /token` → `{"token": "...", "baseUrl": "..."}` (503 when not authenticated)
+//! - `GET /state` → `{"state": "authenticated", "expiresAt": "..."}` (no secrets)
+//! - `POST /rpc` → JSON-RPC 2.0 with the app's `get_state`, `manual_refresh`
+//!   and `get_token` commands, for editors and scripts that drive the app
+//!
+//! e.g. `curl --unix-socket ~/.dymium/token.sock http://localhost/token`
+
+use crate::services::config::{TokenState, TokenStatePublic};
+use crate::services::error_code::FailureKind;
+use crate::services::proxy::SharedUpstream;
+use crate::services::token::SharedTokenService;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TokenSocketError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] crate::services::config::ConfigError),
+}
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The command itself failed
+const COMMAND_FAILED: i64 = -32000;
+
+/// Called after an RPC call changes the token state, so the tray can follow
+pub type StateListener = Arc<dyn Fn(&TokenState) + Send + Sync>;
+
+#[derive(Clone)]
+struct SocketState {
+    upstream: SharedUpstream,
+    token_service: Arc<SharedTokenService>,
+    on_change: Option<StateListener>,
+}
+
+/// A JSON-RPC 2.0 request; no method takes parameters
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    /// Absent for notifications, which get no response
+    #[serde(default)]
+    id: Option<Value>,
+}
+
+/// Token state without the token
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StateResponse {
+    state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<FailureKind>,
+}
+
+/// Service for the local token socket
+pub struct TokenSocketService;
+
+impl TokenSocketService {
+    /// Socket path (~/.dymium/token.sock)
+    #[cfg(unix)]
+    pub fn path() -> Result<std::path::PathBuf, TokenSocketError> {
+        Ok(crate::services::config::AppConfig::config_dir()?.join("token.sock"))
+    }
+
+    /// Pipe name (\\.\pipe\dymium-provider-<user>)
+    #[cfg(windows)]
+    pub fn pipe_name() -> String {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        format!(r"\\.\pipe\dymium-provider-{}", user)
+    }
+
+    /// Serve until the listener fails
+    pub async fn serve(
+        upstream: SharedUpstream,
+        token_service: Arc<SharedTokenService>,
+        on_change: Option<StateListener>,
+    ) -> Result<(), TokenSocketError> {
+        let app = Router::new()
+            .route("/token", get(get_token))
+            .route("/state", get(get_state))
+            .route("/rpc", post(rpc))
+            .with_state(SocketState {
+                upstream,
+                token_service,
+                on_change,
+            });
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = Self::path()?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            // Left over from a previous run
+            let _ = std::fs::remove_file(&path);
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            log::info!("Token socket listening on {}", path.display());
+            axum::serve(listener, app).await?;
+        }
+
+        #[cfg(windows)]
+        {
+            let listener = pipe::PipeListener::new(Self::pipe_name());
+            log::info!("Token pipe listening on {}", Self::pipe_name());
+            axum::serve(listener, app).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn get_token(State(state): State<SocketState>) -> Response {
+    let upstream = state.upstream.read().map(|u| u.clone()).unwrap_or(None);
+    match upstream {
+        Some(upstream) => Json(json!({
+            "token": upstream.token,
+            "baseUrl": upstream.base_url,
+        }))
+        .into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Dymium Provider is not authenticated" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_state(State(state): State<SocketState>) -> Json<StateResponse> {
+    // Read without the lock, so a verification in progress doesn't hold up
+    // the answer
+    let snapshot = state.token_service.snapshot();
+    let (name, expires_at, error, kind) = match &snapshot.state {
+        TokenState::Idle => ("idle", None, None, None),
+        TokenState::Authenticating => ("authenticating", None, None, None),
+        TokenState::Verifying => ("verifying", None, None, None),
+        TokenState::Authenticated { expires_at, .. } => {
+            ("authenticated", Some(*expires_at), None, None)
+        }
+        TokenState::Failed { error, kind, .. } => {
+            ("failed", None, Some(error.clone()), Some(*kind))
+        }
+        TokenState::Locked => ("locked", None, None, None),
+    };
+    Json(StateResponse {
+        state: name,
+        expires_at,
+        error,
+        kind,
+    })
+}
+
+async fn rpc(State(state): State<SocketState>, body: Bytes) -> Response {
+    let request: RpcRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return rpc_response(Value::Null, Err((PARSE_ERROR, e.to_string()))),
+    };
+    if request.jsonrpc != "2.0" {
+        let id = request.id.unwrap_or(Value::Null);
+        return rpc_response(id, Err((INVALID_REQUEST, "jsonrpc must be \"2.0\"".into())));
+    }
+
+    let result = match request.method.as_str() {
+        "get_state" => Ok(json!(TokenStatePublic::from(
+            &state.token_service.snapshot().state
+        ))),
+        "manual_refresh" => {
+            let mut service = state.token_service.lock().await;
+            service.record_activity();
+            let result = service.manual_refresh().await;
+            if let Some(on_change) = &state.on_change {
+                on_change(service.state());
+            }
+            result
+                .map(|()| json!(TokenStatePublic::from(service.state())))
+                .map_err(|e| (COMMAND_FAILED, e.to_string()))
+        }
+        "get_token" => {
+            let service = state.token_service.lock().await;
+            let base_url = state
+                .upstream
+                .read()
+                .ok()
+                .and_then(|u| u.as_ref().map(|u| u.base_url.clone()));
+            service
+                .access_token()
+                .map(|token| {
+                    json!({
+                        "token": token.token,
+                        "expiresAt": token.expires_at,
+                        "baseUrl": base_url,
+                    })
+                })
+                .map_err(|e| (COMMAND_FAILED, e.to_string()))
+        }
+        other => Err((METHOD_NOT_FOUND, format!("Unknown method {}", other))),
+    };
+
+    match request.id {
+        Some(id) => rpc_response(id, result),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+fn rpc_response(id: Value, result: Result<Value, (i64, String)>) -> Response {
+    let body = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    };
+    Json(body).into_response()
+}
+
+#[cfg(windows)]
+mod pipe {
+    use std::io;
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+
+    /// Accepts connections on a named pipe, one server instance per client
+    pub struct PipeListener {
+        name: String,
+        next: Option<NamedPipeServer>,
+    }
+
+    impl PipeListener {
+        pub fn new(name: String) -> Self {
+            Self { name, next: None }
+        }
+    }
+
+    impl axum::serve::Listener for PipeListener {
+        type Io = NamedPipeServer;
+        type Addr = String;
+
+        async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+            loop {
+                if self.next.is_none() {
+                    match ServerOptions::new()
+                        .reject_remote_clients(true)
+                        .create(&self.name)
+                    {
+                        Ok(server) => self.next = Some(server),
+                        Err(e) => {
+                            log::warn!("Failed to create pipe {}: {}", self.name, e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    }
+                }
+                if let Some(server) = &self.next {
+                    match server.connect().await {
+                        Ok(()) => {
+                            if let Some(server) = self.next.take() {
+                                return (server, self.name.clone());
+                            }
+                        }
+                        Err(e) => {
+                            log::warn!("Pipe connection failed: {}", e);
+                            self.next = None;
+                        }
+                    }
+                }
+            }
+        }
+
+        fn local_addr(&self) -> io::Result<Self::Addr> {
+            Ok(self.name.clone())
+        }
+    }
+}
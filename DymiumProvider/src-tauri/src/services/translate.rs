@@ -0,0 +1,1289 @@
+//! Anthropic ↔ OpenAI API translation for the local proxy
+//!
+//! Lets clients that speak the Anthropic Messages API (`POST /v1/messages`)
+//! use GhostLLM's OpenAI-compatible chat completions route, and OpenAI clients
+//! use routes marked `"api": "anthropic"`. Requests, responses, errors and
+//! streaming events are mapped both ways, including tool calls. Features with
+//! no counterpart (e.g. Anthropic thinking blocks) are dropped.
+
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Max tokens sent to Anthropic when an OpenAI client doesn't set one
+/// (Anthropic requires it)
+const DEFAULT_MAX_TOKENS: u64 = 4096;
+
+/// Which way a request is translated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Anthropic client, OpenAI upstream
+    AnthropicToOpenAi,
+    /// OpenAI client, Anthropic upstream
+    OpenAiToAnthropic,
+}
+
+/// A request being translated, kept to translate its response
+#[derive(Debug, Clone)]
+pub struct Translation {
+    direction: Direction,
+    model: String,
+    stream: bool,
+    include_usage: bool,
+}
+
+impl Translation {
+    /// Translate a request body. Returns the translation and the body to send
+    /// upstream, or `None` if the body isn't a JSON object.
+    pub fn request(direction: Direction, body: &[u8]) -> Option<(Self, Vec<u8>)> {
+        let value: Value = serde_json::from_slice(body).ok()?;
+        let request = value.as_object()?;
+        let translated = match direction {
+            Direction::AnthropicToOpenAi => anthropic_request_to_openai(request),
+            Direction::OpenAiToAnthropic => openai_request_to_anthropic(request),
+        };
+        let translation = Self {
+            direction,
+            model: str_field(request, "model").to_string(),
+            stream: request
+                .get("stream")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            include_usage: value
+                .pointer("/stream_options/include_usage")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        };
+        Some((translation, serde_json::to_vec(&translated).ok()?))
+    }
+
+    /// Upstream path the translated request goes to
+    pub fn upstream_path(&self) -> &'static str {
+        match self.direction {
+            Direction::AnthropicToOpenAi => "/v1/chat/completions",
+            Direction::OpenAiToAnthropic => "/v1/messages",
+        }
+    }
+
+    /// Whether the client asked for a streamed response
+    pub fn is_stream(&self) -> bool {
+        self.stream
+    }
+
+    /// Translate a complete (non-streamed or error) response body
+    pub fn response(&self, success: bool, body: &[u8]) -> Vec<u8> {
+        let Ok(response) = serde_json::from_slice::<Value>(body) else {
+            return body.to_vec();
+        };
+        let translated = match (self.direction, success) {
+            (Direction::AnthropicToOpenAi, true) => {
+                openai_response_to_anthropic(&response, &self.model)
+            }
+            (Direction::AnthropicToOpenAi, false) => json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": error_message(&response) },
+            }),
+            (Direction::OpenAiToAnthropic, true) => {
+                anthropic_response_to_openai(&response, &self.model)
+            }
+            (Direction::OpenAiToAnthropic, false) => json!({
+                "error": { "type": "api_error", "message": error_message(&response) },
+            }),
+        };
+        serde_json::to_vec(&translated).unwrap_or_else(|_| body.to_vec())
+    }
+
+    /// Translator for a streamed response
+    pub fn stream_translator(&self) -> StreamTranslator {
+        let inner = match self.direction {
+            Direction::AnthropicToOpenAi => Inner::ToAnthropic(ToAnthropicStream::new(&self.model)),
+            Direction::OpenAiToAnthropic => {
+                Inner::ToOpenAi(ToOpenAiStream::new(&self.model, self.include_usage))
+            }
+        };
+        StreamTranslator {
+            buffer: Vec::new(),
+            inner,
+        }
+    }
+}
+
+/// Translates a server-sent event stream as it arrives
+pub struct StreamTranslator {
+    /// Incomplete line carried over from the last chunk, as raw bytes so a
+    /// character split across chunks is decoded whole
+    buffer: Vec<u8>,
+    inner: Inner,
+}
+
+enum Inner {
+    ToAnthropic(ToAnthropicStream),
+    ToOpenAi(ToOpenAiStream),
+}
+
+impl StreamTranslator {
+    /// Translate a chunk of the upstream stream
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(chunk);
+        let mut out = String::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let bytes: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&bytes);
+            let Some(data) = line.trim_end().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            match &mut self.inner {
+                Inner::ToAnthropic(s) => s.data(data, &mut out),
+                Inner::ToOpenAi(s) => s.data(data, &mut out),
+            }
+        }
+        out.into_bytes()
+    }
+
+    /// The upstream stream ended; close anything still open
+    pub fn finish(&mut self) -> Vec<u8> {
+        // A last line without a trailing newline
+        let mut out = self.feed(b"\n");
+        let mut tail = String::new();
+        match &mut self.inner {
+            Inner::ToAnthropic(s) => s.finish(&mut tail),
+            Inner::ToOpenAi(s) => s.finish(&mut tail),
+        }
+        out.extend_from_slice(tail.as_bytes());
+        out
+    }
+}
+
+// --- Anthropic client → OpenAI upstream ---
+
+fn anthropic_request_to_openai(request: &Map<String, Value>) -> Value {
+    let mut messages = Vec::new();
+    let system = match request.get("system") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(blocks)) => blocks_text(blocks),
+        _ => String::new(),
+    };
+    if !system.is_empty() {
+        messages.push(json!({ "role": "system", "content": system }));
+    }
+
+    for message in request
+        .get("messages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let role = message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user");
+        let blocks = match message.get("content") {
+            Some(Value::String(text)) => {
+                messages.push(json!({ "role": role, "content": text }));
+                continue;
+            }
+            Some(Value::Array(blocks)) => blocks,
+            _ => continue,
+        };
+
+        if role == "assistant" {
+            let text = blocks_text(blocks);
+            let tool_calls: Vec<Value> = blocks
+                .iter()
+                .filter(|b| b.get("type").and_then(Value::as_str) == Some("tool_use"))
+                .map(|b| {
+                    json!({
+                        "id": b.get("id").cloned().unwrap_or(Value::Null),
+                        "type": "function",
+                        "function": {
+                            "name": b.get("name").cloned().unwrap_or(Value::Null),
+                            "arguments": b.get("input").map(Value::to_string).unwrap_or_else(|| "{}".to_string()),
+                        },
+                    })
+                })
+                .collect();
+            let mut out = json!({
+                "role": "assistant",
+                "content": if text.is_empty() { Value::Null } else { Value::String(text) },
+            });
+            if !tool_calls.is_empty() {
+                out["tool_calls"] = Value::Array(tool_calls);
+            }
+            messages.push(out);
+            continue;
+        }
+
+        // Tool results become `tool` messages, which must directly follow the
+        // assistant message that made the calls
+        let mut parts = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(Value::as_str) {
+                Some("text") => parts.push(json!({ "type": "text", "text": block["text"] })),
+                Some("image") => {
+                    if let Some(url) = image_url(block) {
+                        parts.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+                    }
+                }
+                Some("tool_result") => {
+                    let content = match block.get("content") {
+                        Some(Value::String(text)) => text.clone(),
+                        Some(Value::Array(blocks)) => blocks_text(blocks),
+                        _ => String::new(),
+                    };
+                    messages.push(json!({
+                        "role": "tool",
+                        "tool_call_id": block.get("tool_use_id").cloned().unwrap_or(Value::Null),
+                        "content": content,
+                    }));
+                }
+                _ => {}
+            }
+        }
+        if !parts.is_empty() {
+            messages.push(json!({ "role": role, "content": parts }));
+        }
+    }
+
+    let mut out = Map::new();
+    out.insert(
+        "model".into(),
+        request.get("model").cloned().unwrap_or(Value::Null),
+    );
+    out.insert("messages".into(), Value::Array(messages));
+    for (from, to) in [
+        ("max_tokens", "max_tokens"),
+        ("temperature", "temperature"),
+        ("top_p", "top_p"),
+        ("stop_sequences", "stop"),
+        ("stream", "stream"),
+    ] {
+        if let Some(value) = request.get(from) {
+            out.insert(to.into(), value.clone());
+        }
+    }
+    if request.get("stream").and_then(Value::as_bool) == Some(true) {
+        out.insert("stream_options".into(), json!({ "include_usage": true }));
+    }
+    if let Some(user) = request.get("metadata").and_then(|m| m.get("user_id")) {
+        out.insert("user".into(), user.clone());
+    }
+    if let Some(tools) = request.get("tools").and_then(Value::as_array) {
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|t| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.get("name").cloned().unwrap_or(Value::Null),
+                        "description": t.get("description").cloned().unwrap_or(Value::Null),
+                        "parameters": t.get("input_schema").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                    },
+                })
+            })
+            .collect();
+        out.insert("tools".into(), Value::Array(tools));
+    }
+    if let Some(choice) = request.get("tool_choice") {
+        let choice = match choice.get("type").and_then(Value::as_str) {
+            Some("any") => json!("required"),
+            Some("none") => json!("none"),
+            Some("tool") => json!({ "type": "function", "function": { "name": choice["name"] } }),
+            _ => json!("auto"),
+        };
+        out.insert("tool_choice".into(), choice);
+    }
+    Value::Object(out)
+}
+
+fn openai_response_to_anthropic(response: &Value, model: &str) -> Value {
+    let choice = &response["choices"][0];
+    let message = &choice["message"];
+    let mut content = Vec::new();
+    if let Some(text) = message.get("content").and_then(Value::as_str) {
+        if !text.is_empty() {
+            content.push(json!({ "type": "text", "text": text }));
+        }
+    }
+    for call in message
+        .get("tool_calls")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        content.push(json!({
+            "type": "tool_use",
+            "id": call["id"],
+            "name": call["function"]["name"],
+            "input": parse_arguments(call["function"]["arguments"].as_str()),
+        }));
+    }
+
+    json!({
+        "id": response.get("id").cloned().unwrap_or_else(|| json!("msg_dymium")),
+        "type": "message",
+        "role": "assistant",
+        "model": response.get("model").and_then(Value::as_str).unwrap_or(model),
+        "content": content,
+        "stop_reason": anthropic_stop_reason(choice.get("finish_reason").and_then(Value::as_str)),
+        "stop_sequence": Value::Null,
+        "usage": {
+            "input_tokens": response["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+            "output_tokens": response["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+        },
+    })
+}
+
+/// OpenAI chat completion chunks → Anthropic message events
+struct ToAnthropicStream {
+    model: String,
+    started: bool,
+    finished: bool,
+    /// Index and kind of the content block currently open
+    open_block: Option<(usize, BlockKind)>,
+    next_block: usize,
+    /// OpenAI tool call index → Anthropic content block index
+    tool_blocks: HashMap<u64, usize>,
+    stop_reason: &'static str,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Text,
+    Tool,
+}
+
+impl ToAnthropicStream {
+    fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            started: false,
+            finished: false,
+            open_block: None,
+            next_block: 0,
+            tool_blocks: HashMap::new(),
+            stop_reason: "end_turn",
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    fn data(&mut self, data: &str, out: &mut String) {
+        if data == "[DONE]" {
+            self.finish(out);
+            return;
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(data) else {
+            return;
+        };
+        if let Some(error) = chunk.get("error") {
+            let message = error_message(&json!({ "error": error }));
+            event(
+                out,
+                "error",
+                &json!({ "type": "error", "error": { "type": "api_error", "message": message } }),
+            );
+            return;
+        }
+        if !self.started {
+            self.started = true;
+            event(
+                out,
+                "message_start",
+                &json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": chunk.get("id").cloned().unwrap_or_else(|| json!("msg_dymium")),
+                        "type": "message",
+                        "role": "assistant",
+                        "model": chunk.get("model").and_then(Value::as_str).unwrap_or(&self.model),
+                        "content": [],
+                        "stop_reason": Value::Null,
+                        "stop_sequence": Value::Null,
+                        "usage": { "input_tokens": 0, "output_tokens": 0 },
+                    },
+                }),
+            );
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(self.input_tokens);
+            self.output_tokens = usage["completion_tokens"]
+                .as_u64()
+                .unwrap_or(self.output_tokens);
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(text) = delta.get("content").and_then(Value::as_str) {
+            if !text.is_empty() {
+                let index = self.block(BlockKind::Text, None, out);
+                event(
+                    out,
+                    "content_block_delta",
+                    &json!({
+                        "type": "content_block_delta",
+                        "index": index,
+                        "delta": { "type": "text_delta", "text": text },
+                    }),
+                );
+            }
+        }
+        for call in delta
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let tool_index = call.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let index = match self.tool_blocks.get(&tool_index) {
+                Some(index) => *index,
+                None => {
+                    let index = self.block(BlockKind::Tool, Some(call), out);
+                    self.tool_blocks.insert(tool_index, index);
+                    index
+                }
+            };
+            if let Some(args) = call["function"]["arguments"].as_str() {
+                if !args.is_empty() {
+                    event(
+                        out,
+                        "content_block_delta",
+                        &json!({
+                            "type": "content_block_delta",
+                            "index": index,
+                            "delta": { "type": "input_json_delta", "partial_json": args },
+                        }),
+                    );
+                }
+            }
+        }
+        if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.stop_reason = anthropic_stop_reason(Some(reason));
+        }
+    }
+
+    /// Index of a block of `kind` to write into, opening one if needed. Tool
+    /// calls always get a new block.
+    fn block(&mut self, kind: BlockKind, call: Option<&Value>, out: &mut String) -> usize {
+        if let Some((index, open)) = self.open_block {
+            if open == kind && kind == BlockKind::Text {
+                return index;
+            }
+            self.close_block(out);
+        }
+        let index = self.next_block;
+        self.next_block += 1;
+        let content_block = match call {
+            Some(call) => json!({
+                "type": "tool_use",
+                "id": call["id"],
+                "name": call["function"]["name"],
+                "input": {},
+            }),
+            None => json!({ "type": "text", "text": "" }),
+        };
+        event(
+            out,
+            "content_block_start",
+            &json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": content_block,
+            }),
+        );
+        self.open_block = Some((index, kind));
+        index
+    }
+
+    fn close_block(&mut self, out: &mut String) {
+        if let Some((index, _)) = self.open_block.take() {
+            event(
+                out,
+                "content_block_stop",
+                &json!({ "type": "content_block_stop", "index": index }),
+            );
+        }
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        if self.finished || !self.started {
+            return;
+        }
+        self.finished = true;
+        self.close_block(out);
+        event(
+            out,
+            "message_delta",
+            &json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": self.stop_reason, "stop_sequence": Value::Null },
+                "usage": { "input_tokens": self.input_tokens, "output_tokens": self.output_tokens },
+            }),
+        );
+        event(out, "message_stop", &json!({ "type": "message_stop" }));
+    }
+}
+
+// --- OpenAI client → Anthropic upstream ---
+
+fn openai_request_to_anthropic(request: &Map<String, Value>) -> Value {
+    let mut system = Vec::new();
+    let mut messages: Vec<Value> = Vec::new();
+
+    for message in request
+        .get("messages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let role = message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user");
+        let (role, blocks) = match role {
+            "system" | "developer" => {
+                system.push(content_text(message.get("content")));
+                continue;
+            }
+            "tool" => (
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.get("tool_call_id").cloned().unwrap_or(Value::Null),
+                    "content": content_text(message.get("content")),
+                })],
+            ),
+            "assistant" => {
+                let mut blocks = Vec::new();
+                let text = content_text(message.get("content"));
+                if !text.is_empty() {
+                    blocks.push(json!({ "type": "text", "text": text }));
+                }
+                for call in message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": call["id"],
+                        "name": call["function"]["name"],
+                        "input": parse_arguments(call["function"]["arguments"].as_str()),
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            _ => ("user", user_blocks(message.get("content"))),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+
+        // Anthropic requires alternating roles; merge consecutive messages
+        match messages.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(content) = last["content"].as_array_mut() {
+                    content.extend(blocks);
+                }
+            }
+            _ => messages.push(json!({ "role": role, "content": blocks })),
+        }
+    }
+
+    let max_tokens = request
+        .get("max_completion_tokens")
+        .or_else(|| request.get("max_tokens"))
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_MAX_TOKENS);
+    let mut out = Map::new();
+    out.insert(
+        "model".into(),
+        request.get("model").cloned().unwrap_or(Value::Null),
+    );
+    out.insert("max_tokens".into(), json!(max_tokens));
+    out.insert("messages".into(), Value::Array(messages));
+    let system: Vec<String> = system.into_iter().filter(|s| !s.is_empty()).collect();
+    if !system.is_empty() {
+        out.insert("system".into(), json!(system.join("\n\n")));
+    }
+    for key in ["temperature", "top_p", "stream"] {
+        if let Some(value) = request.get(key) {
+            out.insert(key.into(), value.clone());
+        }
+    }
+    match request.get("stop") {
+        Some(Value::String(stop)) => {
+            out.insert("stop_sequences".into(), json!([stop]));
+        }
+        Some(Value::Array(stop)) => {
+            out.insert("stop_sequences".into(), Value::Array(stop.clone()));
+        }
+        _ => {}
+    }
+    if let Some(user) = request.get("user") {
+        out.insert("metadata".into(), json!({ "user_id": user }));
+    }
+    if let Some(tools) = request.get("tools").and_then(Value::as_array) {
+        let tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|t| t.get("function"))
+            .map(|f| {
+                let mut tool = json!({
+                    "name": f["name"],
+                    "input_schema": f.get("parameters").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                });
+                if let Some(description) = f.get("description") {
+                    tool["description"] = description.clone();
+                }
+                tool
+            })
+            .collect();
+        out.insert("tools".into(), Value::Array(tools));
+    }
+    if let Some(choice) = request.get("tool_choice") {
+        let choice = match choice {
+            Value::String(s) if s == "required" => json!({ "type": "any" }),
+            Value::String(s) if s == "none" => json!({ "type": "none" }),
+            Value::Object(_) => json!({ "type": "tool", "name": choice["function"]["name"] }),
+            _ => json!({ "type": "auto" }),
+        };
+        out.insert("tool_choice".into(), choice);
+    }
+    Value::Object(out)
+}
+
+fn anthropic_response_to_openai(response: &Value, model: &str) -> Value {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in response["content"].as_array().into_iter().flatten() {
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(json!({
+                "id": block["id"],
+                "type": "function",
+                "function": { "name": block["name"], "arguments": block["input"].to_string() },
+            })),
+            _ => {}
+        }
+    }
+    let mut message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { Value::String(text) },
+    });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+
+    let input = response["usage"]["input_tokens"].as_u64().unwrap_or(0);
+    let output = response["usage"]["output_tokens"].as_u64().unwrap_or(0);
+    json!({
+        "id": response.get("id").cloned().unwrap_or_else(|| json!("chatcmpl-dymium")),
+        "object": "chat.completion",
+        "created": Utc::now().timestamp(),
+        "model": response.get("model").and_then(Value::as_str).unwrap_or(model),
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": openai_finish_reason(response["stop_reason"].as_str()),
+        }],
+        "usage": { "prompt_tokens": input, "completion_tokens": output, "total_tokens": input + output },
+    })
+}
+
+/// Anthropic message events → OpenAI chat completion chunks
+struct ToOpenAiStream {
+    id: String,
+    model: String,
+    created: i64,
+    include_usage: bool,
+    done: bool,
+    /// Anthropic content block index → OpenAI tool call index
+    tool_calls: HashMap<u64, usize>,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl ToOpenAiStream {
+    fn new(model: &str, include_usage: bool) -> Self {
+        Self {
+            id: "chatcmpl-dymium".to_string(),
+            model: model.to_string(),
+            created: Utc::now().timestamp(),
+            include_usage,
+            done: false,
+            tool_calls: HashMap::new(),
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    fn data(&mut self, data: &str, out: &mut String) {
+        let Ok(event) = serde_json::from_str::<Value>(data) else {
+            return;
+        };
+        match event.get("type").and_then(Value::as_str) {
+            Some("message_start") => {
+                let message = &event["message"];
+                if let Some(id) = message["id"].as_str() {
+                    self.id = id.to_string();
+                }
+                if let Some(model) = message["model"].as_str() {
+                    self.model = model.to_string();
+                }
+                self.input_tokens = message["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                self.chunk(
+                    out,
+                    json!({ "role": "assistant", "content": "" }),
+                    Value::Null,
+                );
+            }
+            Some("content_block_start") => {
+                let block = &event["content_block"];
+                if block["type"] == "tool_use" {
+                    let index = self.tool_calls.len();
+                    self.tool_calls
+                        .insert(event["index"].as_u64().unwrap_or(0), index);
+                    self.chunk(
+                        out,
+                        json!({ "tool_calls": [{
+                            "index": index,
+                            "id": block["id"],
+                            "type": "function",
+                            "function": { "name": block["name"], "arguments": "" },
+                        }] }),
+                        Value::Null,
+                    );
+                }
+            }
+            Some("content_block_delta") => {
+                let delta = &event["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        self.chunk(out, json!({ "content": delta["text"] }), Value::Null);
+                    }
+                    Some("input_json_delta") => {
+                        let block = event["index"].as_u64().unwrap_or(0);
+                        if let Some(index) = self.tool_calls.get(&block).copied() {
+                            self.chunk(
+                                out,
+                                json!({ "tool_calls": [{
+                                    "index": index,
+                                    "function": { "arguments": delta["partial_json"] },
+                                }] }),
+                                Value::Null,
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some("message_delta") => {
+                if let Some(output) = event["usage"]["output_tokens"].as_u64() {
+                    self.output_tokens = output;
+                }
+                let reason = openai_finish_reason(event["delta"]["stop_reason"].as_str());
+                self.chunk(out, json!({}), json!(reason));
+            }
+            Some("message_stop") => self.finish(out),
+            Some("error") => {
+                let message = error_message(&event);
+                sse_data(
+                    out,
+                    &json!({ "error": { "type": "api_error", "message": message } }),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn chunk(&self, out: &mut String, delta: Value, finish_reason: Value) {
+        sse_data(
+            out,
+            &json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+            }),
+        );
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+        if self.include_usage {
+            sse_data(
+                out,
+                &json!({
+                    "id": self.id,
+                    "object": "chat.completion.chunk",
+                    "created": self.created,
+                    "model": self.model,
+                    "choices": [],
+                    "usage": {
+                        "prompt_tokens": self.input_tokens,
+                        "completion_tokens": self.output_tokens,
+                        "total_tokens": self.input_tokens + self.output_tokens,
+                    },
+                }),
+            );
+        }
+        out.push_str("data: [DONE]\n\n");
+    }
+}
+
+// --- Helpers ---
+
+fn str_field<'a>(object: &'a Map<String, Value>, key: &str) -> &'a str {
+    object.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Concatenated text of Anthropic content blocks
+fn blocks_text(blocks: &[Value]) -> String {
+    blocks
+        .iter()
+        .filter(|b| b.get("type").and_then(Value::as_str) == Some("text"))
+        .filter_map(|b| b.get("text").and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of OpenAI message content (a string or an array of parts)
+fn content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// OpenAI user content → Anthropic content blocks
+fn user_blocks(content: Option<&Value>) -> Vec<Value> {
+    match content {
+        Some(Value::String(text)) => vec![json!({ "type": "text", "text": text })],
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(Value::as_str) {
+                Some("text") => Some(json!({ "type": "text", "text": part["text"] })),
+                Some("image_url") => {
+                    let url = part["image_url"]["url"].as_str()?;
+                    let source = match url
+                        .strip_prefix("data:")
+                        .and_then(|rest| rest.split_once(";base64,"))
+                    {
+                        Some((media_type, data)) => {
+                            json!({ "type": "base64", "media_type": media_type, "data": data })
+                        }
+                        None => json!({ "type": "url", "url": url }),
+                    };
+                    Some(json!({ "type": "image", "source": source }))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Anthropic image block → OpenAI image URL (data URL for base64 images)
+fn image_url(block: &Value) -> Option<String> {
+    let source = block.get("source")?;
+    match source.get("type").and_then(Value::as_str) {
+        Some("base64") => Some(format!(
+            "data:{};base64,{}",
+            source["media_type"].as_str()?,
+            source["data"].as_str()?
+        )),
+        Some("url") => source["url"].as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Tool call arguments (a JSON string) as an object
+fn parse_arguments(arguments: Option<&str>) -> Value {
+    arguments
+        .and_then(|a| serde_json::from_str(a).ok())
+        .unwrap_or_else(|| json!({}))
+}
+
+fn anthropic_stop_reason(finish_reason: Option<&str>) -> &'static str {
+    match finish_reason {
+        Some("length") => "max_tokens",
+        Some("tool_calls") | Some("function_call") => "tool_use",
+        _ => "end_turn",
+    }
+}
+
+fn openai_finish_reason(stop_reason: Option<&str>) -> &'static str {
+    match stop_reason {
+        Some("max_tokens") => "length",
+        Some("tool_use") => "tool_calls",
+        _ => "stop",
+    }
+}
+
+/// Message from an OpenAI or Anthropic error body
+fn error_message(body: &Value) -> String {
+    body.pointer("/error/message")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| body.to_string())
+}
+
+fn event(out: &mut String, name: &str, data: &Value) {
+    out.push_str(&format!("event: {}\ndata: {}\n\n", name, data));
+}
+
+fn sse_data(out: &mut String, data: &Value) {
+    out.push_str(&format!("data: {}\n\n", data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(direction: Direction, body: Value) -> (Translation, Value) {
+        let (translation, sent) =
+            Translation::request(direction, body.to_string().as_bytes()).unwrap();
+        (translation, serde_json::from_slice(&sent).unwrap())
+    }
+
+    /// The `data:` payloads of an SSE stream, `[DONE]` as a string
+    fn sse(bytes: &[u8]) -> Vec<Value> {
+        String::from_utf8(bytes.to_vec())
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str(data).unwrap_or_else(|_| json!(data)))
+            .collect()
+    }
+
+    #[test]
+    fn anthropic_request_maps_messages_and_tools() {
+        let (translation, sent) = request(
+            Direction::AnthropicToOpenAi,
+            json!({
+                "model": "claude",
+                "system": "Be brief",
+                "max_tokens": 100,
+                "stop_sequences": ["END"],
+                "stream": true,
+                "messages": [
+                    { "role": "user", "content": "Weather?" },
+                    { "role": "assistant", "content": [
+                        { "type": "text", "text": "Checking" },
+                        { "type": "tool_use", "id": "call_1", "name": "weather", "input": { "city": "Oslo" } },
+                    ] },
+                    { "role": "user", "content": [
+                        { "type": "tool_result", "tool_use_id": "call_1", "content": "Sunny" },
+                    ] },
+                ],
+                "tools": [{ "name": "weather", "description": "Look up", "input_schema": { "type": "object" } }],
+                "tool_choice": { "type": "tool", "name": "weather" },
+            }),
+        );
+        assert!(translation.is_stream());
+        assert_eq!(translation.upstream_path(), "/v1/chat/completions");
+        assert_eq!(sent["stop"], json!(["END"]));
+        assert_eq!(sent["stream_options"]["include_usage"], json!(true));
+        let messages = sent["messages"].as_array().unwrap();
+        assert_eq!(
+            messages[0],
+            json!({ "role": "system", "content": "Be brief" })
+        );
+        assert_eq!(
+            messages[1],
+            json!({ "role": "user", "content": "Weather?" })
+        );
+        assert_eq!(messages[2]["content"], json!("Checking"));
+        let call = &messages[2]["tool_calls"][0];
+        assert_eq!(call["id"], json!("call_1"));
+        assert_eq!(call["function"]["name"], json!("weather"));
+        assert_eq!(
+            serde_json::from_str::<Value>(call["function"]["arguments"].as_str().unwrap()).unwrap(),
+            json!({ "city": "Oslo" })
+        );
+        assert_eq!(
+            messages[3],
+            json!({ "role": "tool", "tool_call_id": "call_1", "content": "Sunny" })
+        );
+        assert_eq!(
+            sent["tools"][0]["function"]["parameters"],
+            json!({ "type": "object" })
+        );
+        assert_eq!(
+            sent["tool_choice"],
+            json!({ "type": "function", "function": { "name": "weather" } })
+        );
+    }
+
+    #[test]
+    fn openai_request_maps_messages_and_tools() {
+        let (translation, sent) = request(
+            Direction::OpenAiToAnthropic,
+            json!({
+                "model": "gpt",
+                "stop": "END",
+                "messages": [
+                    { "role": "system", "content": "Be brief" },
+                    { "role": "user", "content": "Weather?" },
+                    { "role": "assistant", "content": null, "tool_calls": [
+                        { "id": "call_1", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Oslo\"}" } },
+                    ] },
+                    { "role": "tool", "tool_call_id": "call_1", "content": "Sunny" },
+                    { "role": "user", "content": "Thanks" },
+                ],
+                "tools": [{ "type": "function", "function": { "name": "weather", "parameters": { "type": "object" } } }],
+                "tool_choice": "required",
+            }),
+        );
+        assert!(!translation.is_stream());
+        assert_eq!(translation.upstream_path(), "/v1/messages");
+        assert_eq!(sent["system"], json!("Be brief"));
+        assert_eq!(sent["max_tokens"], json!(DEFAULT_MAX_TOKENS));
+        assert_eq!(sent["stop_sequences"], json!(["END"]));
+        let messages = sent["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[1]["content"][0],
+            json!({ "type": "tool_use", "id": "call_1", "name": "weather", "input": { "city": "Oslo" } })
+        );
+        // The tool result and the next user message share one user turn
+        assert_eq!(messages[2]["role"], json!("user"));
+        assert_eq!(
+            messages[2]["content"],
+            json!([
+                { "type": "tool_result", "tool_use_id": "call_1", "content": "Sunny" },
+                { "type": "text", "text": "Thanks" },
+            ])
+        );
+        assert_eq!(
+            sent["tools"][0]["input_schema"],
+            json!({ "type": "object" })
+        );
+        assert_eq!(sent["tool_choice"], json!({ "type": "any" }));
+    }
+
+    #[test]
+    fn non_object_request_is_not_translated() {
+        assert!(Translation::request(Direction::AnthropicToOpenAi, b"[1]").is_none());
+        assert!(Translation::request(Direction::OpenAiToAnthropic, b"nope").is_none());
+    }
+
+    #[test]
+    fn openai_response_becomes_anthropic_message() {
+        let (translation, _) = request(Direction::AnthropicToOpenAi, json!({ "model": "claude" }));
+        let body = json!({
+            "id": "chatcmpl-1",
+            "choices": [{
+                "message": {
+                    "content": "Checking",
+                    "tool_calls": [{ "id": "call_1", "function": { "name": "weather", "arguments": "{\"city\":\"Oslo\"}" } }],
+                },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 },
+        });
+        let out: Value =
+            serde_json::from_slice(&translation.response(true, body.to_string().as_bytes()))
+                .unwrap();
+        assert_eq!(out["model"], json!("claude"));
+        assert_eq!(out["stop_reason"], json!("tool_use"));
+        assert_eq!(
+            out["content"],
+            json!([
+                { "type": "text", "text": "Checking" },
+                { "type": "tool_use", "id": "call_1", "name": "weather", "input": { "city": "Oslo" } },
+            ])
+        );
+        assert_eq!(
+            out["usage"],
+            json!({ "input_tokens": 10, "output_tokens": 5 })
+        );
+    }
+
+    #[test]
+    fn anthropic_response_becomes_openai_completion() {
+        let (translation, _) = request(Direction::OpenAiToAnthropic, json!({ "model": "gpt" }));
+        let body = json!({
+            "id": "msg_1",
+            "content": [{ "type": "tool_use", "id": "call_1", "name": "weather", "input": { "city": "Oslo" } }],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 10, "output_tokens": 5 },
+        });
+        let out: Value =
+            serde_json::from_slice(&translation.response(true, body.to_string().as_bytes()))
+                .unwrap();
+        let choice = &out["choices"][0];
+        assert_eq!(choice["finish_reason"], json!("tool_calls"));
+        assert_eq!(choice["message"]["content"], Value::Null);
+        let call = &choice["message"]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], json!("weather"));
+        assert_eq!(
+            serde_json::from_str::<Value>(call["function"]["arguments"].as_str().unwrap()).unwrap(),
+            json!({ "city": "Oslo" })
+        );
+        assert_eq!(out["usage"]["total_tokens"], json!(15));
+    }
+
+    #[test]
+    fn error_responses_keep_the_message() {
+        let body = br#"{"error":{"message":"rate limited"}}"#;
+        let (to_anthropic, _) = request(Direction::AnthropicToOpenAi, json!({}));
+        let out: Value = serde_json::from_slice(&to_anthropic.response(false, body)).unwrap();
+        assert_eq!(out["type"], json!("error"));
+        assert_eq!(out["error"]["message"], json!("rate limited"));
+        let (to_openai, _) = request(Direction::OpenAiToAnthropic, json!({}));
+        let out: Value = serde_json::from_slice(&to_openai.response(false, body)).unwrap();
+        assert_eq!(out["error"]["message"], json!("rate limited"));
+    }
+
+    #[test]
+    fn openai_stream_becomes_anthropic_events() {
+        let (translation, _) = request(
+            Direction::AnthropicToOpenAi,
+            json!({ "model": "claude", "stream": true }),
+        );
+        let mut stream = translation.stream_translator();
+        let upstream = [
+            json!({ "id": "c1", "choices": [{ "delta": { "content": "Hi" } }] }),
+            json!({ "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "id": "call_1", "function": { "name": "weather", "arguments": "{\"city\":" } },
+            ] } }] }),
+            json!({ "choices": [{ "delta": { "tool_calls": [
+                { "index": 0, "function": { "arguments": "\"Oslo\"}" } },
+            ] } }] }),
+            json!({ "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+            json!({ "choices": [], "usage": { "prompt_tokens": 10, "completion_tokens": 5 } }),
+        ];
+        let mut out = Vec::new();
+        for chunk in &upstream {
+            out.extend(stream.feed(format!("data: {}\n\n", chunk).as_bytes()));
+        }
+        out.extend(stream.feed(b"data: [DONE]\n\n"));
+        out.extend(stream.finish());
+
+        let events = sse(&out);
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert_eq!(events[1]["content_block"]["type"], json!("text"));
+        assert_eq!(events[2]["delta"]["text"], json!("Hi"));
+        assert_eq!(events[4]["index"], json!(1));
+        assert_eq!(events[4]["content_block"]["name"], json!("weather"));
+        let arguments: String = events[5..7]
+            .iter()
+            .map(|e| e["delta"]["partial_json"].as_str().unwrap())
+            .collect();
+        assert_eq!(arguments, r#"{"city":"Oslo"}"#);
+        assert_eq!(events[8]["delta"]["stop_reason"], json!("tool_use"));
+        assert_eq!(
+            events[8]["usage"],
+            json!({ "input_tokens": 10, "output_tokens": 5 })
+        );
+    }
+
+    #[test]
+    fn anthropic_stream_becomes_openai_chunks() {
+        let (translation, _) = request(
+            Direction::OpenAiToAnthropic,
+            json!({ "model": "gpt", "stream": true, "stream_options": { "include_usage": true } }),
+        );
+        let mut stream = translation.stream_translator();
+        let upstream = [
+            json!({ "type": "message_start", "message": { "id": "msg_1", "model": "claude", "usage": { "input_tokens": 10 } } }),
+            json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Hi" } }),
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({ "type": "content_block_start", "index": 1, "content_block": { "type": "tool_use", "id": "call_1", "name": "weather" } }),
+            json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": "{}" } }),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "tool_use" }, "usage": { "output_tokens": 5 } }),
+            json!({ "type": "message_stop" }),
+        ];
+        let mut out = Vec::new();
+        for event in &upstream {
+            let name = event["type"].as_str().unwrap();
+            out.extend(stream.feed(format!("event: {}\ndata: {}\n\n", name, event).as_bytes()));
+        }
+        out.extend(stream.finish());
+
+        let chunks = sse(&out);
+        assert_eq!(chunks.len(), 7);
+        assert_eq!(chunks[0]["id"], json!("msg_1"));
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], json!("assistant"));
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], json!("Hi"));
+        let call = &chunks[2]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call["index"], json!(0));
+        assert_eq!(call["id"], json!("call_1"));
+        assert_eq!(call["function"]["name"], json!("weather"));
+        assert_eq!(
+            chunks[3]["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"],
+            json!("{}")
+        );
+        assert_eq!(
+            chunks[4]["choices"][0]["finish_reason"],
+            json!("tool_calls")
+        );
+        assert_eq!(chunks[5]["usage"]["total_tokens"], json!(15));
+        assert_eq!(chunks[6], json!("[DONE]"));
+    }
+
+    #[test]
+    fn stream_keeps_characters_split_across_chunks() {
+        let (translation, _) = request(
+            Direction::AnthropicToOpenAi,
+            json!({ "model": "claude", "stream": true }),
+        );
+        let mut stream = translation.stream_translator();
+        let line = format!(
+            "data: {}\n\n",
+            json!({ "choices": [{ "delta": { "content": "héllo ✓" } }] })
+        );
+        let bytes = line.as_bytes();
+        let split = line.find('é').unwrap() + 1;
+        let mut out = stream.feed(&bytes[..split]);
+        assert!(out.is_empty());
+        out.extend(stream.feed(&bytes[split..]));
+        out.extend(stream.finish());
+
+        let events = sse(&out);
+        assert_eq!(events[2]["delta"]["text"], json!("héllo ✓"));
+    }
+
+    #[test]
+    fn stream_handles_a_last_line_without_newline() {
+        let (translation, _) = request(
+            Direction::OpenAiToAnthropic,
+            json!({ "model": "gpt", "stream": true }),
+        );
+        let mut stream = translation.stream_translator();
+        let out = stream.feed(br#"data: {"type":"message_start","message":{}}"#);
+        assert!(out.is_empty());
+        let chunks = sse(&stream.finish());
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], json!("assistant"));
+        assert_eq!(chunks.last(), Some(&json!("[DONE]")));
+    }
+}
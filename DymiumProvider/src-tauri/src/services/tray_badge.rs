@@ -0,0 +1,115 @@
+//! Tray icon status badges
+//!
+//! Draws a coloured dot over the lower-right corner of the tray icon so the
+//! token state is visible without opening the menu: green with a check when
+//! authenticated, yellow while connecting or verifying, red when failed and
+//! grey when idle or locked.
+
+use crate::services::config::TokenState;
+use tauri::image::Image;
+
+/// Badge radius as a fraction of the icon size
+const RADIUS: f32 = 0.22;
+
+/// Status badge for a token state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge {
+    Connected,
+    Busy,
+    Failed,
+    Inactive,
+}
+
+impl Badge {
+    pub fn for_state(state: &TokenState) -> Self {
+        match state {
+            TokenState::Authenticated { .. } => Self::Connected,
+            TokenState::Authenticating | TokenState::Verifying => Self::Busy,
+            TokenState::Failed { .. } => Self::Failed,
+            TokenState::Idle | TokenState::Locked => Self::Inactive,
+        }
+    }
+
+    /// Whether the badge carries colour. macOS renders template icons in
+    /// monochrome, so these have to be shown as regular icons.
+    pub fn is_coloured(self) -> bool {
+        self != Self::Inactive
+    }
+
+    fn colour(self) -> [u8; 3] {
+        match self {
+            Self::Connected => [52, 199, 89],
+            Self::Busy => [255, 204, 0],
+            Self::Failed => [255, 59, 48],
+            Self::Inactive => [142, 142, 147],
+        }
+    }
+
+    /// The base icon with this badge drawn over it
+    pub fn render(self, base: &Image<'_>) -> Image<'static> {
+        let (width, height) = (base.width(), base.height());
+        let mut rgba = base.rgba().to_vec();
+        let r = width.min(height) as f32 * RADIUS;
+        let (cx, cy) = (width as f32 - r - 1.0, height as f32 - r - 1.0);
+        // Clear a margin around the dot so it stands apart from the glyph
+        let margin = r * 0.3;
+        let [red, green, blue] = self.colour();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let distance = (px * px + py * py).sqrt();
+                if distance > r + margin + 0.5 {
+                    continue;
+                }
+                let i = ((y * width + x) * 4) as usize;
+                let cutout = coverage(r + margin - distance);
+                rgba[i + 3] = (rgba[i + 3] as f32 * (1.0 - cutout)) as u8;
+
+                let fill = coverage(r - distance);
+                if fill == 0.0 {
+                    continue;
+                }
+                let mut pixel = [red as f32, green as f32, blue as f32];
+                if self == Self::Connected {
+                    let check = coverage(r * 0.12 - distance_to_check(px / r, py / r) * r);
+                    for channel in &mut pixel {
+                        *channel += (255.0 - *channel) * check;
+                    }
+                }
+                blend(&mut rgba[i..i + 4], pixel, fill);
+            }
+        }
+
+        Image::new_owned(rgba, width, height)
+    }
+}
+
+/// Anti-aliased coverage for a signed distance inside an edge, in pixels
+fn coverage(inside: f32) -> f32 {
+    (inside + 0.5).clamp(0.0, 1.0)
+}
+
+/// Distance from a point to the check mark, in badge radii
+fn distance_to_check(x: f32, y: f32) -> f32 {
+    let (a, b, c) = ((-0.45, 0.0), (-0.1, 0.35), (0.45, -0.3));
+    distance_to_segment((x, y), a, b).min(distance_to_segment((x, y), b, c))
+}
+
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    let (ex, ey) = (p.0 - a.0 - t * dx, p.1 - a.1 - t * dy);
+    (ex * ex + ey * ey).sqrt()
+}
+
+/// Draw `colour` over an RGBA pixel with the given opacity
+fn blend(pixel: &mut [u8], colour: [f32; 3], alpha: f32) {
+    let below = pixel[3] as f32 / 255.0;
+    let out = alpha + below * (1.0 - alpha);
+    for (channel, value) in pixel.iter_mut().zip(colour) {
+        let mixed = (value * alpha + *channel as f32 * below * (1.0 - alpha)) / out;
+        *channel = mixed.round() as u8;
+    }
+    pixel[3] = (out * 255.0).round() as u8;
+}
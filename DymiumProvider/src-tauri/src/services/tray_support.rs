@@ -0,0 +1,91 @@
+//! Tray availability
+//!
+//! On Linux the tray icon is a StatusNotifierItem (AppIndicator), shown only
+//! if something on the session bus owns `org.kde.StatusNotifierWatcher`:
+//! KDE, most other desktops, or GNOME with the AppIndicator extension. Plain
+//! GNOME on Wayland has no tray at all, so without a watcher the app falls
+//! back to window mode (see `WindowMode`). The bus is asked with
+//! `dbus-send`, or `gdbus` if that is missing; if neither answers, the tray
+//! is assumed to work.
+
+use crate::services::config::WindowMode;
+
+/// D-Bus name of the StatusNotifier host registry
+#[cfg(target_os = "linux")]
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+/// Service for checking whether a tray icon can be shown
+pub struct TraySupport;
+
+impl TraySupport {
+    /// Whether the desktop can show the tray icon
+    pub fn available() -> bool {
+        platform::available()
+    }
+
+    /// Whether to run in window mode under `mode`
+    pub fn window_mode(mode: WindowMode) -> bool {
+        if cfg!(mobile) {
+            // The window is the whole app
+            return true;
+        }
+        match mode {
+            WindowMode::Auto => !Self::available(),
+            WindowMode::Always => true,
+            WindowMode::Never => false,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::WATCHER;
+    use std::process::Command;
+
+    pub fn available() -> bool {
+        let dbus_send = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus.NameHasOwner",
+                &format!("string:{}", WATCHER),
+            ])
+            .output();
+        if let Ok(output) = &dbus_send {
+            if output.status.success() {
+                return String::from_utf8_lossy(&output.stdout).contains("boolean true");
+            }
+        }
+        let gdbus = Command::new("gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.DBus",
+                "--object-path",
+                "/org/freedesktop/DBus",
+                "--method",
+                "org.freedesktop.DBus.NameHasOwner",
+                WATCHER,
+            ])
+            .output();
+        match gdbus {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).contains("true")
+            }
+            _ => {
+                log::warn!("Could not ask D-Bus for a tray; assuming there is one");
+                true
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    pub fn available() -> bool {
+        true
+    }
+}
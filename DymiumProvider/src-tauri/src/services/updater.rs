@@ -0,0 +1,474 @@
+//! Signed self-update
+//!
+//! Reads the manifest the Tauri updater uses (`latest.json`, as published by
+//! `tauri-action`) from `updater.manifestUrl` (`updater.betaManifestUrl` on
+//! the beta channel, if set) and installs the build for this platform.
+//! Artifacts must be signed with the `tauri signer` key built in with
+//! `DYMIUM_UPDATER_PUBKEY`; the signatures are minisign's. Builds without
+//! one take `updater.publicKey` from the org file, never from the user's
+//! config.json, which anything running as the user can write. The staged
+//! file is checked again right before it is installed, and the checked bytes
+//! are installed from a private copy. Nothing is downloaded until the user
+//! asks for it, and nothing is replaced until they choose to restart:
+//! - Linux: the AppImage file is replaced; .deb and .rpm packages are
+//!   installed with `pkexec`.
+//! - macOS: the .app bundle is swapped for the one in the .app.tar.gz.
+//! - Windows: the NSIS or MSI installer runs passively and starts the new
+//!   version.
+
+use crate::services::config::{AppConfig, ConfigError, UpdateChannel, UpdaterConfig};
+use crate::services::files;
+use crate::services::minisign::{MinisignError, PublicKey};
+use crate::services::org_config::OrgConfig;
+use crate::services::tls_trust::{HttpClients, PlainHttpRefused};
+use crate::services::updates::{compare_versions, UpdateInfo};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Updater public key of official builds
+const BUILTIN_PUBLIC_KEY: Option<&str> = option_env!("DYMIUM_UPDATER_PUBKEY");
+
+#[derive(Error, Debug)]
+pub enum UpdaterError {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("{0}")]
+    PlainHttp(#[from] PlainHttpRefused),
+    #[error("Update signature check failed: {0}")]
+    Signature(#[from] MinisignError),
+    #[error("updater.manifestUrl is not set")]
+    NoManifest,
+    #[error("No updater public key is configured")]
+    NoPublicKey,
+    #[error("Can't install {0} updates here")]
+    Unsupported(String),
+    #[error("Installer failed: {0}")]
+    Installer(String),
+}
+
+/// The updater manifest
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(default)]
+    pub_date: Option<String>,
+    #[serde(default)]
+    platforms: HashMap<String, Artifact>,
+}
+
+/// One platform's build in the manifest
+#[derive(Debug, Clone, Deserialize)]
+struct Artifact {
+    url: String,
+    /// The .sig file (a base64-encoded minisign signature)
+    signature: String,
+}
+
+/// A newer release with a build for this platform
+#[derive(Debug, Clone)]
+pub struct UpdatePackage {
+    pub version: String,
+    artifact: Artifact,
+}
+
+/// A downloaded and verified update, waiting for a restart
+#[derive(Debug, Clone)]
+pub struct StagedUpdate {
+    pub version: String,
+    path: PathBuf,
+    /// Checked again before installing, as the file sits in ~/.dymium
+    signature: String,
+}
+
+/// Service for downloading and installing signed updates
+pub struct UpdaterService;
+
+impl UpdaterService {
+    /// Whether updates can be installed: a manifest and a key to check
+    /// them with
+    pub fn enabled(settings: &UpdaterConfig) -> bool {
+        settings
+            .manifest_url
+            .as_deref()
+            .is_some_and(|url| !url.trim().is_empty())
+            && Self::public_key().is_some()
+    }
+
+    /// The built-in key, or else the org file's `updater.publicKey`
+    fn public_key() -> Option<String> {
+        BUILTIN_PUBLIC_KEY.map(str::to_string).or_else(|| {
+            OrgConfig::load_file()
+                .settings()
+                .get("updater")?
+                .get("publicKey")?
+                .as_str()
+                .filter(|key| !key.trim().is_empty())
+                .map(str::to_string)
+        })
+    }
+
+    /// Check `data` against a signature from the manifest
+    fn verify(data: &[u8], signature: &str) -> Result<(), UpdaterError> {
+        let public_key = Self::public_key().ok_or(UpdaterError::NoPublicKey)?;
+        PublicKey::parse(&unwrap_base64(&public_key))?.verify(data, &unwrap_base64(signature))?;
+        Ok(())
+    }
+
+    fn updates_dir() -> Result<PathBuf, UpdaterError> {
+        Ok(AppConfig::config_dir()?.join("updates"))
+    }
+
+    /// Fetch `channel`'s manifest and compare it with this build. The
+    /// package is set when there's a newer version with a build for this
+    /// platform.
+    pub async fn check(
+        clients: &HttpClients,
+        settings: &UpdaterConfig,
+        channel: UpdateChannel,
+    ) -> Result<(UpdateInfo, Option<UpdatePackage>), UpdaterError> {
+        let beta = match channel {
+            UpdateChannel::Beta => settings
+                .beta_manifest_url
+                .as_deref()
+                .filter(|url| !url.trim().is_empty()),
+            UpdateChannel::Stable => None,
+        };
+        let url = beta
+            .or(settings.manifest_url.as_deref())
+            .ok_or(UpdaterError::NoManifest)?;
+        let manifest: Manifest = clients
+            .for_url(url)?
+            .get(url)
+            .header(
+                "User-Agent",
+                concat!("dymium-provider/", env!("CARGO_PKG_VERSION")),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let current = env!("CARGO_PKG_VERSION");
+        let latest = manifest.version.trim_start_matches('v');
+        let newer = compare_versions(latest, current) == Ordering::Greater;
+        let info = UpdateInfo {
+            current_version: current.to_string(),
+            latest_version: latest.to_string(),
+            update_available: newer,
+            url: None,
+            published_at: manifest.pub_date.clone(),
+        };
+        if !newer {
+            return Ok((info, None));
+        }
+        let targets = platform::targets();
+        let artifact = targets
+            .iter()
+            .find_map(|target| manifest.platforms.get(target));
+        let package = match artifact {
+            Some(artifact) => Some(UpdatePackage {
+                version: latest.to_string(),
+                artifact: artifact.clone(),
+            }),
+            None => {
+                log::warn!("Release {} has no build for {}", latest, targets[0]);
+                None
+            }
+        };
+        Ok((info, package))
+    }
+
+    /// Download the package and check its signature
+    pub async fn download(
+        clients: &HttpClients,
+        package: &UpdatePackage,
+    ) -> Result<StagedUpdate, UpdaterError> {
+        let url = &package.artifact.url;
+        log::info!("Downloading update {} from {}", package.version, url);
+        let data = clients
+            .for_url(url)?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Self::verify(&data, &package.artifact.signature)?;
+
+        let name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("update");
+        let dir = Self::updates_dir()?;
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        fs::write(&path, &data)?;
+        log::info!(
+            "Update {} verified and saved to {}",
+            package.version,
+            path.display()
+        );
+        Ok(StagedUpdate {
+            version: package.version.clone(),
+            path,
+            signature: package.artifact.signature.clone(),
+        })
+    }
+
+    /// Install a staged update. The caller restarts the app afterwards
+    /// (exits, on Windows, where the installer starts the new version).
+    ///
+    /// The bytes whose signature was checked are written to a new private
+    /// directory and installed from there, so swapping the staged file after
+    /// the check has no effect.
+    pub fn install(staged: &StagedUpdate) -> Result<(), UpdaterError> {
+        let data = fs::read(&staged.path)?;
+        Self::verify(&data, &staged.signature)?;
+        let dir = Self::install_dir()?;
+        let path = dir.join(staged.path.file_name().unwrap_or("update".as_ref()));
+        let result = files::create_private(&path)
+            .and_then(|mut file| file.write_all(&data))
+            .map_err(UpdaterError::from)
+            .and_then(|()| {
+                log::info!("Installing update {}", staged.version);
+                platform::install(&path)
+            });
+        // The Windows installers run after the app exits and still need it
+        if cfg!(not(windows)) || result.is_err() {
+            let _ = fs::remove_dir_all(&dir);
+        }
+        result
+    }
+
+    /// A new directory, accessible by this user only, to install from
+    fn install_dir() -> Result<PathBuf, UpdaterError> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        let dir =
+            std::env::temp_dir().join(format!("dymium-update-{}-{}", std::process::id(), nanos));
+        files::create_private_dir(&dir)?;
+        Ok(dir)
+    }
+}
+
+/// `tauri signer` base64-encodes whole key and signature files; plain
+/// minisign ones are used as they are
+fn unwrap_base64(value: &str) -> String {
+    STANDARD
+        .decode(value.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .filter(|text| text.contains("comment:"))
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Lower-case file name of `path`
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Unpack a .tar.gz into `dir` and return the single entry in it
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn unpack(archive: &Path, dir: &Path) -> Result<PathBuf, UpdaterError> {
+    fs::create_dir_all(dir)?;
+    run(std::process::Command::new("tar")
+        .arg("-xzf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dir))?;
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    match entries.len() {
+        1 => Ok(entries.remove(0).path()),
+        _ => Err(UpdaterError::Unsupported(file_name(archive))),
+    }
+}
+
+/// Run an installer command, failing unless it succeeds
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(command: &mut std::process::Command) -> Result<(), UpdaterError> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(UpdaterError::Installer(format!(
+            "{} exited with {}",
+            command.get_program().to_string_lossy(),
+            status
+        )))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{file_name, run, unpack, UpdaterError};
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Manifest keys for this build, most specific first
+    pub fn targets() -> Vec<String> {
+        let target = format!("linux-{}", std::env::consts::ARCH);
+        let bundle = if std::env::var_os("APPIMAGE").is_some() {
+            "appimage"
+        } else if Path::new("/etc/debian_version").exists() {
+            "deb"
+        } else {
+            "rpm"
+        };
+        vec![format!("{}-{}", target, bundle), target]
+    }
+
+    pub fn install(path: &Path) -> Result<(), UpdaterError> {
+        let name = file_name(path);
+        if name.ends_with(".tar.gz") {
+            let dir = path.with_extension("unpacked");
+            return install(&unpack(path, &dir)?);
+        }
+        if name.ends_with(".appimage") {
+            let target = std::env::var_os("APPIMAGE")
+                .map(PathBuf::from)
+                .ok_or_else(|| UpdaterError::Unsupported("AppImage".to_string()))?;
+            // Renamed into place, so the running image is never half-written
+            let staging = target.with_extension("new");
+            fs::copy(path, &staging)?;
+            fs::set_permissions(&staging, fs::Permissions::from_mode(0o755))?;
+            fs::rename(&staging, &target)?;
+            return Ok(());
+        }
+        if name.ends_with(".deb") {
+            return run(Command::new("pkexec").args(["dpkg", "-i"]).arg(path));
+        }
+        if name.ends_with(".rpm") {
+            return run(Command::new("pkexec").args(["rpm", "-U"]).arg(path));
+        }
+        Err(UpdaterError::Unsupported(name))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{file_name, unpack, UpdaterError};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Manifest keys for this build, most specific first
+    pub fn targets() -> Vec<String> {
+        let target = format!("darwin-{}", std::env::consts::ARCH);
+        vec![format!("{}-app", target), target]
+    }
+
+    /// The .app bundle this binary runs from
+    fn bundle_path() -> Result<PathBuf, UpdaterError> {
+        let exe = std::env::current_exe()?;
+        exe.ancestors()
+            .find(|p| p.extension().is_some_and(|ext| ext == "app"))
+            .filter(|p| !p.to_string_lossy().contains("/AppTranslocation/"))
+            .map(Path::to_path_buf)
+            .ok_or_else(|| UpdaterError::Unsupported("app bundle".to_string()))
+    }
+
+    pub fn install(path: &Path) -> Result<(), UpdaterError> {
+        let name = file_name(path);
+        if !name.ends_with(".app.tar.gz") {
+            return Err(UpdaterError::Unsupported(name));
+        }
+        let bundle = bundle_path()?;
+        let parent = bundle
+            .parent()
+            .ok_or_else(|| UpdaterError::Unsupported("app bundle".to_string()))?;
+        // Unpacked next to the bundle, so both moves are renames
+        let dir = parent.join(".dymium-provider-update");
+        let _ = fs::remove_dir_all(&dir);
+        let result = unpack(path, &dir).and_then(|new_bundle| {
+            let backup = bundle.with_extension("app.old");
+            let _ = fs::remove_dir_all(&backup);
+            fs::rename(&bundle, &backup)?;
+            if let Err(e) = fs::rename(&new_bundle, &bundle) {
+                let _ = fs::rename(&backup, &bundle);
+                return Err(e.into());
+            }
+            let _ = fs::remove_dir_all(&backup);
+            Ok(())
+        });
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{file_name, UpdaterError};
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Manifest keys for this build, most specific first
+    pub fn targets() -> Vec<String> {
+        let target = format!("windows-{}", std::env::consts::ARCH);
+        vec![
+            format!("{}-nsis", target),
+            format!("{}-msi", target),
+            target,
+        ]
+    }
+
+    pub fn install(path: &Path) -> Result<(), UpdaterError> {
+        let name = file_name(path);
+        // The app exits right after; both start the new version when done
+        if name.ends_with(".msi") {
+            Command::new("msiexec")
+                .arg("/i")
+                .arg(path)
+                .args(["/passive", "AUTOLAUNCHAPP=True"])
+                .spawn()?;
+            return Ok(());
+        }
+        if name.ends_with(".exe") {
+            Command::new(path).args(["/P", "/R"]).spawn()?;
+            return Ok(());
+        }
+        Err(UpdaterError::Unsupported(name))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::{file_name, UpdaterError};
+    use std::path::Path;
+
+    pub fn targets() -> Vec<String> {
+        vec![format!(
+            "{}-{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )]
+    }
+
+    pub fn install(path: &Path) -> Result<(), UpdaterError> {
+        Err(UpdaterError::Unsupported(file_name(path)))
+    }
+}
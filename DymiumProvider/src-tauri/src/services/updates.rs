@@ -0,0 +1,172 @@
+//! Update discovery
+//!
+//! Asks the release feed (GitHub's latest release for this repository, or
+//! `updateFeedUrl`) for the newest version and compares it with the running
+//! one. On the beta channel pre-releases count too, and the default feed is
+//! the list of all releases. Installing is `UpdaterService`'s job, when it's
+//! set up.
+
+use crate::services::config::UpdateChannel;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+/// Latest GitHub release of the app
+pub const DEFAULT_FEED_URL: &str =
+    "https://api.github.com/repos/kojinglick-dymium/dymium-provider/releases/latest";
+
+/// All GitHub releases of the app, pre-releases included
+pub const BETA_FEED_URL: &str =
+    "https://api.github.com/repos/kojinglick-dymium/dymium-provider/releases";
+
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Release feed returned {0}")]
+    FeedError(u16),
+    #[error("Release feed lists no releases")]
+    NoRelease,
+}
+
+/// A release as the feed describes it (GitHub's release JSON)
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    html_url: Option<String>,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+}
+
+/// A feed holds one release (`/releases/latest`) or a list (`/releases`)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Feed {
+    One(Release),
+    Many(Vec<Release>),
+}
+
+/// Result of an update check
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest_version: String,
+    pub update_available: bool,
+    /// Release page to download from
+    pub url: Option<String>,
+    pub published_at: Option<String>,
+}
+
+/// Service for checking the release feed
+pub struct UpdateService;
+
+impl UpdateService {
+    /// The feed to check on `channel` when `updateFeedUrl` isn't set
+    pub fn default_feed_url(channel: UpdateChannel) -> &'static str {
+        match channel {
+            UpdateChannel::Stable => DEFAULT_FEED_URL,
+            UpdateChannel::Beta => BETA_FEED_URL,
+        }
+    }
+
+    /// Fetch the newest release on `channel` and compare it with this build
+    pub async fn check(feed_url: &str, channel: UpdateChannel) -> Result<UpdateInfo, UpdateError> {
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .build()?;
+        let response = client
+            .get(feed_url)
+            .header(
+                "User-Agent",
+                concat!("dymium-provider/", env!("CARGO_PKG_VERSION")),
+            )
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(UpdateError::FeedError(response.status().as_u16()));
+        }
+        let releases = match response.json().await? {
+            Feed::One(release) => vec![release],
+            Feed::Many(releases) => releases,
+        };
+        let release = releases
+            .into_iter()
+            .filter(|r| !r.draft && (channel == UpdateChannel::Beta || !r.prerelease))
+            .max_by(|a, b| {
+                compare_versions(
+                    a.tag_name.trim_start_matches('v'),
+                    b.tag_name.trim_start_matches('v'),
+                )
+            })
+            .ok_or(UpdateError::NoRelease)?;
+
+        let current = env!("CARGO_PKG_VERSION");
+        let latest = release.tag_name.trim_start_matches('v');
+        Ok(UpdateInfo {
+            current_version: current.to_string(),
+            latest_version: latest.to_string(),
+            update_available: compare_versions(latest, current) == Ordering::Greater,
+            url: release.html_url,
+            published_at: release.published_at,
+        })
+    }
+}
+
+/// Compare versions ("0.1.15", "0.2.0-beta.1") like semver: by the dotted
+/// numbers, then a pre-release sorts before its release. Build metadata is
+/// ignored.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> (Vec<u64>, Option<String>) {
+        let v = v.split('+').next().unwrap_or_default();
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (v, None),
+        };
+        let numbers = core.split('.').map(|p| p.parse().unwrap_or(0)).collect();
+        (numbers, pre)
+    };
+    let ((a, a_pre), (b, b_pre)) = (split(a), split(b));
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|o| o.is_ne())
+        .unwrap_or_else(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => compare_prerelease(&a, &b),
+        })
+}
+
+/// Compare pre-release tags ("beta.2", "rc.1") identifier by identifier:
+/// numbers numerically and below words, words alphabetically
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if order.is_ne() {
+                    return order;
+                }
+            }
+        }
+    }
+}
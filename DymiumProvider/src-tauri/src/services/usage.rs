@@ -0,0 +1,135 @@
+//! Usage metering
+//!
+//! Daily request and token counts per GhostLLM app and model, aggregated from
+//! traffic through the local proxy and kept in ~/.dymium/usage.json. Token
+//! counts come from the response's `usage` block, so streaming requests only
+//! count tokens when the client asks for `stream_options.include_usage`.
+
+use crate::services::config::{AppConfig, ConfigError};
+use crate::services::files;
+use crate::services::proxy_log::Usage;
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Days of history kept
+const RETENTION_DAYS: i64 = 365;
+
+#[derive(Error, Debug)]
+pub enum UsageError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+}
+
+/// Totals for one app and model on one day (UTC)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub date: NaiveDate,
+    /// GhostLLM app, or "default" without one
+    pub app: String,
+    pub model: String,
+    pub requests: u64,
+    /// Requests that got an error status (or no response)
+    pub errors: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// Shared usage totals, saved after every update
+#[derive(Clone, Default)]
+pub struct UsageMeter {
+    records: Arc<Mutex<Vec<UsageRecord>>>,
+}
+
+impl UsageMeter {
+    /// Get the usage file path (~/.dymium/usage.json)
+    pub fn path() -> Result<PathBuf, UsageError> {
+        Ok(AppConfig::config_dir()?.join("usage.json"))
+    }
+
+    /// Load saved totals; starts empty if there are none or they can't be read
+    pub fn load() -> Self {
+        let records = Self::path()
+            .and_then(|path| {
+                Ok(match files::read_optional(&path)? {
+                    Some(content) => serde_json::from_str(&content)?,
+                    None => Vec::new(),
+                })
+            })
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load usage stats: {}", e);
+                Vec::new()
+            });
+        Self {
+            records: Arc::new(Mutex::new(records)),
+        }
+    }
+
+    /// Count one request
+    pub fn record(&self, app: Option<&str>, model: Option<&str>, success: bool, usage: Usage) {
+        let Ok(mut records) = self.records.lock() else {
+            return;
+        };
+        let today = Utc::now().date_naive();
+        let app = app.filter(|a| !a.is_empty()).unwrap_or("default");
+        let model = model.unwrap_or("unknown");
+
+        let index = records
+            .iter()
+            .position(|r| r.date == today && r.app == app && r.model == model)
+            .unwrap_or_else(|| {
+                records.push(UsageRecord {
+                    date: today,
+                    app: app.to_string(),
+                    model: model.to_string(),
+                    requests: 0,
+                    errors: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    total_tokens: 0,
+                });
+                records.len() - 1
+            });
+        let record = &mut records[index];
+        record.requests += 1;
+        record.errors += u64::from(!success);
+        record.prompt_tokens += usage.prompt_tokens;
+        record.completion_tokens += usage.completion_tokens;
+        record.total_tokens += usage.total_tokens;
+
+        let cutoff = today - Duration::days(RETENTION_DAYS);
+        records.retain(|r| r.date >= cutoff);
+        if let Err(e) = Self::save(&records) {
+            log::warn!("Failed to save usage stats: {}", e);
+        }
+    }
+
+    /// Totals for the last `days` days (all kept history if `None`), oldest first
+    pub fn stats(&self, days: Option<u32>) -> Vec<UsageRecord> {
+        let Ok(records) = self.records.lock() else {
+            return Vec::new();
+        };
+        let cutoff = days.map(|d| Utc::now().date_naive() - Duration::days(i64::from(d)));
+        let mut stats: Vec<UsageRecord> = records
+            .iter()
+            .filter(|r| cutoff.is_none_or(|c| r.date > c))
+            .cloned()
+            .collect();
+        stats.sort_by(|a, b| (a.date, &a.app, &a.model).cmp(&(b.date, &b.app, &b.model)));
+        stats
+    }
+
+    fn save(records: &[UsageRecord]) -> Result<(), UsageError> {
+        let content = serde_json::to_string_pretty(records)?;
+        files::write_atomic(&Self::path()?, content.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! Master-password vault
+//!
+//! Optional app-level protection for stored credentials. When enabled, secrets
+//! are kept out of ~/.dymium/config.json and written to ~/.dymium/vault.json,
+//! encrypted with a key derived from the user's master password
+//! (Argon2id + XChaCha20-Poly1305).
+//!
+//! The vault starts locked on every launch. Until it is unlocked the token
+//! service has no credentials to authenticate with, and it locks itself again
+//! after the configured period of inactivity.
+
+use crate::services::config::{AppConfig, Secrets};
+use crate::services::files;
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const VAULT_VERSION: u32 = 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum VaultError {
+    #[error("Vault is locked")]
+    Locked,
+    #[error("Incorrect master password")]
+    WrongPassword,
+    #[error("No vault found")]
+    NotFound,
+    #[error("Master password must not be empty")]
+    EmptyPassword,
+    #[error("Unsupported vault version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Vault IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Vault format error: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("Vault crypto error: {0}")]
+    Crypto(String),
+    #[error("Config directory not found")]
+    NoDir,
+}
+
+/// On-disk vault format (~/.dymium/vault.json)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Master-password vault holding the derived key while unlocked
+pub struct Vault {
+    key: Option<[u8; KEY_LEN]>,
+    salt: Vec<u8>,
+    last_activity: Instant,
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vault {
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            salt: Vec::new(),
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// Get the vault file path (~/.dymium/vault.json)
+    pub fn path() -> Result<PathBuf, VaultError> {
+        AppConfig::config_dir()
+            .map(|p| p.join("vault.json"))
+            .map_err(|_| VaultError::NoDir)
+    }
+
+    /// Whether the vault key is currently held in memory
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Create a new vault protected by `master_password` and store `secrets` in it.
+    /// Replaces any existing vault file.
+    pub fn create(&mut self, master_password: &str, secrets: &Secrets) -> Result<(), VaultError> {
+        if master_password.is_empty() {
+            return Err(VaultError::EmptyPassword);
+        }
+
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        self.key = Some(derive_key(master_password, &salt)?);
+        self.salt = salt;
+        self.touch();
+        self.store(secrets)?;
+        log::info!("Created credential vault");
+        Ok(())
+    }
+
+    /// Unlock the vault with `master_password` and return the stored secrets
+    pub fn unlock(&mut self, master_password: &str) -> Result<Secrets, VaultError> {
+        let (secrets, key, salt) = Self::open(&Self::read_file()?, master_password)?;
+        self.key = Some(key);
+        self.salt = salt;
+        self.touch();
+        log::info!("Credential vault unlocked");
+        Ok(secrets)
+    }
+
+    /// Decrypt `file` with `master_password`. Returns the secrets, the key
+    /// and the salt.
+    fn open(
+        file: &VaultFile,
+        master_password: &str,
+    ) -> Result<(Secrets, [u8; KEY_LEN], Vec<u8>), VaultError> {
+        let salt = BASE64
+            .decode(&file.salt)
+            .map_err(|e| VaultError::Crypto(e.to_string()))?;
+        let nonce = BASE64
+            .decode(&file.nonce)
+            .map_err(|e| VaultError::Crypto(e.to_string()))?;
+        let ciphertext = BASE64
+            .decode(&file.ciphertext)
+            .map_err(|e| VaultError::Crypto(e.to_string()))?;
+        if nonce.len() != 24 {
+            return Err(VaultError::Crypto("invalid nonce length".to_string()));
+        }
+
+        let key = derive_key(master_password, &salt)?;
+        let cipher = XChaCha20Poly1305::new(&key.into());
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| VaultError::WrongPassword)?;
+        let secrets: Secrets = serde_json::from_slice(&plaintext)?;
+        Ok((secrets, key, salt))
+    }
+
+    /// Forget the derived key. Secrets stay encrypted on disk.
+    pub fn lock(&mut self) {
+        if let Some(mut key) = self.key.take() {
+            key.fill(0);
+            log::info!("Credential vault locked");
+        }
+    }
+
+    /// Encrypt `secrets` with the unlocked key and write them to disk
+    pub fn store(&self, secrets: &Secrets) -> Result<(), VaultError> {
+        let file = self.seal(secrets)?;
+
+        // A crash mid-write must leave the previous vault intact, or the
+        // credentials are gone
+        let path = Self::path()?;
+        files::write_atomic(&path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+        files::restrict_to_owner(&path)?;
+
+        log::debug!("Wrote credential vault to {}", path.display());
+        Ok(())
+    }
+
+    /// Encrypt `secrets` with the unlocked key, under a fresh nonce
+    fn seal(&self, secrets: &Secrets) -> Result<VaultFile, VaultError> {
+        let key = self.key.as_ref().ok_or(VaultError::Locked)?;
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(secrets)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|e| VaultError::Crypto(e.to_string()))?;
+
+        Ok(VaultFile {
+            version: VAULT_VERSION,
+            salt: BASE64.encode(&self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    /// Delete the vault file from disk
+    pub fn destroy() -> Result<(), VaultError> {
+        let path = Self::path()?;
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("Deleted credential vault");
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(VaultError::Io(e)),
+        }
+    }
+
+    /// Record user activity (resets the auto-lock timer)
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Time since the last recorded user activity
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    fn read_file() -> Result<VaultFile, VaultError> {
+        let path = Self::path()?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(VaultError::NotFound),
+            Err(e) => return Err(VaultError::Io(e)),
+        };
+        Self::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<VaultFile, VaultError> {
+        let file: VaultFile = serde_json::from_str(content)?;
+        if file.version != VAULT_VERSION {
+            return Err(VaultError::UnsupportedVersion(file.version));
+        }
+        Ok(file)
+    }
+}
+
+impl Drop for Vault {
+    fn drop(&mut self) {
+        self.lock();
+    }
+}
+
+/// Derive the vault encryption key from the master password (Argon2id)
+fn derive_key(master_password: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], VaultError> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(master_password.as_bytes(), salt, &mut key)
+        .map_err(|e| VaultError::Crypto(e.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> Secrets {
+        Secrets {
+            client_secret: Some("client-secret".to_string()),
+            password: None,
+            refresh_token: Some("refresh-token".to_string()),
+            static_api_key: None,
+        }
+    }
+
+    /// A vault sealed with "correct horse", as it would be written to disk
+    fn sealed() -> String {
+        let mut vault = Vault::new();
+        vault.salt = vec![7; SALT_LEN];
+        vault.key = Some(derive_key("correct horse", &vault.salt).unwrap());
+        serde_json::to_string_pretty(&vault.seal(&secrets()).unwrap()).unwrap()
+    }
+
+    fn open(content: &str, password: &str) -> Result<Secrets, VaultError> {
+        Vault::open(&Vault::parse(content)?, password).map(|(secrets, _, _)| secrets)
+    }
+
+    #[test]
+    fn round_trip() {
+        let content = sealed();
+        assert!(!content.contains("refresh-token"));
+        let opened = open(&content, "correct horse").unwrap();
+        assert_eq!(opened.client_secret.as_deref(), Some("client-secret"));
+        assert_eq!(opened.refresh_token.as_deref(), Some("refresh-token"));
+        assert_eq!(opened.password, None);
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        assert!(matches!(
+            open(&sealed(), "battery staple"),
+            Err(VaultError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn truncated_or_corrupted_file_is_rejected() {
+        let content = sealed();
+        let mut file: VaultFile = serde_json::from_str(&content).unwrap();
+
+        // Cut off mid-write
+        assert!(matches!(
+            open(&content[..content.len() / 2], "correct horse"),
+            Err(VaultError::Format(_))
+        ));
+
+        // Ciphertext missing its tail, or with a byte flipped
+        let mut ciphertext = BASE64.decode(&file.ciphertext).unwrap();
+        let truncated = VaultFile {
+            ciphertext: BASE64.encode(&ciphertext[..ciphertext.len() - 1]),
+            ..serde_json::from_str(&content).unwrap()
+        };
+        assert!(Vault::open(&truncated, "correct horse").is_err());
+        ciphertext[0] ^= 1;
+        file.ciphertext = BASE64.encode(&ciphertext);
+        assert!(Vault::open(&file, "correct horse").is_err());
+
+        // A nonce of the wrong size, and a version this build can't read
+        let short_nonce = content.replace(&file.nonce, &BASE64.encode([0u8; 12]));
+        assert!(matches!(
+            open(&short_nonce, "correct horse"),
+            Err(VaultError::Crypto(_))
+        ));
+        let future = content.replace("\"version\": 1", "\"version\": 2");
+        assert!(matches!(
+            open(&future, "correct horse"),
+            Err(VaultError::UnsupportedVersion(2))
+        ));
+    }
+}
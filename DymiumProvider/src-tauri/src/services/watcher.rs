@@ -0,0 +1,56 @@
+//! Change notifications for managed files
+//!
+//! The parent directories are watched rather than the files themselves: most
+//! editors (and our own atomic writes) save by renaming a new file over the old
+//! one, which a watch on the original inode would miss.
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+/// Watches a set of files; dropped to stop watching
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Start watching `paths`. Every filesystem event touching one of them
+    /// sends a message on the returned channel; callers should debounce.
+    pub fn new(paths: &[PathBuf]) -> notify::Result<(Self, UnboundedReceiver<()>)> {
+        let names: HashSet<OsString> = paths
+            .iter()
+            .filter_map(|p| p.file_name().map(|n| n.to_os_string()))
+            .collect();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    let relevant = event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name().is_some_and(|n| names.contains(n)));
+                    if relevant {
+                        let _ = tx.send(());
+                    }
+                }
+                Err(e) => log::warn!("File watcher error: {}", e),
+            })?;
+
+        let dirs: HashSet<PathBuf> = paths
+            .iter()
+            .filter_map(|p| p.parent().map(|d| d.to_path_buf()))
+            .collect();
+        for dir in dirs {
+            // The directory must exist to be watched
+            fs::create_dir_all(&dir)?;
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            log::debug!("Watching {} for changes", dir.display());
+        }
+
+        Ok((Self { _watcher: watcher }, rx))
+    }
+}
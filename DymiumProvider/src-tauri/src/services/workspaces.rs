@@ -0,0 +1,106 @@
+//! Per-project workspace profiles
+//!
+//! `workspaces` maps project directories to saved profiles. Each directory
+//! gets a project-local opencode.json whose `dymium` provider points at that
+//! profile's endpoint and GhostLLM app; OpenCode merges it over the global
+//! config when started in the project, so the models and API key still come
+//! from there. Only profiles with the same Keycloak client and user (or the
+//! same static key) as the active settings can be used this way, since all
+//! projects share one token.
+
+use crate::services::config::{AppConfig, Profile};
+use crate::services::jsonc;
+use crate::services::opencode::OpenCodeService;
+use serde_json::json;
+use std::path::PathBuf;
+use thiserror::Error;
+
+const API_KEY: &[&str] = &["provider", "dymium", "api"];
+const BASE_URL_KEY: &[&str] = &["provider", "dymium", "options", "baseURL"];
+
+#[derive(Error, Debug)]
+pub enum WorkspaceError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("No profile named {0}")]
+    UnknownProfile(String),
+    #[error("Profile {0} uses another login than the active settings")]
+    OtherIdentity(String),
+    #[error("Workspace profiles need a direct connection, not socket-only mode")]
+    SocketOnly,
+    #[error("Not a directory: {0}")]
+    NotADirectory(String),
+    #[error("Home directory not found")]
+    NoHomeDir,
+}
+
+/// Service for project-local OpenCode configs
+pub struct WorkspaceService;
+
+impl WorkspaceService {
+    /// Project-local opencode.json of `dir` (`~/` expanded)
+    pub fn config_path(dir: &str) -> Result<PathBuf, WorkspaceError> {
+        let dir = match dir.trim().strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()
+                .map(|home| home.join(rest))
+                .ok_or(WorkspaceError::NoHomeDir)?,
+            None => PathBuf::from(dir.trim()),
+        };
+        if !dir.is_dir() {
+            return Err(WorkspaceError::NotADirectory(dir.display().to_string()));
+        }
+        Ok(dir.join("opencode.json"))
+    }
+
+    /// `config` with the connection settings of `profile` applied
+    pub fn profile_config(config: &AppConfig, profile: &str) -> Result<AppConfig, WorkspaceError> {
+        let saved = config
+            .profiles
+            .get(profile)
+            .ok_or_else(|| WorkspaceError::UnknownProfile(profile.to_string()))?;
+        let active = Profile::from_config(config);
+        if saved.auth_mode != active.auth_mode || !saved.same_identity(&active) {
+            return Err(WorkspaceError::OtherIdentity(profile.to_string()));
+        }
+        let mut workspace = config.clone();
+        saved.apply(&mut workspace);
+        Ok(workspace)
+    }
+
+    /// Write the dymium base URL of `profile` into `dir`'s opencode.json
+    pub fn write(config: &AppConfig, dir: &str, profile: &str) -> Result<(), WorkspaceError> {
+        if config.socket_only {
+            // OpenCode goes through the proxy, which serves only one endpoint
+            return Err(WorkspaceError::SocketOnly);
+        }
+        let base_url = OpenCodeService::compute_base_url(&Self::profile_config(config, profile)?);
+        let path = Self::config_path(dir)?;
+        let edits: [(&[&str], _); 2] = [
+            (API_KEY, Some(json!(&base_url))),
+            (BASE_URL_KEY, Some(json!(&base_url))),
+        ];
+        if jsonc::update_file(&path, &edits)? {
+            log::info!("Pointed {} at profile {}", path.display(), profile);
+        }
+        Ok(())
+    }
+
+    /// Bring every workspace's opencode.json up to date. Failures are logged
+    /// only; the global config is what matters most.
+    pub fn sync(config: &AppConfig) {
+        for (dir, profile) in &config.workspaces {
+            if let Err(e) = Self::write(config, dir, profile) {
+                log::warn!("Failed to update OpenCode config in {}: {}", dir, e);
+            }
+        }
+    }
+
+    /// Remove the dymium provider from `dir`'s opencode.json
+    pub fn clear(dir: &str) -> Result<(), WorkspaceError> {
+        let path = Self::config_path(dir)?;
+        if jsonc::update_file(&path, &[(&["provider", "dymium"], None)])? {
+            log::info!("Removed dymium provider from {}", path.display());
+        }
+        Ok(())
+    }
+}
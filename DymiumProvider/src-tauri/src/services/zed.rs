@@ -0,0 +1,112 @@
+//! Zed integration
+//!
+//! Maintains a "Dymium" provider under `language_models.openai_compatible` in
+//! Zed's settings.json with the GhostLLM URL and model list. Zed reads the key
+//! for such a provider from the `DYMIUM_API_KEY` environment variable (or its
+//! own keychain entry), so the token itself is not written here. Comments and
+//! the rest of the file are preserved, and the file is only rewritten when the
+//! block actually changes.
+
+use crate::services::config::{AppConfig, ModelEntry};
+use crate::services::integrations::{Integration, SyncContext};
+use crate::services::jsonc;
+use crate::services::opencode::{OpenCodeService, DEFAULT_CONTEXT_LIMIT};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use thiserror::Error;
+
+const PROVIDER_KEY: &[&str] = &["language_models", "openai_compatible", "Dymium"];
+
+#[derive(Error, Debug)]
+pub enum ZedError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Config directory not found")]
+    NoConfigDir,
+}
+
+/// Service for managing Zed's language model settings
+pub struct ZedService;
+
+impl ZedService {
+    /// Get Zed's settings path (~/.config/zed/settings.json, or
+    /// %APPDATA%\Zed\settings.json on Windows)
+    pub fn settings_path() -> Result<PathBuf, ZedError> {
+        let path = if cfg!(windows) {
+            dirs::config_dir().map(|p| p.join("Zed").join("settings.json"))
+        } else {
+            dirs::home_dir().map(|p| p.join(".config/zed/settings.json"))
+        };
+        path.ok_or(ZedError::NoConfigDir)
+    }
+
+    /// Write the Dymium provider block for the current endpoint and models
+    pub fn sync(config: &AppConfig, models: &[ModelEntry]) -> Result<(), ZedError> {
+        if models.is_empty() {
+            log::debug!("No GhostLLM models known yet; leaving Zed settings alone");
+            return Ok(());
+        }
+
+        let available_models: Vec<Value> = models
+            .iter()
+            .map(|m| {
+                let mut entry = json!({
+                    "name": m.id,
+                    "display_name": m.name.as_deref().unwrap_or(&m.id),
+                    "max_tokens": m.context_limit.unwrap_or(DEFAULT_CONTEXT_LIMIT),
+                });
+                if let Some(output) = m.output_limit {
+                    entry["max_output_tokens"] = json!(output);
+                }
+                entry
+            })
+            .collect();
+        let provider = json!({
+            "api_url": OpenCodeService::compute_base_url(config),
+            "available_models": available_models,
+        });
+
+        let path = Self::settings_path()?;
+        if jsonc::update_file(&path, &[(PROVIDER_KEY, Some(provider))])? {
+            log::info!("Updated Dymium provider in {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Remove the Dymium provider block (on log out / mode switch)
+    pub fn clear() {
+        let result = Self::settings_path()
+            .and_then(|path| Ok(jsonc::update_file(&path, &[(PROVIDER_KEY, None)])?));
+        match result {
+            Ok(true) => log::info!("Cleared Dymium provider from Zed settings"),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to clear Zed settings: {}", e),
+        }
+    }
+}
+
+impl Integration for ZedService {
+    fn id(&self) -> &'static str {
+        "zed"
+    }
+
+    fn name(&self) -> &'static str {
+        "Zed"
+    }
+
+    fn target_files(&self, _config: &AppConfig) -> Vec<PathBuf> {
+        Self::settings_path().into_iter().collect()
+    }
+
+    fn template(&self) -> &'static str {
+        "the Dymium provider under language_models.openai_compatible"
+    }
+
+    fn sync(&self, ctx: &SyncContext) -> anyhow::Result<()> {
+        Ok(Self::sync(ctx.config, ctx.models)?)
+    }
+
+    fn clear(&self, _config: &AppConfig) {
+        Self::clear()
+    }
+}
@@ -6,12 +6,29 @@ import "./App.css";
 
 // Types matching Rust backend
 type AuthMode = "OAuth" | "StaticKey";
+type SecretKind = "staticApiKey" | "clientSecret" | "password";
+
+type FailureKind =
+  | "unauthorized"
+  | "endpointTimeout"
+  | "endpointUnreachable"
+  | "serverError"
+  | "certificate"
+  | "openCodeWrite"
+  | "fileWrite"
+  | "config"
+  | "missingCredentials"
+  | "keystore"
+  | "locked"
+  | "disabled"
+  | "other";
 
 interface TokenState {
-  type: "idle" | "authenticating" | "verifying" | "authenticated" | "failed";
+  type: "idle" | "authenticating" | "verifying" | "authenticated" | "failed" | "locked";
   token?: string;
   expiresAt?: string;
   error?: string;
+  kind?: FailureKind;
 }
 
 interface AppConfig {
@@ -23,25 +40,31 @@ interface AppConfig {
   realm: string;
   refreshIntervalSeconds: number;
   ghostllmApp?: string;
-  clientSecret?: string;
-  password?: string;
-  staticApiKey?: string;
+  hasClientSecret: boolean;
+  hasPassword: boolean;
+  hasStaticApiKey: boolean;
+  vaultEnabled: boolean;
+  vaultAutoLockMinutes: number;
 }
 
-function statusLabelFromError(error?: string): string {
-  const normalized = (error || "").toLowerCase();
-  if (
-    normalized.includes("401") ||
-    normalized.includes("unauthorized") ||
-    normalized.includes("invalid api key") ||
-    normalized.includes("invalid oidc token")
-  ) {
-    return "Unauthorized";
-  }
-  if (normalized.includes("timed out")) return "Endpoint timeout";
-  if (normalized.includes("cannot reach llm endpoint")) return "Endpoint unreachable";
-  if (normalized.includes("failed to update opencode config")) return "OpenCode config error";
-  return "Failed";
+const FAILURE_LABELS: Record<FailureKind, string> = {
+  unauthorized: "Unauthorized",
+  endpointTimeout: "Endpoint timeout",
+  endpointUnreachable: "Endpoint unreachable",
+  serverError: "Server error",
+  certificate: "Certificate error",
+  openCodeWrite: "OpenCode config error",
+  fileWrite: "File error",
+  config: "Config error",
+  missingCredentials: "No credentials",
+  keystore: "Keystore error",
+  locked: "Locked",
+  disabled: "Disabled",
+  other: "Failed",
+};
+
+function statusLabelFromKind(kind?: FailureKind): string {
+  return FAILURE_LABELS[kind ?? "other"] ?? "Failed";
 }
 
 // Ghost icon component
@@ -92,9 +115,16 @@ function App() {
   // Form fields - Static Key
   const [staticApiKey, setStaticApiKey] = useState("");
 
+  // Master-password vault
+  const [masterPassword, setMasterPassword] = useState("");
+
+  // No tray to reopen the window from: minimize instead of hiding
+  const [windowMode, setWindowMode] = useState(false);
+
   // Load initial state
   useEffect(() => {
     loadState();
+    invoke<boolean>("get_window_mode").then(setWindowMode);
     
     // Listen for state changes from backend
     const unlisten = listen<TokenState>("token-state-changed", (event) => {
@@ -109,8 +139,8 @@ function App() {
   async function loadState() {
     try {
       const [state, cfg] = await Promise.all([
-        invoke<TokenState>("get_state"),
-        invoke<AppConfig>("get_config"),
+        invoke<TokenState>("get_state_public"),
+        invoke<AppConfig>("get_config_public"),
       ]);
       setTokenState(state);
       setConfig(cfg);
@@ -123,13 +153,22 @@ function App() {
       setGhostllmApp(cfg.ghostllmApp || "");
       setRealm(cfg.realm || "dymium");
       setClientId(cfg.clientId || "dymium");
-      setStaticApiKey(cfg.staticApiKey || "");
-      // Don't populate secrets for security
+      // Secrets stay in the backend; `revealSecret` asks for them
     } catch (e) {
       console.error("Failed to load state:", e);
     }
   }
 
+  // Fill a secret field after the OS confirms it's the user
+  async function revealSecret(secret: SecretKind, set: (value: string) => void) {
+    setError(null);
+    try {
+      set(await invoke<string>("reveal_secret", { secret }));
+    } catch (e) {
+      setError(String(e));
+    }
+  }
+
   async function handleSave() {
     setIsSaving(true);
     setError(null);
@@ -158,7 +197,7 @@ function App() {
       await invoke("start_refresh_loop");
       
       // Hide window after successful save
-      await getCurrentWindow().hide();
+      await closeWindow();
     } catch (e) {
       setError(String(e));
     } finally {
@@ -188,8 +227,32 @@ function App() {
     }
   }
 
+  async function handleUnlock() {
+    setError(null);
+    try {
+      await invoke("unlock_vault", { masterPassword });
+      setMasterPassword("");
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      await loadState();
+    }
+  }
+
+  async function closeWindow() {
+    if (windowMode) {
+      await getCurrentWindow().minimize();
+    } else {
+      await getCurrentWindow().hide();
+    }
+  }
+
   async function handleCancel() {
-    await getCurrentWindow().hide();
+    await closeWindow();
+  }
+
+  async function handleQuit() {
+    await invoke("quit_app");
   }
 
   const isFormValid = authMode === "OAuth"
@@ -198,8 +261,8 @@ function App() {
 
   const hasCredentials = config && (
     authMode === "OAuth"
-      ? config.clientSecret && config.password
-      : config.staticApiKey
+      ? config.hasClientSecret && config.hasPassword
+      : config.hasStaticApiKey
   );
 
   return (
@@ -292,6 +355,15 @@ function App() {
                   onChange={(e) => setClientSecret(e.target.value)}
                   placeholder="Client secret from Keycloak"
                 />
+                {config?.hasClientSecret && !clientSecret && (
+                  <button
+                    type="button"
+                    className="btn btn-secondary"
+                    onClick={() => revealSecret("clientSecret", setClientSecret)}
+                  >
+                    Reveal stored secret
+                  </button>
+                )}
               </div>
               <div className="field">
                 <label>Password</label>
@@ -301,6 +373,15 @@ function App() {
                   onChange={(e) => setPassword(e.target.value)}
                   placeholder="Your password"
                 />
+                {config?.hasPassword && !password && (
+                  <button
+                    type="button"
+                    className="btn btn-secondary"
+                    onClick={() => revealSecret("password", setPassword)}
+                  >
+                    Reveal stored password
+                  </button>
+                )}
               </div>
             </div>
 
@@ -352,6 +433,15 @@ function App() {
                   onChange={(e) => setStaticApiKey(e.target.value)}
                   placeholder="Your GhostLLM API key"
                 />
+                {config?.hasStaticApiKey && !staticApiKey && (
+                  <button
+                    type="button"
+                    className="btn btn-secondary"
+                    onClick={() => revealSecret("staticApiKey", setStaticApiKey)}
+                  >
+                    Reveal stored key
+                  </button>
+                )}
               </div>
               <div className="field">
                 <label>GhostLLM App</label>
@@ -365,7 +455,7 @@ function App() {
             </div>
 
             <p className="info-text">
-              Static API key is stored in ~/.dymium/config.json. No automatic refresh needed.
+              Static API key is stored in the system keystore. No automatic refresh needed.
             </p>
           </>
         )}
@@ -415,11 +505,37 @@ function App() {
           </div>
         )}
 
+        {tokenState.type === "locked" && (
+          <div className="status-section">
+            <div className="status-row">
+              <span className="label">Status:</span>
+              <span className="value warning">Locked</span>
+            </div>
+            <div className="field">
+              <label>Master Password</label>
+              <input
+                type="password"
+                value={masterPassword}
+                onChange={(e) => setMasterPassword(e.target.value)}
+                onKeyDown={(e) => e.key === "Enter" && masterPassword && handleUnlock()}
+                placeholder="Unlock stored credentials"
+              />
+            </div>
+            <button
+              className="btn btn-primary"
+              onClick={handleUnlock}
+              disabled={!masterPassword}
+            >
+              Unlock
+            </button>
+          </div>
+        )}
+
         {tokenState.type === "failed" && (
           <div className="status-section">
             <div className="status-row">
               <span className="label">Status:</span>
-              <span className="value error">{statusLabelFromError(tokenState.error)}</span>
+              <span className="value error">{statusLabelFromKind(tokenState.kind)}</span>
             </div>
             <div className="status-row">
               <span className="value error">{tokenState.error}</span>
@@ -438,6 +554,11 @@ function App() {
         <button className="btn btn-secondary" onClick={handleCancel}>
           Cancel
         </button>
+        {windowMode && (
+          <button className="btn btn-secondary" onClick={handleQuit}>
+            Quit
+          </button>
+        )}
         <div className="spacer" />
         {hasCredentials && (
           <>
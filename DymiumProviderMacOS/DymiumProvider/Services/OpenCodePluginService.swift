@@ -1,3 +1,4 @@
+import CryptoKit
 import Foundation
 
 /// Service responsible for creating and managing the OpenCode plugin for Dymium authentication
@@ -40,14 +41,30 @@ final class OpenCodePluginService {
         // Write package.json
         let packageJson = createPackageJson()
         let packageJsonPath = pluginDir.appendingPathComponent("package.json")
-        try packageJson.write(to: packageJsonPath, atomically: true, encoding: .utf8)
+        let wrotePackageJson = try writeIfChanged(packageJson, to: packageJsonPath)
         
         // Write index.ts (the main plugin code)
         let indexTs = createIndexTs()
         let indexTsPath = pluginDir.appendingPathComponent("index.ts")
-        try indexTs.write(to: indexTsPath, atomically: true, encoding: .utf8)
+        let wroteIndexTs = try writeIfChanged(indexTs, to: indexTsPath)
         
-        print("✅ Dymium OpenCode plugin created at \(pluginDir.path)")
+        if wrotePackageJson || wroteIndexTs {
+            print("✅ Dymium OpenCode plugin updated at \(pluginDir.path)")
+        }
+    }
+    
+    /// Write `content` to `url` unless the file's SHA-256 already matches it.
+    /// OpenCode reloads the plugin whenever its files change, so they are
+    /// only touched when the embedded source does.
+    /// - Returns: Whether the file was written
+    private func writeIfChanged(_ content: String, to url: URL) throws -> Bool {
+        let data = Data(content.utf8)
+        if let existing = try? Data(contentsOf: url),
+           SHA256.hash(data: existing) == SHA256.hash(data: data) {
+            return false
+        }
+        try data.write(to: url, options: .atomic)
+        return true
     }
     
     /// Create the package.json content